@@ -0,0 +1,94 @@
+use iced_core::{BorderRadius, Color};
+use iced_style::checkbox::{self, Appearance};
+
+use super::Wrap;
+use crate::{ColorScheme, WithColorScheme};
+
+// ----- Mirror trait -----
+
+pub trait StyleSheet {
+    type Style: Default;
+
+    fn active(&self, style: &Self::Style, is_checked: bool) -> Appearance;
+    fn hovered(&self, style: &Self::Style, is_checked: bool) -> Appearance;
+}
+
+// ----- DevAuto impl -----
+
+pub trait DevAuto: WithColorScheme {}
+impl DevAuto for ColorScheme {}
+
+#[derive(Debug, Clone, Default)]
+pub enum Checkbox {
+    #[default]
+    Default,
+}
+
+impl<T> StyleSheet for T
+where
+    T: DevAuto,
+{
+    type Style = Checkbox;
+
+    fn active(&self, style: &Self::Style, is_checked: bool) -> Appearance {
+        let palette = self.palette();
+
+        match style {
+            Checkbox::Default => Appearance {
+                background: if is_checked {
+                    palette.primary.base.accent.into()
+                } else {
+                    palette.surface.weak.base.into()
+                },
+                icon_color: palette.primary.base.on_accent,
+                border_radius: BorderRadius::from(2.0),
+                border_width: 1.0,
+                border_color: if is_checked {
+                    palette.primary.base.accent
+                } else {
+                    palette.surface.strong.base
+                },
+                text_color: Some(palette.surface.base.on_base),
+            },
+        }
+    }
+
+    fn hovered(&self, style: &Self::Style, is_checked: bool) -> Appearance {
+        let palette = self.palette();
+
+        match style {
+            Checkbox::Default => Appearance {
+                border_color: palette.primary.base.accent,
+                background: if is_checked {
+                    palette.primary.strong.accent.into()
+                } else {
+                    Color {
+                        a: 0.2,
+                        ..palette.primary.base.accent
+                    }
+                    .into()
+                },
+                ..self.active(style, is_checked)
+            },
+        }
+    }
+}
+
+// ----- Impl the actual trait -----
+
+impl<T> checkbox::StyleSheet for Wrap<T>
+where
+    T: StyleSheet,
+{
+    type Style = T::Style;
+
+    #[inline]
+    fn active(&self, style: &Self::Style, is_checked: bool) -> Appearance {
+        T::active(self, style, is_checked)
+    }
+
+    #[inline]
+    fn hovered(&self, style: &Self::Style, is_checked: bool) -> Appearance {
+        T::hovered(self, style, is_checked)
+    }
+}
@@ -8,21 +8,25 @@ use derive_more::{Deref, DerefMut, From};
 
 pub mod application;
 pub mod button;
+pub mod checkbox;
 pub mod container;
 pub mod pane_grid;
 pub mod rule;
 pub mod scrollable;
 pub mod svg;
 pub mod text;
+pub mod text_input;
 
 pub use application::Application;
 pub use button::Button;
+pub use checkbox::Checkbox;
 pub use container::Container;
 pub use pane_grid::PaneGrid;
 pub use rule::Rule;
 pub use scrollable::Scrollable;
 pub use svg::Svg;
 pub use text::Text;
+pub use text_input::TextInput;
 
 /// A wrapper type which exists so stylesheets can be auto-implemented.
 #[repr(transparent)]
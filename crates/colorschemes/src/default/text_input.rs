@@ -0,0 +1,149 @@
+use iced_core::{BorderRadius, Color};
+use iced_style::text_input::{self, Appearance};
+
+use super::Wrap;
+use crate::{ColorScheme, WithColorScheme};
+
+// ----- Mirror trait -----
+
+pub trait StyleSheet {
+    type Style: Default;
+
+    fn active(&self, style: &Self::Style) -> Appearance;
+    fn focused(&self, style: &Self::Style) -> Appearance;
+    fn placeholder_color(&self, style: &Self::Style) -> Color;
+    fn value_color(&self, style: &Self::Style) -> Color;
+    fn disabled_color(&self, style: &Self::Style) -> Color;
+    fn selection_color(&self, style: &Self::Style) -> Color;
+
+    fn hovered(&self, style: &Self::Style) -> Appearance {
+        self.focused(style)
+    }
+
+    fn disabled(&self, style: &Self::Style) -> Appearance;
+}
+
+// ----- DevAuto impl -----
+
+pub trait DevAuto: WithColorScheme {}
+impl DevAuto for ColorScheme {}
+
+#[derive(Debug, Clone, Default)]
+pub enum TextInput {
+    #[default]
+    Default,
+}
+
+impl<T> StyleSheet for T
+where
+    T: DevAuto,
+{
+    type Style = TextInput;
+
+    fn active(&self, style: &Self::Style) -> Appearance {
+        let palette = self.palette();
+
+        match style {
+            Self::Style::Default => Appearance {
+                background: palette.surface.weak.base.into(),
+                border_radius: BorderRadius::from(2.0),
+                border_width: 1.0,
+                border_color: palette.surface.strong.base,
+                icon_color: palette.surface.weak.on_base,
+            },
+        }
+    }
+
+    fn focused(&self, style: &Self::Style) -> Appearance {
+        let palette = self.palette();
+
+        match style {
+            Self::Style::Default => Appearance {
+                border_color: palette.primary.base.accent,
+                ..self.active(style)
+            },
+        }
+    }
+
+    fn placeholder_color(&self, _: &Self::Style) -> Color {
+        let palette = self.palette();
+
+        Color {
+            a: 0.6,
+            ..palette.surface.weak.on_base
+        }
+    }
+
+    fn value_color(&self, _: &Self::Style) -> Color {
+        self.palette().surface.base.on_base
+    }
+
+    fn disabled_color(&self, style: &Self::Style) -> Color {
+        self.placeholder_color(style)
+    }
+
+    fn selection_color(&self, _: &Self::Style) -> Color {
+        Color {
+            a: 0.5,
+            ..self.palette().primary.base.accent
+        }
+    }
+
+    fn disabled(&self, style: &Self::Style) -> Appearance {
+        let palette = self.palette();
+
+        Appearance {
+            background: palette.surface.base.base.into(),
+            ..self.active(style)
+        }
+    }
+}
+
+// ----- Impl the actual trait -----
+
+impl<T> text_input::StyleSheet for Wrap<T>
+where
+    T: StyleSheet,
+{
+    type Style = T::Style;
+
+    #[inline]
+    fn active(&self, style: &Self::Style) -> Appearance {
+        T::active(self, style)
+    }
+
+    #[inline]
+    fn focused(&self, style: &Self::Style) -> Appearance {
+        T::focused(self, style)
+    }
+
+    #[inline]
+    fn placeholder_color(&self, style: &Self::Style) -> Color {
+        T::placeholder_color(self, style)
+    }
+
+    #[inline]
+    fn value_color(&self, style: &Self::Style) -> Color {
+        T::value_color(self, style)
+    }
+
+    #[inline]
+    fn disabled_color(&self, style: &Self::Style) -> Color {
+        T::disabled_color(self, style)
+    }
+
+    #[inline]
+    fn selection_color(&self, style: &Self::Style) -> Color {
+        T::selection_color(self, style)
+    }
+
+    #[inline]
+    fn hovered(&self, style: &Self::Style) -> Appearance {
+        T::hovered(self, style)
+    }
+
+    #[inline]
+    fn disabled(&self, style: &Self::Style) -> Appearance {
+        T::disabled(self, style)
+    }
+}
@@ -0,0 +1,252 @@
+//! Turning the `Exec` line of a desktop entry into commands to run.
+//!
+//! Arguments are separated by spaces, and may be quoted with `"`. Field codes like `%f` are
+//! replaced with the files being opened, as described in the
+//! [desktop entry spec](https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html).
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::vfs::Uri;
+
+use super::App;
+
+/// A single argument of an `Exec` line, before field codes are replaced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Arg {
+    text: String,
+    quoted: bool,
+}
+
+/// The command lines opening some files with an app, one for every process to start.
+///
+/// Apps taking a single file, with `%f` or `%u`, are started once for every file. Apps that don't
+/// take files at all are started once, without them. Nothing is returned if the `Exec` line is
+/// invalid.
+pub fn commands(app: &App, files: &[PathBuf]) -> Vec<Vec<OsString>> {
+    let Some(args) = split(&app.exec) else {
+        return vec![];
+    };
+
+    let has = |codes: &[&str]| {
+        args.iter()
+            .any(|a| !a.quoted && codes.iter().any(|c| a.text.contains(c)))
+    };
+
+    if files.len() > 1 && has(&["%f", "%u"]) && !has(&["%F", "%U"]) {
+        files
+            .iter()
+            .map(|f| expand(app, &args, std::slice::from_ref(f)))
+            .collect()
+    } else {
+        vec![expand(app, &args, files)]
+    }
+}
+
+/// Split an `Exec` line into arguments, or [`None`] if a quote isn't closed.
+fn split(exec: &str) -> Option<Vec<Arg>> {
+    let mut args = vec![];
+    let mut chars = exec.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+
+        let Some(first) = chars.next() else {
+            break;
+        };
+
+        let mut text = String::new();
+
+        if first == '"' {
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => match chars.next()? {
+                        c @ ('"' | '`' | '$' | '\\') => text.push(c),
+                        c => {
+                            text.push('\\');
+                            text.push(c);
+                        }
+                    },
+                    c => text.push(c),
+                }
+            }
+
+            args.push(Arg { text, quoted: true });
+        } else {
+            text.push(first);
+
+            while let Some(c) = chars.next_if(|c| *c != ' ' && *c != '\t') {
+                text.push(c);
+            }
+
+            args.push(Arg {
+                text,
+                quoted: false,
+            });
+        }
+    }
+
+    // the first argument is the program, so there has to be one
+    (!args.is_empty()).then_some(args)
+}
+
+/// Replace the field codes in some arguments.
+fn expand(app: &App, args: &[Arg], files: &[PathBuf]) -> Vec<OsString> {
+    let mut expanded = vec![];
+
+    for arg in args {
+        // field codes can't be used in quoted arguments
+        if arg.quoted {
+            expanded.push(arg.text.clone().into());
+            continue;
+        }
+
+        match arg.text.as_str() {
+            "%F" => expanded.extend(files.iter().map(|f| f.as_os_str().to_owned())),
+            "%U" => expanded.extend(files.iter().map(|f| url(f))),
+            "%i" => {
+                if let Some(icon) = &app.icon {
+                    expanded.push("--icon".into());
+                    expanded.push(icon.into());
+                }
+            }
+            text => {
+                if let Some(arg) = expand_inline(app, text, files.first()) {
+                    expanded.push(arg);
+                }
+            }
+        }
+    }
+
+    expanded
+}
+
+/// Replace the field codes inside a single argument, leaving it out if nothing is left of it.
+fn expand_inline(app: &App, text: &str, file: Option<&PathBuf>) -> Option<OsString> {
+    let mut arg = OsString::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            arg.push(c.encode_utf8(&mut [0; 4]));
+            continue;
+        }
+
+        match chars.next() {
+            Some('%') => arg.push("%"),
+            Some('f') => {
+                if let Some(file) = file {
+                    arg.push(file);
+                }
+            }
+            Some('u') => {
+                if let Some(file) = file {
+                    arg.push(url(file));
+                }
+            }
+            Some('c') => arg.push(&app.name),
+            Some('k') => arg.push(&app.path),
+            // lists can only be whole arguments, and the rest are deprecated
+            _ => {}
+        }
+    }
+
+    (!arg.is_empty()).then_some(arg)
+}
+
+/// A `file://` URL for a local path.
+fn url(path: &Path) -> OsString {
+    Uri::local(path).to_string().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(exec: &str) -> App {
+        App {
+            id: "editor.desktop".to_owned(),
+            name: "Editor".to_owned(),
+            generic_name: None,
+            icon: Some("editor".to_owned()),
+            exec: exec.to_owned(),
+            working_dir: None,
+            mime_types: vec![],
+            terminal: false,
+            no_display: false,
+            path: PathBuf::from("/usr/share/applications/editor.desktop"),
+        }
+    }
+
+    fn files() -> Vec<PathBuf> {
+        vec![PathBuf::from("/tmp/a b.txt"), PathBuf::from("/tmp/c.txt")]
+    }
+
+    fn strings(commands: Vec<Vec<OsString>>) -> Vec<Vec<String>> {
+        commands
+            .into_iter()
+            .map(|c| c.into_iter().map(|a| a.into_string().unwrap()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn file_lists_start_one_process() {
+        assert_eq!(
+            strings(commands(&app("editor --new %F"), &files())),
+            [["editor", "--new", "/tmp/a b.txt", "/tmp/c.txt"]]
+        );
+    }
+
+    #[test]
+    fn single_files_start_a_process_each() {
+        assert_eq!(
+            strings(commands(&app("editor %f"), &files())),
+            [["editor", "/tmp/a b.txt"], ["editor", "/tmp/c.txt"]]
+        );
+    }
+
+    #[test]
+    fn urls_are_escaped() {
+        assert_eq!(
+            strings(commands(&app("viewer %U"), &files()[..1])),
+            [["viewer", "file:///tmp/a%20b.txt"]]
+        );
+    }
+
+    #[test]
+    fn other_codes_are_replaced() {
+        assert_eq!(
+            strings(commands(
+                &app("editor %i --title=%c --from %k 100%% %d"),
+                &[]
+            )),
+            [vec![
+                "editor",
+                "--icon",
+                "editor",
+                "--title=Editor",
+                "--from",
+                "/usr/share/applications/editor.desktop",
+                "100%",
+            ]]
+        );
+    }
+
+    #[test]
+    fn quoted_arguments_are_kept_as_is() {
+        assert_eq!(
+            strings(commands(
+                &app(r#"sh -c "echo \"%f\" \$HOME" %f"#),
+                &files()[1..]
+            )),
+            [["sh", "-c", r#"echo "%f" $HOME"#, "/tmp/c.txt"]]
+        );
+    }
+
+    #[test]
+    fn unclosed_quotes_are_refused() {
+        assert!(commands(&app(r#"editor "%f"#), &files()).is_empty());
+        assert!(commands(&app("  "), &files()).is_empty());
+    }
+}
@@ -0,0 +1,330 @@
+//! Reading the key files used by `.desktop` files and `mimeapps.list`.
+//!
+//! Key files are much like INI files: `[Group]` headers, each followed by `Key=Value` lines, with
+//! `#` starting comments. Values may be localised with keys like `Name[de]`.
+
+use std::env;
+
+/// A group of keys, like `[Desktop Entry]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Group {
+    pub name: String,
+    /// Keys and their raw values, in the order they're written.
+    pub entries: Vec<(String, String)>,
+}
+
+impl Group {
+    /// Raw value of a key, with escapes left in.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// A string value.
+    pub fn string(&self, key: &str) -> Option<String> {
+        self.get(key).map(unescape)
+    }
+
+    /// A boolean value, which is false unless it's set to `true`.
+    pub fn boolean(&self, key: &str) -> bool {
+        self.get(key) == Some("true")
+    }
+
+    /// A list of strings separated by `;`.
+    pub fn list(&self, key: &str) -> Vec<String> {
+        self.get(key).map(split_list).unwrap_or_default()
+    }
+
+    /// A localised string, in the language that best matches some locales.
+    pub fn localised(&self, key: &str, locales: &[String]) -> Option<String> {
+        locales
+            .iter()
+            .find_map(|locale| self.string(&format!("{key}[{locale}]")))
+            .or_else(|| self.string(key))
+    }
+}
+
+/// Read every group in a key file, skipping lines that aren't valid.
+pub fn parse(text: &str) -> Vec<Group> {
+    let mut groups: Vec<Group> = vec![];
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            groups.push(Group {
+                name: name.to_owned(),
+                entries: vec![],
+            });
+            continue;
+        }
+
+        // keys before the first group don't belong anywhere
+        let (Some(group), Some((key, value))) = (groups.last_mut(), line.split_once('=')) else {
+            continue;
+        };
+
+        group
+            .entries
+            .push((key.trim_end().to_owned(), value.trim_start().to_owned()));
+    }
+
+    groups
+}
+
+/// Set a key in a key file, or remove it, keeping everything else as it is.
+///
+/// A new key goes at the end of its group, and a new group at the end of the file. Comments,
+/// blank lines and other groups are left alone.
+pub fn set(text: &str, group: &str, key: &str, value: Option<&str>) -> String {
+    let mut lines: Vec<String> = text.lines().map(ToOwned::to_owned).collect();
+
+    let mut in_group = false;
+    // where a new key would go, after the last key of the group
+    let mut end = None;
+    let mut found = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_group = name == group;
+
+            if in_group {
+                end = Some(index + 1);
+            }
+            continue;
+        }
+
+        if !in_group || trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        end = Some(index + 1);
+
+        if trimmed
+            .split_once('=')
+            .is_some_and(|(k, _)| k.trim_end() == key)
+        {
+            found = Some(index);
+            break;
+        }
+    }
+
+    match (found, end, value) {
+        (Some(index), _, Some(value)) => lines[index] = format!("{key}={value}"),
+        (Some(index), _, None) => {
+            lines.remove(index);
+        }
+        (None, Some(end), Some(value)) => lines.insert(end, format!("{key}={value}")),
+        (None, None, Some(value)) => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+
+            lines.push(format!("[{group}]"));
+            lines.push(format!("{key}={value}"));
+        }
+        (None, _, None) => {}
+    }
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+/// Replace the escapes allowed in values, like `\s` for a space.
+pub fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            // other escapes are left for whoever reads the value, like quoting in `Exec`
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+/// Split a list value on `;`, leaving out empty items.
+pub fn split_list(value: &str) -> Vec<String> {
+    let mut items = vec![];
+    let mut item = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            // escapes are kept for `unescape`, except for separators that aren't
+            '\\' => match chars.next() {
+                Some(';') => item.push(';'),
+                Some(c) => {
+                    item.push('\\');
+                    item.push(c);
+                }
+                None => item.push('\\'),
+            },
+            ';' => items.push(std::mem::take(&mut item)),
+            c => item.push(c),
+        }
+    }
+
+    items.push(item);
+
+    items
+        .iter()
+        .filter(|i| !i.is_empty())
+        .map(|i| unescape(i))
+        .collect()
+}
+
+/// Locale names to look for localised values with, best match first.
+///
+/// A locale like `de_DE.UTF-8@euro` is matched as `de_DE@euro`, `de_DE`, `de@euro` and `de`.
+pub fn locales() -> Vec<String> {
+    let Some(locale) = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|v| env::var(v).ok())
+        .find(|v| !v.is_empty())
+    else {
+        return vec![];
+    };
+
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale.as_str(), None),
+    };
+
+    // the encoding is never part of the key
+    let locale = locale.split('.').next().unwrap_or_default();
+    let (language, country) = match locale.split_once('_') {
+        Some((language, country)) => (language, Some(country)),
+        None => (locale, None),
+    };
+
+    let mut bases = vec![language.to_owned()];
+
+    if let Some(country) = country {
+        bases.insert(0, format!("{language}_{country}"));
+    }
+
+    bases
+        .into_iter()
+        .flat_map(|base| {
+            let modified = modifier.map(|m| format!("{base}@{m}"));
+            modified.into_iter().chain(std::iter::once(base))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIMEAPPS: &str = "\
+# written by hand
+[Default Applications]
+text/plain=editor.desktop
+
+# images
+image/png=viewer.desktop
+
+[Added Associations]
+text/plain=other.desktop;
+";
+
+    #[test]
+    fn keys_are_replaced_in_place() {
+        let text = set(
+            MIMEAPPS,
+            "Default Applications",
+            "text/plain",
+            Some("new.desktop"),
+        );
+
+        assert_eq!(text, MIMEAPPS.replace("=editor.desktop", "=new.desktop"));
+    }
+
+    #[test]
+    fn new_keys_go_after_the_last_in_their_group() {
+        let text = set(
+            MIMEAPPS,
+            "Default Applications",
+            "text/html",
+            Some("browser.desktop"),
+        );
+
+        assert_eq!(
+            text,
+            MIMEAPPS.replace(
+                "image/png=viewer.desktop\n",
+                "image/png=viewer.desktop\ntext/html=browser.desktop\n"
+            )
+        );
+    }
+
+    #[test]
+    fn new_groups_go_at_the_end() {
+        let text = set("", "Default Applications", "text/plain", Some("a.desktop"));
+        assert_eq!(text, "[Default Applications]\ntext/plain=a.desktop\n");
+
+        let text = set(MIMEAPPS, "Removed Associations", "a/b", Some("c.desktop"));
+        assert_eq!(
+            text,
+            format!("{MIMEAPPS}\n[Removed Associations]\na/b=c.desktop\n")
+        );
+    }
+
+    #[test]
+    fn keys_are_removed() {
+        let text = set(MIMEAPPS, "Added Associations", "text/plain", None);
+        assert_eq!(text, MIMEAPPS.replace("text/plain=other.desktop;\n", ""));
+
+        // keys of other groups, and missing keys, are left alone
+        assert_eq!(set(MIMEAPPS, "Nothing", "text/plain", None), MIMEAPPS);
+        assert_eq!(set(MIMEAPPS, "Added Associations", "a/b", None), MIMEAPPS);
+    }
+
+    #[test]
+    fn groups_are_parsed() {
+        let groups =
+            parse("stray=1\n[A]\nName = x\nName[de]=y\n# c\nbad line\n[B]\nList=a;b\\;c;;\n");
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].get("Name"), Some("x"));
+        assert_eq!(
+            groups[0].localised("Name", &["de".to_owned()]).as_deref(),
+            Some("y")
+        );
+        assert_eq!(groups[0].localised("Name", &[]).as_deref(), Some("x"));
+        assert_eq!(groups[1].list("List"), ["a", "b;c"]);
+    }
+
+    #[test]
+    fn escapes_are_replaced() {
+        assert_eq!(unescape(r"a\sb\tc\\d"), "a b\tc\\d");
+        assert_eq!(unescape(r#"say \"hi\""#), r#"say \"hi\""#);
+        assert_eq!(unescape("end\\"), "end\\");
+    }
+}
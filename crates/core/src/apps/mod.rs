@@ -0,0 +1,580 @@
+//! Installed applications, and which of them open which types of files.
+//!
+//! Applications are read from `.desktop` files in the `applications` folder of every
+//! [data directory](dirs::data_dirs), as described by the
+//! [desktop entry spec](https://specifications.freedesktop.org/desktop-entry-spec/latest/). Which
+//! apps open which MIME types comes from the types each app lists, adjusted by every
+//! `mimeapps.list`, as described by the
+//! [MIME applications spec](https://specifications.freedesktop.org/mime-apps-spec/latest/).
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use crate::{dirs, mime};
+
+mod exec;
+mod keyfile;
+
+/// Name of the files listing associations between apps and types.
+const MIMEAPPS: &str = "mimeapps.list";
+
+/// An application installed with a `.desktop` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct App {
+    /// Desktop file id, such as `org.gnome.TextEditor.desktop`.
+    pub id: String,
+    /// Name shown to users, in their language if possible.
+    pub name: String,
+    /// What kind of app it is, such as "Text Editor".
+    pub generic_name: Option<String>,
+    /// Name of an icon in the icon theme, or a path to one.
+    pub icon: Option<String>,
+    /// Command line, with field codes like `%f` still in it.
+    pub exec: String,
+    /// Directory to run the app in.
+    pub working_dir: Option<PathBuf>,
+    /// MIME types the app says it can open.
+    pub mime_types: Vec<String>,
+    /// Run the app in a terminal.
+    pub terminal: bool,
+    /// Left out of menus, though it may still be used to open files.
+    pub no_display: bool,
+    /// Where the desktop file is.
+    pub path: PathBuf,
+}
+
+impl App {
+    /// Read a desktop file, unless it isn't for an app that can be run.
+    fn read(id: String, path: PathBuf, locales: &[String]) -> Option<Self> {
+        let text = fs::read_to_string(&path).ok()?;
+        let groups = keyfile::parse(&text);
+        let entry = groups.iter().find(|g| g.name == "Desktop Entry")?;
+
+        if entry.get("Type") != Some("Application") || entry.boolean("Hidden") {
+            return None;
+        }
+
+        // apps can say which program they need, so they aren't shown without it
+        if let Some(program) = entry.string("TryExec") {
+            find_program(&program)?;
+        }
+
+        Some(Self {
+            id,
+            name: entry.localised("Name", locales)?,
+            generic_name: entry.localised("GenericName", locales),
+            icon: entry.string("Icon").filter(|i| !i.is_empty()),
+            exec: entry.string("Exec")?,
+            working_dir: entry
+                .string("Path")
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from),
+            mime_types: entry.list("MimeType"),
+            terminal: entry.boolean("Terminal"),
+            no_display: entry.boolean("NoDisplay"),
+            path,
+        })
+    }
+
+    /// Check if the app says it can open a type.
+    #[must_use]
+    pub fn supports(&self, mime: &str) -> bool {
+        self.mime_types.iter().any(|m| m == mime)
+    }
+
+    /// Start the app, opening some local files with it.
+    ///
+    /// Apps that only open a single file at a time are started once for each file.
+    pub fn launch(&self, files: &[PathBuf]) -> io::Result<()> {
+        let commands = exec::commands(self, files);
+
+        if commands.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} has an invalid command line", self.id),
+            ));
+        }
+
+        for args in commands {
+            let mut command = if self.terminal {
+                let mut command = Command::new(terminal());
+                command.arg("-e").args(&args);
+                command
+            } else {
+                let mut command = Command::new(&args[0]);
+                command.args(&args[1..]);
+                command
+            };
+
+            if let Some(dir) = &self.working_dir {
+                command.current_dir(dir);
+            }
+
+            let mut child = command
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()?;
+
+            // reap the app when it exits, so it doesn't linger as a zombie
+            thread::spawn(move || child.wait());
+        }
+
+        Ok(())
+    }
+}
+
+/// The terminal emulator to run terminal apps in.
+fn terminal() -> String {
+    env::var("TERMINAL")
+        .ok()
+        .filter(|t| !t.is_empty())
+        .or_else(|| {
+            ["x-terminal-emulator", "xterm"]
+                .into_iter()
+                .find(|t| find_program(t).is_some())
+                .map(ToOwned::to_owned)
+        })
+        .unwrap_or_else(|| "xterm".to_owned())
+}
+
+/// Find a program by name in `PATH`, or check that an absolute path to one exists.
+fn find_program(program: &str) -> Option<PathBuf> {
+    let program = Path::new(program);
+
+    if program.is_absolute() {
+        return program.is_file().then(|| program.to_path_buf());
+    }
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|p| p.is_file())
+}
+
+/// The associations in a single `mimeapps.list`.
+#[derive(Debug, Clone, Default)]
+struct MimeApps {
+    /// Preferred apps for each type, best first.
+    defaults: HashMap<String, Vec<String>>,
+    /// Apps that open a type, on top of those that say they do.
+    added: HashMap<String, Vec<String>>,
+    /// Apps that shouldn't open a type, even though they say they do.
+    removed: HashMap<String, Vec<String>>,
+}
+
+impl MimeApps {
+    fn read(path: &Path) -> Option<Self> {
+        let text = fs::read_to_string(path).ok()?;
+        let mut apps = Self::default();
+
+        for group in keyfile::parse(&text) {
+            let map = match group.name.as_str() {
+                "Default Applications" => &mut apps.defaults,
+                "Added Associations" => &mut apps.added,
+                "Removed Associations" => &mut apps.removed,
+                _ => continue,
+            };
+
+            for (mime, ids) in &group.entries {
+                map.entry(mime.clone())
+                    .or_default()
+                    .extend(keyfile::split_list(ids));
+            }
+        }
+
+        Some(apps)
+    }
+}
+
+lazy_static::lazy_static! {
+    /// The apps last read, if they have been.
+    static ref SHARED: Mutex<Option<Shared>> = Mutex::new(None);
+}
+
+/// Apps read before, and when what they were read from last changed.
+#[derive(Debug)]
+struct Shared {
+    apps: Vec<Option<SystemTime>>,
+    lists: Vec<Option<SystemTime>>,
+    registry: Arc<Registry>,
+}
+
+/// Every installed app, and the associations between apps and types.
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    /// Apps by their desktop file id.
+    apps: HashMap<String, App>,
+    /// Ids of every app, in order of the directories they were found in.
+    order: Vec<String>,
+    /// Every `mimeapps.list`, most important first.
+    lists: Vec<MimeApps>,
+}
+
+impl Registry {
+    /// Read every installed app and `mimeapps.list`.
+    pub fn load() -> Self {
+        let locales = keyfile::locales();
+
+        let mut registry = Self::default();
+        let mut seen = HashSet::new();
+
+        for dir in dirs::data_dirs() {
+            let applications = dir.join("applications");
+            let mut files = vec![];
+            list_desktop_files(&applications, &mut files);
+
+            for path in files {
+                let Some(id) = desktop_id(&applications, &path) else {
+                    continue;
+                };
+
+                // earlier directories hide apps with the same id, even if they can't be run
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+
+                if let Some(app) = App::read(id.clone(), path, &locales) {
+                    registry.order.push(id.clone());
+                    registry.apps.insert(id, app);
+                }
+            }
+        }
+
+        registry.lists = read_lists();
+        registry
+    }
+
+    /// The installed apps, shared by everyone asking for them.
+    ///
+    /// Reading every desktop file takes a while, so apps are only read again once an
+    /// `applications` folder has changed. Associations are read again whenever a
+    /// `mimeapps.list` has.
+    pub fn shared() -> Arc<Self> {
+        let (apps, lists) = (apps_stamp(), lists_stamp());
+        let mut shared = SHARED.lock().unwrap();
+
+        let registry = match shared.take() {
+            Some(s) if s.apps == apps && s.lists == lists => s.registry,
+            Some(s) if s.apps == apps => {
+                let mut registry = Self::clone(&s.registry);
+                registry.lists = read_lists();
+                Arc::new(registry)
+            }
+            _ => Arc::new(Self::load()),
+        };
+
+        *shared = Some(Shared {
+            apps,
+            lists,
+            registry: registry.clone(),
+        });
+
+        registry
+    }
+
+    /// An app by its desktop file id.
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&App> {
+        self.apps.get(id)
+    }
+
+    /// Every type with a default app set in a `mimeapps.list`, sorted by type.
+    pub fn defaults(&self) -> Vec<(&str, &App)> {
+        let mut mimes: Vec<&str> = self
+            .lists
+            .iter()
+            .flat_map(|list| list.defaults.keys())
+            .map(String::as_str)
+            .collect();
+
+        mimes.sort_unstable();
+        mimes.dedup();
+
+        mimes
+            .into_iter()
+            .filter_map(|mime| {
+                let app = self
+                    .lists
+                    .iter()
+                    .filter_map(|list| list.defaults.get(mime))
+                    .flatten()
+                    .find_map(|id| self.get(id))?;
+
+                Some((mime, app))
+            })
+            .collect()
+    }
+
+    /// Every app, sorted by name.
+    pub fn all(&self) -> Vec<&App> {
+        let mut apps: Vec<_> = self.apps.values().collect();
+        apps.sort_by_cached_key(|a| a.name.to_lowercase());
+        apps
+    }
+
+    /// The app that opens a type when nothing else is picked.
+    ///
+    /// Types without an app of their own are opened with the app of the closest type they're a
+    /// subclass of, so scripts without a better app open in a text editor.
+    #[must_use]
+    pub fn default_for(&self, mime: &str) -> Option<&App> {
+        lineage(mime).into_iter().find_map(|mime| {
+            self.lists
+                .iter()
+                .filter_map(|list| list.defaults.get(mime))
+                .flatten()
+                .find_map(|id| self.get(id))
+                .or_else(|| self.associated(mime).into_iter().next())
+        })
+    }
+
+    /// Every app that opens a type or a type it's a subclass of, with the default first.
+    pub fn apps_for(&self, mime: &str) -> Vec<&App> {
+        let mut apps: Vec<&App> = vec![];
+
+        for mime in lineage(mime) {
+            for app in self.associated(mime) {
+                if !apps.contains(&app) {
+                    apps.push(app);
+                }
+            }
+        }
+
+        if let Some(default) = self.default_for(mime) {
+            apps.retain(|a| a.id != default.id);
+            apps.insert(0, default);
+        }
+
+        apps
+    }
+
+    /// Apps associated with a type, in order of preference.
+    ///
+    /// More important lists can remove associations added by less important ones, and apps saying
+    /// they open a type come after every association that was added.
+    fn associated(&self, mime: &str) -> Vec<&App> {
+        let mut ids: Vec<&str> = vec![];
+        let mut removed: HashSet<&str> = HashSet::new();
+
+        for list in &self.lists {
+            for id in list.added.get(mime).into_iter().flatten() {
+                if !removed.contains(id.as_str()) && !ids.contains(&id.as_str()) {
+                    ids.push(id);
+                }
+            }
+
+            removed.extend(
+                list.removed
+                    .get(mime)
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str),
+            );
+        }
+
+        for id in &self.order {
+            let supported = self.apps[id].supports(mime);
+
+            if supported && !removed.contains(id.as_str()) && !ids.contains(&id.as_str()) {
+                ids.push(id);
+            }
+        }
+
+        ids.into_iter().filter_map(|id| self.get(id)).collect()
+    }
+}
+
+/// A type, under the name it goes by, followed by every type it's a subclass of.
+fn lineage(mime: &str) -> Vec<&str> {
+    let database = &*mime::DATABASE;
+    let mime = database.unalias(mime);
+
+    std::iter::once(mime)
+        .chain(database.ancestors(mime))
+        .collect()
+}
+
+/// Make an app the default for a type, in the user's own `mimeapps.list`.
+///
+/// The app is also added to the associations of the type, and no longer removed from them, so
+/// it's offered for it from then on.
+/// Lists for the current desktop, like `gnome-mimeapps.list`, still take precedence.
+pub fn set_default(mime: &str, id: &str) -> io::Result<()> {
+    edit_user_list(|text| {
+        let groups = keyfile::parse(text);
+        let list = |group: &str| {
+            groups
+                .iter()
+                .filter(|g| g.name == group)
+                .find_map(|g| g.get(mime).map(keyfile::split_list))
+                .unwrap_or_default()
+        };
+
+        // the new default goes first, without repeating it
+        let added: String = std::iter::once(id.to_owned())
+            .chain(list("Added Associations").into_iter().filter(|a| a != id))
+            .map(|a| a + ";")
+            .collect();
+
+        let removed: String = list("Removed Associations")
+            .into_iter()
+            .filter(|r| r != id)
+            .map(|r| r + ";")
+            .collect();
+
+        let text = keyfile::set(text, "Default Applications", mime, Some(&format!("{id};")));
+        let text = keyfile::set(&text, "Added Associations", mime, Some(&added));
+        keyfile::set(
+            &text,
+            "Removed Associations",
+            mime,
+            (!removed.is_empty()).then_some(removed.as_str()),
+        )
+    })
+}
+
+/// Remove the default app of a type from the user's own `mimeapps.list`.
+///
+/// Defaults set by the system, or for the current desktop, are left alone.
+pub fn unset_default(mime: &str) -> io::Result<()> {
+    edit_user_list(|text| keyfile::set(text, "Default Applications", mime, None))
+}
+
+/// Change the user's own `mimeapps.list`, creating it if needed.
+fn edit_user_list<F>(edit: F) -> io::Result<()>
+where
+    F: FnOnce(&str) -> String,
+{
+    let dir = dirs::BASE.config_dir();
+    let path = dir.join(MIMEAPPS);
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    fs::create_dir_all(dir)?;
+
+    // other programs read the list at any time, so it's replaced in one go
+    let temporary = dir.join(format!(".{MIMEAPPS}.doseer"));
+    fs::write(&temporary, edit(&text))?;
+    fs::rename(&temporary, &path)
+}
+
+/// Every `.desktop` file below a directory.
+fn list_desktop_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).map(|e| e.path()).collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            list_desktop_files(&path, files);
+        } else if path.extension().is_some_and(|e| e == "desktop") {
+            files.push(path);
+        }
+    }
+}
+
+/// Every `mimeapps.list`, most important first.
+fn read_lists() -> Vec<MimeApps> {
+    mimeapps_paths()
+        .iter()
+        .filter_map(|p| MimeApps::read(p))
+        .collect()
+}
+
+/// When every folder apps are read from was last changed, to tell when apps are installed or
+/// removed.
+fn apps_stamp() -> Vec<Option<SystemTime>> {
+    let mut times = vec![];
+
+    for dir in dirs::data_dirs() {
+        dir_times(&dir.join("applications"), &mut times);
+    }
+
+    times
+}
+
+/// When a folder and every folder below it were last changed.
+fn dir_times(dir: &Path, times: &mut Vec<Option<SystemTime>>) {
+    times.push(modified(dir));
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).map(|e| e.path()).collect();
+    entries.sort();
+
+    for path in entries.iter().filter(|p| p.is_dir()) {
+        dir_times(path, times);
+    }
+}
+
+/// When every `mimeapps.list` was last changed.
+fn lists_stamp() -> Vec<Option<SystemTime>> {
+    mimeapps_paths().iter().map(|p| modified(p)).collect()
+}
+
+/// When something was last changed, if it's there.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The id of a desktop file, which is its path below `applications` with `/` replaced by `-`.
+fn desktop_id(applications: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(applications).ok()?;
+
+    let parts: Vec<_> = relative
+        .components()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<_>>()?;
+
+    Some(parts.join("-"))
+}
+
+/// The desktops being used, like `gnome`, from `XDG_CURRENT_DESKTOP`.
+fn current_desktops() -> Vec<String> {
+    env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|d| !d.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Every place a `mimeapps.list` can be, most important first.
+///
+/// Lists for the current desktop, like `gnome-mimeapps.list`, come before the generic one in each
+/// directory.
+fn mimeapps_paths() -> Vec<PathBuf> {
+    let desktops = current_desktops();
+
+    let dirs = dirs::config_dirs().into_iter().chain(
+        dirs::data_dirs()
+            .into_iter()
+            .map(|d| d.join("applications")),
+    );
+
+    dirs.flat_map(|dir| {
+        desktops
+            .iter()
+            .map(|d| format!("{d}-{MIMEAPPS}"))
+            .chain(std::iter::once(MIMEAPPS.to_owned()))
+            .map(|name| dir.join(name))
+            .collect::<Vec<_>>()
+    })
+    .collect()
+}
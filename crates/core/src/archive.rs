@@ -0,0 +1,544 @@
+//! Browsing zip and tar archives as if they were directories.
+//!
+//! Items inside an archive are addressed by joining their path within the archive onto the path of
+//! the archive itself, so `/home/me/docs.zip/notes/todo.txt` is `notes/todo.txt` inside
+//! `/home/me/docs.zip`. Archives are indexed once when opened, and items are only decompressed
+//! when they are extracted.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, FileTimes, Permissions};
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{Local, NaiveDate, TimeZone};
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use crate::entry::{Entry, Kind};
+use crate::path::PathWrap;
+use crate::task::CancelToken;
+
+/// Kinds of archives that can be browsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    Zip,
+    Tar,
+    /// A gzip compressed tar.
+    TarGz,
+}
+
+impl Format {
+    /// Work out the format of an archive from its name.
+    #[must_use]
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+
+        if name.ends_with(".zip") || name.ends_with(".jar") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// An item inside an archive.
+#[derive(Debug, Clone)]
+pub struct Item {
+    /// Path within the archive, never absolute and never containing `..`.
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// Uncompressed size in bytes.
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// Unix permission bits, if the archive stores them.
+    pub mode: Option<u32>,
+}
+
+/// An indexed archive.
+#[derive(Debug)]
+pub struct Archive {
+    path: PathBuf,
+    format: Format,
+    /// Every item by path, including directories only implied by the items inside them.
+    items: BTreeMap<PathBuf, Item>,
+    /// Permissions of the archive file, which items without their own start from.
+    permissions: Permissions,
+}
+
+impl Archive {
+    /// Open and index an archive.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let format = Format::detect(&path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not an archive"))?;
+        let permissions = fs::metadata(&path)?.permissions();
+
+        let found = match format {
+            Format::Zip => index_zip(File::open(&path)?)?,
+            Format::Tar | Format::TarGz => index_tar(reader(&path, format)?)?,
+        };
+
+        let mut items = BTreeMap::new();
+
+        for item in found {
+            // directories are often only implied by the paths of the files in them
+            for parent in item.path.ancestors().skip(1) {
+                if parent.as_os_str().is_empty() {
+                    break;
+                }
+
+                items.entry(parent.to_path_buf()).or_insert_with(|| Item {
+                    path: parent.to_path_buf(),
+                    is_dir: true,
+                    size: 0,
+                    modified: None,
+                    mode: None,
+                });
+            }
+
+            items.insert(item.path.clone(), item);
+        }
+
+        Ok(Self {
+            path,
+            format,
+            items,
+            permissions,
+        })
+    }
+
+    /// Where the archive is.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[inline]
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// An item by its path within the archive.
+    #[must_use]
+    #[inline]
+    pub fn get(&self, inner: &Path) -> Option<&Item> {
+        self.items.get(inner)
+    }
+
+    /// Check if a path within the archive is a directory. The empty path is the archive itself.
+    #[must_use]
+    pub fn is_dir(&self, inner: &Path) -> bool {
+        inner.as_os_str().is_empty() || self.get(inner).is_some_and(|i| i.is_dir)
+    }
+
+    /// Items directly inside a directory of the archive.
+    pub fn list<'a>(&'a self, dir: &'a Path) -> impl Iterator<Item = &'a Item> + 'a {
+        self.items
+            .values()
+            .filter(move |i| i.path.parent() == Some(dir))
+    }
+
+    /// Entries for the items directly inside a directory of the archive.
+    pub fn entries(&self, dir: &Path) -> Vec<Entry> {
+        self.list(dir).map(|i| self.entry(i)).collect()
+    }
+
+    /// Describe an item as an entry, with its path inside the archive's own path.
+    #[must_use]
+    pub fn entry(&self, item: &Item) -> Entry {
+        #[allow(unused_mut)]
+        let mut permissions = self.permissions.clone();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            // the archive file's own mode would make folders unbrowsable
+            match item.mode {
+                Some(mode) => permissions.set_mode(mode),
+                None if item.is_dir => permissions.set_mode(0o755),
+                None => {}
+            }
+        }
+
+        let path = PathWrap::from_into_path(self.path.join(&item.path));
+
+        Entry {
+            kind: if item.is_dir {
+                Kind::Directory
+            } else {
+                Kind::File
+            },
+            size: if item.is_dir {
+                self.size_of(&item.path)
+            } else {
+                item.size
+            },
+            modified: item.modified,
+            created: None,
+            accessed: None,
+            permissions,
+            owner: None,
+            hidden: path
+                .file_name()
+                .is_some_and(|n| n.as_encoded_bytes().starts_with(b".")),
+            symlink: None,
+            path,
+        }
+    }
+
+    /// Combined size of an item and everything inside it.
+    #[must_use]
+    pub fn size_of(&self, inner: &Path) -> u64 {
+        self.items
+            .range(inner.to_path_buf()..)
+            .take_while(|(p, _)| p.starts_with(inner))
+            .filter(|(_, i)| !i.is_dir)
+            .map(|(_, i)| i.size)
+            .sum()
+    }
+
+    /// Number of files in an item, counting itself if it's a file.
+    #[must_use]
+    pub fn files_in(&self, inner: &Path) -> u64 {
+        self.items
+            .range(inner.to_path_buf()..)
+            .take_while(|(p, _)| p.starts_with(inner))
+            .filter(|(_, i)| !i.is_dir)
+            .count() as u64
+    }
+
+    /// Copy items out of the archive, each to its own target path.
+    ///
+    /// Targets are given as `(path within the archive, target)` pairs, and directories are
+    /// extracted with everything inside them. Every directory created and file written is passed
+    /// to `visit`, along with the number of bytes written or why it failed. Fails as a whole only
+    /// if the archive itself can't be read.
+    pub fn extract<F>(
+        &self,
+        targets: &[(PathBuf, PathBuf)],
+        cancel: &CancelToken,
+        mut visit: F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(&Path, io::Result<u64>),
+    {
+        let target = |inner: &Path| {
+            targets.iter().find_map(|(from, to)| {
+                let rest = inner.strip_prefix(from).ok()?;
+
+                Some(if rest.as_os_str().is_empty() {
+                    to.clone()
+                } else {
+                    to.join(rest)
+                })
+            })
+        };
+
+        // directories first, so empty ones are extracted too
+        for item in self.items.values().filter(|i| i.is_dir) {
+            if let Some(target) = target(&item.path) {
+                visit(&target, fs::create_dir_all(&target).map(|_| 0));
+            }
+        }
+
+        match self.format {
+            Format::Zip => {
+                let mut zip = ZipArchive::new(File::open(&self.path)?)?;
+
+                for index in 0..zip.len() {
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+
+                    let mut file = zip.by_index(index)?;
+
+                    let Some(inner) = clean(Path::new(file.name())) else {
+                        continue;
+                    };
+
+                    if file.is_dir() {
+                        continue;
+                    }
+
+                    if let Some(target) = target(&inner) {
+                        let result = self.write(&inner, &mut file, &target);
+                        visit(&target, result);
+                    }
+                }
+            }
+            Format::Tar | Format::TarGz => {
+                let mut tar = tar::Archive::new(reader(&self.path, self.format)?);
+
+                for entry in tar.entries()? {
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+
+                    let mut entry = entry?;
+
+                    if !entry.header().entry_type().is_file() {
+                        continue;
+                    }
+
+                    let Some(inner) = clean(&entry.path()?) else {
+                        continue;
+                    };
+
+                    if let Some(target) = target(&inner) {
+                        let result = self.write(&inner, &mut entry, &target);
+                        visit(&target, result);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the whole contents of a file in the archive.
+    pub fn read(&self, inner: &Path) -> io::Result<Vec<u8>> {
+        let not_found = || io::Error::new(io::ErrorKind::NotFound, "no such file in the archive");
+
+        match self.get(inner) {
+            Some(item) if !item.is_dir => {}
+            _ => return Err(not_found()),
+        }
+
+        let mut data = vec![];
+
+        match self.format {
+            Format::Zip => {
+                let mut zip = ZipArchive::new(File::open(&self.path)?)?;
+
+                for index in 0..zip.len() {
+                    let mut file = zip.by_index(index)?;
+
+                    if !file.is_dir() && clean(Path::new(file.name())).as_deref() == Some(inner) {
+                        file.read_to_end(&mut data)?;
+                        return Ok(data);
+                    }
+                }
+            }
+            Format::Tar | Format::TarGz => {
+                let mut tar = tar::Archive::new(reader(&self.path, self.format)?);
+
+                for entry in tar.entries()? {
+                    let mut entry = entry?;
+
+                    if entry.header().entry_type().is_file()
+                        && clean(&entry.path()?).as_deref() == Some(inner)
+                    {
+                        entry.read_to_end(&mut data)?;
+                        return Ok(data);
+                    }
+                }
+            }
+        }
+
+        Err(not_found())
+    }
+
+    /// Write the contents of a file to a target, keeping its permissions and modification time.
+    fn write(&self, inner: &Path, contents: &mut dyn Read, target: &Path) -> io::Result<u64> {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = File::create(target)?;
+        let written = io::copy(contents, &mut file)?;
+
+        if let Some(item) = self.get(inner) {
+            #[cfg(unix)]
+            if let Some(mode) = item.mode {
+                use std::os::unix::fs::PermissionsExt;
+
+                file.set_permissions(Permissions::from_mode(mode & 0o777))?;
+            }
+
+            if let Some(modified) = item.modified {
+                file.set_times(FileTimes::new().set_modified(modified))?;
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+/// Split a path into an archive and the path of an item within it.
+///
+/// Returns [`None`] if no part of the path is an archive. The archive itself splits into an empty
+/// path within it.
+#[must_use]
+pub fn split(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    for archive in path.ancestors() {
+        if Format::detect(archive).is_some() && archive.is_file() {
+            let inner = path.strip_prefix(archive).ok()?;
+            return Some((archive.to_path_buf(), inner.to_path_buf()));
+        }
+    }
+
+    None
+}
+
+/// Check if a path is something inside an archive, rather than a real item.
+#[must_use]
+#[inline]
+pub fn is_inside(path: &Path) -> bool {
+    split(path).is_some_and(|(_, inner)| !inner.as_os_str().is_empty())
+}
+
+/// Read a tar, decompressing it if needed.
+fn reader(path: &Path, format: Format) -> io::Result<Box<dyn Read>> {
+    let file = BufReader::new(File::open(path)?);
+
+    Ok(match format {
+        Format::TarGz => Box::new(GzDecoder::new(file)),
+        _ => Box::new(file),
+    })
+}
+
+/// Every item listed in a zip.
+fn index_zip(file: File) -> io::Result<Vec<Item>> {
+    let mut zip = ZipArchive::new(BufReader::new(file))?;
+    let mut items = Vec::with_capacity(zip.len());
+
+    for index in 0..zip.len() {
+        // only the headers are needed, so don't decompress anything
+        let file = zip.by_index_raw(index)?;
+
+        let Some(path) = clean(Path::new(file.name())) else {
+            continue;
+        };
+
+        let time = file.last_modified();
+        let modified =
+            NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())
+                .and_then(|d| {
+                    d.and_hms_opt(
+                        time.hour().into(),
+                        time.minute().into(),
+                        time.second().into(),
+                    )
+                })
+                // zip times have no time zone, and are usually local
+                .and_then(|t| Local.from_local_datetime(&t).earliest())
+                .map(SystemTime::from);
+
+        items.push(Item {
+            path,
+            is_dir: file.is_dir(),
+            size: file.size(),
+            modified,
+            mode: file.unix_mode().map(|m| m & 0o7777),
+        });
+    }
+
+    Ok(items)
+}
+
+/// Every file and directory listed in a tar.
+fn index_tar(reader: Box<dyn Read>) -> io::Result<Vec<Item>> {
+    let mut tar = tar::Archive::new(reader);
+    let mut items = vec![];
+
+    for entry in tar.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        let kind = header.entry_type();
+
+        // links and special files aren't shown
+        if !kind.is_file() && !kind.is_dir() {
+            continue;
+        }
+
+        let Some(path) = clean(&entry.path()?) else {
+            continue;
+        };
+
+        items.push(Item {
+            path,
+            is_dir: kind.is_dir(),
+            size: header.size()?,
+            modified: header
+                .mtime()
+                .ok()
+                .map(|s| SystemTime::UNIX_EPOCH + Duration::from_secs(s)),
+            mode: header.mode().ok().map(|m| m & 0o7777),
+        });
+    }
+
+    Ok(items)
+}
+
+/// A path within an archive with anything that could escape it removed.
+///
+/// Returns [`None`] for paths that would escape the archive, or that name the archive itself.
+fn clean(path: &Path) -> Option<PathBuf> {
+    let mut clean = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => clean.push(c),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    (!clean.as_os_str().is_empty()).then_some(clean)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_kept_inside() {
+        assert_eq!(clean(Path::new("a/b")), Some(PathBuf::from("a/b")));
+        assert_eq!(clean(Path::new("./a/./b/")), Some(PathBuf::from("a/b")));
+
+        assert_eq!(clean(Path::new("../a")), None);
+        assert_eq!(clean(Path::new("a/../../b")), None);
+        assert_eq!(clean(Path::new("/etc/passwd")), None);
+        assert_eq!(clean(Path::new(".")), None);
+        assert_eq!(clean(Path::new("")), None);
+    }
+
+    /// Add a file to a tar, writing its name as is, so it may escape.
+    fn append(builder: &mut tar::Builder<File>, name: &str, contents: &[u8]) {
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        builder.append(&header, contents).unwrap();
+    }
+
+    #[test]
+    fn escaping_items_are_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.tar");
+
+        let mut builder = tar::Builder::new(File::create(&path).unwrap());
+        append(&mut builder, "docs/readme.txt", b"hello");
+        append(&mut builder, "../evil", b"");
+        append(&mut builder, "/etc/evil", b"");
+        builder.into_inner().unwrap();
+
+        let archive = Archive::open(&path).unwrap();
+
+        // the folder is only implied by the file inside it
+        assert!(archive.is_dir(Path::new("docs")));
+        assert_eq!(archive.get(Path::new("docs/readme.txt")).unwrap().size, 5);
+
+        let top: Vec<_> = archive.list(Path::new("")).map(|i| &i.path).collect();
+        assert_eq!(top, [Path::new("docs")]);
+    }
+}
@@ -1,10 +1,25 @@
 //! Directory tools.
 
-use std::path::Path;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
 
 use directories::{BaseDirs, ProjectDirs, UserDirs};
 
+use crate::entry::Entry;
+use crate::filter::Filter;
 use crate::path::PathWrap;
+use crate::sort::Sort;
+use crate::task::CancelToken;
+use crate::vfs::{self, Uri};
+
+/// Number of entries in the first batch of a background read. Later batches double in size.
+const FIRST_BATCH: usize = 128;
+/// Largest batch of a background read.
+const MAX_BATCH: usize = 8192;
 
 lazy_static::lazy_static! {
     pub static ref PROJECT: ProjectDirs
@@ -47,56 +62,562 @@ macro_rules! resource_make {
     };
 }
 
+/// Directories with shared data such as installed applications, most important first.
+///
+/// This is the user's own data directory followed by `XDG_DATA_DIRS`, as described by the
+/// [XDG base directory spec](https://specifications.freedesktop.org/basedir-spec/latest/).
+pub fn data_dirs() -> Vec<PathBuf> {
+    std::iter::once(BASE.data_dir().to_path_buf())
+        .chain(search_path("XDG_DATA_DIRS", "/usr/local/share:/usr/share"))
+        .collect()
+}
+
+/// Directories with configuration shared between programs, most important first.
+///
+/// This is the user's own configuration directory followed by `XDG_CONFIG_DIRS`.
+pub fn config_dirs() -> Vec<PathBuf> {
+    std::iter::once(BASE.config_dir().to_path_buf())
+        .chain(search_path("XDG_CONFIG_DIRS", "/etc/xdg"))
+        .collect()
+}
+
+/// Absolute paths in a variable like `PATH`, or in a default if it's unset or empty.
+fn search_path(variable: &str, default: &str) -> Vec<PathBuf> {
+    let value = env::var_os(variable)
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| default.into());
+
+    // relative paths are invalid, and ignored
+    env::split_paths(&value)
+        .filter(|p| p.is_absolute())
+        .collect()
+}
+
+/// Create a directory, and any missing parents, only accessible by the current user.
+pub(crate) fn create_private_dir(path: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    builder.create(path)
+}
+
+/// A change to a single item, as reported by a filesystem watcher.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Created(PathBuf),
+    Removed(PathBuf),
+    /// Renamed from the first path to the second.
+    Renamed(PathBuf, PathBuf),
+    /// Contents or metadata changed.
+    Modified(PathBuf),
+}
+
+impl Change {
+    /// Check if this change affects an item directly inside a directory.
+    #[must_use]
+    pub fn is_inside(&self, directory: &Path) -> bool {
+        let inside = |p: &PathBuf| p.parent() == Some(directory);
+
+        match self {
+            Self::Created(p) | Self::Removed(p) | Self::Modified(p) => inside(p),
+            Self::Renamed(from, to) => inside(from) || inside(to),
+        }
+    }
+
+    /// Every path affected by this change.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        let (first, second) = match self {
+            Self::Created(p) | Self::Removed(p) | Self::Modified(p) => (p, None),
+            Self::Renamed(from, to) => (from, Some(to)),
+        };
+
+        std::iter::once(first.as_path()).chain(second.map(PathBuf::as_path))
+    }
+}
+
 /// Reads the contents of a specific directory.
 ///
 /// Designed to be readable and self-update as frequently as possible.
 #[derive(Debug)]
 pub struct Contents {
     /// The currently open location.
-    location: PathWrap,
+    location: Uri,
     /// Items in current location.
-    contents: Vec<PathWrap>,
+    contents: Vec<Entry>,
+    /// Order of the items.
+    sort: Sort,
+    /// Which items are shown.
+    filter: Filter,
+    /// Changes received while still being read in the background.
+    pending: Option<Vec<Change>>,
 }
 
 impl Contents {
+    #[inline]
     pub fn new<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
-        let location = PathWrap::from_path(path);
+        Self::with_options(path, Sort::default(), Filter::default())
+    }
 
+    /// Read a path with a specific sort order and filter.
+    #[inline]
+    pub fn with_options<P: AsRef<Path>>(
+        path: P,
+        sort: Sort,
+        filter: Filter,
+    ) -> anyhow::Result<Self> {
+        Self::at(vfs::locate(path), sort, filter)
+    }
+
+    /// Read a location on any backend.
+    pub fn at(location: Uri, sort: Sort, filter: Filter) -> anyhow::Result<Self> {
         let mut items = Vec::new();
-        Self::read_items_into(&location, &mut items)?;
+        Self::read_items_into(&location, &filter, &mut items)?;
+        sort.apply(&mut items);
         let contents = items;
 
-        Ok(Self { location, contents })
+        Ok(Self {
+            location,
+            contents,
+            sort,
+            filter,
+            pending: None,
+        })
     }
 
-    /// Get the location this tab points to.
+    /// Empty contents, to be filled in by a [`Reader`].
+    ///
+    /// Changes applied before [`Contents::finish`] is called are held back until then, so they
+    /// aren't undone by entries read before the change happened.
+    pub fn empty(location: Uri, sort: Sort, filter: Filter) -> Self {
+        Self {
+            location,
+            contents: vec![],
+            sort,
+            filter,
+            pending: Some(vec![]),
+        }
+    }
+
+    /// Start reading this location in the background.
+    #[must_use]
+    pub fn reader(&self, cancel: CancelToken) -> Reader {
+        Reader::new(self.location.clone(), self.filter.clone(), cancel)
+    }
+
+    /// Add a batch of entries from a background read.
+    pub fn extend(&mut self, mut batch: Vec<Entry>) {
+        self.sort.apply(&mut batch);
+        self.contents.extend(batch);
+        // cheap, since this is just merging two sorted runs
+        self.sort.apply(&mut self.contents);
+    }
+
+    /// Mark a background read as done, applying any changes received in the meantime.
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        for change in self.pending.take().unwrap_or_default() {
+            self.apply(&change)?;
+        }
+
+        Ok(())
+    }
+
+    /// Check if the contents are still being read in the background.
+    #[inline]
+    pub fn is_loading(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Get the path this tab points to, on whichever backend it's on.
     #[inline]
     pub fn location(&self) -> &PathWrap {
+        self.location.path()
+    }
+
+    /// Get the location this tab points to.
+    #[inline]
+    pub fn uri(&self) -> &Uri {
         &self.location
     }
 
     /// Read the contents of this directory.
     #[inline]
-    pub fn contents(&self) -> &[PathWrap] {
+    pub fn contents(&self) -> &[Entry] {
         &self.contents
     }
 
+    /// How the contents are sorted.
+    #[inline]
+    pub fn sort(&self) -> Sort {
+        self.sort
+    }
+
+    /// Change the sort order, re-sorting the current contents.
+    pub fn set_sort(&mut self, sort: Sort) {
+        self.sort = sort;
+        self.sort.apply(&mut self.contents);
+    }
+
+    /// Which items are shown.
+    #[inline]
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    /// Change which items are shown, re-reading the contents.
+    pub fn set_filter(&mut self, filter: Filter) -> anyhow::Result<()> {
+        self.filter = filter;
+        self.update_contents()
+    }
+
     /// Update contents if needed.
+    ///
+    /// This replaces any background read, so changes held back for it are dropped.
     pub fn update_contents(&mut self) -> anyhow::Result<()> {
-        Self::read_items_into(&self.location, &mut self.contents)
+        self.pending = None;
+        Self::read_items_into(&self.location, &self.filter, &mut self.contents)?;
+        self.sort.apply(&mut self.contents);
+        Ok(())
+    }
+
+    /// Apply a single change without re-reading the whole directory.
+    ///
+    /// Changes to items outside this location are ignored. Returns whether the contents changed.
+    pub fn apply(&mut self, change: &Change) -> anyhow::Result<bool> {
+        if let Some(pending) = &mut self.pending {
+            pending.push(change.clone());
+            return Ok(false);
+        }
+
+        match change {
+            Change::Created(path) | Change::Modified(path) => {
+                if self.contains(path) && self.filter.depends_on(path) {
+                    self.update_contents()?;
+                    return Ok(true);
+                }
+
+                Ok(self.reload(path))
+            }
+            Change::Removed(path) => {
+                if self.contains(path) && self.filter.depends_on(path) {
+                    self.update_contents()?;
+                    return Ok(true);
+                }
+
+                Ok(self.remove(path).is_some())
+            }
+            Change::Renamed(from, to) => {
+                let removed = self.apply(&Change::Removed(from.clone()))?;
+                let created = self.apply(&Change::Created(to.clone()))?;
+
+                Ok(removed || created)
+            }
+        }
+    }
+
+    /// Check if a path is directly inside this location.
+    fn contains(&self, path: &Path) -> bool {
+        path.parent() == Some(self.location().as_ref())
+    }
+
+    /// Remove an item, returning its position.
+    fn remove(&mut self, path: &Path) -> Option<usize> {
+        let index = self.contents.iter().position(|e| e.path.as_ref() == path)?;
+        self.contents.remove(index);
+        Some(index)
+    }
+
+    /// Re-read a single item, putting it back in sorted position if it's still visible.
+    fn reload(&mut self, path: &Path) -> bool {
+        if !self.contains(path) {
+            return false;
+        }
+
+        let removed = self.remove(path).is_some();
+
+        let uri = self.location.with_path(path);
+        let entry = match vfs::backend(&uri).and_then(|b| b.stat(&uri)) {
+            Ok(e) if self.filter.matcher_at(&self.location).is_visible(&e) => e,
+            _ => return removed,
+        };
+
+        let index = self
+            .contents
+            .partition_point(|e| self.sort.compare(e, &entry).is_lt());
+        self.contents.insert(index, entry);
+
+        true
     }
 
-    /// Get items in this location.
+    /// Get items in this location that pass the filter.
     ///
     /// Clears the provided buffer of all its previous contents.
-    fn read_items_into(path: &Path, buf: &mut Vec<PathWrap>) -> anyhow::Result<()> {
+    fn read_items_into(
+        location: &Uri,
+        filter: &Filter,
+        buf: &mut Vec<Entry>,
+    ) -> anyhow::Result<()> {
         buf.clear();
 
+        let matcher = filter.matcher_at(location);
+
         // TODO: collect_into when its stabilized
-        for entry in path.read_dir()?.filter_map(Result::ok) {
-            buf.push(PathWrap::from_into_path(entry.path()));
+        // items removed while reading are skipped
+        for entry in vfs::backend(location)?
+            .list(location)?
+            .filter_map(Result::ok)
+        {
+            if matcher.is_visible(&entry) {
+                buf.push(entry);
+            }
         }
 
         Ok(())
     }
 }
+
+/// Handle to a directory being read on a background thread.
+///
+/// Iterating blocks until the next batch of entries is available, and stops once the
+/// whole directory has been read or the read was cancelled.
+#[derive(Debug)]
+pub struct Reader {
+    batches: Receiver<io::Result<Vec<Entry>>>,
+    cancel: CancelToken,
+}
+
+impl Reader {
+    /// Start reading a location on a background thread.
+    #[must_use]
+    pub fn new(location: Uri, filter: Filter, cancel: CancelToken) -> Self {
+        let (sender, batches) = mpsc::channel();
+
+        let worker_cancel = cancel.clone();
+        thread::spawn(move || {
+            let entries = match vfs::backend(&location).and_then(|b| b.list(&location)) {
+                Ok(e) => e,
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+            };
+
+            let matcher = filter.matcher_at(&location);
+
+            let mut size = FIRST_BATCH;
+            let mut batch = Vec::with_capacity(size);
+
+            // items removed while reading are skipped
+            for entry in entries.filter_map(Result::ok) {
+                if worker_cancel.is_cancelled() {
+                    return;
+                }
+
+                if !matcher.is_visible(&entry) {
+                    continue;
+                }
+
+                batch.push(entry);
+
+                if batch.len() == size {
+                    if sender.send(Ok(batch)).is_err() {
+                        return;
+                    }
+
+                    size = (size * 2).min(MAX_BATCH);
+                    batch = Vec::with_capacity(size);
+                }
+            }
+
+            if !batch.is_empty() {
+                let _ = sender.send(Ok(batch));
+            }
+        });
+
+        Self { batches, cancel }
+    }
+
+    /// Stop reading as soon as possible.
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+}
+
+impl Iterator for Reader {
+    type Item = io::Result<Vec<Entry>>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.batches.recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Names of the items listed, in order.
+    fn names(contents: &Contents) -> Vec<String> {
+        contents
+            .contents()
+            .iter()
+            .map(|e| e.name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// A folder with a few files, and a folder next to it.
+    fn setup() -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let root = tempfile::tempdir().unwrap();
+        let (dir, other) = (root.path().join("dir"), root.path().join("other"));
+
+        fs::create_dir(&dir).unwrap();
+        fs::create_dir(&other).unwrap();
+        for name in ["b", "d"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        (root, dir, other)
+    }
+
+    #[test]
+    fn created_items_are_added_in_order() {
+        let (_root, dir, other) = setup();
+        let mut contents = Contents::new(&dir).unwrap();
+
+        fs::write(dir.join("c"), "").unwrap();
+        assert!(contents.apply(&Change::Created(dir.join("c"))).unwrap());
+        assert_eq!(names(&contents), ["b", "c", "d"]);
+
+        // items elsewhere and hidden items aren't listed
+        fs::write(other.join("a"), "").unwrap();
+        assert!(!contents.apply(&Change::Created(other.join("a"))).unwrap());
+        fs::write(dir.join(".hidden"), "").unwrap();
+        assert!(!contents
+            .apply(&Change::Created(dir.join(".hidden")))
+            .unwrap());
+        assert_eq!(names(&contents), ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn removed_items_are_dropped() {
+        let (_root, dir, _other) = setup();
+        let mut contents = Contents::new(&dir).unwrap();
+
+        fs::remove_file(dir.join("b")).unwrap();
+        assert!(contents.apply(&Change::Removed(dir.join("b"))).unwrap());
+        assert_eq!(names(&contents), ["d"]);
+
+        assert!(!contents.apply(&Change::Removed(dir.join("b"))).unwrap());
+    }
+
+    #[test]
+    fn modified_items_are_read_again() {
+        let (_root, dir, _other) = setup();
+        let mut contents = Contents::new(&dir).unwrap();
+
+        fs::write(dir.join("d"), "bigger").unwrap();
+        assert!(contents.apply(&Change::Modified(dir.join("d"))).unwrap());
+
+        assert_eq!(names(&contents), ["b", "d"]);
+        assert_eq!(contents.contents()[1].size, 6);
+    }
+
+    #[test]
+    fn renames_inside_move_items() {
+        let (_root, dir, _other) = setup();
+        let mut contents = Contents::new(&dir).unwrap();
+
+        fs::rename(dir.join("b"), dir.join("e")).unwrap();
+        let change = Change::Renamed(dir.join("b"), dir.join("e"));
+        assert!(contents.apply(&change).unwrap());
+
+        assert_eq!(names(&contents), ["d", "e"]);
+    }
+
+    #[test]
+    fn renames_into_the_listing_add_items() {
+        let (_root, dir, other) = setup();
+        let mut contents = Contents::new(&dir).unwrap();
+
+        fs::write(other.join("x"), "").unwrap();
+        fs::rename(other.join("x"), dir.join("a")).unwrap();
+        let change = Change::Renamed(other.join("x"), dir.join("a"));
+        assert!(contents.apply(&change).unwrap());
+
+        assert_eq!(names(&contents), ["a", "b", "d"]);
+    }
+
+    #[test]
+    fn renames_out_of_the_listing_drop_items() {
+        let (_root, dir, other) = setup();
+        let mut contents = Contents::new(&dir).unwrap();
+
+        fs::rename(dir.join("b"), other.join("b")).unwrap();
+        let change = Change::Renamed(dir.join("b"), other.join("b"));
+        assert!(contents.apply(&change).unwrap());
+
+        assert_eq!(names(&contents), ["d"]);
+    }
+
+    #[test]
+    fn ignore_file_changes_read_everything_again() {
+        let (_root, dir, _other) = setup();
+        let mut filter = Filter::default();
+        filter.respect_ignore = true;
+        let mut contents = Contents::with_options(&dir, Sort::default(), filter).unwrap();
+
+        fs::write(dir.join(".ignore"), "b\n").unwrap();
+        assert!(contents
+            .apply(&Change::Created(dir.join(".ignore")))
+            .unwrap());
+
+        assert_eq!(names(&contents), ["d"]);
+    }
+
+    #[test]
+    fn changes_while_reading_wait_until_finished() {
+        let (_root, dir, _other) = setup();
+        let mut contents = Contents::empty(vfs::locate(&dir), Sort::default(), Filter::default());
+
+        // read before the changes below
+        let read = [
+            Entry::read(dir.join("b")).unwrap(),
+            Entry::read(dir.join("d")).unwrap(),
+        ];
+
+        fs::remove_file(dir.join("b")).unwrap();
+        fs::write(dir.join("c"), "").unwrap();
+        assert!(!contents.apply(&Change::Removed(dir.join("b"))).unwrap());
+        assert!(!contents.apply(&Change::Created(dir.join("c"))).unwrap());
+        assert!(contents.is_loading());
+
+        contents.extend(read.to_vec());
+        assert_eq!(names(&contents), ["b", "d"]);
+
+        contents.finish().unwrap();
+        assert!(!contents.is_loading());
+        assert_eq!(names(&contents), ["c", "d"]);
+    }
+
+    #[test]
+    fn changes_know_where_they_are() {
+        let dir = Path::new("/home/me");
+        let renamed = Change::Renamed(dir.join("a"), PathBuf::from("/tmp/a"));
+
+        assert!(renamed.is_inside(dir));
+        assert!(renamed.is_inside(Path::new("/tmp")));
+        assert!(!Change::Created(dir.join("sub/a")).is_inside(dir));
+        assert_eq!(
+            renamed.paths().collect::<Vec<_>>(),
+            [dir.join("a"), PathBuf::from("/tmp/a")]
+        );
+    }
+}
@@ -0,0 +1,450 @@
+//! Finding files with the same contents.
+//!
+//! Files are compared in stages, each more expensive than the last: first by size, then by a hash
+//! of their first few kilobytes, and only then by a hash of their whole contents. Most files are
+//! told apart long before they're read in full.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, Metadata};
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+use ignore::WalkState;
+use rayon::prelude::*;
+
+use crate::filter::Filter;
+use crate::path::PathWrap;
+use crate::search::{self, Search};
+use crate::size;
+use crate::task::CancelToken;
+
+/// How much of the start of each file is hashed before hashing it whole.
+const PARTIAL: u64 = 4 * 1024;
+
+/// How many files are listed or compared between progress reports.
+const REPORT_EVERY: usize = 256;
+
+/// Where to look for duplicates.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Directory to search in.
+    pub root: PathWrap,
+    /// Which items are searched.
+    pub filter: Filter,
+}
+
+/// How far a search has got.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Files found so far.
+    pub listed: usize,
+    /// Files that share their size with another, and so may have copies.
+    pub candidates: usize,
+    /// Candidates compared with the others of their size so far.
+    pub compared: usize,
+}
+
+/// Files with exactly the same contents.
+#[derive(Debug, Clone)]
+pub struct Group {
+    /// Size of each file.
+    pub size: u64,
+    /// Every copy, in path order.
+    pub paths: Vec<PathWrap>,
+}
+
+impl Group {
+    /// Space that would be freed by keeping only one copy.
+    #[inline]
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64).saturating_sub(1)
+    }
+}
+
+/// Forget copies that are gone, such as after being trashed, along with groups left with only one.
+pub fn prune(groups: &mut Vec<Group>) {
+    for group in groups.iter_mut() {
+        group.paths.retain(|p| p.exists());
+    }
+
+    groups.retain(|g| g.paths.len() > 1);
+}
+
+/// Something reported by a running search.
+#[derive(Debug, Clone)]
+pub enum Found {
+    Progress(Progress),
+    Group(Group),
+}
+
+/// A file that may have copies.
+struct Candidate {
+    path: PathWrap,
+    size: u64,
+}
+
+/// Start looking for duplicates on background threads.
+///
+/// Empty files aren't reported, and neither are several hard links to the same file, since
+/// removing them frees no space.
+pub fn spawn(query: Query, cancel: CancelToken) -> Search<Found> {
+    let (sender, found) = mpsc::channel();
+
+    let worker_cancel = cancel.clone();
+    thread::spawn(move || {
+        let finder = Finder {
+            sender,
+            cancel: worker_cancel,
+            listed: AtomicUsize::new(0),
+            compared: AtomicUsize::new(0),
+            candidates: AtomicUsize::new(0),
+        };
+
+        let files = finder.list(&query);
+
+        if finder.cancel.is_cancelled() {
+            return;
+        }
+
+        let by_size = group_by(files, |f| Some(f.size));
+        let candidates: usize = by_size.iter().map(Vec::len).sum();
+
+        finder.candidates.store(candidates, Ordering::Relaxed);
+        finder.report();
+
+        size::POOL.install(|| {
+            by_size
+                .into_par_iter()
+                .for_each_with(finder.sender.clone(), |sender, files| {
+                    for group in finder.compare(files) {
+                        if sender.send(Found::Group(group)).is_err() {
+                            finder.cancel.cancel();
+                        }
+                    }
+                });
+        });
+
+        finder.report();
+    });
+
+    Search::new(found, cancel)
+}
+
+/// State shared while looking for duplicates.
+struct Finder {
+    sender: Sender<Found>,
+    cancel: CancelToken,
+    listed: AtomicUsize,
+    candidates: AtomicUsize,
+    compared: AtomicUsize,
+}
+
+impl Finder {
+    /// Every non-empty file below the root, with only one path for each hard linked file.
+    fn list(&self, query: &Query) -> Vec<Candidate> {
+        let files = &Mutex::new(vec![]);
+        let links = &Mutex::new(HashSet::new());
+
+        search::walker(&query.root, &query.filter).run(|| {
+            Box::new(move |result| {
+                if self.cancel.is_cancelled() {
+                    return WalkState::Quit;
+                }
+
+                let Ok(dent) = result else {
+                    return WalkState::Continue;
+                };
+
+                // symlinks are never followed, so only regular files count
+                if !dent.file_type().is_some_and(|t| t.is_file()) {
+                    return WalkState::Continue;
+                }
+
+                let Ok(metadata) = dent.metadata() else {
+                    return WalkState::Continue;
+                };
+
+                if metadata.len() == 0 || !is_first_link(&metadata, links) {
+                    return WalkState::Continue;
+                }
+
+                files.lock().unwrap().push(Candidate {
+                    path: PathWrap::from_path(dent.path()),
+                    size: metadata.len(),
+                });
+
+                let listed = self.listed.fetch_add(1, Ordering::Relaxed);
+
+                if listed.is_multiple_of(REPORT_EVERY) {
+                    self.report();
+                }
+
+                WalkState::Continue
+            })
+        });
+
+        let mut files = files.lock().unwrap();
+        std::mem::take(&mut *files)
+    }
+
+    /// Split files of the same size into groups with the same contents.
+    fn compare(&self, files: Vec<Candidate>) -> Vec<Group> {
+        let (size, count) = (files[0].size, files.len());
+
+        // small files are read whole by the first hash, so there's nothing more to compare
+        let partial = group_by(files, |f| self.hash(&f.path, Some(PARTIAL)));
+
+        let groups = if size <= PARTIAL {
+            partial
+        } else {
+            partial
+                .into_iter()
+                .flat_map(|files| group_by(files, |f| self.hash(&f.path, None)))
+                .collect()
+        };
+
+        let before = self.compared.fetch_add(count, Ordering::Relaxed);
+
+        if before / REPORT_EVERY != (before + count) / REPORT_EVERY {
+            self.report();
+        }
+
+        groups
+            .into_iter()
+            .map(|files| {
+                let mut paths: Vec<_> = files.into_iter().map(|f| f.path).collect();
+                paths.sort();
+
+                Group { size, paths }
+            })
+            .collect()
+    }
+
+    /// Hash the start of a file, or all of it, or [`None`] if it can't be read or the search was
+    /// cancelled.
+    fn hash(&self, path: &Path, limit: Option<u64>) -> Option<blake3::Hash> {
+        let mut reader = File::open(path).ok()?.take(limit.unwrap_or(u64::MAX));
+
+        let mut hasher = blake3::Hasher::new();
+        let mut buf = vec![0; 64 * 1024];
+
+        loop {
+            if self.cancel.is_cancelled() {
+                return None;
+            }
+
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => hasher.update(&buf[..read]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return None,
+            };
+        }
+
+        Some(hasher.finalize())
+    }
+
+    /// Send how far the search has got.
+    fn report(&self) {
+        let _ = self.sender.send(Found::Progress(Progress {
+            listed: self.listed.load(Ordering::Relaxed),
+            candidates: self.candidates.load(Ordering::Relaxed),
+            compared: self.compared.load(Ordering::Relaxed),
+        }));
+    }
+}
+
+/// Group files by some key, leaving out files without one and groups of a single file.
+fn group_by<K, F>(files: Vec<Candidate>, key: F) -> Vec<Vec<Candidate>>
+where
+    K: Eq + std::hash::Hash,
+    F: Fn(&Candidate) -> Option<K>,
+{
+    let mut groups: HashMap<K, Vec<Candidate>> = HashMap::new();
+
+    for file in files {
+        if let Some(key) = key(&file) {
+            groups.entry(key).or_default().push(file);
+        }
+    }
+
+    groups.into_values().filter(|g| g.len() > 1).collect()
+}
+
+/// Check if no other link to the same file was listed yet.
+#[cfg(unix)]
+fn is_first_link(metadata: &Metadata, links: &Mutex<HashSet<(u64, u64)>>) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.nlink() < 2
+        || links
+            .lock()
+            .unwrap()
+            .insert((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn is_first_link(_: &Metadata, _: &Mutex<HashSet<(u64, u64)>>) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn wrap(path: impl AsRef<Path>) -> PathWrap {
+        PathWrap::from_into_path(path.as_ref().to_path_buf())
+    }
+
+    /// Every group found below a directory, in path order.
+    fn find(root: &Path) -> Vec<Group> {
+        let query = Query {
+            root: wrap(root),
+            filter: Filter::default(),
+        };
+
+        let mut groups: Vec<_> = spawn(query, CancelToken::new())
+            .filter_map(|found| match found {
+                Found::Group(group) => Some(group),
+                Found::Progress(_) => None,
+            })
+            .collect();
+        groups.sort_by(|a, b| a.paths.cmp(&b.paths));
+        groups
+    }
+
+    #[test]
+    fn only_identical_files_are_grouped() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a"), "same content").unwrap();
+        fs::write(dir.path().join("sub/b"), "same content").unwrap();
+        // as big as the others, but different
+        fs::write(dir.path().join("c"), "diff content").unwrap();
+        fs::write(dir.path().join("d"), "unique").unwrap();
+
+        let groups = find(dir.path());
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].size, 12);
+        assert_eq!(
+            groups[0].paths,
+            [wrap(dir.path().join("a")), wrap(dir.path().join("sub/b"))]
+        );
+        assert_eq!(groups[0].wasted(), 12);
+    }
+
+    #[test]
+    fn large_files_are_compared_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let start = vec![b'x'; PARTIAL as usize * 2];
+
+        let mut one = start.clone();
+        one.push(b'1');
+        let mut two = start;
+        two.push(b'2');
+
+        fs::write(dir.path().join("one"), &one).unwrap();
+        fs::write(dir.path().join("one copy"), &one).unwrap();
+        // the same as the others until the very end
+        fs::write(dir.path().join("two"), &two).unwrap();
+
+        let groups = find(dir.path());
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(
+            groups[0].paths,
+            [
+                wrap(dir.path().join("one")),
+                wrap(dir.path().join("one copy"))
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_files_are_not_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "").unwrap();
+        fs::write(dir.path().join("b"), "").unwrap();
+
+        assert!(find(dir.path()).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_are_not_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "content").unwrap();
+        fs::hard_link(dir.path().join("a"), dir.path().join("b")).unwrap();
+
+        assert!(find(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn cancelled_searches_find_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "content").unwrap();
+        fs::write(dir.path().join("b"), "content").unwrap();
+
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let query = Query {
+            root: wrap(dir.path()),
+            filter: Filter::default(),
+        };
+
+        assert_eq!(spawn(query, cancel).count(), 0);
+    }
+
+    #[test]
+    fn groups_of_one_are_left_out() {
+        let candidate = |name: &str, size| Candidate {
+            path: wrap(name),
+            size,
+        };
+        let files = vec![
+            candidate("a", 1),
+            candidate("b", 1),
+            candidate("c", 2),
+            candidate("d", 3),
+        ];
+
+        let groups = group_by(files, |f| (f.size != 3).then_some(f.size));
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].len(), 2);
+    }
+
+    #[test]
+    fn pruning_forgets_missing_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name| wrap(dir.path().join(name));
+        for name in ["a", "b", "c", "x", "y"] {
+            fs::write(path(name), "").unwrap();
+        }
+
+        let mut groups = vec![
+            Group {
+                size: 1,
+                paths: vec![path("a"), path("b"), path("c")],
+            },
+            Group {
+                size: 1,
+                paths: vec![path("x"), path("y")],
+            },
+        ];
+
+        fs::remove_file(path("b")).unwrap();
+        fs::remove_file(path("y")).unwrap();
+        prune(&mut groups);
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].paths, [path("a"), path("c")]);
+    }
+}
@@ -0,0 +1,193 @@
+//! Directory entries with their metadata.
+//!
+//! Metadata is gathered once when an entry is read, so views can sort and display items without
+//! touching the filesystem again.
+
+use std::ffi::OsStr;
+use std::fs::{self, Metadata, Permissions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::path::PathWrap;
+
+/// What an entry is. Symlinks are described by what they point to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Kind {
+    Directory,
+    File,
+    /// A block or character device.
+    Device,
+    /// A named pipe.
+    Fifo,
+    Socket,
+    /// Anything else, including symlinks that don't point anywhere.
+    Other,
+}
+
+impl Kind {
+    fn from_metadata(meta: &Metadata) -> Self {
+        let ty = meta.file_type();
+
+        if ty.is_dir() {
+            return Self::Directory;
+        }
+        if ty.is_file() {
+            return Self::File;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+
+            if ty.is_block_device() || ty.is_char_device() {
+                return Self::Device;
+            }
+            if ty.is_fifo() {
+                return Self::Fifo;
+            }
+            if ty.is_socket() {
+                return Self::Socket;
+            }
+        }
+
+        Self::Other
+    }
+}
+
+/// User and group owning an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Owner {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// A single item in a directory.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathWrap,
+    pub kind: Kind,
+    /// Size in bytes. For symlinks, this is the size of the target.
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub permissions: Permissions,
+    /// Not available on every platform.
+    pub owner: Option<Owner>,
+    pub hidden: bool,
+    /// Where this entry points to, if it's a symlink.
+    pub symlink: Option<PathBuf>,
+}
+
+impl Entry {
+    /// Read an entry and its metadata.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_path(PathWrap::from_path(path))
+    }
+
+    /// Read an entry from an already wrapped path.
+    pub fn read_path(path: PathWrap) -> io::Result<Self> {
+        let own = fs::symlink_metadata(&path)?;
+
+        let (symlink, meta) = if own.file_type().is_symlink() {
+            // A broken link still gets an entry, described by its own metadata
+            (Some(fs::read_link(&path)?), fs::metadata(&path).ok())
+        } else {
+            (None, None)
+        };
+
+        let broken = symlink.is_some() && meta.is_none();
+        let meta = meta.unwrap_or(own);
+
+        Ok(Self {
+            kind: if broken {
+                Kind::Other
+            } else {
+                Kind::from_metadata(&meta)
+            },
+            size: meta.len(),
+            modified: meta.modified().ok(),
+            created: meta.created().ok(),
+            accessed: meta.accessed().ok(),
+            permissions: meta.permissions(),
+            owner: owner(&meta),
+            hidden: is_hidden(&path, &meta),
+            symlink,
+            path,
+        })
+    }
+
+    /// Name of this entry, ie the last component of its path.
+    #[must_use]
+    #[inline]
+    pub fn name(&self) -> &OsStr {
+        self.path.display()
+    }
+
+    /// Check if this entry is a directory, or a symlink to one.
+    #[must_use]
+    #[inline]
+    pub fn is_dir(&self) -> bool {
+        self.kind == Kind::Directory
+    }
+
+    /// Check if this entry is a symlink.
+    #[must_use]
+    #[inline]
+    pub fn is_symlink(&self) -> bool {
+        self.symlink.is_some()
+    }
+
+    /// Unix permission bits, such as `0o755`.
+    #[cfg(unix)]
+    #[must_use]
+    #[inline]
+    pub fn mode(&self) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+
+        self.permissions.mode() & 0o7777
+    }
+}
+
+impl AsRef<Path> for Entry {
+    #[inline]
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+fn owner(meta: &Metadata) -> Option<Owner> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(Owner {
+        uid: meta.uid(),
+        gid: meta.gid(),
+    })
+}
+
+#[cfg(not(unix))]
+fn owner(_: &Metadata) -> Option<Owner> {
+    None
+}
+
+/// Dotfiles are hidden everywhere, and windows also has a hidden attribute.
+fn is_hidden(path: &Path, _meta: &Metadata) -> bool {
+    let dotfile = path
+        .file_name()
+        .is_some_and(|n| n.as_encoded_bytes().starts_with(b"."));
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+        if _meta.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0 {
+            return true;
+        }
+    }
+
+    dotfile
+}
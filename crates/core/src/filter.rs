@@ -0,0 +1,160 @@
+//! Hiding entries from directory listings.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+
+use crate::entry::Entry;
+use crate::vfs::Uri;
+
+/// Ignore files, from highest to lowest precedence within a directory.
+const IGNORE_FILES: [&str; 2] = [".ignore", ".gitignore"];
+
+/// Which entries are shown in a listing.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Show dotfiles and other hidden entries.
+    pub show_hidden: bool,
+    /// Hide entries matched by `.gitignore` and `.ignore` files.
+    pub respect_ignore: bool,
+    /// Entries matching any of these globs are never shown.
+    excludes: Arc<GlobSet>,
+}
+
+impl Filter {
+    /// A filter excluding entries that match any of the given globs.
+    ///
+    /// Globs are matched against both the name and the full path of an entry, so `target` and
+    /// `/home/*/src/**/target` both work.
+    pub fn with_excludes<S: AsRef<str>>(patterns: &[S]) -> Result<Self, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+
+        for pattern in patterns {
+            builder.add(Glob::new(pattern.as_ref())?);
+        }
+
+        Ok(Self {
+            excludes: Arc::new(builder.build()?),
+            ..Self::default()
+        })
+    }
+
+    /// Check if a path matches any of the exclude globs.
+    #[must_use]
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|n| self.excludes.is_match(n)) || self.excludes.is_match(path)
+    }
+
+    /// Check if changing a file can change which entries this filter shows.
+    #[must_use]
+    pub fn depends_on(&self, path: &Path) -> bool {
+        self.respect_ignore
+            && path
+                .file_name()
+                .is_some_and(|n| IGNORE_FILES.iter().any(|i| n == *i))
+    }
+
+    /// Prepare to filter the contents of a directory.
+    #[must_use]
+    pub fn matcher(&self, location: &Path) -> Matcher<'_> {
+        let ignores = if self.respect_ignore {
+            ignores_for(location)
+        } else {
+            vec![]
+        };
+
+        Matcher {
+            filter: self,
+            ignores,
+        }
+    }
+
+    /// Prepare to filter the contents of a location on any backend.
+    ///
+    /// Ignore files are only read from the local filesystem.
+    #[must_use]
+    pub fn matcher_at(&self, location: &Uri) -> Matcher<'_> {
+        if location.is_local() {
+            return self.matcher(location.path());
+        }
+
+        Matcher {
+            filter: self,
+            ignores: vec![],
+        }
+    }
+}
+
+/// A [`Filter`] applied to a specific directory.
+#[derive(Debug)]
+pub struct Matcher<'a> {
+    filter: &'a Filter,
+    /// Ignore files that apply to the directory, most specific first.
+    ignores: Vec<Gitignore>,
+}
+
+impl Matcher<'_> {
+    /// Check if an entry should be shown.
+    #[must_use]
+    pub fn is_visible(&self, entry: &Entry) -> bool {
+        if entry.hidden && !self.filter.show_hidden {
+            return false;
+        }
+
+        if self.filter.is_excluded(&entry.path) {
+            return false;
+        }
+
+        // an ignored directory hides everything in it, even once it's opened
+        for ignore in &self.ignores {
+            if !entry.path.starts_with(ignore.path()) {
+                continue;
+            }
+
+            match ignore.matched_path_or_any_parents(&entry.path, entry.is_dir()) {
+                Match::None => {}
+                Match::Ignore(_) => return false,
+                Match::Whitelist(_) => return true,
+            }
+        }
+
+        true
+    }
+}
+
+/// Load every ignore file that applies to a directory.
+///
+/// `.ignore` files apply anywhere, while `.gitignore` files only apply inside a git repository.
+fn ignores_for(location: &Path) -> Vec<Gitignore> {
+    let repo = location.ancestors().find(|a| a.join(".git").exists());
+
+    let mut ignores = vec![];
+
+    for dir in location.ancestors() {
+        for name in IGNORE_FILES {
+            if name == ".gitignore" && repo.is_none_or(|r| !dir.starts_with(r)) {
+                continue;
+            }
+
+            let file = dir.join(name);
+
+            if !file.is_file() {
+                continue;
+            }
+
+            let mut builder = GitignoreBuilder::new(dir);
+
+            // a partially broken ignore file still has useful rules
+            builder.add(&file);
+
+            if let Ok(ignore) = builder.build() {
+                ignores.push(ignore);
+            }
+        }
+    }
+
+    ignores
+}
@@ -0,0 +1,420 @@
+//! Searching the contents of files.
+//!
+//! Like [filename search](crate::search), directory trees are walked on several threads at once.
+//! Each file is searched as a whole, and its matches are streamed back as soon as it is done.
+//! Binary files and files over a size limit are skipped.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+use ignore::WalkState;
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::filter::Filter;
+use crate::path::PathWrap;
+use crate::search::{self, Search};
+use crate::task::CancelToken;
+
+/// Files larger than this are skipped unless a query says otherwise.
+pub const MAX_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Lines shown around each match unless a query says otherwise.
+pub const CONTEXT: usize = 2;
+
+/// How much of the start of a file is checked for binary content.
+const BINARY_CHECK: usize = 8 * 1024;
+
+/// Longer lines are cut off in snippets.
+const MAX_LINE: usize = 240;
+
+/// A file stops being searched after this many matches.
+const MAX_MATCHES: usize = 1000;
+
+/// Whether letter case matters when matching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Case {
+    /// Case-insensitive unless the query has uppercase letters.
+    #[default]
+    Smart,
+    Sensitive,
+    Insensitive,
+}
+
+impl Case {
+    /// Every option, in the order they are usually presented.
+    pub const ALL: [Self; 3] = [Self::Smart, Self::Sensitive, Self::Insensitive];
+
+    /// Human readable name.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Smart => "Smart Case",
+            Self::Sensitive => "Match Case",
+            Self::Insensitive => "Ignore Case",
+        }
+    }
+
+    /// The option after this one, wrapping around.
+    #[must_use]
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|c| *c == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Check if matching the given text is case-insensitive.
+    fn is_insensitive(self, text: &str) -> bool {
+        match self {
+            Self::Smart => !text.chars().any(char::is_uppercase),
+            Self::Sensitive => false,
+            Self::Insensitive => true,
+        }
+    }
+}
+
+/// What to search for, and where.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Directory to search in.
+    pub root: PathWrap,
+    pub text: String,
+    /// Treat the text as a regular expression rather than literally.
+    pub regex: bool,
+    pub case: Case,
+    /// Which items are searched.
+    pub filter: Filter,
+    /// Files larger than this many bytes are skipped.
+    pub max_size: u64,
+    /// Number of lines to keep before and after each match.
+    pub context: usize,
+}
+
+impl Query {
+    /// A literal, smart case query with the default limits.
+    pub fn new<S: Into<String>>(root: PathWrap, text: S, filter: Filter) -> Self {
+        Self {
+            root,
+            text: text.into(),
+            regex: false,
+            case: Case::default(),
+            filter,
+            max_size: MAX_SIZE,
+            context: CONTEXT,
+        }
+    }
+}
+
+/// A matching line.
+#[derive(Debug, Clone)]
+pub struct Match {
+    /// Line number, starting from 1.
+    pub line: usize,
+    /// Character the first match on the line starts at, starting from 1.
+    pub column: usize,
+    pub text: String,
+    /// Lines right before the match, in order.
+    pub before: Vec<String>,
+    /// Lines right after the match, in order.
+    pub after: Vec<String>,
+}
+
+/// Every match within a file.
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub path: PathWrap,
+    pub matches: Vec<Match>,
+}
+
+/// Start searching file contents on background threads.
+pub fn spawn(query: Query, cancel: CancelToken) -> Result<Search<Hit>, regex::Error> {
+    let pattern = if query.regex {
+        query.text.clone()
+    } else {
+        regex::escape(&query.text)
+    };
+
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(query.case.is_insensitive(&query.text))
+        .build()?;
+
+    let (sender, hits) = mpsc::channel();
+
+    let worker_cancel = cancel.clone();
+    thread::spawn(move || {
+        let walker = search::walker(&query.root, &query.filter);
+        let regex = &regex;
+        let query = &query;
+        let cancel = &worker_cancel;
+
+        walker.run(|| {
+            let sender = sender.clone();
+
+            Box::new(move |result| {
+                if cancel.is_cancelled() {
+                    return WalkState::Quit;
+                }
+
+                let Ok(dent) = result else {
+                    return WalkState::Continue;
+                };
+
+                if !dent.file_type().is_some_and(|t| t.is_file()) {
+                    return WalkState::Continue;
+                }
+
+                // unreadable, binary and oversized files are skipped
+                let Some(data) = read(dent.path(), query.max_size) else {
+                    return WalkState::Continue;
+                };
+
+                let matches = find(&data, regex, query.context);
+
+                if matches.is_empty() {
+                    return WalkState::Continue;
+                }
+
+                let hit = Hit {
+                    path: PathWrap::from_path(dent.path()),
+                    matches,
+                };
+
+                if sender.send(hit).is_err() {
+                    return WalkState::Quit;
+                }
+
+                WalkState::Continue
+            })
+        });
+    });
+
+    Ok(Search::new(hits, cancel))
+}
+
+/// Read a whole file, unless it's too big or looks binary.
+fn read(path: &Path, max_size: u64) -> Option<Vec<u8>> {
+    if fs::metadata(path).ok()?.len() > max_size {
+        return None;
+    }
+
+    let mut data = vec![];
+
+    // the file may have grown since checking
+    File::open(path)
+        .ok()?
+        .take(max_size)
+        .read_to_end(&mut data)
+        .ok()?;
+
+    let start = &data[..data.len().min(BINARY_CHECK)];
+
+    if start.contains(&0) {
+        return None;
+    }
+
+    Some(data)
+}
+
+/// Find every matching line in some text.
+fn find(data: &[u8], regex: &Regex, context: usize) -> Vec<Match> {
+    let mut lines: Vec<&[u8]> = data.split(|b| *b == b'\n').collect();
+
+    // a trailing newline doesn't start another line
+    if lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+
+    let mut matches = vec![];
+
+    for (index, line) in lines.iter().enumerate() {
+        let Some(found) = regex.find(line) else {
+            continue;
+        };
+
+        let before = lines[index.saturating_sub(context)..index]
+            .iter()
+            .map(|l| snippet(l))
+            .collect();
+        let after = lines[index + 1..lines.len().min(index + 1 + context)]
+            .iter()
+            .map(|l| snippet(l))
+            .collect();
+
+        matches.push(Match {
+            line: index + 1,
+            column: String::from_utf8_lossy(&line[..found.start()])
+                .chars()
+                .count()
+                + 1,
+            text: snippet(line),
+            before,
+            after,
+        });
+
+        if matches.len() >= MAX_MATCHES {
+            break;
+        }
+    }
+
+    matches
+}
+
+/// A line as text, cut off if too long.
+fn snippet(line: &[u8]) -> String {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let text = String::from_utf8_lossy(line);
+
+    match text.char_indices().nth(MAX_LINE) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Names of the files with hits for a query, sorted.
+    fn files(root: &Path, text: &str, regex: bool, case: Case) -> Vec<String> {
+        let query = Query {
+            regex,
+            case,
+            ..Query::new(PathWrap::from_path(root), text, Filter::default())
+        };
+
+        let mut names: Vec<_> = spawn(query, CancelToken::new())
+            .unwrap()
+            .map(|hit| hit.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::write(dir.path().join("lower.txt"), "fn main() {}\n").unwrap();
+        fs::write(dir.path().join("upper.txt"), "FN MAIN() {}\n").unwrap();
+        fs::write(dir.path().join("other.txt"), "nothing here\n").unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn literal_text_isnt_a_regex() {
+        let dir = tree();
+
+        // as a regex, this would match every line
+        assert_eq!(
+            files(dir.path(), "()", false, Case::Smart),
+            ["lower.txt", "upper.txt"]
+        );
+        assert!(files(dir.path(), "m.in", false, Case::Smart).is_empty());
+    }
+
+    #[test]
+    fn regexes_match() {
+        let dir = tree();
+
+        assert_eq!(
+            files(dir.path(), r"m.in\(\)", true, Case::Smart),
+            ["lower.txt", "upper.txt"]
+        );
+        assert_eq!(
+            files(dir.path(), "^nothing", true, Case::Smart),
+            ["other.txt"]
+        );
+    }
+
+    #[test]
+    fn invalid_regexes_are_errors() {
+        let query = Query {
+            regex: true,
+            ..Query::new(PathWrap::from_path("/"), "(", Filter::default())
+        };
+
+        assert!(spawn(query, CancelToken::new()).is_err());
+    }
+
+    #[test]
+    fn case_modes_are_respected() {
+        let dir = tree();
+
+        assert_eq!(
+            files(dir.path(), "main", false, Case::Smart),
+            ["lower.txt", "upper.txt"]
+        );
+        assert_eq!(files(dir.path(), "MAIN", false, Case::Smart), ["upper.txt"]);
+        assert_eq!(
+            files(dir.path(), "main", false, Case::Sensitive),
+            ["lower.txt"]
+        );
+        assert_eq!(
+            files(dir.path(), "MAIN", false, Case::Insensitive),
+            ["lower.txt", "upper.txt"]
+        );
+    }
+
+    #[test]
+    fn binary_and_large_files_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("text.txt"), "needle\n").unwrap();
+        fs::write(dir.path().join("binary.bin"), b"\0\x01needle\n").unwrap();
+
+        assert_eq!(
+            files(dir.path(), "needle", false, Case::Smart),
+            ["text.txt"]
+        );
+
+        let query = Query {
+            max_size: 3,
+            ..Query::new(PathWrap::from_path(dir.path()), "needle", Filter::default())
+        };
+        assert_eq!(spawn(query, CancelToken::new()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn matches_have_lines_and_context() {
+        let regex = Regex::new("needle").unwrap();
+        let data = b"one\ntwo\nhay needle\r\nthree\nfour\nfive\n";
+
+        let matches = find(data, &regex, 1);
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, 3);
+        assert_eq!(matches[0].column, 5);
+        assert_eq!(matches[0].text, "hay needle");
+        assert_eq!(matches[0].before, ["two"]);
+        assert_eq!(matches[0].after, ["three"]);
+    }
+
+    #[test]
+    fn context_stops_at_the_ends() {
+        let regex = Regex::new("x").unwrap();
+
+        let matches = find(b"x\nmiddle\nx", &regex, 2);
+
+        assert_eq!(matches.iter().map(|m| m.line).collect::<Vec<_>>(), [1, 3]);
+        assert!(matches[0].before.is_empty());
+        assert_eq!(matches[0].after, ["middle", "x"]);
+        assert_eq!(matches[1].before, ["x", "middle"]);
+        assert!(matches[1].after.is_empty());
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let regex = Regex::new("x").unwrap();
+
+        assert_eq!(find("ééx".as_bytes(), &regex, 0)[0].column, 3);
+    }
+
+    #[test]
+    fn long_lines_are_cut_off() {
+        let line = "a".repeat(MAX_LINE + 10);
+        let text = snippet(line.as_bytes());
+
+        assert_eq!(text.chars().count(), MAX_LINE + 1);
+        assert!(text.ends_with('…'));
+    }
+}
@@ -0,0 +1,474 @@
+//! Undo/redo history for file operations.
+//!
+//! Every finished [`Report`] is turned into a [`Record`] of what actually changed on disk. Undoing
+//! a record produces an [`Operation`] that reverses it, and the report of *that* operation is in
+//! turn recorded on the redo stack - so redoing is just undoing an undo.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::time::SystemTime;
+
+use crate::ops::copy::exists;
+use crate::ops::{Conflict, Operation, Report};
+use crate::path::PathWrap;
+
+/// Maximum number of records kept for undoing.
+const LIMIT: usize = 100;
+
+/// Which way to step through history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Step {
+    Undo,
+    Redo,
+}
+
+/// Size and modification time of an item, used to tell if it changed after an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub path: PathWrap,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Snapshot {
+    /// Take a snapshot of an item as it is now.
+    fn take(path: &PathWrap) -> Option<Self> {
+        let meta = fs::symlink_metadata(path).ok()?;
+
+        Some(Self {
+            path: path.clone(),
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+
+    /// Check whether the item still looks like it did.
+    fn is_current(&self) -> bool {
+        Self::take(&self.path).as_ref() == Some(self)
+    }
+}
+
+/// A change to the filesystem that can be reversed.
+#[derive(Debug, Clone)]
+pub enum Record {
+    /// Items were copied. Holds the copies that were made.
+    Copied(Vec<Snapshot>),
+    /// Items were moved or renamed, as `(from, to)` pairs.
+    Moved(Vec<(PathWrap, PathWrap)>),
+    /// Items were moved to the trash, as `(original, trashed)` pairs.
+    Trashed(Vec<(PathWrap, PathWrap)>),
+    /// Items were restored from the trash, as `(trashed, restored)` pairs.
+    Restored(Vec<(PathWrap, PathWrap)>),
+    /// An empty directory was created.
+    CreatedDir(PathWrap),
+    /// An empty directory was removed.
+    RemovedDir(PathWrap),
+}
+
+impl Record {
+    /// Work out what changed from the report of a finished operation.
+    ///
+    /// Permanent deletions can't be reversed, so they don't produce a record. Neither do
+    /// directories merged into existing ones, since what was there before can't be told apart
+    /// from what was added, and undoing would take both.
+    #[must_use]
+    pub fn from_report(report: &Report) -> Option<Self> {
+        let completed = || report.completed.iter().filter(|c| !c.merged);
+
+        let pairs = || {
+            completed()
+                .filter_map(|c| Some((c.source.clone(), c.destination.clone()?)))
+                .collect::<Vec<_>>()
+        };
+
+        let copied = || {
+            Self::Copied(
+                completed()
+                    .filter_map(|c| Snapshot::take(c.destination.as_ref()?))
+                    .collect(),
+            )
+        };
+
+        let record = match &report.operation {
+            Operation::Copy { .. } | Operation::Extract { .. } => copied(),
+            // only copies on this computer can be taken back
+            Operation::Transfer { destination, .. } if destination.is_local() => copied(),
+            Operation::Move { .. } | Operation::Rename { .. } => Self::Moved(pairs()),
+            Operation::Trash { .. } => Self::Trashed(pairs()),
+            Operation::Restore { .. } => Self::Restored(pairs()),
+            Operation::CreateDir { path } if !report.completed.is_empty() => {
+                Self::CreatedDir(path.clone())
+            }
+            Operation::RemoveDir { path } if !report.completed.is_empty() => {
+                Self::RemovedDir(path.clone())
+            }
+            _ => return None,
+        };
+
+        (!record.is_empty()).then_some(record)
+    }
+
+    /// Check if this record doesn't describe any change.
+    fn is_empty(&self) -> bool {
+        match self {
+            Self::Copied(c) => c.is_empty(),
+            Self::Moved(p) | Self::Trashed(p) | Self::Restored(p) => p.is_empty(),
+            Self::CreatedDir(_) | Self::RemovedDir(_) => false,
+        }
+    }
+
+    /// The operation that reverses this record, if the filesystem still allows it.
+    ///
+    /// Nothing is ever overwritten, and items modified since the operation are left alone.
+    pub fn inverse(&self) -> Result<Operation, Refused> {
+        match self {
+            Self::Copied(copies) => {
+                for copy in copies {
+                    if !exists(&copy.path) {
+                        return Err(Refused::Missing(copy.path.clone()));
+                    }
+                    if !copy.is_current() {
+                        return Err(Refused::Modified(copy.path.clone()));
+                    }
+                }
+
+                // Copies are trashed rather than deleted, just in case
+                Ok(Operation::Trash {
+                    targets: copies.iter().map(|c| c.path.clone()).collect(),
+                })
+            }
+            Self::Moved(pairs) => {
+                check_pairs(pairs)?;
+
+                Ok(Operation::Rename {
+                    sources: pairs.iter().map(|(_, to)| to.clone()).collect(),
+                    targets: pairs.iter().map(|(from, _)| from.clone()).collect(),
+                })
+            }
+            Self::Trashed(pairs) => {
+                check_pairs(pairs)?;
+
+                Ok(Operation::Restore {
+                    items: pairs.iter().map(|(_, trashed)| trashed.clone()).collect(),
+                    conflict: Conflict::Skip,
+                })
+            }
+            Self::Restored(pairs) => {
+                for (_, restored) in pairs {
+                    if !exists(restored) {
+                        return Err(Refused::Missing(restored.clone()));
+                    }
+                }
+
+                Ok(Operation::Trash {
+                    targets: pairs.iter().map(|(_, restored)| restored.clone()).collect(),
+                })
+            }
+            Self::CreatedDir(path) => {
+                let is_empty = fs::read_dir(path)
+                    .map_err(|_| Refused::Missing(path.clone()))?
+                    .next()
+                    .is_none();
+
+                if !is_empty {
+                    return Err(Refused::Modified(path.clone()));
+                }
+
+                Ok(Operation::RemoveDir { path: path.clone() })
+            }
+            Self::RemovedDir(path) => {
+                if exists(path) {
+                    return Err(Refused::Occupied(path.clone()));
+                }
+
+                Ok(Operation::CreateDir { path: path.clone() })
+            }
+        }
+    }
+}
+
+/// Make sure every `(from, to)` pair can be moved back from `to` to `from`.
+///
+/// A `from` may be taken by another item being moved back, such as when names were swapped.
+fn check_pairs(pairs: &[(PathWrap, PathWrap)]) -> Result<(), Refused> {
+    let moving: HashSet<&PathWrap> = pairs.iter().map(|(_, to)| to).collect();
+
+    for (from, to) in pairs {
+        if !exists(to) {
+            return Err(Refused::Missing(to.clone()));
+        }
+        if exists(from) && !moving.contains(from) {
+            return Err(Refused::Occupied(from.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Why an undo or redo was refused.
+#[derive(Debug, Clone)]
+pub enum Refused {
+    /// An item that needs to be moved back no longer exists.
+    Missing(PathWrap),
+    /// Something now exists where an item would be moved back to.
+    Occupied(PathWrap),
+    /// An item was changed after the operation.
+    Modified(PathWrap),
+}
+
+impl fmt::Display for Refused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(p) => write!(f, "{} no longer exists", p.display().to_string_lossy()),
+            Self::Occupied(p) => write!(f, "{} already exists", p.display().to_string_lossy()),
+            Self::Modified(p) => write!(f, "{} has been modified", p.display().to_string_lossy()),
+        }
+    }
+}
+
+impl std::error::Error for Refused {}
+
+/// Undo and redo stacks.
+#[derive(Debug, Default)]
+pub struct Journal {
+    undo: VecDeque<Record>,
+    redo: Vec<Record>,
+}
+
+impl Journal {
+    #[must_use]
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a finished operation.
+    ///
+    /// `step` says whether the operation was started by undoing or redoing, or [`None`] if it's
+    /// a new operation, which clears the redo stack.
+    pub fn record(&mut self, report: &Report, step: Option<Step>) {
+        let Some(record) = Record::from_report(report) else {
+            return;
+        };
+
+        match step {
+            Some(Step::Undo) => self.redo.push(record),
+            Some(Step::Redo) => self.push_undo(record),
+            None => {
+                self.redo.clear();
+                self.push_undo(record);
+            }
+        }
+    }
+
+    fn push_undo(&mut self, record: Record) {
+        if self.undo.len() == LIMIT {
+            self.undo.pop_front();
+        }
+
+        self.undo.push_back(record);
+    }
+
+    /// Take the operation that undoes or redoes the latest change.
+    ///
+    /// Returns [`None`] if there's nothing to step through. If the change can't be safely
+    /// reversed, it's kept in history so it can be tried again once the conflict is resolved.
+    pub fn step(&mut self, step: Step) -> Result<Option<Operation>, Refused> {
+        let record = match step {
+            Step::Undo => self.undo.back(),
+            Step::Redo => self.redo.last(),
+        };
+
+        let Some(record) = record else {
+            return Ok(None);
+        };
+
+        let operation = record.inverse()?;
+
+        match step {
+            Step::Undo => self.undo.pop_back(),
+            Step::Redo => self.redo.pop(),
+        };
+
+        Ok(Some(operation))
+    }
+
+    /// Check if there's anything to undo or redo.
+    #[must_use]
+    pub fn can(&self, step: Step) -> bool {
+        match step {
+            Step::Undo => !self.undo.is_empty(),
+            Step::Redo => !self.redo.is_empty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn wrap(path: impl AsRef<Path>) -> PathWrap {
+        PathWrap::from_into_path(path.as_ref().to_path_buf())
+    }
+
+    #[test]
+    fn moves_are_renamed_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (wrap(dir.path().join("a")), wrap(dir.path().join("b")));
+        fs::write(&b, "").unwrap();
+
+        let record = Record::Moved(vec![(a.clone(), b.clone())]);
+
+        let Ok(Operation::Rename { sources, targets }) = record.inverse() else {
+            panic!("expected a rename");
+        };
+        assert_eq!(sources, [b]);
+        assert_eq!(targets, [a]);
+    }
+
+    #[test]
+    fn merged_moves_are_not_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("from"), dir.path().join("to"));
+        fs::create_dir_all(from.join("photos")).unwrap();
+        fs::create_dir_all(to.join("photos")).unwrap();
+        fs::write(from.join("photos").join("new.jpg"), "").unwrap();
+        fs::write(to.join("photos").join("old.jpg"), "").unwrap();
+        fs::write(from.join("notes.txt"), "").unwrap();
+
+        let report = crate::ops::spawn(Operation::Move {
+            sources: vec![wrap(from.join("photos")), wrap(from.join("notes.txt"))],
+            destination: wrap(&to),
+            conflict: Conflict::Overwrite,
+        })
+        .wait()
+        .unwrap();
+
+        assert!(report.failed.is_empty());
+        assert!(report.completed[0].merged);
+        assert!(!report.completed[1].merged);
+
+        // moving the merged folder back would take what was already there along with it
+        let Some(Record::Moved(pairs)) = Record::from_report(&report) else {
+            panic!("expected a move");
+        };
+        assert_eq!(
+            pairs,
+            [(wrap(from.join("notes.txt")), wrap(to.join("notes.txt")))]
+        );
+    }
+
+    #[test]
+    fn merged_copies_are_not_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("from"), dir.path().join("to"));
+        fs::create_dir_all(from.join("photos")).unwrap();
+        fs::create_dir_all(to.join("photos")).unwrap();
+        fs::write(to.join("photos").join("old.jpg"), "").unwrap();
+
+        let report = crate::ops::spawn(Operation::Copy {
+            sources: vec![wrap(from.join("photos"))],
+            destination: wrap(&to),
+            conflict: Conflict::Overwrite,
+        })
+        .wait()
+        .unwrap();
+
+        assert!(report.completed[0].merged);
+        assert!(Record::from_report(&report).is_none());
+    }
+
+    #[test]
+    fn swapped_names_can_be_moved_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (wrap(dir.path().join("a")), wrap(dir.path().join("b")));
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "").unwrap();
+
+        assert!(check_pairs(&[(a.clone(), b.clone()), (b, a)]).is_ok());
+    }
+
+    #[test]
+    fn taken_names_are_not_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (wrap(dir.path().join("a")), wrap(dir.path().join("b")));
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "").unwrap();
+
+        assert!(matches!(
+            check_pairs(&[(a.clone(), b)]),
+            Err(Refused::Occupied(p)) if p == a
+        ));
+    }
+
+    #[test]
+    fn missing_items_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (wrap(dir.path().join("a")), wrap(dir.path().join("b")));
+
+        assert!(matches!(
+            check_pairs(&[(a, b.clone())]),
+            Err(Refused::Missing(p)) if p == b
+        ));
+    }
+
+    #[test]
+    fn modified_copies_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let copy = wrap(dir.path().join("copy"));
+        fs::write(&copy, "copied").unwrap();
+
+        let record = Record::Copied(vec![Snapshot::take(&copy).unwrap()]);
+        assert!(matches!(record.inverse(), Ok(Operation::Trash { .. })));
+
+        fs::write(&copy, "changed since").unwrap();
+        assert!(matches!(record.inverse(), Err(Refused::Modified(_))));
+    }
+
+    #[test]
+    fn created_dirs_are_only_removed_while_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let created = wrap(dir.path().join("new"));
+        fs::create_dir(&created).unwrap();
+
+        let record = Record::CreatedDir(created.clone());
+        assert!(matches!(record.inverse(), Ok(Operation::RemoveDir { .. })));
+
+        fs::write(created.join("file"), "").unwrap();
+        assert!(matches!(record.inverse(), Err(Refused::Modified(_))));
+    }
+
+    #[test]
+    fn removed_dirs_are_not_created_over_something_else() {
+        let dir = tempfile::tempdir().unwrap();
+        let removed = wrap(dir.path().join("gone"));
+
+        let record = Record::RemovedDir(removed.clone());
+        assert!(matches!(record.inverse(), Ok(Operation::CreateDir { .. })));
+
+        fs::write(&removed, "").unwrap();
+        assert!(matches!(record.inverse(), Err(Refused::Occupied(_))));
+    }
+
+    #[test]
+    fn refused_steps_stay_in_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let removed = wrap(dir.path().join("gone"));
+        fs::write(&removed, "").unwrap();
+
+        let mut journal = Journal::new();
+        journal.push_undo(Record::RemovedDir(removed.clone()));
+
+        assert!(journal.step(Step::Undo).is_err());
+        assert!(journal.can(Step::Undo));
+
+        fs::remove_file(&removed).unwrap();
+
+        assert!(journal.step(Step::Undo).unwrap().is_some());
+        assert!(!journal.can(Step::Undo));
+        assert!(journal.step(Step::Undo).unwrap().is_none());
+    }
+}
@@ -1,7 +1,27 @@
 //! Core functionality for `doseer`. Basically anything that's not UI.
 
+pub mod apps;
+pub mod archive;
 pub mod dirs;
+pub mod duplicates;
+pub mod entry;
+pub mod filter;
+pub mod grep;
+pub mod journal;
+pub mod mime;
+pub mod mounts;
+pub mod ops;
 pub mod path;
+pub mod permissions;
+pub mod rename;
+pub mod search;
+pub mod size;
+pub mod sort;
+pub mod task;
+pub mod thumbnail;
+pub mod trash;
+pub mod usage;
+pub mod vfs;
 
 #[doc(hidden)]
 pub use paste as __paste;
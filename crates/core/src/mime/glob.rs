@@ -0,0 +1,223 @@
+//! File name patterns from `globs2` files.
+//!
+//! Every line is `weight:type:pattern`, optionally followed by `:cs` if the pattern is case
+//! sensitive. Most patterns are either a whole name, like `Makefile`, or a suffix, like `*.png`,
+//! so those are looked up directly instead of being matched one by one.
+
+use std::collections::HashMap;
+
+use globset::{GlobBuilder, GlobMatcher};
+
+/// Weight of patterns that don't give one.
+const DEFAULT_WEIGHT: u32 = 50;
+
+/// Pattern used to drop the patterns of a type given by less important directories.
+pub const NO_GLOBS: &str = "__NOGLOBS__";
+
+/// A file name pattern for a type.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pub weight: u32,
+    pub mime: String,
+    pub pattern: String,
+    pub case_sensitive: bool,
+}
+
+impl Glob {
+    /// Parse a line of a `globs2` file, or [`None`] for comments and invalid lines.
+    pub fn parse(line: &str) -> Option<Self> {
+        if line.starts_with('#') {
+            return None;
+        }
+
+        let mut fields = line.splitn(3, ':');
+        let weight = fields.next()?.parse().unwrap_or(DEFAULT_WEIGHT);
+        let mime = fields.next()?;
+        let rest = fields.next()?;
+
+        // patterns can't have colons in them, so anything after one is a flag
+        let (pattern, flags) = rest.split_once(':').unwrap_or((rest, ""));
+
+        if mime.is_empty() || pattern.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            weight,
+            mime: mime.to_owned(),
+            pattern: pattern.to_owned(),
+            case_sensitive: flags.split(',').any(|f| f == "cs"),
+        })
+    }
+
+    /// Length of the pattern, for picking the most specific of several matches.
+    fn len(&self) -> usize {
+        self.pattern.chars().count()
+    }
+}
+
+/// Patterns indexed for matching names.
+#[derive(Debug, Default)]
+pub struct Globs {
+    globs: Vec<Glob>,
+    /// Whole names, lowercased.
+    literals: HashMap<String, Vec<usize>>,
+    /// Text after the `*` of suffix patterns, lowercased.
+    suffixes: HashMap<String, Vec<usize>>,
+    /// Everything else.
+    patterns: Vec<(usize, GlobMatcher)>,
+}
+
+impl Globs {
+    /// Index some patterns. Invalid ones are left out.
+    pub fn new(globs: Vec<Glob>) -> Self {
+        let mut indexed = Self::default();
+
+        for (index, glob) in globs.iter().enumerate() {
+            let special = |c: char| matches!(c, '*' | '?' | '[' | '\\');
+
+            if !glob.pattern.contains(special) {
+                indexed
+                    .literals
+                    .entry(glob.pattern.to_lowercase())
+                    .or_default()
+                    .push(index);
+            } else if let Some(suffix) = glob
+                .pattern
+                .strip_prefix('*')
+                .filter(|s| !s.contains(special))
+            {
+                indexed
+                    .suffixes
+                    .entry(suffix.to_lowercase())
+                    .or_default()
+                    .push(index);
+            } else if let Ok(pattern) = GlobBuilder::new(&glob.pattern)
+                .case_insensitive(!glob.case_sensitive)
+                .build()
+            {
+                indexed.patterns.push((index, pattern.compile_matcher()));
+            }
+        }
+
+        indexed.globs = globs;
+        indexed
+    }
+
+    /// Types whose patterns match a name best, without duplicates.
+    ///
+    /// Patterns matching with the same case as the name win over those only matching when case is
+    /// ignored. Of those, only the patterns with the highest weight count, and of those only the
+    /// longest ones. More than one type is returned if that still leaves several.
+    pub fn matches(&self, name: &str) -> Vec<&str> {
+        let lower = name.to_lowercase();
+        // every match, and whether it matched without ignoring case
+        let mut found: Vec<(&Glob, bool)> = vec![];
+
+        let literals = self.literals.get(&lower).into_iter().flatten();
+        found.extend(literals.map(|i| {
+            let glob = &self.globs[*i];
+            (glob, glob.pattern == name)
+        }));
+
+        for (start, _) in lower.char_indices() {
+            let suffixes = self.suffixes.get(&lower[start..]).into_iter().flatten();
+            found.extend(suffixes.map(|i| {
+                let glob = &self.globs[*i];
+                (glob, name.ends_with(&glob.pattern[1..]))
+            }));
+        }
+
+        found.extend(
+            self.patterns
+                .iter()
+                .filter(|(_, p)| p.is_match(name))
+                .map(|(i, _)| (&self.globs[*i], self.globs[*i].case_sensitive)),
+        );
+
+        found.retain(|(glob, exact)| *exact || !glob.case_sensitive);
+
+        if found.iter().any(|(_, exact)| *exact) {
+            found.retain(|(_, exact)| *exact);
+        }
+
+        let Some(best) = found.iter().map(|(g, _)| (g.weight, g.len())).max() else {
+            return vec![];
+        };
+
+        let mut mimes: Vec<&str> = vec![];
+
+        for (glob, _) in found {
+            if (glob.weight, glob.len()) == best && !mimes.contains(&glob.mime.as_str()) {
+                mimes.push(&glob.mime);
+            }
+        }
+
+        mimes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_parsed() {
+        let glob = Glob::parse("80:text/x-c++src:*.C:cs").unwrap();
+        assert_eq!(glob.weight, 80);
+        assert_eq!(glob.mime, "text/x-c++src");
+        assert_eq!(glob.pattern, "*.C");
+        assert!(glob.case_sensitive);
+
+        let glob = Glob::parse("heavy:text/plain:*.txt").unwrap();
+        assert_eq!(glob.weight, DEFAULT_WEIGHT);
+        assert!(!glob.case_sensitive);
+
+        assert!(Glob::parse("# 50:text/plain:*.txt").is_none());
+        assert!(Glob::parse("50::*.txt").is_none());
+        assert!(Glob::parse("50:text/plain:").is_none());
+        assert!(Glob::parse("nonsense").is_none());
+    }
+
+    fn globs() -> Globs {
+        Globs::new(
+            [
+                "50:text/plain:*.txt",
+                "40:text/x-log:*.txt",
+                "50:application/gzip:*.gz",
+                "50:application/x-compressed-tar:*.tar.gz",
+                "50:text/x-c++src:*.C:cs",
+                "50:text/x-csrc:*.c",
+                "50:text/x-makefile:Makefile",
+                "10:text/x-readme:README*",
+                "50:image/x-one:*.pic",
+                "50:image/x-two:*.pic",
+            ]
+            .into_iter()
+            .filter_map(Glob::parse)
+            .collect(),
+        )
+    }
+
+    #[test]
+    fn heaviest_and_longest_patterns_win() {
+        let globs = globs();
+
+        assert_eq!(globs.matches("notes.txt"), ["text/plain"]);
+        assert_eq!(globs.matches("a.tar.gz"), ["application/x-compressed-tar"]);
+        assert_eq!(globs.matches("a.gz"), ["application/gzip"]);
+        assert_eq!(globs.matches("a.pic"), ["image/x-one", "image/x-two"]);
+        assert!(globs.matches("nothing").is_empty());
+    }
+
+    #[test]
+    fn case_is_only_ignored_when_allowed() {
+        let globs = globs();
+
+        assert_eq!(globs.matches("main.C"), ["text/x-c++src"]);
+        assert_eq!(globs.matches("main.c"), ["text/x-csrc"]);
+        assert_eq!(globs.matches("NOTES.TXT"), ["text/plain"]);
+        assert_eq!(globs.matches("makefile"), ["text/x-makefile"]);
+        assert_eq!(globs.matches("readme.md"), ["text/x-readme"]);
+    }
+}
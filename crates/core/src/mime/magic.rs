@@ -0,0 +1,333 @@
+//! Rules recognising types by their contents, from `magic` files.
+//!
+//! The file starts with `MIME-Magic\0\n`, followed by sections headed `[priority:type]`. Every
+//! rule of a section is a line of the form
+//!
+//! ```text
+//! [indent]>offset=<length: u16 be><value>[&<mask>][~word size][+range]\n
+//! ```
+//!
+//! A rule only counts if one of the rules nested right below it, with an indent one deeper, also
+//! matches. Rules without nested rules count on their own.
+
+/// Start of every `magic` file.
+const HEADER: &[u8] = b"MIME-Magic\0\n";
+
+/// Rule used to drop the rules of a type given by less important directories.
+const NO_MAGIC: &[u8] = b"__NOMAGIC__";
+
+/// The rules of a type.
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub priority: u32,
+    pub mime: String,
+    /// Whether less important rules for the type should be dropped.
+    pub no_magic: bool,
+    rules: Vec<Rule>,
+}
+
+/// Bytes expected somewhere in a file.
+#[derive(Debug, Clone)]
+struct Rule {
+    indent: u32,
+    offset: usize,
+    value: Vec<u8>,
+    mask: Option<Vec<u8>>,
+    /// Number of offsets after the first one the value may be found at.
+    range: usize,
+}
+
+impl Section {
+    /// Check if some data, read from the start of a file, matches the rules.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        matches_level(&self.rules, 0, data)
+    }
+
+    /// How much of a file has to be read to check every rule.
+    pub fn extent(&self) -> usize {
+        self.rules
+            .iter()
+            .map(|r| r.offset + r.range - 1 + r.value.len())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl Rule {
+    fn matches(&self, data: &[u8]) -> bool {
+        (self.offset..self.offset + self.range).any(|start| {
+            let Some(found) = data.get(start..start + self.value.len()) else {
+                return false;
+            };
+
+            match &self.mask {
+                Some(mask) => found
+                    .iter()
+                    .zip(&self.value)
+                    .zip(mask)
+                    .all(|((f, v), m)| f & m == v & m),
+                None => found == self.value,
+            }
+        })
+    }
+}
+
+/// Check if any rule at an indent matches, along with one of the rules nested below it.
+///
+/// Every rule given is expected to be at least that deep.
+fn matches_level(rules: &[Rule], indent: u32, data: &[u8]) -> bool {
+    let mut start = 0;
+
+    while let Some(rule) = rules.get(start) {
+        let end = rules[start + 1..]
+            .iter()
+            .position(|r| r.indent <= rule.indent)
+            .map_or(rules.len(), |p| start + 1 + p);
+
+        if rule.indent == indent && rule.matches(data) {
+            let nested = &rules[start + 1..end];
+
+            if nested.is_empty() || matches_level(nested, indent + 1, data) {
+                return true;
+            }
+        }
+
+        start = end;
+    }
+
+    false
+}
+
+/// Parse a `magic` file, or [`None`] if it isn't one.
+///
+/// Lines that can't be understood are skipped, in case a newer version of the format added
+/// something to them.
+pub fn parse(data: &[u8]) -> Option<Vec<Section>> {
+    let mut reader = Reader {
+        data: data.strip_prefix(HEADER)?,
+    };
+    let mut sections = vec![];
+
+    while !reader.data.is_empty() {
+        if reader.eat(b'[') {
+            let header = reader.until(b'\n');
+            let header = std::str::from_utf8(header).ok();
+
+            let section = header
+                .and_then(|h| h.strip_suffix(']'))
+                .and_then(|h| h.split_once(':'))
+                .and_then(|(priority, mime)| {
+                    Some(Section {
+                        priority: priority.parse().ok()?,
+                        mime: mime.to_owned(),
+                        no_magic: false,
+                        rules: vec![],
+                    })
+                });
+
+            sections.extend(section);
+            continue;
+        }
+
+        let rule = reader.rule();
+
+        // values can contain newlines, so a line is only skipped once it couldn't be parsed
+        if rule.is_none() {
+            reader.until(b'\n');
+        }
+
+        match (sections.last_mut(), rule) {
+            (Some(section), Some(Line::Rule(rule))) => section.rules.push(rule),
+            (Some(section), Some(Line::NoMagic)) => section.no_magic = true,
+            _ => {}
+        }
+    }
+
+    Some(sections)
+}
+
+/// A line inside a section.
+enum Line {
+    Rule(Rule),
+    NoMagic,
+}
+
+/// Reads the binary rules of a `magic` file.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Skip a byte if it's the one expected.
+    fn eat(&mut self, byte: u8) -> bool {
+        match self.data.split_first() {
+            Some((first, rest)) if *first == byte => {
+                self.data = rest;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Take some bytes.
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(taken)
+    }
+
+    /// Take everything up to a byte, skipping the byte itself.
+    fn until(&mut self, byte: u8) -> &'a [u8] {
+        let end = self
+            .data
+            .iter()
+            .position(|b| *b == byte)
+            .unwrap_or(self.data.len());
+        let taken = &self.data[..end];
+
+        self.data = self.data.get(end + 1..).unwrap_or_default();
+        taken
+    }
+
+    /// A decimal number, if there is one.
+    fn number(&mut self) -> Option<usize> {
+        let len = self.data.iter().take_while(|b| b.is_ascii_digit()).count();
+        let digits = std::str::from_utf8(self.take(len)?).ok()?;
+
+        digits.parse().ok()
+    }
+
+    /// A rule, along with the newline ending it.
+    fn rule(&mut self) -> Option<Line> {
+        if self.data.starts_with(NO_MAGIC) {
+            self.take(NO_MAGIC.len());
+            return self.eat(b'\n').then_some(Line::NoMagic);
+        }
+
+        let indent = self.number().unwrap_or(0);
+
+        if !self.eat(b'>') {
+            return None;
+        }
+
+        let offset = self.number()?;
+
+        if !self.eat(b'=') {
+            return None;
+        }
+
+        let len = self.take(2)?;
+        let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+        let mut value = self.take(len)?.to_vec();
+
+        let mut mask = if self.eat(b'&') {
+            Some(self.take(len)?.to_vec())
+        } else {
+            None
+        };
+
+        let word_size = if self.eat(b'~') { self.number()? } else { 1 };
+        let range = if self.eat(b'+') { self.number()? } else { 1 };
+
+        if !self.eat(b'\n') {
+            return None;
+        }
+
+        // words are stored big endian, but are matched in the byte order of this machine
+        if cfg!(target_endian = "little") && word_size > 1 {
+            for bytes in std::iter::once(&mut value).chain(&mut mask) {
+                for word in bytes.chunks_exact_mut(word_size) {
+                    word.reverse();
+                }
+            }
+        }
+
+        Some(Line::Rule(Rule {
+            indent: indent.try_into().ok()?,
+            offset,
+            value,
+            mask,
+            range: range.max(1),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A rule line, with the value length written the way `magic` files do.
+    fn rule(prefix: &str, value: &[u8], suffix: &[u8]) -> Vec<u8> {
+        let mut line = prefix.as_bytes().to_vec();
+        line.extend((value.len() as u16).to_be_bytes());
+        line.extend(value);
+        line.extend(suffix);
+        line.push(b'\n');
+        line
+    }
+
+    fn sections() -> Vec<Section> {
+        let mut data = HEADER.to_vec();
+
+        data.extend(b"[50:image/png]\n");
+        data.extend(rule(">0=", b"\x89PNG", b""));
+
+        // a zip is only an odt if it says so further in
+        data.extend(b"[70:application/vnd.oasis.opendocument.text]\n");
+        data.extend(rule(">0=", b"PK", b""));
+        data.extend(rule("1>30=", b"mimetype", b""));
+        data.extend(b"2>this line isn't understood\n");
+
+        // the first byte only has its high bits checked, anywhere in the first four bytes
+        data.extend(b"[40:application/x-masked]\n");
+        data.extend(rule(">0=", b"\xf0", b"&\xf0+4"));
+
+        data.extend(b"[50:text/x-dropped]\n__NOMAGIC__\n");
+        data.extend(b"[nonsense]\n");
+
+        parse(&data).unwrap()
+    }
+
+    #[test]
+    fn sections_are_parsed() {
+        let sections = sections();
+
+        let mimes: Vec<_> = sections.iter().map(|s| s.mime.as_str()).collect();
+        assert_eq!(
+            mimes,
+            [
+                "image/png",
+                "application/vnd.oasis.opendocument.text",
+                "application/x-masked",
+                "text/x-dropped",
+            ]
+        );
+        assert_eq!(sections[1].priority, 70);
+        assert!(sections[3].no_magic);
+        assert_eq!(sections[1].extent(), 38);
+
+        assert!(parse(b"not magic").is_none());
+    }
+
+    #[test]
+    fn rules_are_matched() {
+        let sections = sections();
+
+        assert!(sections[0].matches(b"\x89PNG\r\n"));
+        assert!(!sections[0].matches(b"\x89PN"));
+
+        let mut odt = b"PK\x03\x04".to_vec();
+        odt.resize(30, 0);
+        odt.extend(b"mimetypeapplication/vnd.oasis.opendocument.text");
+        assert!(sections[1].matches(&odt));
+        assert!(!sections[1].matches(b"PK\x03\x04 just a zip"));
+
+        assert!(sections[2].matches(b"\0\0\xf5"));
+        assert!(!sections[2].matches(b"\0\0\0\0\xff"));
+        assert!(!sections[2].matches(b"\x0f"));
+    }
+}
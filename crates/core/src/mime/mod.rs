@@ -0,0 +1,378 @@
+//! Telling the MIME type of files, from their names and contents.
+//!
+//! Types are read from the `mime` folder of every [data directory](dirs::data_dirs), as
+//! described by the
+//! [shared MIME info spec](https://specifications.freedesktop.org/shared-mime-info-spec/latest/).
+//! Names are matched against the patterns in `globs2`, and when that isn't enough, the start of
+//! the file is checked against the rules in `magic`.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::dirs;
+use crate::entry::{Entry, Kind};
+
+mod glob;
+mod magic;
+
+use glob::{Glob, Globs};
+use magic::Section;
+
+/// Type of data nothing more is known about.
+pub const OCTET_STREAM: &str = "application/octet-stream";
+/// Type of text nothing more is known about.
+pub const TEXT_PLAIN: &str = "text/plain";
+/// Type of directories.
+pub const DIRECTORY: &str = "inode/directory";
+
+/// Most of a file that's ever read to check its contents.
+const MAX_EXTENT: usize = 64 * 1024;
+/// How much of a file is checked for looking like text.
+const TEXT_EXTENT: usize = 512;
+
+lazy_static::lazy_static! {
+    /// The types installed on this system.
+    pub static ref DATABASE: Database = Database::load();
+}
+
+/// Every known type, and how to recognise them.
+#[derive(Debug, Default)]
+pub struct Database {
+    globs: Globs,
+    /// Sorted by priority, highest first.
+    magic: Vec<Section>,
+    /// How much of a file to read to check every magic rule.
+    extent: usize,
+    /// Other names of types, and the name they go by.
+    aliases: HashMap<String, String>,
+    /// Types every type is a subclass of.
+    parents: HashMap<String, Vec<String>>,
+    /// Icons given to types, instead of one named after the type.
+    icons: HashMap<String, String>,
+    /// Icons shown for types when the theme has nothing more specific.
+    generic_icons: HashMap<String, String>,
+}
+
+impl Database {
+    /// Read the types installed in every data directory.
+    pub fn load() -> Self {
+        let dirs: Vec<PathBuf> = dirs::data_dirs()
+            .into_iter()
+            .map(|d| d.join("mime"))
+            .collect();
+        Self::load_from(&dirs)
+    }
+
+    /// Read the types in some `mime` directories, most important first.
+    pub fn load_from(dirs: &[PathBuf]) -> Self {
+        let mut database = Self::default();
+        let mut globs: Vec<Glob> = vec![];
+
+        // less important directories first, so more important ones can replace what they said
+        for dir in dirs.iter().rev() {
+            if let Ok(text) = fs::read_to_string(dir.join("globs2")) {
+                let new: Vec<Glob> = text.lines().filter_map(Glob::parse).collect();
+
+                for glob in new.iter().filter(|g| g.pattern == glob::NO_GLOBS) {
+                    globs.retain(|g| g.mime != glob.mime);
+                }
+
+                globs.extend(new.into_iter().filter(|g| g.pattern != glob::NO_GLOBS));
+            }
+
+            if let Some(sections) = fs::read(dir.join("magic"))
+                .ok()
+                .and_then(|d| magic::parse(&d))
+            {
+                for section in sections.iter().filter(|s| s.no_magic) {
+                    database.magic.retain(|s| s.mime != section.mime);
+                }
+
+                // the more important rules come first among those of the same priority
+                let mut sections = sections;
+                sections.append(&mut database.magic);
+                database.magic = sections;
+            }
+
+            for (alias, mime) in pairs(&dir.join("aliases"), ' ') {
+                database.aliases.insert(alias, mime);
+            }
+
+            for (mime, parent) in pairs(&dir.join("subclasses"), ' ') {
+                let parents = database.parents.entry(mime).or_default();
+
+                if !parents.contains(&parent) {
+                    parents.push(parent);
+                }
+            }
+
+            database.icons.extend(pairs(&dir.join("icons"), ':'));
+            database
+                .generic_icons
+                .extend(pairs(&dir.join("generic-icons"), ':'));
+        }
+
+        database
+            .magic
+            .sort_by_key(|s| std::cmp::Reverse(s.priority));
+        database.extent = database
+            .magic
+            .iter()
+            .map(Section::extent)
+            .max()
+            .unwrap_or(0)
+            .min(MAX_EXTENT);
+        database.globs = Globs::new(globs);
+
+        database
+    }
+
+    /// The name a type goes by, if it's an alias for another one.
+    #[must_use]
+    pub fn unalias<'a>(&'a self, mime: &'a str) -> &'a str {
+        self.aliases.get(mime).map_or(mime, String::as_str)
+    }
+
+    /// Types a type is a direct subclass of.
+    ///
+    /// Besides those listed, every kind of text is plain text, and everything that isn't a
+    /// directory or some other special file is a stream of bytes.
+    pub fn parents(&self, mime: &str) -> Vec<&str> {
+        let mime = self.unalias(mime);
+        let mut parents: Vec<&str> = self
+            .parents
+            .get(mime)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+
+        if mime.starts_with("text/") && mime != TEXT_PLAIN && !parents.contains(&TEXT_PLAIN) {
+            parents.push(TEXT_PLAIN);
+        }
+
+        if !mime.starts_with("inode/") && mime != OCTET_STREAM && parents.is_empty() {
+            parents.push(OCTET_STREAM);
+        }
+
+        parents
+    }
+
+    /// Every type a type is a subclass of, closest first.
+    pub fn ancestors(&self, mime: &str) -> Vec<&str> {
+        let mut ancestors: Vec<&str> = self.parents(mime);
+        let mut next = 0;
+
+        while let Some(mime) = ancestors.get(next) {
+            for parent in self.parents(mime) {
+                if !ancestors.contains(&parent) {
+                    ancestors.push(parent);
+                }
+            }
+
+            next += 1;
+        }
+
+        ancestors
+    }
+
+    /// Check if a type is another one, or a subclass of it.
+    #[must_use]
+    pub fn is_a(&self, mime: &str, of: &str) -> bool {
+        let mime = self.unalias(mime);
+        let of = self.unalias(of);
+
+        mime == of || self.ancestors(mime).contains(&of)
+    }
+
+    /// Types whose patterns match a file name best, without looking at the file.
+    pub fn for_name(&self, name: &str) -> Vec<&str> {
+        self.globs.matches(name)
+    }
+
+    /// The type recognised from the start of a file, if any.
+    #[must_use]
+    pub fn for_data(&self, data: &[u8]) -> Option<&str> {
+        self.magic
+            .iter()
+            .find(|s| s.matches(data))
+            .map(|s| s.mime.as_str())
+    }
+
+    /// Best guess of the type of an entry from its name alone, for when reading every file would
+    /// take too long.
+    #[must_use]
+    pub fn guess(&self, entry: &Entry) -> &str {
+        if let Some(mime) = special(entry) {
+            return mime;
+        }
+
+        let name = entry.name().to_string_lossy();
+        self.for_name(&name)
+            .first()
+            .copied()
+            .unwrap_or(OCTET_STREAM)
+    }
+
+    /// The type of an entry, reading the start of it if its name isn't enough.
+    pub fn detect(&self, entry: &Entry) -> String {
+        if let Some(mime) = special(entry) {
+            // devices are told apart by looking at them again
+            if entry.kind == Kind::Device {
+                return device(&entry.path);
+            }
+
+            return mime.to_owned();
+        }
+
+        self.detect_file(&entry.path)
+    }
+
+    /// The type of a file at a path.
+    pub fn detect_path(&self, path: &Path) -> String {
+        match Entry::read(path) {
+            Ok(entry) => self.detect(&entry),
+            // there's nothing to read, so the name is all there is
+            Err(_) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                self.for_name(&name)
+                    .first()
+                    .map_or(OCTET_STREAM, |m| m)
+                    .to_owned()
+            }
+        }
+    }
+
+    /// The type of a regular file.
+    ///
+    /// A single type matching the name is trusted. Otherwise the contents pick between the types
+    /// matching it, or if none do, decide the type by themselves.
+    fn detect_file(&self, path: &Path) -> String {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let candidates = self.for_name(&name);
+
+        if let [mime] = candidates[..] {
+            return mime.to_owned();
+        }
+
+        let data = match read_start(path, self.extent.max(TEXT_EXTENT)) {
+            Ok(data) => data,
+            Err(_) => return candidates.first().map_or(OCTET_STREAM, |m| m).to_owned(),
+        };
+
+        if let Some(mime) = candidates.first() {
+            let picked = self
+                .magic
+                .iter()
+                .filter(|s| candidates.iter().any(|c| self.is_a(&s.mime, c)))
+                .find(|s| s.matches(&data));
+
+            return picked.map_or(*mime, |s| &s.mime).to_owned();
+        }
+
+        if let Some(mime) = self.for_data(&data) {
+            return mime.to_owned();
+        }
+
+        // empty files are most likely about to be written, which is done with a text editor
+        if looks_like_text(&data[..data.len().min(TEXT_EXTENT)]) {
+            TEXT_PLAIN.to_owned()
+        } else {
+            OCTET_STREAM.to_owned()
+        }
+    }
+
+    /// Names of icons for a type, from the most specific to the most generic.
+    pub fn icon_names(&self, mime: &str) -> Vec<String> {
+        let mime = self.unalias(mime);
+        let mut names = vec![];
+
+        for mime in std::iter::once(mime).chain(self.ancestors(mime)) {
+            let generic = self.generic_icons.get(mime).cloned().unwrap_or_else(|| {
+                let media = mime.split('/').next().unwrap_or(mime);
+                format!("{media}-x-generic")
+            });
+
+            let own = self.icons.get(mime).cloned();
+
+            for name in own.into_iter().chain([mime.replace('/', "-"), generic]) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+
+        names
+    }
+}
+
+/// The type of a file with [`DATABASE`].
+pub fn detect(path: &Path) -> String {
+    DATABASE.detect_path(path)
+}
+
+/// Type of an entry that isn't a regular file.
+fn special(entry: &Entry) -> Option<&'static str> {
+    match entry.kind {
+        Kind::Directory => Some(DIRECTORY),
+        Kind::File => None,
+        Kind::Device => Some("inode/chardevice"),
+        Kind::Fifo => Some("inode/fifo"),
+        Kind::Socket => Some("inode/socket"),
+        // symlinks pointing nowhere
+        Kind::Other => Some("inode/symlink"),
+    }
+}
+
+/// Type of a device.
+fn device(path: &Path) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        if fs::metadata(path).is_ok_and(|m| m.file_type().is_block_device()) {
+            return "inode/blockdevice".to_owned();
+        }
+    }
+
+    let _ = path;
+    "inode/chardevice".to_owned()
+}
+
+/// Read up to some bytes from the start of a file.
+fn read_start(path: &Path, len: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(len);
+    File::open(path)?.take(len as u64).read_to_end(&mut data)?;
+
+    Ok(data)
+}
+
+/// Check if the start of a file looks like text: valid UTF-8, without control characters other
+/// than those used for formatting.
+fn looks_like_text(data: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(data) {
+        Ok(_) => true,
+        // the last character may have been cut off
+        Err(e) => e.error_len().is_none(),
+    };
+
+    valid
+        && !data
+            .iter()
+            .any(|b| (*b < 0x20 && !b"\t\n\r\x0c\x1b".contains(b)) || *b == 0x7f)
+}
+
+/// Pairs of words on every line of a file, skipping comments.
+fn pairs(path: &Path, separator: char) -> Vec<(String, String)> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return vec![];
+    };
+
+    text.lines()
+        .filter(|l| !l.starts_with('#'))
+        .filter_map(|l| l.split_once(separator))
+        .map(|(a, b)| (a.trim().to_owned(), b.trim().to_owned()))
+        .collect()
+}
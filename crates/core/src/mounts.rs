@@ -0,0 +1,431 @@
+//! Mounted filesystems.
+//!
+//! Mounts are read from `/proc/self/mountinfo`, so everything here is only useful on Linux. Other
+//! platforms just don't list anything.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where the kernel lists the mounts visible to this process.
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+/// Where udev links devices by filesystem label.
+const LABELS: &str = "/dev/disk/by-label";
+
+/// Filesystem types that don't store files, or only store them in memory.
+const PSEUDO_TYPES: [&str; 28] = [
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fuse.gvfsd-fuse",
+    "fuse.portal",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "overlay",
+    "proc",
+    "pstore",
+    "ramfs",
+    "rpc_pipefs",
+    "securityfs",
+    "selinuxfs",
+    "squashfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+    "zram",
+];
+
+/// Filesystem types that store files on another machine.
+const NETWORK_TYPES: [&str; 7] = ["afs", "cifs", "fuse.sshfs", "nfs", "nfs4", "smb3", "smbfs"];
+
+/// Directories whose mounts belong to the system rather than the user.
+const SYSTEM_DIRS: [&str; 6] = ["/boot", "/dev", "/proc", "/run/user", "/snap", "/sys"];
+
+/// A mounted filesystem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    /// Major and minor number of the device, shared by every mount of the same filesystem.
+    pub device: (u32, u32),
+    /// Directory within the filesystem that is mounted, `/` unless it's a bind mount.
+    pub root: PathBuf,
+    /// Where the filesystem is mounted.
+    pub point: PathBuf,
+    /// Filesystem type, such as `ext4`.
+    pub fs_type: String,
+    /// What is mounted, such as `/dev/sda1` or `server:/export`.
+    pub source: String,
+    /// Label of the filesystem, if it has one.
+    pub label: Option<String>,
+}
+
+impl Mount {
+    /// Check if this filesystem doesn't store files, or only stores them in memory.
+    #[must_use]
+    pub fn is_pseudo(&self) -> bool {
+        PSEUDO_TYPES.contains(&self.fs_type.as_str())
+    }
+
+    /// Check if this filesystem stores files on another machine.
+    #[must_use]
+    pub fn is_network(&self) -> bool {
+        NETWORK_TYPES.contains(&self.fs_type.as_str())
+    }
+
+    /// Check if this is worth showing as a device a user would browse.
+    #[must_use]
+    pub fn is_device(&self) -> bool {
+        let system =
+            self.point != Path::new("/") && SYSTEM_DIRS.iter().any(|d| self.point.starts_with(d));
+
+        !self.is_pseudo() && !system && (self.source.starts_with("/dev/") || self.is_network())
+    }
+
+    /// Human readable name, preferring the label.
+    #[must_use]
+    pub fn name(&self) -> String {
+        if let Some(label) = &self.label {
+            return label.clone();
+        }
+
+        if self.point == Path::new("/") {
+            return "File System".to_owned();
+        }
+
+        self.point
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.source.clone())
+    }
+
+    /// Capacity and free space, if the filesystem can tell.
+    #[must_use]
+    pub fn usage(&self) -> Option<Usage> {
+        usage(&self.point)
+    }
+}
+
+/// Capacity and free space of a filesystem, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub total: u64,
+    pub free: u64,
+    /// Free space usable without special privileges.
+    pub available: u64,
+}
+
+impl Usage {
+    /// Bytes in use.
+    #[inline]
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    /// Fraction of the filesystem in use, from `0.0` to `1.0`.
+    #[must_use]
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        self.used() as f32 / self.total as f32
+    }
+}
+
+/// Every mounted filesystem, in mount order.
+pub fn list() -> Vec<Mount> {
+    let Ok(mountinfo) = fs::read_to_string(MOUNTINFO) else {
+        return vec![];
+    };
+
+    let labels = labels();
+
+    parse(&mountinfo)
+        .map(|mut mount| {
+            mount.label = labels
+                .iter()
+                .find(|(_, device)| device.as_os_str() == mount.source.as_str())
+                .map(|(label, _)| label.clone());
+            mount
+        })
+        .collect()
+}
+
+/// Filesystems a user would browse, with a single mount for each.
+///
+/// Pseudo and system filesystems are left out, as are bind mounts of something already listed.
+pub fn devices() -> Vec<Mount> {
+    let mut devices: Vec<Mount> = vec![];
+
+    for mount in list().into_iter().filter(Mount::is_device) {
+        match devices.iter_mut().find(|m| m.device == mount.device) {
+            // prefer mounts of the whole filesystem, then the shortest path
+            Some(existing) => {
+                let better = (
+                    mount.root == Path::new("/"),
+                    std::cmp::Reverse(mount.point.as_os_str().len()),
+                ) > (
+                    existing.root == Path::new("/"),
+                    std::cmp::Reverse(existing.point.as_os_str().len()),
+                );
+
+                if better {
+                    *existing = mount;
+                }
+            }
+            None => devices.push(mount),
+        }
+    }
+
+    devices
+}
+
+/// Parse the contents of a `mountinfo` file.
+fn parse(mountinfo: &str) -> impl Iterator<Item = Mount> + '_ {
+    mountinfo.lines().filter_map(|line| {
+        // id parent major:minor root point options [optional...] - type source super-options
+        let mut fields = line.split(' ');
+
+        let device = fields.nth(2)?;
+        let root = fields.next()?;
+        let point = fields.next()?;
+
+        // optional fields come in any number, ended by a lone dash
+        let mut rest = fields.skip(1).skip_while(|f| *f != "-").skip(1);
+        let fs_type = rest.next()?;
+        let source = rest.next()?;
+
+        let (major, minor) = device.split_once(':')?;
+
+        Some(Mount {
+            device: (major.parse().ok()?, minor.parse().ok()?),
+            root: PathBuf::from(unescape(root)),
+            point: PathBuf::from(unescape(point)),
+            fs_type: fs_type.to_owned(),
+            source: unescape(source),
+            label: None,
+        })
+    })
+}
+
+/// Undo the octal escapes of spaces, tabs, newlines and backslashes.
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        let code: String = chars.clone().take(3).collect();
+
+        match u8::from_str_radix(&code, 8) {
+            Ok(byte) if code.len() == 3 => {
+                out.push(byte as char);
+                chars.nth(2);
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Undo the `\xNN` escapes udev uses for unsafe bytes in labels, such as spaces and slashes.
+fn unescape_label(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i..i + 4)
+            .filter(|e| e.starts_with(b"\\x"))
+            .and_then(|e| std::str::from_utf8(&e[2..]).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    // escaped bytes may be parts of characters, so they're only decoded once they're together
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Filesystem labels, with the device each belongs to.
+fn labels() -> Vec<(String, PathBuf)> {
+    let Ok(read) = fs::read_dir(LABELS) else {
+        return vec![];
+    };
+
+    read.filter_map(Result::ok)
+        .filter_map(|e| {
+            let device = fs::canonicalize(e.path()).ok()?;
+            let label = unescape_label(&e.file_name().to_string_lossy());
+
+            Some((label, device))
+        })
+        .collect()
+}
+
+/// Capacity and free space of the filesystem a path is on.
+#[cfg(unix)]
+pub fn usage(path: &Path) -> Option<Usage> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+    // SAFETY: the path is nul terminated, and `stat` is only read after a successful call
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+
+        stat.assume_init()
+    };
+
+    let block = stat.f_frsize as u64;
+
+    Some(Usage {
+        total: stat.f_blocks as u64 * block,
+        free: stat.f_bfree as u64 * block,
+        available: stat.f_bavail as u64 * block,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn usage(_: &Path) -> Option<Usage> {
+    None
+}
+
+/// Notices when filesystems are mounted or unmounted.
+#[derive(Debug)]
+pub struct Monitor {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    mountinfo: File,
+}
+
+impl Monitor {
+    /// Start noticing changes.
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            mountinfo: File::open(MOUNTINFO)?,
+        })
+    }
+
+    /// Block until the mounts change, or until the timeout passes.
+    ///
+    /// Returns whether the mounts changed.
+    #[cfg(target_os = "linux")]
+    pub fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        use std::os::fd::AsRawFd;
+
+        // the kernel flags mountinfo with an error priority event on every change
+        let mut poll = libc::pollfd {
+            fd: self.mountinfo.as_raw_fd(),
+            events: libc::POLLERR | libc::POLLPRI,
+            revents: 0,
+        };
+
+        let timeout = timeout.as_millis().try_into().unwrap_or(libc::c_int::MAX);
+
+        // SAFETY: a single valid pollfd is passed
+        let ready = unsafe { libc::poll(&mut poll, 1, timeout) };
+
+        if ready < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if ready == 0 {
+            return Ok(false);
+        }
+
+        // events keep firing until the file is read again
+        self.mountinfo = File::open(MOUNTINFO)?;
+        Ok(true)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        std::thread::sleep(timeout);
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_are_undone() {
+        assert_eq!(unescape(r"/media/My\040Drive"), "/media/My Drive");
+        assert_eq!(unescape(r"a\011b\012c\134d"), "a\tb\nc\\d");
+        // anything that isn't a whole escape is kept
+        assert_eq!(unescape(r"a\b"), r"a\b");
+        assert_eq!(unescape(r"end\04"), r"end\04");
+        assert_eq!(unescape(r"\"), r"\");
+    }
+
+    #[test]
+    fn label_escapes_are_undone() {
+        assert_eq!(unescape_label(r"My\x20Drive"), "My Drive");
+        assert_eq!(unescape_label(r"a\x2fb\x5cc\x23"), "a/b\\c#");
+        // bytes of a single character can be escaped separately
+        assert_eq!(unescape_label(r"caf\xc3\xa9"), "café");
+        assert_eq!(unescape_label("plain"), "plain");
+        // anything that isn't a whole escape is kept
+        assert_eq!(unescape_label(r"a\xzz"), r"a\xzz");
+        assert_eq!(unescape_label(r"end\x2"), r"end\x2");
+    }
+
+    #[test]
+    fn mountinfo_is_parsed() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+36 22 8:17 / /media/My\\040Drive rw,nosuid shared:20 master:1 - vfat /dev/sdb1 rw
+40 22 8:1 /home/shared /srv/bind rw - ext4 /dev/sda1 rw
+41 22 0:50 / /mnt/nas rw - nfs4 nas:/export rw
+42 22 0:21 / /proc rw - proc proc rw
+not a mount
+";
+        let mounts: Vec<_> = parse(mountinfo).collect();
+
+        assert_eq!(mounts.len(), 5);
+
+        assert_eq!(mounts[1].device, (8, 17));
+        assert_eq!(mounts[1].point, Path::new("/media/My Drive"));
+        assert_eq!(mounts[1].fs_type, "vfat");
+        assert_eq!(mounts[1].source, "/dev/sdb1");
+        assert_eq!(mounts[1].name(), "My Drive");
+
+        assert_eq!(mounts[2].root, Path::new("/home/shared"));
+        assert_eq!(mounts[0].name(), "File System");
+
+        let devices: Vec<_> = mounts.iter().filter(|m| m.is_device()).collect();
+        assert_eq!(devices.len(), 4);
+        assert!(mounts[3].is_network());
+        assert!(mounts[4].is_pseudo());
+    }
+}
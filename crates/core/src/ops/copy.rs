@@ -0,0 +1,693 @@
+//! Copy and move implementation.
+
+use std::collections::HashSet;
+use std::fs::{self, File, FileTimes};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::{Conflict, Context, REPORT_INTERVAL};
+use crate::path::PathWrap;
+
+/// Size of the buffer used when copying file contents.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Copy, or move if `remove_source` is set, every source into the destination directory.
+pub(super) fn copy(
+    ctx: &mut Context,
+    sources: &[PathWrap],
+    destination: &Path,
+    conflict: Conflict,
+    remove_source: bool,
+) {
+    for source in sources {
+        if ctx.is_cancelled() {
+            return;
+        }
+
+        let Some(name) = source.file_name() else {
+            ctx.fail(
+                source,
+                io::Error::new(io::ErrorKind::InvalidInput, "no file name"),
+            );
+            continue;
+        };
+
+        let is_dir = fs::symlink_metadata(source).is_ok_and(|m| m.is_dir());
+
+        // Checked before anything is done about conflicts, so nothing is replaced for nothing
+        if is_dir && destination.starts_with(source) {
+            ctx.fail(
+                source,
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "cannot copy a directory into itself",
+                ),
+            );
+            continue;
+        }
+
+        let mut target = destination.join(name);
+
+        // Moving an item onto itself is a no-op, while copying it would clobber the source
+        if target == source.as_ref() {
+            if remove_source {
+                ctx.skip(source);
+                ctx.complete(source, Some(&target));
+                continue;
+            }
+
+            target = unique_name(&target, is_dir);
+        } else if exists(&target) {
+            match conflict {
+                Conflict::Rename => target = unique_name(&target, is_dir),
+                Conflict::Skip => {
+                    ctx.skip(source);
+                    continue;
+                }
+                Conflict::Overwrite => {
+                    // Replacing a file with a directory or vice-versa can't be done in place
+                    let target_is_dir = fs::symlink_metadata(&target).is_ok_and(|m| m.is_dir());
+
+                    if target_is_dir != is_dir {
+                        if let Err(e) = remove_all(&target) {
+                            ctx.fail(&target, e);
+                            continue;
+                        }
+                    }
+                }
+            }
+        }
+
+        if remove_source {
+            match fs::rename(source, &target) {
+                Ok(_) => {
+                    ctx.skip(&target);
+                    ctx.complete(source, Some(&target));
+                    continue;
+                }
+                // Crossing filesystems or merging into an existing directory needs a full copy
+                Err(e) if e.kind() == io::ErrorKind::CrossesDevices || exists(&target) => {}
+                Err(e) => {
+                    ctx.fail(source, e);
+                    continue;
+                }
+            }
+        }
+
+        let merged = is_dir && exists(&target);
+
+        if !copy_tree(ctx, source, &target) {
+            continue;
+        }
+
+        if remove_source {
+            if let Err(e) = remove_all(source) {
+                ctx.fail(source, e);
+                continue;
+            }
+        }
+
+        if merged {
+            ctx.complete_merged(source, &target);
+        } else {
+            ctx.complete(source, Some(&target));
+        }
+    }
+}
+
+/// Move each source to the exact target path at the same index.
+///
+/// Unlike [`copy`], existing targets are never replaced or renamed around. Renames are all or
+/// nothing: nothing is moved unless every target is free and different from the others, and if
+/// one fails the others are moved back. Targets may be other sources, such as when two names are swapped.
+pub(super) fn rename(ctx: &mut Context, sources: &[PathWrap], targets: &[PathWrap]) {
+    let moving: HashSet<&Path> = sources.iter().map(AsRef::as_ref).collect();
+    let pairs: Vec<_> = sources.iter().zip(targets).collect();
+
+    // one item would replace the other
+    let mut claimed = HashSet::new();
+
+    if let Some(duplicate) = pairs.iter().position(|(_, t)| !claimed.insert(t.as_ref())) {
+        let error = io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "another item is renamed to the same name",
+        );
+        fail_all(ctx, &pairs, duplicate, error);
+        return;
+    }
+
+    if let Some(blocked) = pairs
+        .iter()
+        .position(|(s, t)| s != t && exists(t) && !moving.contains(t.as_ref()))
+    {
+        let error = io::Error::new(io::ErrorKind::AlreadyExists, "target already exists");
+        fail_all(ctx, &pairs, blocked, error);
+        return;
+    }
+
+    // names taken by other sources are only free once everything is out of the way
+    let chained = pairs
+        .iter()
+        .any(|(s, t)| s != t && moving.contains(t.as_ref()));
+
+    let staged = if chained {
+        match stage(sources) {
+            Ok(staged) => staged,
+            Err((index, e)) => {
+                fail_all(ctx, &pairs, index, e);
+                return;
+            }
+        }
+    } else {
+        sources.iter().map(|s| s.to_path_buf()).collect()
+    };
+
+    for (index, ((_, target), from)) in pairs.iter().zip(&staged).enumerate() {
+        let result = if ctx.is_cancelled() {
+            Err(None)
+        } else if from.as_path() == target.as_ref() {
+            Ok(())
+        } else {
+            move_item(ctx, from, target).map_err(Some)
+        };
+
+        ctx.file_done();
+
+        if let Err(error) = result {
+            // put everything back the way it was, by way of the staged names, since a source
+            // may be another item's target
+            for ((_, target), from) in pairs[..index].iter().zip(&staged).rev() {
+                if from.as_path() != target.as_ref() {
+                    let _ = move_item(ctx, target, from);
+                }
+            }
+            for ((source, _), from) in pairs.iter().zip(&staged) {
+                if from.as_path() != source.as_ref() {
+                    let _ = fs::rename(from, source);
+                }
+            }
+
+            if let Some(error) = error {
+                fail_all(ctx, &pairs, index, error);
+            }
+            return;
+        }
+    }
+
+    for (source, target) in pairs {
+        ctx.complete(source, Some(target));
+    }
+}
+
+/// Move every source aside to a temporary name next to it.
+///
+/// If one can't be moved, the others are moved back and the index of the failed one returned.
+fn stage(sources: &[PathWrap]) -> Result<Vec<PathBuf>, (usize, io::Error)> {
+    let mut staged: Vec<PathBuf> = vec![];
+
+    for (index, source) in sources.iter().enumerate() {
+        let name = source.file_name().unwrap_or_default().to_string_lossy();
+        let parent = source.parent().unwrap_or(Path::new(""));
+
+        let temporary = (0..)
+            .map(|n| parent.join(format!(".{name}.doseer-rename-{n}")))
+            .find(|p| !exists(p))
+            .unwrap();
+
+        if let Err(e) = fs::rename(source, &temporary) {
+            for (source, temporary) in sources.iter().zip(&staged) {
+                let _ = fs::rename(temporary, source);
+            }
+
+            return Err((index, e));
+        }
+
+        staged.push(temporary);
+    }
+
+    Ok(staged)
+}
+
+/// Move an item, copying it if it's on another filesystem.
+fn move_item(ctx: &mut Context, source: &Path, target: &Path) -> io::Result<()> {
+    match fs::rename(source, target) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            if !copy_tree(ctx, source, target) {
+                let _ = remove_all(target);
+                return Err(io::Error::other("failed to copy to the other filesystem"));
+            }
+
+            remove_all(source)
+        }
+        result => result,
+    }
+}
+
+/// Record the failure of one rename, and that none of the others were done because of it.
+fn fail_all(ctx: &mut Context, pairs: &[(&PathWrap, &PathWrap)], failed: usize, error: io::Error) {
+    let mut error = Some(error);
+
+    for (index, (source, _)) in pairs.iter().enumerate() {
+        let error = if index == failed { error.take() } else { None };
+        let error = error
+            .unwrap_or_else(|| io::Error::other("not renamed, since another item couldn't be"));
+
+        ctx.fail(source, error);
+    }
+}
+
+/// Recursively copy a path, returning whether everything was copied.
+fn copy_tree(ctx: &mut Context, source: &Path, target: &Path) -> bool {
+    let meta = match fs::symlink_metadata(source) {
+        Ok(m) => m,
+        Err(e) => {
+            ctx.fail(source, e);
+            return false;
+        }
+    };
+
+    let file_type = meta.file_type();
+
+    if file_type.is_symlink() {
+        let result = copy_symlink(source, target);
+        ctx.file_done();
+
+        return match result {
+            Ok(_) => true,
+            Err(e) => {
+                ctx.fail(source, e);
+                false
+            }
+        };
+    }
+
+    if !file_type.is_dir() {
+        return copy_file(ctx, source, target, meta.len());
+    }
+
+    // Directories merged into were there before, so only new ones are removed when cancelled
+    let created = match fs::create_dir(target) {
+        Ok(_) => true,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists && target.is_dir() => false,
+        Err(e) => {
+            ctx.fail(target, e);
+            return false;
+        }
+    };
+
+    let entries = match fs::read_dir(source) {
+        Ok(e) => e,
+        Err(e) => {
+            ctx.fail(source, e);
+            return false;
+        }
+    };
+
+    let mut ok = true;
+
+    for entry in entries {
+        if ctx.is_cancelled() {
+            break;
+        }
+
+        match entry {
+            Ok(entry) => ok &= copy_tree(ctx, &entry.path(), &target.join(entry.file_name())),
+            Err(e) => {
+                ctx.fail(source, e);
+                ok = false;
+            }
+        }
+    }
+
+    // Don't leave half copied directories around
+    if ctx.is_cancelled() {
+        if created {
+            let _ = fs::remove_dir_all(target);
+        }
+
+        return false;
+    }
+
+    // Set permissions last, in case the source directory is read-only
+    let _ = fs::set_permissions(target, meta.permissions());
+
+    ok
+}
+
+/// Copy the contents of a single file, reporting progress along the way.
+fn copy_file(ctx: &mut Context, source: &Path, target: &Path, len: u64) -> bool {
+    let result = (|| -> io::Result<bool> {
+        let mut reader = File::open(source)?;
+        let mut writer = File::create(target)?;
+
+        let mut buf = vec![0; BUFFER_SIZE];
+        let mut done = 0;
+        let mut last_report = 0;
+
+        loop {
+            if ctx.is_cancelled() {
+                return Ok(false);
+            }
+
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            writer.write_all(&buf[..n])?;
+
+            done += n as u64;
+            ctx.advance(n as u64);
+
+            if done - last_report >= REPORT_INTERVAL {
+                last_report = done;
+                ctx.file_progress(source, done, len);
+            }
+        }
+
+        let meta = reader.metadata()?;
+        let mut times = FileTimes::new();
+
+        if let Ok(modified) = meta.modified() {
+            times = times.set_modified(modified);
+        }
+        if let Ok(accessed) = meta.accessed() {
+            times = times.set_accessed(accessed);
+        }
+
+        let _ = writer.set_times(times);
+        writer.set_permissions(meta.permissions())?;
+
+        Ok(true)
+    })();
+
+    ctx.file_done();
+
+    match result {
+        Ok(true) => {
+            ctx.file_progress(source, len, len);
+            true
+        }
+        // Don't leave half copied files around
+        Ok(false) => {
+            let _ = fs::remove_file(target);
+            false
+        }
+        Err(e) => {
+            let _ = fs::remove_file(target);
+            ctx.fail(source, e);
+            false
+        }
+    }
+}
+
+/// Recreate a symlink at a new location.
+fn copy_symlink(source: &Path, target: &Path) -> io::Result<()> {
+    let link = fs::read_link(source)?;
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(link, target)
+    }
+
+    #[cfg(windows)]
+    {
+        if source.is_dir() {
+            std::os::windows::fs::symlink_dir(link, target)
+        } else {
+            std::os::windows::fs::symlink_file(link, target)
+        }
+    }
+}
+
+/// Remove a file or directory tree.
+pub(crate) fn remove_all(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Check if anything, including a broken symlink, exists at a path.
+#[inline]
+pub(crate) fn exists(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+/// Find an unused name for a copy, like `file (copy).txt` or `file (copy 2).txt`.
+#[inline]
+pub(crate) fn unique_name(target: &Path, is_dir: bool) -> PathBuf {
+    unique_name_with(target, is_dir, exists)
+}
+
+/// Find an unused name for a copy, checking whether names are used with `exists`.
+pub(crate) fn unique_name_with<F>(target: &Path, is_dir: bool, exists: F) -> PathBuf
+where
+    F: Fn(&Path) -> bool,
+{
+    let parent = target.parent().unwrap_or(Path::new(""));
+
+    let name = target
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+
+    // Directories don't have extensions, no matter what their name looks like
+    let (stem, extension) = match target.extension() {
+        Some(ext) if !is_dir => {
+            let ext = ext.to_string_lossy();
+            (
+                name[..name.len() - ext.len() - 1].to_owned(),
+                format!(".{ext}"),
+            )
+        }
+        _ => (name, String::new()),
+    };
+
+    (1..)
+        .map(|n| {
+            let suffix = if n == 1 {
+                "copy".to_owned()
+            } else {
+                format!("copy {n}")
+            };
+
+            parent.join(format!("{stem} ({suffix}){extension}"))
+        })
+        .find(|p| !exists(p))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::{self, Operation, Report};
+    use crate::task::CancelToken;
+
+    fn wrap(path: impl AsRef<Path>) -> PathWrap {
+        PathWrap::from_into_path(path.as_ref().to_path_buf())
+    }
+
+    fn rename_all(sources: &[PathWrap], targets: &[PathWrap]) -> Report {
+        ops::spawn(Operation::Rename {
+            sources: sources.to_vec(),
+            targets: targets.to_vec(),
+        })
+        .wait()
+        .unwrap()
+    }
+
+    /// Names of everything in a directory, sorted.
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn copy_all(sources: &[PathWrap], destination: &Path, conflict: Conflict) -> Report {
+        ops::spawn(Operation::Copy {
+            sources: sources.to_vec(),
+            destination: wrap(destination),
+            conflict,
+        })
+        .wait()
+        .unwrap()
+    }
+
+    #[test]
+    fn trees_are_copied() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        fs::create_dir_all(source.join("inner")).unwrap();
+        fs::write(source.join("inner/file"), "contents").unwrap();
+        fs::create_dir(dir.path().join("destination")).unwrap();
+
+        let report = copy_all(
+            &[wrap(&source)],
+            &dir.path().join("destination"),
+            Conflict::Skip,
+        );
+
+        assert!(report.is_success());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("destination/source/inner/file")).unwrap(),
+            "contents"
+        );
+        assert_eq!(names(&source), ["inner"]);
+    }
+
+    #[test]
+    fn directories_are_not_copied_into_themselves() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        fs::create_dir_all(source.join("inner")).unwrap();
+        fs::write(source.join("inner/source"), "").unwrap();
+
+        // the conflict with `inner/source` isn't resolved by replacing it first
+        let report = copy_all(&[wrap(&source)], &source.join("inner"), Conflict::Overwrite);
+
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].error.kind(), io::ErrorKind::InvalidInput);
+        assert!(source.join("inner/source").is_file());
+    }
+
+    #[test]
+    fn conflicts_are_renamed_around() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = wrap(dir.path().join("file.txt"));
+        fs::write(&file, "").unwrap();
+
+        // copying next to itself always makes a new name
+        let report = copy_all(std::slice::from_ref(&file), dir.path(), Conflict::Overwrite);
+
+        assert!(report.is_success());
+        assert_eq!(names(dir.path()).len(), 2);
+
+        let report = copy_all(&[file], dir.path(), Conflict::Rename);
+
+        assert!(report.is_success());
+        assert_eq!(names(dir.path()).len(), 3);
+    }
+
+    #[test]
+    fn cancelled_copies_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("file"), "contents").unwrap();
+
+        let (sender, _progress) = std::sync::mpsc::channel();
+        let cancel = CancelToken::new();
+        let mut ctx = Context::new(&sender, &cancel);
+        cancel.cancel();
+
+        let target = dir.path().join("target");
+        assert!(!copy_tree(&mut ctx, &source, &target));
+        assert!(!target.exists());
+
+        // folders that were already there are merged into, so they stay
+        fs::create_dir(&target).unwrap();
+        assert!(!copy_tree(&mut ctx, &source, &target));
+        assert!(target.is_dir());
+    }
+
+    #[test]
+    fn names_can_be_swapped() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (wrap(dir.path().join("a")), wrap(dir.path().join("b")));
+        fs::write(&a, "first").unwrap();
+        fs::write(&b, "second").unwrap();
+
+        let report = rename_all(&[a.clone(), b.clone()], &[b.clone(), a.clone()]);
+
+        assert!(report.is_success());
+        assert_eq!(fs::read_to_string(&a).unwrap(), "second");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "first");
+        // nothing staged is left behind
+        assert_eq!(names(dir.path()), ["a", "b"]);
+    }
+
+    #[test]
+    fn taken_targets_stop_every_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b, c) = (
+            wrap(dir.path().join("a")),
+            wrap(dir.path().join("b")),
+            wrap(dir.path().join("c")),
+        );
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "").unwrap();
+        fs::write(&c, "").unwrap();
+
+        let report = rename_all(&[a, b], &[wrap(dir.path().join("d")), c]);
+
+        assert_eq!(report.failed.len(), 2);
+        assert!(report.completed.is_empty());
+        assert_eq!(names(dir.path()), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn duplicate_targets_stop_every_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (wrap(dir.path().join("a")), wrap(dir.path().join("b")));
+        fs::write(&a, "first").unwrap();
+        fs::write(&b, "second").unwrap();
+
+        let same = wrap(dir.path().join("same"));
+        let report = rename_all(&[a, b], &[same.clone(), same]);
+
+        assert_eq!(report.failed.len(), 2);
+        assert!(report.completed.is_empty());
+        assert_eq!(names(dir.path()), ["a", "b"]);
+    }
+
+    #[test]
+    fn failed_renames_are_rolled_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (wrap(dir.path().join("a")), wrap(dir.path().join("b")));
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "").unwrap();
+
+        // the second target's folder doesn't exist, so it fails after the first is renamed
+        let report = rename_all(
+            &[a, b],
+            &[
+                wrap(dir.path().join("x")),
+                wrap(dir.path().join("missing/y")),
+            ],
+        );
+
+        assert_eq!(report.failed.len(), 2);
+        assert_eq!(names(dir.path()), ["a", "b"]);
+    }
+
+    #[test]
+    fn failed_swaps_are_unstaged() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b, c) = (
+            wrap(dir.path().join("a")),
+            wrap(dir.path().join("b")),
+            wrap(dir.path().join("c")),
+        );
+        fs::write(&a, "first").unwrap();
+        fs::write(&b, "second").unwrap();
+        fs::write(&c, "").unwrap();
+
+        let report = rename_all(
+            &[a.clone(), b.clone(), c],
+            &[b.clone(), a.clone(), wrap(dir.path().join("missing/c"))],
+        );
+
+        assert_eq!(report.failed.len(), 3);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "first");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "second");
+        assert_eq!(names(dir.path()), ["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,89 @@
+//! Permanent deletion implementation.
+
+use std::fs;
+use std::path::Path;
+
+use super::Context;
+use crate::path::PathWrap;
+use crate::trash;
+
+/// Delete every target, along with everything inside it.
+pub(super) fn delete(ctx: &mut Context, targets: &[PathWrap]) {
+    for target in targets {
+        if ctx.is_cancelled() {
+            return;
+        }
+
+        if remove_tree(ctx, target) {
+            // Deleting something from the trash also removes its trash info
+            if let Err(e) = trash::forget(target) {
+                ctx.fail(target, e);
+                continue;
+            }
+
+            ctx.complete(target, None);
+        }
+    }
+}
+
+/// Remove a path one file at a time so progress can be reported, returning whether everything
+/// was removed.
+fn remove_tree(ctx: &mut Context, path: &Path) -> bool {
+    let meta = match fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(e) => {
+            ctx.fail(path, e);
+            return false;
+        }
+    };
+
+    if !meta.is_dir() {
+        let result = fs::remove_file(path);
+
+        ctx.advance(meta.len());
+        ctx.file_done();
+
+        return match result {
+            Ok(_) => true,
+            Err(e) => {
+                ctx.fail(path, e);
+                false
+            }
+        };
+    }
+
+    let entries = match fs::read_dir(path) {
+        Ok(e) => e,
+        Err(e) => {
+            ctx.fail(path, e);
+            return false;
+        }
+    };
+
+    let mut ok = true;
+
+    for entry in entries {
+        if ctx.is_cancelled() {
+            return false;
+        }
+
+        match entry {
+            Ok(entry) => ok &= remove_tree(ctx, &entry.path()),
+            Err(e) => {
+                ctx.fail(path, e);
+                ok = false;
+            }
+        }
+    }
+
+    ctx.file_progress(path, 0, 0);
+
+    if ok {
+        if let Err(e) = fs::remove_dir(path) {
+            ctx.fail(path, e);
+            return false;
+        }
+    }
+
+    ok
+}
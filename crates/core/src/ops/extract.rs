@@ -0,0 +1,135 @@
+//! Extracting items out of archives.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::copy::{remove_all, unique_name};
+use super::{Conflict, Context};
+use crate::archive::{self, Archive};
+use crate::path::PathWrap;
+
+/// Extract every source, each of which is inside an archive, into the destination directory.
+pub(super) fn extract(
+    ctx: &mut Context,
+    sources: &[PathWrap],
+    destination: &Path,
+    conflict: Conflict,
+) {
+    // sources can come from several archives, and each should only be read once
+    let mut archives: Vec<(PathBuf, Vec<(&PathWrap, PathBuf)>)> = vec![];
+
+    for source in sources {
+        let Some((archive, inner)) = archive::split(source) else {
+            ctx.fail(
+                source,
+                io::Error::new(io::ErrorKind::InvalidInput, "not inside an archive"),
+            );
+            continue;
+        };
+
+        match archives.iter_mut().find(|(a, _)| *a == archive) {
+            Some((_, items)) => items.push((source, inner)),
+            None => archives.push((archive, vec![(source, inner)])),
+        }
+    }
+
+    for (path, items) in archives {
+        if ctx.is_cancelled() {
+            return;
+        }
+
+        let archive = match Archive::open(&path) {
+            Ok(a) => a,
+            Err(e) => {
+                ctx.fail(&path, e);
+                continue;
+            }
+        };
+
+        let mut targets = vec![];
+        let mut skipped = vec![];
+
+        for (source, inner) in items {
+            let Some(name) = inner.file_name() else {
+                // the archive as a whole is extracted into a folder named after it
+                let name = path.file_stem().unwrap_or(path.as_os_str());
+                targets.push((source, inner, unique_name(&destination.join(name), true)));
+                continue;
+            };
+
+            let is_dir = archive.is_dir(&inner);
+            let mut target = destination.join(name);
+
+            if fs::symlink_metadata(&target).is_ok() {
+                match conflict {
+                    Conflict::Rename => target = unique_name(&target, is_dir),
+                    Conflict::Skip => {
+                        skipped.push(inner);
+                        continue;
+                    }
+                    Conflict::Overwrite => {
+                        // a file and a directory can't replace each other in place
+                        let target_is_dir = fs::symlink_metadata(&target).is_ok_and(|m| m.is_dir());
+
+                        if target_is_dir != is_dir {
+                            if let Err(e) = remove_all(&target) {
+                                ctx.fail(&target, e);
+                                continue;
+                            }
+                        }
+                    }
+                }
+            }
+
+            targets.push((source, inner, target));
+        }
+
+        for (_, inner, _) in &targets {
+            ctx.totals.files_total += archive.files_in(inner);
+            ctx.totals.bytes_total += archive.size_of(inner);
+        }
+
+        // skipped items are done without anything to do, like when copying
+        for inner in &skipped {
+            let (files, bytes) = (archive.files_in(inner), archive.size_of(inner));
+            ctx.totals.files_total += files;
+            ctx.totals.files_done += files;
+            ctx.totals.bytes_total += bytes;
+            ctx.totals.bytes_done += bytes;
+        }
+
+        let pairs: Vec<_> = targets
+            .iter()
+            .map(|(_, inner, target)| (inner.clone(), target.clone()))
+            .collect();
+        let failed_before = ctx.failed.len();
+
+        let result = archive.extract(&pairs, ctx.cancel, |target, result| match result {
+            Ok(bytes) => {
+                ctx.advance(bytes);
+
+                if target.is_file() {
+                    ctx.file_done();
+                    ctx.file_progress(target, bytes, bytes);
+                }
+            }
+            Err(e) => ctx.fail(target, e),
+        });
+
+        if let Err(e) = result {
+            ctx.fail(&path, e);
+            continue;
+        }
+
+        for (source, _, target) in &targets {
+            let failed = ctx.failed[failed_before..]
+                .iter()
+                .any(|f| f.path.starts_with(target));
+
+            if !failed && !ctx.is_cancelled() {
+                ctx.complete(source, Some(target));
+            }
+        }
+    }
+}
@@ -0,0 +1,534 @@
+//! File operations - copying, moving, trashing and deleting - run on background threads.
+//!
+//! Every operation is started with [`spawn`], which returns a [`Job`] handle. A job streams
+//! [`Progress`] updates while it runs and always ends with a [`Progress::Finished`] carrying the
+//! final [`Report`] of what succeeded and what failed.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::path::PathWrap;
+use crate::permissions::Change;
+use crate::task::CancelToken;
+use crate::trash;
+use crate::vfs::Uri;
+
+pub(crate) mod copy;
+mod delete;
+mod extract;
+mod permissions;
+mod transfer;
+
+/// What to do when the destination of a copy or move already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Conflict {
+    /// Pick a new, unused name such as `file (copy).txt`.
+    #[default]
+    Rename,
+    /// Replace the existing destination.
+    Overwrite,
+    /// Leave the existing destination alone and skip the source.
+    Skip,
+}
+
+/// A file operation to perform.
+#[derive(Debug, Clone)]
+pub enum Operation {
+    /// Copy items into a directory.
+    Copy {
+        sources: Vec<PathWrap>,
+        destination: PathWrap,
+        conflict: Conflict,
+    },
+    /// Move items into a directory.
+    Move {
+        sources: Vec<PathWrap>,
+        destination: PathWrap,
+        conflict: Conflict,
+    },
+    /// Permanently delete items.
+    Delete { targets: Vec<PathWrap> },
+    /// Move items to the trash.
+    Trash { targets: Vec<PathWrap> },
+    /// Move trashed items back to where they came from.
+    Restore {
+        items: Vec<PathWrap>,
+        conflict: Conflict,
+    },
+    /// Move each source to the exact target path at the same index, never replacing anything.
+    Rename {
+        sources: Vec<PathWrap>,
+        targets: Vec<PathWrap>,
+    },
+    /// Copy items out of archives into a directory.
+    ///
+    /// Sources are paths inside archives, as described in [`archive`](crate::archive).
+    Extract {
+        sources: Vec<PathWrap>,
+        destination: PathWrap,
+        conflict: Conflict,
+    },
+    /// Copy items into a directory on any backend, such as to or from a server.
+    Transfer {
+        sources: Vec<Uri>,
+        destination: Uri,
+        conflict: Conflict,
+    },
+    /// Permanently delete items on any backend.
+    Remove { targets: Vec<Uri> },
+    /// Create an empty directory.
+    CreateDir { path: PathWrap },
+    /// Remove an empty directory.
+    RemoveDir { path: PathWrap },
+    /// Change the permissions or owner of items.
+    SetPermissions {
+        targets: Vec<PathWrap>,
+        change: Change,
+    },
+}
+
+impl Operation {
+    /// The local items this operation acts on.
+    ///
+    /// Items that may be elsewhere, such as on a server, aren't included.
+    #[must_use]
+    pub fn sources(&self) -> &[PathWrap] {
+        match self {
+            Self::Copy { sources, .. }
+            | Self::Move { sources, .. }
+            | Self::Rename { sources, .. }
+            | Self::Extract { sources, .. } => sources,
+            Self::Delete { targets }
+            | Self::Trash { targets }
+            | Self::SetPermissions { targets, .. } => targets,
+            Self::Restore { items, .. } => items,
+            Self::CreateDir { path } | Self::RemoveDir { path } => std::slice::from_ref(path),
+            Self::Transfer { .. } | Self::Remove { .. } => &[],
+        }
+    }
+
+    /// Number of items this operation acts on, wherever they are.
+    #[must_use]
+    pub fn count(&self) -> usize {
+        match self {
+            Self::Transfer { sources, .. } => sources.len(),
+            Self::Remove { targets } => targets.len(),
+            _ => self.sources().len(),
+        }
+    }
+
+    /// Short human readable description of the operation, such as `"Copying"`.
+    #[must_use]
+    pub const fn verb(&self) -> &'static str {
+        match self {
+            Self::Copy { .. } => "Copying",
+            Self::Move { .. } => "Moving",
+            Self::Delete { .. } => "Deleting",
+            Self::Trash { .. } => "Trashing",
+            Self::Restore { .. } => "Restoring",
+            Self::Rename { .. } => "Renaming",
+            Self::Extract { .. } => "Extracting",
+            Self::Transfer { .. } => "Copying",
+            Self::Remove { .. } => "Deleting",
+            Self::CreateDir { .. } => "Creating",
+            Self::RemoveDir { .. } => "Removing",
+            Self::SetPermissions { .. } => "Changing",
+        }
+    }
+}
+
+/// Byte and file counters for a job.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub files_done: u64,
+    pub files_total: u64,
+}
+
+impl Totals {
+    /// Fraction of work completed, from `0.0` to `1.0`.
+    ///
+    /// Measured in bytes where possible, falling back to file counts for jobs that only contain
+    /// empty files.
+    #[must_use]
+    pub fn fraction(&self) -> f32 {
+        if self.bytes_total > 0 {
+            self.bytes_done as f32 / self.bytes_total as f32
+        } else if self.files_total > 0 {
+            self.files_done as f32 / self.files_total as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Progress updates sent by a running [`Job`].
+#[derive(Debug, Clone)]
+pub enum Progress {
+    /// The job has finished measuring its sources.
+    Scanned(Totals),
+    /// Progress on a single file.
+    File {
+        path: PathWrap,
+        done: u64,
+        total: u64,
+    },
+    /// Overall progress.
+    Total(Totals),
+    /// The job is done. This is always the last update sent.
+    Finished(Report),
+}
+
+/// A top-level item that was fully processed.
+#[derive(Debug, Clone)]
+pub struct Completed {
+    /// The item the operation was asked to act on.
+    pub source: PathWrap,
+    /// Where the item ended up, if it still exists.
+    pub destination: Option<PathWrap>,
+    /// The item was a directory merged into one that was already there, so the destination
+    /// holds more than the item.
+    pub merged: bool,
+}
+
+/// A file that could not be processed.
+#[derive(Debug, Clone)]
+pub struct Failed {
+    pub path: PathWrap,
+    pub error: Arc<io::Error>,
+}
+
+/// Final outcome of a job.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// The operation that was run.
+    pub operation: Operation,
+    /// Top-level items that were processed without errors.
+    pub completed: Vec<Completed>,
+    /// Every file that failed, along with the reason.
+    pub failed: Vec<Failed>,
+    /// Whether the job was stopped before it could finish.
+    pub cancelled: bool,
+}
+
+impl Report {
+    /// Whether every item was processed.
+    #[must_use]
+    #[inline]
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty() && !self.cancelled
+    }
+}
+
+/// Handle to a running operation.
+///
+/// Iterating over a job blocks until the next [`Progress`] update is available, and stops after
+/// the job has finished.
+#[derive(Debug)]
+pub struct Job {
+    progress: Receiver<Progress>,
+    cancel: CancelToken,
+}
+
+impl Job {
+    /// Ask the job to stop as soon as possible.
+    ///
+    /// Partially copied files are cleaned up, and the job still finishes with a [`Report`].
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// The token used to cancel this job.
+    #[must_use]
+    #[inline]
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    /// Block until the job is done, discarding all intermediate progress.
+    pub fn wait(self) -> Option<Report> {
+        self.into_iter().find_map(|p| match p {
+            Progress::Finished(report) => Some(report),
+            _ => None,
+        })
+    }
+}
+
+impl Iterator for Job {
+    type Item = Progress;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.progress.recv().ok()
+    }
+}
+
+/// Start an operation on a background thread.
+#[must_use]
+#[inline]
+pub fn spawn(operation: Operation) -> Job {
+    spawn_with(operation, CancelToken::new())
+}
+
+/// Start an operation on a background thread, cancellable through an existing token.
+#[must_use]
+pub fn spawn_with(operation: Operation, cancel: CancelToken) -> Job {
+    let (sender, progress) = mpsc::channel();
+
+    let worker_cancel = cancel.clone();
+    thread::spawn(move || {
+        let report = run(operation, &sender, &worker_cancel);
+        let _ = sender.send(Progress::Finished(report));
+    });
+
+    Job { progress, cancel }
+}
+
+/// Run an operation to completion on the current thread.
+fn run(operation: Operation, sender: &Sender<Progress>, cancel: &CancelToken) -> Report {
+    let mut ctx = Context::new(sender, cancel);
+
+    match &operation {
+        Operation::Copy { .. } | Operation::Move { .. } | Operation::Delete { .. } => {
+            ctx.measure(operation.sources())
+        }
+        // Extracting measures what's in the archive itself, and transfers what they list
+        Operation::Extract { .. } | Operation::Transfer { .. } => {}
+        // Everything else is (usually) a rename, so measuring every file would be a waste
+        _ => {
+            ctx.totals.files_total = operation.count() as u64;
+        }
+    }
+
+    let _ = sender.send(Progress::Scanned(ctx.totals));
+
+    match &operation {
+        Operation::Copy {
+            sources,
+            destination,
+            conflict,
+        } => copy::copy(&mut ctx, sources, destination, *conflict, false),
+        Operation::Move {
+            sources,
+            destination,
+            conflict,
+        } => copy::copy(&mut ctx, sources, destination, *conflict, true),
+        Operation::Delete { targets } => delete::delete(&mut ctx, targets),
+        Operation::Trash { targets } => {
+            for target in targets {
+                if ctx.is_cancelled() {
+                    break;
+                }
+
+                match trash::trash(target) {
+                    Ok(item) => ctx.complete(target, Some(&item.path)),
+                    Err(e) => ctx.fail(target, e),
+                }
+
+                ctx.file_done();
+            }
+        }
+        Operation::Restore { items, conflict } => {
+            for item in items {
+                if ctx.is_cancelled() {
+                    break;
+                }
+
+                match trash::restore(item, *conflict) {
+                    Ok(Some(restored)) => ctx.complete(item, Some(&restored)),
+                    Ok(None) => {}
+                    Err(e) => ctx.fail(item, e),
+                }
+
+                ctx.file_done();
+            }
+        }
+        Operation::Rename { sources, targets } => copy::rename(&mut ctx, sources, targets),
+        Operation::Extract {
+            sources,
+            destination,
+            conflict,
+        } => extract::extract(&mut ctx, sources, destination, *conflict),
+        Operation::Transfer {
+            sources,
+            destination,
+            conflict,
+        } => transfer::transfer(&mut ctx, sources, destination, *conflict),
+        Operation::Remove { targets } => transfer::remove(&mut ctx, targets),
+        Operation::CreateDir { path } => {
+            match fs::create_dir(path) {
+                Ok(_) => ctx.complete(path, Some(path)),
+                Err(e) => ctx.fail(path, e),
+            }
+
+            ctx.file_done();
+        }
+        Operation::RemoveDir { path } => {
+            match fs::remove_dir(path) {
+                Ok(_) => ctx.complete(path, None),
+                Err(e) => ctx.fail(path, e),
+            }
+
+            ctx.file_done();
+        }
+        Operation::SetPermissions { targets, change } => {
+            permissions::set(&mut ctx, targets, change)
+        }
+    }
+
+    let _ = sender.send(Progress::Total(ctx.totals));
+
+    Report {
+        operation,
+        completed: ctx.completed,
+        failed: ctx.failed,
+        cancelled: cancel.is_cancelled(),
+    }
+}
+
+/// Find an unused path for a new item in a directory, like `New Folder` or `New Folder 2`.
+#[must_use]
+pub fn unused_path(directory: &Path, name: &str) -> PathWrap {
+    let path = (1..)
+        .map(|n| match n {
+            1 => directory.join(name),
+            n => directory.join(format!("{name} {n}")),
+        })
+        .find(|p| fs::symlink_metadata(p).is_err())
+        .unwrap();
+
+    PathWrap::from_into_path(path)
+}
+
+/// Minimum number of bytes processed between two progress updates for the same file.
+const REPORT_INTERVAL: u64 = 4 * 1024 * 1024;
+
+/// State shared by the operation implementations while a job is running.
+struct Context<'a> {
+    sender: &'a Sender<Progress>,
+    cancel: &'a CancelToken,
+    totals: Totals,
+    completed: Vec<Completed>,
+    failed: Vec<Failed>,
+}
+
+impl<'a> Context<'a> {
+    fn new(sender: &'a Sender<Progress>, cancel: &'a CancelToken) -> Self {
+        Self {
+            sender,
+            cancel,
+            totals: Totals::default(),
+            completed: vec![],
+            failed: vec![],
+        }
+    }
+
+    #[inline]
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Count the files and bytes under some paths.
+    fn measure(&mut self, paths: &[PathWrap]) {
+        for path in paths {
+            if self.is_cancelled() {
+                return;
+            }
+
+            let (files, bytes) = measure(path, self.cancel);
+            self.totals.files_total += files;
+            self.totals.bytes_total += bytes;
+        }
+    }
+
+    /// Record a failure.
+    fn fail(&mut self, path: &Path, error: io::Error) {
+        self.failed.push(Failed {
+            path: PathWrap::from_path(path),
+            error: Arc::new(error),
+        });
+    }
+
+    /// Record a fully processed top-level item.
+    fn complete(&mut self, source: &PathWrap, destination: Option<&Path>) {
+        self.completed.push(Completed {
+            source: source.clone(),
+            destination: destination.map(PathWrap::from_path),
+            merged: false,
+        });
+    }
+
+    /// Record a top-level directory that was merged into one already at the destination.
+    fn complete_merged(&mut self, source: &PathWrap, destination: &Path) {
+        self.completed.push(Completed {
+            source: source.clone(),
+            destination: Some(PathWrap::from_path(destination)),
+            merged: true,
+        });
+    }
+
+    /// Report progress within a single file.
+    fn file_progress(&mut self, path: &Path, done: u64, total: u64) {
+        let _ = self.sender.send(Progress::File {
+            path: PathWrap::from_path(path),
+            done,
+            total,
+        });
+        let _ = self.sender.send(Progress::Total(self.totals));
+    }
+
+    /// Count some bytes as processed.
+    #[inline]
+    fn advance(&mut self, bytes: u64) {
+        self.totals.bytes_done += bytes;
+    }
+
+    /// Count a file as processed.
+    #[inline]
+    fn file_done(&mut self) {
+        self.totals.files_done += 1;
+    }
+
+    /// Count everything under a path as processed without touching each file, like after a
+    /// single `rename`.
+    fn skip(&mut self, path: &Path) {
+        let (files, bytes) = measure(path, self.cancel);
+        self.totals.files_done += files;
+        self.totals.bytes_done += bytes;
+        let _ = self.sender.send(Progress::Total(self.totals));
+    }
+}
+
+/// Count the files and bytes under a path, without following symlinks.
+fn measure(path: &Path, cancel: &CancelToken) -> (u64, u64) {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return (0, 0);
+    };
+
+    if !meta.is_dir() {
+        return (1, meta.len());
+    }
+
+    let mut files = 0;
+    let mut bytes = 0;
+
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.filter_map(Result::ok) {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let (f, b) = measure(&entry.path(), cancel);
+            files += f;
+            bytes += b;
+        }
+    }
+
+    (files, bytes)
+}
@@ -0,0 +1,61 @@
+//! Changing the permissions and owners of items.
+
+use std::fs;
+use std::path::Path;
+
+use super::Context;
+use crate::path::PathWrap;
+use crate::permissions::{self, Change};
+
+/// Change every target, and everything inside them if the change is recursive.
+pub(super) fn set(ctx: &mut Context, targets: &[PathWrap], change: &Change) {
+    for target in targets {
+        if ctx.is_cancelled() {
+            return;
+        }
+
+        let failed_before = ctx.failed.len();
+        apply_tree(ctx, target, change);
+
+        if ctx.failed.len() == failed_before {
+            ctx.complete(target, Some(target));
+        }
+
+        ctx.file_done();
+    }
+}
+
+/// Change an item, and then what's inside it, like `chmod -R` does.
+///
+/// Directories are changed before they're listed, so a change that makes them readable can be
+/// applied all the way down. Symlinks are never followed.
+fn apply_tree(ctx: &mut Context, path: &Path, change: &Change) {
+    if let Err(e) = permissions::apply(path, change) {
+        ctx.fail(path, e);
+    }
+
+    let is_dir = fs::symlink_metadata(path).is_ok_and(|m| m.is_dir());
+
+    if !change.recursive || !is_dir {
+        return;
+    }
+
+    let entries = match fs::read_dir(path) {
+        Ok(e) => e,
+        Err(e) => {
+            ctx.fail(path, e);
+            return;
+        }
+    };
+
+    for entry in entries {
+        if ctx.is_cancelled() {
+            return;
+        }
+
+        match entry {
+            Ok(entry) => apply_tree(ctx, &entry.path(), change),
+            Err(e) => ctx.fail(path, e),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Copying and deleting items on any backend, such as to and from servers.
+
+use std::io::{self, Read};
+
+use super::copy::unique_name_with;
+use super::{Conflict, Context, REPORT_INTERVAL};
+use crate::entry::Entry;
+use crate::path::PathWrap;
+use crate::vfs::{self, Uri, Vfs};
+
+/// A single file or directory to copy.
+struct Item {
+    source: Uri,
+    target: Uri,
+    is_dir: bool,
+    size: u64,
+}
+
+/// Copy every source into the destination directory, whichever backends they're on.
+pub(super) fn transfer(ctx: &mut Context, sources: &[Uri], destination: &Uri, conflict: Conflict) {
+    let to = match vfs::backend(destination) {
+        Ok(b) => b,
+        Err(e) => {
+            ctx.fail(destination.path(), e);
+            return;
+        }
+    };
+
+    // everything is listed up front, so the totals are known before anything is copied
+    let mut plans = vec![];
+
+    for source in sources {
+        if ctx.is_cancelled() {
+            return;
+        }
+
+        let from = match vfs::backend(source) {
+            Ok(b) => b,
+            Err(e) => {
+                ctx.fail(source.path(), e);
+                continue;
+            }
+        };
+
+        let entry = match from.stat(source) {
+            Ok(e) => e,
+            Err(e) => {
+                ctx.fail(source.path(), e);
+                continue;
+            }
+        };
+
+        let Some(name) = source.path().file_name() else {
+            ctx.fail(
+                source.path(),
+                io::Error::new(io::ErrorKind::InvalidInput, "no file name"),
+            );
+            continue;
+        };
+
+        let mut target = destination.join(name);
+
+        if let Ok(existing) = to.stat(&target) {
+            match conflict {
+                Conflict::Rename => {
+                    let path = unique_name_with(target.path(), entry.is_dir(), |p| {
+                        to.stat(&target.with_path(p)).is_ok()
+                    });
+                    target = target.with_path(path);
+                }
+                Conflict::Skip => continue,
+                Conflict::Overwrite => {
+                    // a file and a directory can't replace each other in place
+                    if existing.is_dir() != entry.is_dir() {
+                        if let Err(e) = to.delete(&target) {
+                            ctx.fail(target.path(), e);
+                            continue;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut items = vec![];
+        plan(ctx, &*from, &entry, source, &target, &mut items);
+
+        for item in items.iter().filter(|i| !i.is_dir) {
+            ctx.totals.files_total += 1;
+            ctx.totals.bytes_total += item.size;
+        }
+
+        plans.push((source, target, from, items));
+    }
+
+    for (source, target, from, items) in plans {
+        let failed_before = ctx.failed.len();
+
+        for item in items {
+            if ctx.is_cancelled() {
+                return;
+            }
+
+            if item.is_dir {
+                match to.create_dir(&item.target) {
+                    Err(e) if e.kind() != io::ErrorKind::AlreadyExists => {
+                        ctx.fail(item.target.path(), e)
+                    }
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            match copy_file(ctx, &*from, &*to, &item) {
+                Ok(_) => {
+                    ctx.file_done();
+                    ctx.file_progress(item.target.path(), item.size, item.size);
+                }
+                // stopping part way through a file isn't a failure, but half of it is no use
+                Err(_) if ctx.is_cancelled() => {
+                    let _ = to.delete(&item.target);
+                    return;
+                }
+                Err(e) => ctx.fail(item.source.path(), e),
+            }
+        }
+
+        if ctx.failed.len() == failed_before {
+            ctx.complete(source.path(), Some(target.path()));
+        }
+    }
+}
+
+/// Permanently delete every target, whichever backends they're on.
+pub(super) fn remove(ctx: &mut Context, targets: &[Uri]) {
+    for target in targets {
+        if ctx.is_cancelled() {
+            break;
+        }
+
+        match vfs::backend(target).and_then(|b| b.delete(target)) {
+            Ok(_) => ctx.complete(target.path(), None),
+            Err(e) => ctx.fail(target.path(), e),
+        }
+
+        ctx.file_done();
+    }
+}
+
+/// List everything to copy from a source, directories before what's inside them.
+fn plan(
+    ctx: &mut Context,
+    from: &dyn Vfs,
+    entry: &Entry,
+    source: &Uri,
+    target: &Uri,
+    items: &mut Vec<Item>,
+) {
+    items.push(Item {
+        source: source.clone(),
+        target: target.clone(),
+        is_dir: entry.is_dir(),
+        size: entry.size,
+    });
+
+    // symlinks to directories could lead back to where they are, so they're copied empty
+    if !entry.is_dir() || entry.is_symlink() {
+        return;
+    }
+
+    let children = match from.list(source) {
+        Ok(c) => c,
+        Err(e) => {
+            ctx.fail(source.path(), e);
+            return;
+        }
+    };
+
+    for child in children {
+        if ctx.is_cancelled() {
+            return;
+        }
+
+        match child {
+            Ok(child) => {
+                let source = source.with_path(&child.path);
+                let target = target.join(child.name());
+
+                plan(ctx, from, &child, &source, &target, items);
+            }
+            Err(e) => ctx.fail(source.path(), e),
+        }
+    }
+}
+
+/// Copy the contents of a single file, reporting progress as it goes.
+fn copy_file(ctx: &mut Context, from: &dyn Vfs, to: &dyn Vfs, item: &Item) -> io::Result<u64> {
+    let mut reader = Reporting {
+        inner: from.open(&item.source)?,
+        ctx,
+        path: item.target.path(),
+        done: 0,
+        reported: 0,
+        total: item.size,
+    };
+
+    to.write(&item.target, &mut reader)
+}
+
+/// A reader counting what's read through it as progress on a file.
+struct Reporting<'a, 'b> {
+    inner: Box<dyn Read + Send>,
+    ctx: &'a mut Context<'b>,
+    path: &'a PathWrap,
+    done: u64,
+    /// Bytes done when progress was last reported.
+    reported: u64,
+    total: u64,
+}
+
+impl Read for Reporting<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.ctx.is_cancelled() {
+            return Err(io::Error::other("cancelled"));
+        }
+
+        let read = self.inner.read(buf)?;
+
+        self.done += read as u64;
+        self.ctx.advance(read as u64);
+
+        if self.done - self.reported >= REPORT_INTERVAL {
+            self.reported = self.done;
+            self.ctx.file_progress(self.path, self.done, self.total);
+        }
+
+        Ok(read)
+    }
+}
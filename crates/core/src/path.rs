@@ -9,7 +9,7 @@ use std::sync::Arc;
 ///
 /// This exists to avoid using more memory than needed since we need to pass around owned [`Path`]s
 /// everywhere in the application.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct PathWrap(Arc<Path>);
 
 impl PathWrap {
@@ -0,0 +1,367 @@
+//! Unix permissions and ownership.
+//!
+//! Modes are the low twelve bits of `st_mode`: read, write and execute for the owner, the group and
+//! everyone else, plus the setuid, setgid and sticky bits. Users and groups are named from
+//! `/etc/passwd` and `/etc/group`, so accounts only known to a directory service show up as their
+//! numeric ids.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Run programs as the owner of the file.
+pub const SETUID: u32 = 0o4000;
+/// Run programs as the group of the file, or give new items in a directory its group.
+pub const SETGID: u32 = 0o2000;
+/// Only let owners delete or rename items in a directory.
+pub const STICKY: u32 = 0o1000;
+
+/// Every bit a mode can have.
+pub const ALL: u32 = 0o7777;
+
+/// Who a permission applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Class {
+    User,
+    Group,
+    Other,
+}
+
+impl Class {
+    /// Every class, in the order modes are written.
+    pub const ALL: [Self; 3] = [Self::User, Self::Group, Self::Other];
+
+    /// Human readable name.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::User => "Owner",
+            Self::Group => "Group",
+            Self::Other => "Others",
+        }
+    }
+}
+
+/// What a permission allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Access {
+    Read,
+    Write,
+    /// Run a file, or look inside a directory.
+    Execute,
+}
+
+impl Access {
+    /// Every kind of access, in the order modes are written.
+    pub const ALL: [Self; 3] = [Self::Read, Self::Write, Self::Execute];
+
+    /// Human readable name.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Read => "Read",
+            Self::Write => "Write",
+            Self::Execute => "Execute",
+        }
+    }
+}
+
+/// The mode bit giving some access to some class.
+#[must_use]
+pub const fn bit(class: Class, access: Access) -> u32 {
+    let shift = match class {
+        Class::User => 6,
+        Class::Group => 3,
+        Class::Other => 0,
+    };
+
+    let access = match access {
+        Access::Read => 0o4,
+        Access::Write => 0o2,
+        Access::Execute => 0o1,
+    };
+
+    access << shift
+}
+
+/// Write a mode the way `ls -l` does, such as `rwxr-sr-t`.
+#[must_use]
+pub fn symbolic(mode: u32) -> String {
+    let special = [SETUID, SETGID, STICKY];
+
+    Class::ALL
+        .into_iter()
+        .zip(special)
+        .flat_map(|(class, special)| {
+            Access::ALL.into_iter().map(move |access| {
+                let set = mode & bit(class, access) != 0;
+
+                match access {
+                    Access::Read if set => 'r',
+                    Access::Write if set => 'w',
+                    Access::Execute if mode & special != 0 => match (class, set) {
+                        (Class::Other, true) => 't',
+                        (Class::Other, false) => 'T',
+                        (_, true) => 's',
+                        (_, false) => 'S',
+                    },
+                    Access::Execute if set => 'x',
+                    _ => '-',
+                }
+            })
+        })
+        .collect()
+}
+
+/// Parse an octal mode, such as `755` or `0644`.
+#[must_use]
+pub fn parse_octal(text: &str) -> Option<u32> {
+    let text = text.trim();
+    let text = text.strip_prefix("0o").unwrap_or(text);
+
+    u32::from_str_radix(text, 8).ok().filter(|m| *m <= ALL)
+}
+
+/// A user or group.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Account {
+    pub id: u32,
+    pub name: String,
+}
+
+/// Every user in `/etc/passwd`, in the order they're listed.
+#[must_use]
+pub fn users() -> Vec<Account> {
+    read_accounts(Path::new("/etc/passwd"))
+}
+
+/// Every group in `/etc/group`, in the order they're listed.
+#[must_use]
+pub fn groups() -> Vec<Account> {
+    read_accounts(Path::new("/etc/group"))
+}
+
+/// Name of the user with some id.
+#[must_use]
+pub fn user_name(uid: u32) -> Option<String> {
+    name_of(users(), uid)
+}
+
+/// Name of the group with some id.
+#[must_use]
+pub fn group_name(gid: u32) -> Option<String> {
+    name_of(groups(), gid)
+}
+
+/// Find a user by name, or by id if a number is given.
+#[must_use]
+pub fn user_id(name: &str) -> Option<u32> {
+    id_of(users(), name)
+}
+
+/// Find a group by name, or by id if a number is given.
+#[must_use]
+pub fn group_id(name: &str) -> Option<u32> {
+    id_of(groups(), name)
+}
+
+/// Read an account database with lines like `name:password:id:...`, skipping lines that aren't.
+fn read_accounts(path: &Path) -> Vec<Account> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return vec![];
+    };
+
+    text.lines()
+        .filter(|l| !l.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next().filter(|n| !n.is_empty())?;
+            let id = fields.nth(1)?.parse().ok()?;
+
+            Some(Account {
+                id,
+                name: name.to_owned(),
+            })
+        })
+        .collect()
+}
+
+fn name_of(accounts: Vec<Account>, id: u32) -> Option<String> {
+    accounts.into_iter().find(|a| a.id == id).map(|a| a.name)
+}
+
+fn id_of(accounts: Vec<Account>, name: &str) -> Option<u32> {
+    let name = name.trim();
+
+    accounts
+        .into_iter()
+        .find(|a| a.name == name)
+        .map(|a| a.id)
+        .or_else(|| name.parse().ok())
+}
+
+/// The user this program runs as.
+#[cfg(unix)]
+#[must_use]
+pub fn current_user() -> u32 {
+    // SAFETY: always succeeds
+    unsafe { libc::geteuid() }
+}
+
+/// Check if the current user may change the mode of an item owned by someone.
+///
+/// Changing the owner takes more than this: only root may give an item away, and owners may only
+/// change its group to one they're in.
+#[cfg(unix)]
+#[must_use]
+pub fn can_change(uid: u32) -> bool {
+    let current = current_user();
+    current == 0 || current == uid
+}
+
+/// Changes to the permissions and ownership of items.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Change {
+    /// Mode for everything that isn't a directory.
+    pub file_mode: Option<u32>,
+    /// Mode for directories.
+    pub dir_mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Change everything inside directories too.
+    pub recursive: bool,
+}
+
+impl Change {
+    /// Check if the change leaves items as they are.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.file_mode.is_none()
+            && self.dir_mode.is_none()
+            && self.uid.is_none()
+            && self.gid.is_none()
+    }
+}
+
+/// Apply a change to a single item, but not to anything inside it.
+///
+/// Symlinks have no mode of their own, so only their owner is changed.
+#[cfg(unix)]
+pub fn apply(path: &Path, change: &Change) -> io::Result<()> {
+    use std::fs::Permissions;
+    use std::os::unix::fs::PermissionsExt;
+
+    let meta = fs::symlink_metadata(path)?;
+
+    if change.uid.is_some() || change.gid.is_some() {
+        std::os::unix::fs::lchown(path, change.uid, change.gid).map_err(|e| {
+            explain(
+                e,
+                "only root can give items away, and owners can only pick groups they're in",
+            )
+        })?;
+    }
+
+    // changing the owner clears setuid and setgid, so the mode goes second
+    let mode = if meta.is_symlink() {
+        None
+    } else if meta.is_dir() {
+        change.dir_mode
+    } else {
+        change.file_mode
+    };
+
+    if let Some(mode) = mode {
+        fs::set_permissions(path, Permissions::from_mode(mode & ALL))
+            .map_err(|e| explain(e, "only the owner of an item can change its permissions"))?;
+    }
+
+    Ok(())
+}
+
+/// Apply a change to a single item, but not to anything inside it.
+#[cfg(not(unix))]
+pub fn apply(_: &Path, _: &Change) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "permissions can't be changed here",
+    ))
+}
+
+/// Say why an action was denied, rather than only that it was.
+fn explain(e: io::Error, why: &str) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("not allowed: {why}"),
+        ),
+        _ => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_are_written_like_ls() {
+        assert_eq!(symbolic(0o755), "rwxr-xr-x");
+        assert_eq!(symbolic(0o640), "rw-r-----");
+        assert_eq!(symbolic(0), "---------");
+        assert_eq!(symbolic(0o4755), "rwsr-xr-x");
+        assert_eq!(symbolic(0o2745), "rwxr-Sr-x");
+        assert_eq!(symbolic(0o1777), "rwxrwxrwt");
+        assert_eq!(symbolic(0o1776), "rwxrwxrwT");
+    }
+
+    #[test]
+    fn octal_modes_are_parsed() {
+        assert_eq!(parse_octal("755"), Some(0o755));
+        assert_eq!(parse_octal(" 0644 "), Some(0o644));
+        assert_eq!(parse_octal("0o1777"), Some(0o1777));
+        assert_eq!(parse_octal("7777"), Some(ALL));
+
+        assert_eq!(parse_octal("17777"), None);
+        assert_eq!(parse_octal("789"), None);
+        assert_eq!(parse_octal("rwx"), None);
+        assert_eq!(parse_octal(""), None);
+    }
+
+    #[test]
+    fn bits_match_classes() {
+        assert_eq!(bit(Class::User, Access::Read), 0o400);
+        assert_eq!(bit(Class::Group, Access::Write), 0o020);
+        assert_eq!(bit(Class::Other, Access::Execute), 0o001);
+    }
+
+    #[test]
+    fn accounts_are_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passwd");
+        fs::write(
+            &path,
+            "# comment\nroot:x:0:0:root:/root:/bin/sh\nme:x:1000:1000::/home/me:/bin/sh\n:x:5:5\nbroken:x:nope\n",
+        )
+        .unwrap();
+
+        let accounts = read_accounts(&path);
+
+        assert_eq!(
+            accounts,
+            [
+                Account {
+                    id: 0,
+                    name: "root".to_owned()
+                },
+                Account {
+                    id: 1000,
+                    name: "me".to_owned()
+                },
+            ]
+        );
+        assert_eq!(name_of(accounts.clone(), 1000).as_deref(), Some("me"));
+        assert_eq!(id_of(accounts.clone(), " me "), Some(1000));
+        // numbers are taken as ids when there's no one by that name
+        assert_eq!(id_of(accounts, "42"), Some(42));
+    }
+}
@@ -0,0 +1,720 @@
+//! Renaming many items at once.
+//!
+//! A [`Pattern`] is a list of [`Rule`]s applied one after another to every name. Planning works out
+//! each new name up front without touching anything, along with anything that would go wrong, so
+//! the result can be previewed and then applied as a single [`Operation::Rename`].
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::Path;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use regex::Regex;
+
+use crate::ops::Operation;
+use crate::path::PathWrap;
+
+/// Longest name most filesystems allow, in bytes.
+const MAX_NAME: usize = 255;
+
+/// Letter case to change names to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Case {
+    #[default]
+    Lower,
+    Upper,
+    /// The first letter of every word in uppercase, and the rest in lowercase.
+    Title,
+}
+
+impl Case {
+    /// Every option, in the order they are usually presented.
+    pub const ALL: [Self; 3] = [Self::Lower, Self::Upper, Self::Title];
+
+    /// Human readable name.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Lower => "lowercase",
+            Self::Upper => "UPPERCASE",
+            Self::Title => "Title Case",
+        }
+    }
+
+    /// The option after this one, wrapping around.
+    #[must_use]
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|c| *c == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    fn apply(self, text: &str) -> String {
+        match self {
+            Self::Lower => text.to_lowercase(),
+            Self::Upper => text.to_uppercase(),
+            Self::Title => {
+                let mut title = String::with_capacity(text.len());
+                let mut word_start = true;
+
+                for c in text.chars() {
+                    if word_start {
+                        title.extend(c.to_uppercase());
+                    } else {
+                        title.extend(c.to_lowercase());
+                    }
+
+                    word_start = !c.is_alphanumeric() && c != '\'';
+                }
+
+                title
+            }
+        }
+    }
+}
+
+/// Where text is added to a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Position {
+    #[default]
+    Start,
+    /// At the end, but before the extension if there is one.
+    End,
+}
+
+/// A single change to names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    /// Replace every occurrence of some text.
+    Replace { find: String, with: String },
+    /// Replace every match of a regular expression. The replacement can use captures, like `$1`
+    /// or `${name}`.
+    Regex { pattern: String, with: String },
+    /// Change the letter case.
+    Case(Case),
+    /// Add a number counting up from item to item, padded with zeros to some width.
+    Counter {
+        start: u64,
+        step: u64,
+        width: usize,
+        position: Position,
+        separator: String,
+    },
+    /// Add the modification date of the item, in a `strftime` format like `%Y-%m-%d`.
+    Date {
+        format: String,
+        position: Position,
+        separator: String,
+    },
+}
+
+/// Rules to rename items with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pattern {
+    pub rules: Vec<Rule>,
+    /// Apply the rules to extensions too, rather than only the rest of the name.
+    pub extension: bool,
+}
+
+/// A pattern that failed to compile.
+#[derive(Debug)]
+pub enum Error {
+    Regex(regex::Error),
+    /// A date format with something that isn't a valid specifier.
+    Date(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Regex(e) => write!(f, "invalid regex: {e}"),
+            Self::Date(format) => write!(f, "invalid date format: {format}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Why an item can't be given its new name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// Nothing is left of the name.
+    Empty,
+    /// The name has a character that names can't have, like `/`.
+    Invalid(char),
+    /// The name is `.` or `..`.
+    Reserved,
+    /// The name is longer than filesystems allow.
+    TooLong,
+    /// Another item, which isn't being renamed, already has the name.
+    Taken,
+    /// Another item being renamed would get the same name.
+    Duplicate(PathWrap),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "the name is empty"),
+            Self::Invalid(c) => write!(f, "names can't contain {c:?}"),
+            Self::Reserved => write!(f, "the name is reserved"),
+            Self::TooLong => write!(f, "the name is longer than {MAX_NAME} bytes"),
+            Self::Taken => write!(f, "an item with this name already exists"),
+            Self::Duplicate(other) => write!(
+                f,
+                "{} would get the same name",
+                other.file_name().unwrap_or_default().to_string_lossy()
+            ),
+        }
+    }
+}
+
+/// The new name of a single item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    pub source: PathWrap,
+    pub target: PathWrap,
+    /// Why the item can't be renamed, if it can't.
+    pub problem: Option<Problem>,
+}
+
+impl Rename {
+    /// Check if the name changes at all.
+    #[inline]
+    pub fn is_change(&self) -> bool {
+        self.source != self.target
+    }
+}
+
+/// New names for every item, in the order the items were given.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub renames: Vec<Rename>,
+}
+
+impl Plan {
+    /// Number of items with a problem.
+    pub fn problems(&self) -> usize {
+        self.renames.iter().filter(|r| r.problem.is_some()).count()
+    }
+
+    /// Number of items whose name changes.
+    pub fn changes(&self) -> usize {
+        self.renames.iter().filter(|r| r.is_change()).count()
+    }
+
+    /// The operation renaming every changed item, unless nothing changes or anything has a
+    /// problem.
+    #[must_use]
+    pub fn operation(&self) -> Option<Operation> {
+        if self.problems() > 0 || self.changes() == 0 {
+            return None;
+        }
+
+        let (sources, targets) = self
+            .renames
+            .iter()
+            .filter(|r| r.is_change())
+            .map(|r| (r.source.clone(), r.target.clone()))
+            .unzip();
+
+        Some(Operation::Rename { sources, targets })
+    }
+}
+
+impl Pattern {
+    /// Work out the new names of some items, without renaming anything.
+    ///
+    /// Counters count in the order the items are given.
+    pub fn plan(&self, paths: &[PathWrap]) -> Result<Plan, Error> {
+        let rules = self
+            .rules
+            .iter()
+            .map(Compiled::new)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut renames: Vec<_> = paths
+            .iter()
+            .enumerate()
+            .map(|(index, source)| {
+                let name = source.file_name().unwrap_or_default().to_string_lossy();
+                let name = self.apply(&rules, &name, source, index as u64);
+
+                Rename {
+                    target: PathWrap::from_into_path(source.with_file_name(&name)),
+                    problem: check(&name).err(),
+                    source: source.clone(),
+                }
+            })
+            .collect();
+
+        find_collisions(&mut renames);
+
+        Ok(Plan { renames })
+    }
+
+    /// The new name of a single item.
+    fn apply(&self, rules: &[Compiled], name: &str, path: &Path, index: u64) -> String {
+        let is_dir = fs::symlink_metadata(path).is_ok_and(|m| m.is_dir());
+
+        // directories don't have extensions, no matter what their name looks like
+        let (mut stem, extension) = match name.rfind('.') {
+            Some(dot) if dot > 0 && !is_dir && !self.extension => {
+                (name[..dot].to_owned(), &name[dot..])
+            }
+            _ => (name.to_owned(), ""),
+        };
+
+        for rule in rules {
+            stem = rule.apply(&stem, path, index);
+        }
+
+        stem + extension
+    }
+}
+
+/// A rule ready to be applied.
+enum Compiled<'a> {
+    Replace {
+        find: &'a str,
+        with: &'a str,
+    },
+    Regex {
+        regex: Regex,
+        with: &'a str,
+    },
+    Case(Case),
+    Counter {
+        start: u64,
+        step: u64,
+        width: usize,
+        position: Position,
+        separator: &'a str,
+    },
+    Date {
+        format: &'a str,
+        position: Position,
+        separator: &'a str,
+    },
+}
+
+impl<'a> Compiled<'a> {
+    fn new(rule: &'a Rule) -> Result<Self, Error> {
+        Ok(match rule {
+            Rule::Replace { find, with } => Self::Replace { find, with },
+            Rule::Regex { pattern, with } => Self::Regex {
+                regex: Regex::new(pattern).map_err(Error::Regex)?,
+                with,
+            },
+            Rule::Case(case) => Self::Case(*case),
+            Rule::Counter {
+                start,
+                step,
+                width,
+                position,
+                separator,
+            } => Self::Counter {
+                start: *start,
+                step: *step,
+                width: *width,
+                position: *position,
+                separator,
+            },
+            Rule::Date {
+                format,
+                position,
+                separator,
+            } => {
+                if StrftimeItems::new(format).any(|i| i == Item::Error) {
+                    return Err(Error::Date(format.clone()));
+                }
+
+                Self::Date {
+                    format,
+                    position: *position,
+                    separator,
+                }
+            }
+        })
+    }
+
+    fn apply(&self, name: &str, path: &Path, index: u64) -> String {
+        match *self {
+            Self::Replace { find: "", .. } => name.to_owned(),
+            Self::Replace { find, with } => name.replace(find, with),
+            Self::Regex { ref regex, with } => regex.replace_all(name, with).into_owned(),
+            Self::Case(case) => case.apply(name),
+            Self::Counter {
+                start,
+                step,
+                width,
+                position,
+                separator,
+            } => {
+                let number = start.saturating_add(step.saturating_mul(index));
+                insert(name, &format!("{number:0width$}"), position, separator)
+            }
+            Self::Date {
+                format,
+                position,
+                separator,
+            } => {
+                let Some(modified) = fs::symlink_metadata(path)
+                    .ok()
+                    .and_then(|m| m.modified().ok())
+                else {
+                    return name.to_owned();
+                };
+
+                let mut date = String::new();
+                let _ = write!(date, "{}", DateTime::<Local>::from(modified).format(format));
+
+                insert(name, &date, position, separator)
+            }
+        }
+    }
+}
+
+/// Add some text to a name, with a separator between them.
+fn insert(name: &str, text: &str, position: Position, separator: &str) -> String {
+    match position {
+        Position::Start => format!("{text}{separator}{name}"),
+        Position::End => format!("{name}{separator}{text}"),
+    }
+}
+
+/// Check that a name can be given to an item.
+fn check(name: &str) -> Result<(), Problem> {
+    if name.is_empty() {
+        return Err(Problem::Empty);
+    }
+    if name == "." || name == ".." {
+        return Err(Problem::Reserved);
+    }
+    if let Some(c) = name.chars().find(|c| *c == '/' || *c == '\0') {
+        return Err(Problem::Invalid(c));
+    }
+    if name.len() > MAX_NAME {
+        return Err(Problem::TooLong);
+    }
+
+    Ok(())
+}
+
+/// Mark renames whose target is taken, either by an item staying where it is or by another
+/// rename.
+fn find_collisions(renames: &mut [Rename]) {
+    let moving: HashSet<PathWrap> = renames
+        .iter()
+        .filter(|r| r.is_change())
+        .map(|r| r.source.clone())
+        .collect();
+
+    let mut claimed: HashMap<PathWrap, PathWrap> = HashMap::new();
+
+    for rename in renames.iter_mut() {
+        if rename.problem.is_some() {
+            continue;
+        }
+
+        if let Some(other) = claimed.get(&rename.target) {
+            rename.problem = Some(Problem::Duplicate(other.clone()));
+            continue;
+        }
+
+        claimed.insert(rename.target.clone(), rename.source.clone());
+
+        let taken = rename.is_change()
+            && fs::symlink_metadata(&rename.target).is_ok()
+            && !moving.contains(&rename.target);
+
+        if taken {
+            rename.problem = Some(Problem::Taken);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::ops;
+
+    /// Create empty files in a directory.
+    fn files(dir: &Path, names: &[&str]) -> Vec<PathWrap> {
+        names
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                fs::write(&path, "").unwrap();
+                PathWrap::from_into_path(path)
+            })
+            .collect()
+    }
+
+    fn replace(find: &str, with: &str) -> Rule {
+        Rule::Replace {
+            find: find.to_owned(),
+            with: with.to_owned(),
+        }
+    }
+
+    fn regex(pattern: &str, with: &str) -> Rule {
+        Rule::Regex {
+            pattern: pattern.to_owned(),
+            with: with.to_owned(),
+        }
+    }
+
+    fn plan(paths: &[PathWrap], rules: Vec<Rule>) -> Plan {
+        Pattern {
+            rules,
+            extension: false,
+        }
+        .plan(paths)
+        .unwrap()
+    }
+
+    /// New names, in the order the items were given.
+    fn names(plan: &Plan) -> Vec<String> {
+        plan.renames
+            .iter()
+            .map(|r| r.target.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn names_are_checked() {
+        assert_eq!(check("notes.txt"), Ok(()));
+        assert_eq!(check(""), Err(Problem::Empty));
+        assert_eq!(check("."), Err(Problem::Reserved));
+        assert_eq!(check(".."), Err(Problem::Reserved));
+        assert_eq!(check("a/b"), Err(Problem::Invalid('/')));
+        assert_eq!(check("a\0b"), Err(Problem::Invalid('\0')));
+        assert_eq!(check(&"a".repeat(MAX_NAME)), Ok(()));
+        assert_eq!(check(&"a".repeat(MAX_NAME + 1)), Err(Problem::TooLong));
+    }
+
+    #[test]
+    fn rules_leave_extensions_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut paths = files(dir.path(), &["photo.jpg", ".hidden"]);
+        fs::create_dir(dir.path().join("archive.d")).unwrap();
+        paths.push(PathWrap::from_into_path(dir.path().join("archive.d")));
+
+        let plan = plan(&paths, vec![Rule::Case(Case::Upper)]);
+
+        assert_eq!(names(&plan), ["PHOTO.jpg", ".HIDDEN", "ARCHIVE.D"]);
+        assert_eq!(plan.problems(), 0);
+    }
+
+    #[test]
+    fn extensions_can_be_renamed() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = files(dir.path(), &["photo.jpg"]);
+
+        let plan = Pattern {
+            rules: vec![Rule::Case(Case::Upper)],
+            extension: true,
+        }
+        .plan(&paths)
+        .unwrap();
+
+        assert_eq!(names(&plan), ["PHOTO.JPG"]);
+    }
+
+    #[test]
+    fn title_case_starts_every_word() {
+        assert_eq!(
+            Case::Title.apply("the CAT's hat-trick"),
+            "The Cat's Hat-Trick"
+        );
+    }
+
+    #[test]
+    fn regex_captures_are_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = files(dir.path(), &["scan-12.png", "scan-7.png", "other.png"]);
+
+        let plan = plan(&paths, vec![regex(r"^(?<kind>\w+)-(\d+)$", "${2}_${kind}")]);
+
+        assert_eq!(names(&plan), ["12_scan.png", "7_scan.png", "other.png"]);
+        assert_eq!(plan.changes(), 2);
+    }
+
+    #[test]
+    fn invalid_patterns_are_errors() {
+        let pattern = Pattern {
+            rules: vec![regex("(", "")],
+            extension: false,
+        };
+        assert!(matches!(pattern.plan(&[]), Err(Error::Regex(_))));
+
+        let pattern = Pattern {
+            rules: vec![Rule::Date {
+                format: "%Y-%".to_owned(),
+                position: Position::Start,
+                separator: String::new(),
+            }],
+            extension: false,
+        };
+        assert!(matches!(pattern.plan(&[]), Err(Error::Date(_))));
+    }
+
+    #[test]
+    fn counters_count_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = files(dir.path(), &["a.txt", "b.txt", "c.txt"]);
+
+        let counter = |position| Rule::Counter {
+            start: 1,
+            step: 2,
+            width: 3,
+            position,
+            separator: "_".to_owned(),
+        };
+
+        let plan = plan(&paths, vec![counter(Position::End)]);
+        assert_eq!(names(&plan), ["a_001.txt", "b_003.txt", "c_005.txt"]);
+
+        let plan = self::plan(&paths, vec![counter(Position::Start)]);
+        assert_eq!(names(&plan), ["001_a.txt", "003_b.txt", "005_c.txt"]);
+    }
+
+    #[test]
+    fn dates_are_inserted() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = files(dir.path(), &["report.pdf"]);
+
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        File::options()
+            .write(true)
+            .open(&paths[0])
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        let plan = plan(
+            &paths,
+            vec![Rule::Date {
+                format: "%Y-%m-%d".to_owned(),
+                position: Position::End,
+                separator: " ".to_owned(),
+            }],
+        );
+
+        let date = DateTime::<Local>::from(modified).format("%Y-%m-%d");
+        assert_eq!(names(&plan), [format!("report {date}.pdf")]);
+    }
+
+    #[test]
+    fn bad_names_are_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = files(dir.path(), &["a", "b.txt", "c.txt"]);
+
+        let plan = plan(
+            &paths,
+            vec![replace("a", ""), replace("b", "x/y"), replace("c", "\0")],
+        );
+
+        let problems: Vec<_> = plan.renames.iter().map(|r| r.problem.clone()).collect();
+        assert_eq!(
+            problems,
+            [
+                Some(Problem::Empty),
+                Some(Problem::Invalid('/')),
+                Some(Problem::Invalid('\0'))
+            ]
+        );
+        assert!(plan.operation().is_none());
+    }
+
+    #[test]
+    fn two_items_cant_get_one_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = files(dir.path(), &["one.txt", "two.txt"]);
+
+        let plan = plan(&paths, vec![regex("^.*$", "same")]);
+
+        assert_eq!(plan.renames[0].problem, None);
+        assert_eq!(
+            plan.renames[1].problem,
+            Some(Problem::Duplicate(paths[0].clone()))
+        );
+        assert!(plan.operation().is_none());
+    }
+
+    #[test]
+    fn names_of_other_items_are_taken() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = files(dir.path(), &["a.txt"]);
+        files(dir.path(), &["b.txt"]);
+
+        let plan = plan(&paths, vec![replace("a", "b")]);
+
+        assert_eq!(plan.renames[0].problem, Some(Problem::Taken));
+        assert!(plan.operation().is_none());
+    }
+
+    #[test]
+    fn items_can_swap_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = files(dir.path(), &["a.txt", "b.txt"]);
+        fs::write(&paths[0], "first").unwrap();
+
+        let plan = plan(
+            &paths,
+            vec![replace("a", "x"), replace("b", "a"), replace("x", "b")],
+        );
+
+        assert_eq!(names(&plan), ["b.txt", "a.txt"]);
+        assert_eq!(plan.problems(), 0);
+
+        let report = ops::spawn(plan.operation().unwrap()).wait().unwrap();
+        assert!(report.failed.is_empty());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("b.txt")).unwrap(),
+            "first"
+        );
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt")).unwrap(), "");
+    }
+
+    #[test]
+    fn items_can_take_names_given_up_in_a_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = files(dir.path(), &["a.txt", "b.txt"]);
+        fs::write(&paths[0], "first").unwrap();
+
+        let plan = plan(&paths, vec![replace("b", "c"), replace("a", "b")]);
+
+        assert_eq!(names(&plan), ["b.txt", "c.txt"]);
+        assert_eq!(plan.problems(), 0);
+
+        let report = ops::spawn(plan.operation().unwrap()).wait().unwrap();
+        assert!(report.failed.is_empty());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("b.txt")).unwrap(),
+            "first"
+        );
+        assert!(dir.path().join("c.txt").exists());
+        assert!(!dir.path().join("a.txt").exists());
+    }
+
+    #[test]
+    fn unchanged_items_are_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = files(dir.path(), &["a.txt", "keep.txt"]);
+
+        let plan = plan(&paths, vec![replace("a", "z"), replace("", "ignored")]);
+
+        assert_eq!(plan.changes(), 1);
+        let Some(Operation::Rename { sources, targets }) = plan.operation() else {
+            panic!("nothing to rename");
+        };
+        assert_eq!(sources, [paths[0].clone()]);
+        assert_eq!(*targets[0], *dir.path().join("z.txt"));
+    }
+}
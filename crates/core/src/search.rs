@@ -0,0 +1,484 @@
+//! Recursive filename search.
+//!
+//! Directory trees are walked on several threads at once, and matches are streamed back as soon
+//! as they are found. Hidden and ignored items are skipped according to a [`Filter`].
+
+use std::fmt;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::{WalkBuilder, WalkParallel, WalkState};
+use regex::{Regex, RegexBuilder};
+
+use crate::entry::Entry;
+use crate::filter::Filter;
+use crate::path::PathWrap;
+use crate::task::CancelToken;
+
+/// How names are matched against a query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Mode {
+    /// The name contains the query.
+    #[default]
+    Substring,
+    /// The name matches a glob, such as `*.rs`.
+    Glob,
+    /// The name matches a regular expression.
+    Regex,
+    /// The name contains every character of the query, in order.
+    Fuzzy,
+}
+
+impl Mode {
+    /// Every mode, in the order they are usually presented.
+    pub const ALL: [Self; 4] = [Self::Substring, Self::Glob, Self::Regex, Self::Fuzzy];
+
+    /// Human readable name.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Substring => "Contains",
+            Self::Glob => "Glob",
+            Self::Regex => "Regex",
+            Self::Fuzzy => "Fuzzy",
+        }
+    }
+
+    /// The mode after this one, wrapping around.
+    #[must_use]
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|m| *m == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// What to search for, and where.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Directory to search in.
+    pub root: PathWrap,
+    pub text: String,
+    pub mode: Mode,
+    /// Which items are searched.
+    pub filter: Filter,
+}
+
+/// A query that failed to compile.
+#[derive(Debug)]
+pub enum Error {
+    Glob(globset::Error),
+    Regex(regex::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Glob(e) => write!(f, "invalid glob: {e}"),
+            Self::Regex(e) => write!(f, "invalid regex: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A query compiled for matching names.
+#[derive(Debug)]
+enum Matcher {
+    Substring {
+        text: String,
+        case_sensitive: bool,
+    },
+    Glob(GlobMatcher),
+    Regex(Regex),
+    Fuzzy {
+        chars: Vec<char>,
+        case_sensitive: bool,
+    },
+}
+
+impl Matcher {
+    /// Compile a query. Matching is case-insensitive unless the query has uppercase letters.
+    fn new(text: &str, mode: Mode) -> Result<Self, Error> {
+        let case_sensitive = text.chars().any(char::is_uppercase);
+        let fold = |s: &str| {
+            if case_sensitive {
+                s.to_owned()
+            } else {
+                s.to_lowercase()
+            }
+        };
+
+        Ok(match mode {
+            Mode::Substring => Self::Substring {
+                text: fold(text),
+                case_sensitive,
+            },
+            Mode::Glob => Self::Glob(
+                GlobBuilder::new(text)
+                    .case_insensitive(!case_sensitive)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(Error::Glob)?
+                    .compile_matcher(),
+            ),
+            Mode::Regex => Self::Regex(
+                RegexBuilder::new(text)
+                    .case_insensitive(!case_sensitive)
+                    .build()
+                    .map_err(Error::Regex)?,
+            ),
+            Mode::Fuzzy => Self::Fuzzy {
+                chars: fold(text).chars().collect(),
+                case_sensitive,
+            },
+        })
+    }
+
+    /// Score a name, or [`None`] if it doesn't match. Higher scores are better matches.
+    fn score(&self, name: &str) -> Option<i64> {
+        match self {
+            Self::Substring {
+                text,
+                case_sensitive,
+            } => {
+                let found = if *case_sensitive {
+                    name.contains(text.as_str())
+                } else {
+                    name.to_lowercase().contains(text.as_str())
+                };
+
+                found.then_some(0)
+            }
+            Self::Glob(glob) => glob.is_match(name).then_some(0),
+            Self::Regex(regex) => regex.is_match(name).then_some(0),
+            Self::Fuzzy {
+                chars,
+                case_sensitive,
+            } => fuzzy_score(chars, name, *case_sensitive),
+        }
+    }
+}
+
+/// Score a name containing every query character in order.
+///
+/// Consecutive characters and characters at the start of words score higher, while gaps between
+/// matched characters score lower.
+fn fuzzy_score(query: &[char], name: &str, case_sensitive: bool) -> Option<i64> {
+    const MATCH: i64 = 1;
+    const CONSECUTIVE: i64 = 5;
+    const WORD_START: i64 = 8;
+    const GAP: i64 = 1;
+
+    let mut query = query.iter().peekable();
+    let mut score = 0;
+    let mut previous: Option<char> = None;
+    let mut last_match: Option<usize> = None;
+
+    for (index, c) in name.chars().enumerate() {
+        let Some(&&wanted) = query.peek() else {
+            break;
+        };
+
+        let folded = if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        };
+
+        if folded == wanted {
+            score += MATCH;
+
+            match last_match {
+                Some(last) if last + 1 == index => score += CONSECUTIVE,
+                Some(last) => score -= GAP * (index - last - 1) as i64,
+                None => score -= GAP * index as i64,
+            }
+
+            let word_start = match previous {
+                None => true,
+                Some(p) => !p.is_alphanumeric() || (p.is_lowercase() && c.is_uppercase()),
+            };
+
+            if word_start {
+                score += WORD_START;
+            }
+
+            last_match = Some(index);
+            query.next();
+        }
+
+        previous = Some(c);
+    }
+
+    query.peek().is_none().then_some(score)
+}
+
+/// A matching item.
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub entry: Entry,
+    /// How well the name matched. Only meaningful for [`Mode::Fuzzy`].
+    pub score: i64,
+}
+
+/// Handle to a running search.
+///
+/// Iterating blocks until the next match is found, and stops once the whole tree has been
+/// searched or the search was cancelled.
+#[derive(Debug)]
+pub struct Search<T = Hit> {
+    hits: Receiver<T>,
+    cancel: CancelToken,
+}
+
+impl<T> Search<T> {
+    #[inline]
+    pub(crate) fn new(hits: Receiver<T>, cancel: CancelToken) -> Self {
+        Self { hits, cancel }
+    }
+
+    /// Stop searching as soon as possible.
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Block until at least one match is found, then take every match found so far.
+    ///
+    /// Returns [`None`] once the search is done.
+    pub fn next_batch(&mut self) -> Option<Vec<T>> {
+        let first = self.hits.recv().ok()?;
+
+        let mut batch = vec![first];
+        batch.extend(self.hits.try_iter());
+
+        Some(batch)
+    }
+}
+
+impl<T> Iterator for Search<T> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.hits.recv().ok()
+    }
+}
+
+/// Start searching on background threads.
+pub fn spawn(query: Query, cancel: CancelToken) -> Result<Search, Error> {
+    let matcher = Matcher::new(&query.text, query.mode)?;
+    let (sender, hits) = mpsc::channel();
+
+    let worker_cancel = cancel.clone();
+    thread::spawn(move || {
+        let walker = walker(&query.root, &query.filter);
+        let matcher = &matcher;
+        let cancel = &worker_cancel;
+
+        walker.run(|| {
+            let sender = sender.clone();
+
+            Box::new(move |result| {
+                if cancel.is_cancelled() {
+                    return WalkState::Quit;
+                }
+
+                let Ok(dent) = result else {
+                    return WalkState::Continue;
+                };
+
+                // don't match the search root itself
+                if dent.depth() == 0 {
+                    return WalkState::Continue;
+                }
+
+                let Some(score) = matcher.score(&dent.file_name().to_string_lossy()) else {
+                    return WalkState::Continue;
+                };
+
+                // items removed while searching are skipped
+                let Ok(entry) = Entry::read(dent.path()) else {
+                    return WalkState::Continue;
+                };
+
+                if sender.send(Hit { entry, score }).is_err() {
+                    return WalkState::Quit;
+                }
+
+                WalkState::Continue
+            })
+        });
+    });
+
+    Ok(Search::new(hits, cancel))
+}
+
+/// Walk a directory tree on several threads, skipping whatever the filter hides.
+pub(crate) fn walker(root: &Path, filter: &Filter) -> WalkParallel {
+    let filter = filter.clone();
+    let respect_ignore = filter.respect_ignore;
+
+    WalkBuilder::new(root)
+        .hidden(!filter.show_hidden)
+        .ignore(respect_ignore)
+        .git_ignore(respect_ignore)
+        .git_exclude(respect_ignore)
+        .git_global(false)
+        .parents(respect_ignore)
+        .filter_entry(move |e| !filter.is_excluded(e.path()))
+        .build_parallel()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn score(query: &str, name: &str) -> Option<i64> {
+        Matcher::new(query, Mode::Fuzzy).unwrap().score(name)
+    }
+
+    fn matches(query: &str, mode: Mode, name: &str) -> bool {
+        Matcher::new(query, mode).unwrap().score(name).is_some()
+    }
+
+    /// Names of everything found for a query, sorted.
+    fn search(root: &Path, text: &str, mode: Mode, filter: Filter) -> Vec<String> {
+        let query = Query {
+            root: PathWrap::from_path(root),
+            text: text.to_owned(),
+            mode,
+            filter,
+        };
+
+        let mut names: Vec<_> = spawn(query, CancelToken::new())
+            .unwrap()
+            .map(|hit| hit.entry.name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// A tree with a hidden file, an ignored file and a nested file.
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::create_dir_all(dir.path().join("src/target")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        fs::write(dir.path().join("src/target/out.rs"), "").unwrap();
+        fs::write(dir.path().join(".hidden.rs"), "").unwrap();
+        fs::write(dir.path().join("generated.rs"), "").unwrap();
+        fs::write(dir.path().join(".ignore"), "generated.rs\n").unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn fuzzy_needs_every_character_in_order() {
+        assert!(score("mrs", "main.rs").is_some());
+        assert!(score("srm", "main.rs").is_none());
+        assert!(score("mainx", "main.rs").is_none());
+        assert!(score("", "anything").is_some());
+    }
+
+    #[test]
+    fn fuzzy_prefers_consecutive_characters() {
+        assert!(score("main", "main.rs") > score("main", "mxaxixn.rs"));
+    }
+
+    #[test]
+    fn fuzzy_prefers_word_starts() {
+        // both match the same characters, but only one at the start of words
+        assert!(score("fb", "foo_bar") > score("fb", "xfxb"));
+        assert!(score("fb", "fooBar") > score("fb", "foobar"));
+    }
+
+    #[test]
+    fn fuzzy_prefers_early_matches() {
+        assert!(score("rs", "rs.txt") > score("rs", "main.rs"));
+    }
+
+    #[test]
+    fn uppercase_queries_are_case_sensitive() {
+        assert!(matches("readme", Mode::Substring, "README.md"));
+        assert!(!matches("README", Mode::Substring, "readme.md"));
+
+        assert!(matches("*.md", Mode::Glob, "README.MD"));
+        assert!(!matches("*.MD", Mode::Glob, "readme.md"));
+
+        assert!(matches("^read", Mode::Regex, "README.md"));
+        assert!(!matches("^READ", Mode::Regex, "readme.md"));
+
+        assert!(score("rdm", "README.md").is_some());
+        assert!(score("RDM", "readme.md").is_none());
+    }
+
+    #[test]
+    fn invalid_queries_are_errors() {
+        assert!(matches!(Matcher::new("[", Mode::Glob), Err(Error::Glob(_))));
+        assert!(matches!(
+            Matcher::new("(", Mode::Regex),
+            Err(Error::Regex(_))
+        ));
+    }
+
+    #[test]
+    fn hidden_and_ignored_items_are_skipped() {
+        let dir = tree();
+
+        assert_eq!(
+            search(dir.path(), "*.rs", Mode::Glob, Filter::default()),
+            ["generated.rs", "main.rs", "out.rs"]
+        );
+
+        let mut filter = Filter::default();
+        filter.show_hidden = true;
+        assert_eq!(
+            search(dir.path(), "*.rs", Mode::Glob, filter),
+            [".hidden.rs", "generated.rs", "main.rs", "out.rs"]
+        );
+
+        let mut filter = Filter::default();
+        filter.respect_ignore = true;
+        assert_eq!(
+            search(dir.path(), "*.rs", Mode::Glob, filter),
+            ["main.rs", "out.rs"]
+        );
+
+        let filter = Filter::with_excludes(&["target"]).unwrap();
+        assert_eq!(
+            search(dir.path(), "*.rs", Mode::Glob, filter),
+            ["generated.rs", "main.rs"]
+        );
+    }
+
+    #[test]
+    fn the_root_is_not_a_hit() {
+        let dir = tree();
+        let name = dir.path().file_name().unwrap().to_string_lossy();
+
+        let found = search(dir.path(), &name, Mode::Substring, Filter::default());
+
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn cancelled_searches_stop() {
+        let dir = tree();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let query = Query {
+            root: PathWrap::from_path(dir.path()),
+            text: String::new(),
+            mode: Mode::Substring,
+            filter: Filter::default(),
+        };
+
+        assert_eq!(spawn(query, cancel).unwrap().count(), 0);
+    }
+}
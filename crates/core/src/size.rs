@@ -0,0 +1,413 @@
+//! Recursive directory sizes.
+//!
+//! Directories are measured on a shared pool of background threads, and the results are cached by
+//! path and modification time. A directory's modification time only changes when its direct
+//! children do, so changes deeper down have to be reported through [`Cache::invalidate`].
+
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::SystemTime;
+
+use ignore::WalkBuilder;
+use lazy_static::lazy_static;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::entry::Entry;
+use crate::task::CancelToken;
+
+lazy_static! {
+    /// Threads shared by every measurement.
+    pub(crate) static ref POOL: ThreadPool = ThreadPoolBuilder::new()
+        .thread_name(|i| format!("doseer-size-{i}"))
+        .build()
+        .expect("failed to start size threads");
+}
+
+/// Combined size of everything inside a directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Size {
+    pub bytes: u64,
+    pub files: u64,
+    pub dirs: u64,
+}
+
+/// How directories are measured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Options {
+    /// Don't descend into directories on other filesystems, such as mount points.
+    pub one_filesystem: bool,
+}
+
+/// The size of a directory, as of some modification time.
+#[derive(Debug, Clone)]
+pub struct Measured {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub size: Size,
+}
+
+/// Sizes of directories measured so far.
+#[derive(Debug, Default)]
+pub struct Cache {
+    sizes: HashMap<PathBuf, (Option<SystemTime>, Size)>,
+}
+
+impl Cache {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Size of a directory, unless it changed since it was measured.
+    #[must_use]
+    pub fn get(&self, entry: &Entry) -> Option<Size> {
+        match self.sizes.get(entry.path.as_ref() as &Path) {
+            Some((modified, size)) if *modified == entry.modified => Some(*size),
+            _ => None,
+        }
+    }
+
+    /// Remember a measured size.
+    pub fn insert(&mut self, measured: Measured) {
+        self.sizes
+            .insert(measured.path, (measured.modified, measured.size));
+    }
+
+    /// Forget the size of a path and every directory containing it.
+    pub fn invalidate(&mut self, path: &Path) {
+        for ancestor in path.ancestors() {
+            self.sizes.remove(ancestor);
+        }
+    }
+
+    /// Directories among some entries that need to be measured.
+    pub fn missing(&self, entries: &[Entry]) -> Vec<PathBuf> {
+        entries
+            .iter()
+            .filter(|e| e.is_dir() && !e.is_symlink() && self.get(e).is_none())
+            .map(|e| e.path.to_path_buf())
+            .collect()
+    }
+
+    /// Total size of some entries, using the cache for directories.
+    ///
+    /// Returns [`None`] if any of the directories haven't been measured yet.
+    #[must_use]
+    pub fn total<'a, I>(&self, entries: I) -> Option<u64>
+    where
+        I: IntoIterator<Item = &'a Entry>,
+    {
+        entries.into_iter().try_fold(0, |total, entry| {
+            let bytes = if entry.is_dir() && !entry.is_symlink() {
+                self.get(entry)?.bytes
+            } else {
+                entry.size
+            };
+
+            Some(total + bytes)
+        })
+    }
+}
+
+/// Handle to running measurements.
+///
+/// Iterating blocks until the next directory is measured, and stops once all of them are done or
+/// the measurement was cancelled.
+#[derive(Debug)]
+pub struct Measurement {
+    results: Receiver<Measured>,
+    cancel: CancelToken,
+}
+
+impl Measurement {
+    /// Stop measuring as soon as possible.
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Block until at least one directory is measured, then take every result so far.
+    ///
+    /// Returns [`None`] once every directory is done.
+    pub fn next_batch(&mut self) -> Option<Vec<Measured>> {
+        let first = self.results.recv().ok()?;
+
+        let mut batch = vec![first];
+        batch.extend(self.results.try_iter());
+
+        Some(batch)
+    }
+}
+
+impl Iterator for Measurement {
+    type Item = Measured;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.results.recv().ok()
+    }
+}
+
+/// Start measuring directories in the background.
+pub fn spawn(dirs: Vec<PathBuf>, options: Options, cancel: CancelToken) -> Measurement {
+    let (sender, results) = mpsc::channel();
+
+    let worker_cancel = cancel.clone();
+    POOL.spawn(move || {
+        dirs.into_par_iter().for_each_with(sender, |sender, path| {
+            if worker_cancel.is_cancelled() {
+                return;
+            }
+
+            // taken before measuring, so changes while measuring make the result stale
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+
+            if let Some(size) = measure(&path, options, &worker_cancel) {
+                let _ = sender.send(Measured {
+                    path,
+                    modified,
+                    size,
+                });
+            }
+        });
+    });
+
+    Measurement { results, cancel }
+}
+
+/// Measure a single directory, or [`None`] if cancelled.
+///
+/// Unreadable items are skipped, and files with several hard links are only counted once.
+pub fn measure(path: &Path, options: Options, cancel: &CancelToken) -> Option<Size> {
+    let walker = WalkBuilder::new(path)
+        .standard_filters(false)
+        .same_file_system(options.one_filesystem)
+        .build();
+
+    let mut size = Size::default();
+    #[cfg(unix)]
+    let mut links = HashSet::new();
+
+    for dent in walker {
+        if cancel.is_cancelled() {
+            return None;
+        }
+
+        let Ok(dent) = dent else {
+            continue;
+        };
+
+        if dent.depth() == 0 {
+            continue;
+        }
+
+        let Ok(metadata) = dent.metadata() else {
+            continue;
+        };
+
+        if metadata.is_dir() {
+            size.dirs += 1;
+            continue;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            if metadata.nlink() > 1 && !links.insert((metadata.dev(), metadata.ino())) {
+                continue;
+            }
+        }
+
+        size.files += 1;
+        size.bytes += metadata.len();
+    }
+
+    Some(size)
+}
+
+/// Human readable size, such as `1.5 MiB`.
+#[must_use]
+pub fn format(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if value < 10.0 {
+        format!("{value:.1} {}", UNITS[unit])
+    } else {
+        format!("{value:.0} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tree of 3 files in 2 folders, 10 bytes in all.
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("one"), "1").unwrap();
+        fs::write(dir.path().join("a/two"), "22").unwrap();
+        fs::write(dir.path().join("a/b/seven"), "7777777").unwrap();
+
+        dir
+    }
+
+    fn entry(path: &Path) -> Entry {
+        Entry::read(path).unwrap()
+    }
+
+    #[test]
+    fn everything_inside_is_counted() {
+        let dir = tree();
+
+        let size = measure(dir.path(), Options::default(), &CancelToken::new()).unwrap();
+
+        assert_eq!(
+            size,
+            Size {
+                bytes: 10,
+                files: 3,
+                dirs: 2
+            }
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_are_counted_once() {
+        let dir = tree();
+        fs::hard_link(dir.path().join("a/b/seven"), dir.path().join("link")).unwrap();
+
+        let size = measure(dir.path(), Options::default(), &CancelToken::new()).unwrap();
+
+        assert_eq!(size.bytes, 10);
+        assert_eq!(size.files, 3);
+    }
+
+    #[test]
+    fn cancelled_measurements_have_no_size() {
+        let dir = tree();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        assert_eq!(measure(dir.path(), Options::default(), &cancel), None);
+        assert_eq!(
+            spawn(vec![dir.path().to_path_buf()], Options::default(), cancel).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn every_directory_is_measured() {
+        let dir = tree();
+        let (root, a) = (dir.path().to_path_buf(), dir.path().join("a"));
+
+        let mut measured: Vec<_> = spawn(
+            vec![root.clone(), a.clone()],
+            Options::default(),
+            CancelToken::new(),
+        )
+        .collect();
+        measured.sort_by(|x, y| x.path.cmp(&y.path));
+
+        assert_eq!(measured.len(), 2);
+        assert_eq!(measured[0].path, root);
+        assert_eq!(measured[0].size.bytes, 10);
+        assert_eq!(measured[1].path, a);
+        assert_eq!(measured[1].size.bytes, 9);
+        assert_eq!(measured[1].modified, entry(&a).modified);
+    }
+
+    #[test]
+    fn only_unmeasured_directories_are_missing() {
+        let dir = tree();
+        let a = dir.path().join("a");
+        let entries = [entry(&a), entry(&dir.path().join("one"))];
+
+        let mut cache = Cache::new();
+        assert_eq!(cache.missing(&entries), std::slice::from_ref(&a));
+        assert_eq!(cache.total(&entries), None);
+
+        cache.insert(Measured {
+            path: a.clone(),
+            modified: entries[0].modified,
+            size: Size {
+                bytes: 9,
+                files: 2,
+                dirs: 1,
+            },
+        });
+
+        assert!(cache.missing(&entries).is_empty());
+        assert_eq!(cache.total(&entries), Some(10));
+    }
+
+    #[test]
+    fn modified_directories_are_missing() {
+        let dir = tree();
+        let a = dir.path().join("a");
+
+        let mut cache = Cache::new();
+        cache.insert(Measured {
+            path: a.clone(),
+            modified: Some(SystemTime::UNIX_EPOCH),
+            size: Size::default(),
+        });
+
+        assert_eq!(cache.get(&entry(&a)), None);
+        assert_eq!(cache.missing(&[entry(&a)]), [a]);
+    }
+
+    #[test]
+    fn invalidating_forgets_every_parent() {
+        let dir = tree();
+        let (root, a, b) = (
+            dir.path().to_path_buf(),
+            dir.path().join("a"),
+            dir.path().join("a/b"),
+        );
+
+        let mut cache = Cache::new();
+        for path in [&root, &a, &b] {
+            cache.insert(Measured {
+                path: path.clone(),
+                modified: entry(path).modified,
+                size: Size::default(),
+            });
+        }
+
+        cache.invalidate(&a.join("two"));
+
+        assert_eq!(cache.get(&entry(&root)), None);
+        assert_eq!(cache.get(&entry(&a)), None);
+        // folders next to or inside the change are unaffected
+        assert_eq!(cache.get(&entry(&b)), Some(Size::default()));
+    }
+
+    #[test]
+    fn sizes_are_formatted() {
+        assert_eq!(format(0), "0 B");
+        assert_eq!(format(1023), "1023 B");
+        assert_eq!(format(1536), "1.5 KiB");
+        assert_eq!(format(20 * 1024 * 1024), "20 MiB");
+    }
+}
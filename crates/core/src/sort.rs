@@ -0,0 +1,247 @@
+//! Ordering of directory entries.
+
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::entry::Entry;
+use crate::mime;
+
+/// What entries are compared by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Key {
+    /// Name, with numbers compared by value so `file2` comes before `file10`.
+    #[default]
+    Name,
+    Size,
+    Modified,
+    /// MIME type, as told by the name, then name.
+    Type,
+}
+
+impl Key {
+    /// Every key, in the order they are usually presented.
+    pub const ALL: [Self; 4] = [Self::Name, Self::Size, Self::Modified, Self::Type];
+
+    /// Human readable name.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::Size => "Size",
+            Self::Modified => "Modified",
+            Self::Type => "Type",
+        }
+    }
+}
+
+/// Direction of a sort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Order {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl Order {
+    /// The opposite direction.
+    #[must_use]
+    #[inline]
+    pub const fn reversed(self) -> Self {
+        match self {
+            Self::Ascending => Self::Descending,
+            Self::Descending => Self::Ascending,
+        }
+    }
+}
+
+/// How to sort a directory listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sort {
+    pub key: Key,
+    pub order: Order,
+    /// Keep directories before everything else, regardless of order.
+    pub folders_first: bool,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Self {
+            key: Key::default(),
+            order: Order::default(),
+            folders_first: true,
+        }
+    }
+}
+
+impl Sort {
+    /// Sort by a key, or flip the order if already sorting by it.
+    #[must_use]
+    pub fn toggle(self, key: Key) -> Self {
+        if self.key == key {
+            Self {
+                order: self.order.reversed(),
+                ..self
+            }
+        } else {
+            Self {
+                key,
+                order: Order::Ascending,
+                ..self
+            }
+        }
+    }
+
+    /// Compare two entries.
+    #[must_use]
+    pub fn compare(&self, a: &Entry, b: &Entry) -> Ordering {
+        self.compare_with(a, b, || mime_type(a).cmp(mime_type(b)))
+    }
+
+    /// Compare two entries, with the order of their types given separately.
+    fn compare_with<F>(&self, a: &Entry, b: &Entry, by_type: F) -> Ordering
+    where
+        F: FnOnce() -> Ordering,
+    {
+        if self.folders_first {
+            let folders = b.is_dir().cmp(&a.is_dir());
+
+            if folders.is_ne() {
+                return folders;
+            }
+        }
+
+        let ordering = match self.key {
+            Key::Name => by_name(a, b),
+            // directory sizes don't mean much, so those stay in name order
+            Key::Size if a.is_dir() && b.is_dir() => by_name(a, b),
+            Key::Size => a.size.cmp(&b.size).then_with(|| by_name(a, b)),
+            Key::Modified => a.modified.cmp(&b.modified).then_with(|| by_name(a, b)),
+            Key::Type => by_type().then_with(|| by_name(a, b)),
+        };
+
+        match self.order {
+            Order::Ascending => ordering,
+            Order::Descending => ordering.reverse(),
+        }
+    }
+
+    /// Sort a list of entries in place.
+    pub fn apply(&self, entries: &mut [Entry]) {
+        if self.key != Key::Type {
+            entries.sort_by(|a, b| self.compare(a, b));
+            return;
+        }
+
+        // guessing a type isn't free, so it's done once for every entry instead of every comparison
+        let types: Vec<&str> = entries.iter().map(mime_type).collect();
+        let mut order: Vec<usize> = (0..entries.len()).collect();
+
+        order.sort_by(|&a, &b| {
+            self.compare_with(&entries[a], &entries[b], || types[a].cmp(types[b]))
+        });
+
+        let sorted: Vec<Entry> = order.into_iter().map(|i| entries[i].clone()).collect();
+        entries.clone_from_slice(&sorted);
+    }
+}
+
+/// Natural name order, falling back to the raw name so the order is always total.
+fn by_name(a: &Entry, b: &Entry) -> Ordering {
+    natural_cmp(&a.name().to_string_lossy(), &b.name().to_string_lossy())
+        .then_with(|| a.name().cmp(b.name()))
+}
+
+/// MIME type guessed from the name, without reading the file. Empty for directories, so they
+/// come first.
+fn mime_type(entry: &Entry) -> &'static str {
+    if entry.is_dir() {
+        return "";
+    }
+
+    mime::DATABASE.guess(entry)
+}
+
+/// Compare strings case-insensitively, treating runs of digits as numbers.
+#[must_use]
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        let ordering = match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+
+                let (xs, ys) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+
+                // a longer number without leading zeros is bigger, otherwise compare digits
+                xs.len()
+                    .cmp(&ys.len())
+                    .then_with(|| xs.cmp(ys))
+                    .then_with(|| x.len().cmp(&y.len()))
+            }
+            (Some(&x), Some(&y)) => {
+                a.next();
+                b.next();
+
+                x.to_lowercase().cmp(y.to_lowercase())
+            }
+        };
+
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+}
+
+/// Consume a run of ascii digits.
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut number = String::new();
+
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        number.push(c);
+    }
+
+    number
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_cmp("file10", "file9"), Ordering::Greater);
+        assert_eq!(natural_cmp("1.2.10", "1.2.9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a1b2", "a1b10"), Ordering::Less);
+    }
+
+    #[test]
+    fn leading_zeros_only_break_ties() {
+        assert_eq!(natural_cmp("007", "7"), Ordering::Greater);
+        assert_eq!(natural_cmp("007", "8"), Ordering::Less);
+        assert_eq!(natural_cmp("img01", "img1"), Ordering::Greater);
+        assert_eq!(natural_cmp("img01", "img2"), Ordering::Less);
+        assert_eq!(natural_cmp("000", "0"), Ordering::Greater);
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(natural_cmp("Apple", "apple"), Ordering::Equal);
+        assert_eq!(natural_cmp("apple", "Banana"), Ordering::Less);
+        assert_eq!(natural_cmp("ZETA", "alpha"), Ordering::Greater);
+    }
+
+    #[test]
+    fn prefixes_come_first() {
+        assert_eq!(natural_cmp("", "a"), Ordering::Less);
+        assert_eq!(natural_cmp("file", "file1"), Ordering::Less);
+        assert_eq!(natural_cmp("file1", "file1"), Ordering::Equal);
+    }
+}
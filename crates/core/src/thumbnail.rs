@@ -0,0 +1,435 @@
+//! Thumbnails of files, shared with other programs.
+//!
+//! Thumbnails are PNG files in the `thumbnails` folder of the user's cache, named after the MD5
+//! hash of the URI of the file they show, as described by the
+//! [thumbnail managing spec](https://specifications.freedesktop.org/thumbnail-spec/latest/). The
+//! URI and modification time of the file are stored inside the thumbnail, so an outdated one can
+//! be told apart and made again.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::Context;
+use image::imageops::FilterType;
+use percent_encoding::{percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::dirs;
+
+/// Characters escaped in the URI a thumbnail is named after. Everything else a path may contain
+/// is escaped, so every program ends up with the same URI for the same file.
+const URI_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'!')
+    .remove(b'~')
+    .remove(b'*')
+    .remove(b'\'')
+    .remove(b'(')
+    .remove(b')')
+    .remove(b'/')
+    .remove(b':')
+    .remove(b'@')
+    .remove(b'&')
+    .remove(b'=')
+    .remove(b'+')
+    .remove(b'$')
+    .remove(b',');
+
+/// Key of the URI of the file shown.
+const KEY_URI: &str = "Thumb::URI";
+/// Key of the modification time of the file shown, in seconds.
+const KEY_MTIME: &str = "Thumb::MTime";
+/// Key of the size of the file shown, in bytes.
+const KEY_SIZE: &str = "Thumb::Size";
+/// Key of the MIME type of the file shown.
+const KEY_MIME: &str = "Thumb::Mime";
+
+/// How big a thumbnail is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Size {
+    Normal,
+    Large,
+}
+
+impl Size {
+    /// Every size, smallest first.
+    pub const ALL: [Self; 2] = [Self::Normal, Self::Large];
+
+    /// Width and height the thumbnail fits in.
+    #[must_use]
+    pub const fn pixels(self) -> u32 {
+        match self {
+            Self::Normal => 128,
+            Self::Large => 256,
+        }
+    }
+
+    /// Name of the folder thumbnails of this size are kept in.
+    #[must_use]
+    pub const fn dir_name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Large => "large",
+        }
+    }
+}
+
+/// What a thumbnail says about the file it shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Source {
+    uri: String,
+    mtime: u64,
+    size: u64,
+}
+
+impl Source {
+    fn read(path: &Path) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        let mtime = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        Ok(Self {
+            uri: uri(path),
+            mtime,
+            size: meta.len(),
+        })
+    }
+
+    /// Name of a thumbnail of the file.
+    fn file_name(&self) -> String {
+        format!("{:x}.png", md5::compute(&self.uri))
+    }
+}
+
+/// Check if thumbnails can be made for a type.
+#[must_use]
+pub fn supports(mime: &str) -> bool {
+    matches!(
+        mime,
+        "image/bmp"
+            | "image/gif"
+            | "image/jpeg"
+            | "image/png"
+            | "image/tiff"
+            | "image/vnd.microsoft.icon"
+            | "image/webp"
+    )
+}
+
+/// Folder thumbnails are kept in.
+#[must_use]
+pub fn cache_dir() -> PathBuf {
+    dirs::BASE.cache_dir().join("thumbnails")
+}
+
+/// A thumbnail of a file at least as big as a size, making one if there isn't one that's up to
+/// date.
+///
+/// Thumbnails made by other programs are used for any type, but only [supported](supports) ones
+/// are made here. Files that couldn't be shown before aren't tried again until they're modified.
+pub fn load(path: &Path, mime: &str, size: Size) -> anyhow::Result<PathBuf> {
+    let source = Source::read(path)?;
+
+    if let Some(thumbnail) = find(&cache_dir(), &source, size) {
+        return Ok(thumbnail);
+    }
+
+    if !supports(mime) {
+        anyhow::bail!("thumbnails of {mime} can't be made");
+    }
+
+    let failure = fail_dir().join(source.file_name());
+
+    if is_valid(&failure, &source) {
+        anyhow::bail!("a thumbnail couldn't be made before");
+    }
+
+    generate(path, &source, mime, size).inspect_err(|_| {
+        // a blank image records the failure, so it isn't tried again every time the file is shown.
+        // Not being able to record it only means trying again.
+        let _ = dirs::create_private_dir(&fail_dir())
+            .and_then(|_| write(&failure, &source, mime, 1, 1, &[0; 4]));
+    })
+}
+
+/// An up-to-date thumbnail in a cache at least as big as a size, made by any program.
+fn find(dir: &Path, source: &Source, size: Size) -> Option<PathBuf> {
+    Size::ALL
+        .into_iter()
+        .filter(|s| *s >= size)
+        .map(|s| dir.join(s.dir_name()).join(source.file_name()))
+        .find(|thumbnail| is_valid(thumbnail, source))
+}
+
+/// Check if a thumbnail shows a file as it is now.
+fn is_valid(thumbnail: &Path, source: &Source) -> bool {
+    let Ok(file) = File::open(thumbnail) else {
+        return false;
+    };
+    let Ok(reader) = png::Decoder::new(file).read_info() else {
+        return false;
+    };
+
+    let text = &reader.info().uncompressed_latin1_text;
+    let get = |key: &str| {
+        text.iter()
+            .find(|t| t.keyword == key)
+            .map(|t| t.text.as_str())
+    };
+
+    get(KEY_URI) == Some(source.uri.as_str())
+        && get(KEY_MTIME).and_then(|m| m.parse().ok()) == Some(source.mtime)
+        // the size is optional, but has to match when it's there
+        && get(KEY_SIZE).is_none_or(|s| s.parse().ok() == Some(source.size))
+}
+
+/// Make a thumbnail of an image.
+fn generate(path: &Path, source: &Source, mime: &str, size: Size) -> anyhow::Result<PathBuf> {
+    let dir = cache_dir();
+
+    // thumbnails of thumbnails would only fill the cache
+    if path.starts_with(&dir) {
+        anyhow::bail!("{} is a thumbnail", path.display());
+    }
+
+    let image = image::open(path).with_context(|| format!("can't read {}", path.display()))?;
+
+    // small images are kept as they are, rather than blown up
+    let image = if image.width() > size.pixels() || image.height() > size.pixels() {
+        image.resize(size.pixels(), size.pixels(), FilterType::Triangle)
+    } else {
+        image
+    };
+    let image = image.to_rgba8();
+
+    let dir = dir.join(size.dir_name());
+    dirs::create_private_dir(&dir)?;
+
+    let thumbnail = dir.join(source.file_name());
+    write(
+        &thumbnail,
+        source,
+        mime,
+        image.width(),
+        image.height(),
+        image.as_raw(),
+    )?;
+
+    Ok(thumbnail)
+}
+
+/// Write a thumbnail, replacing any that's there all at once so nobody reads half of it.
+fn write(
+    thumbnail: &Path,
+    source: &Source,
+    mime: &str,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+) -> io::Result<()> {
+    let temporary = thumbnail.with_extension(format!("{}.tmp", std::process::id()));
+
+    let written = (|| {
+        let file = create_private_file(&temporary)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        for (key, value) in [
+            (KEY_URI, source.uri.clone()),
+            (KEY_MTIME, source.mtime.to_string()),
+            (KEY_SIZE, source.size.to_string()),
+            (KEY_MIME, mime.to_owned()),
+            ("Software", "doseer".to_owned()),
+        ] {
+            encoder.add_text_chunk(key.to_owned(), value)?;
+        }
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(rgba)?;
+        writer.finish()?;
+
+        fs::rename(&temporary, thumbnail)
+    })();
+
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+
+    written
+}
+
+/// Folder recording the files this program couldn't make thumbnails of.
+fn fail_dir() -> PathBuf {
+    cache_dir()
+        .join("fail")
+        .join(concat!("doseer-", env!("CARGO_PKG_VERSION")))
+}
+
+/// The URI a thumbnail is named after.
+fn uri(path: &Path) -> String {
+    let path = path.as_os_str().as_encoded_bytes();
+    format!("file://{}", percent_encode(path, URI_ESCAPE))
+}
+
+/// Create a file only the user can read.
+fn create_private_file(path: &Path) -> io::Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(uri: &str) -> Source {
+        Source {
+            uri: uri.to_owned(),
+            mtime: 1_700_000_000,
+            size: 1234,
+        }
+    }
+
+    #[test]
+    fn uris_are_escaped() {
+        assert_eq!(
+            uri(Path::new("/home/me/a b/50%#?ü.png")),
+            "file:///home/me/a%20b/50%25%23%3F%C3%BC.png"
+        );
+        assert_eq!(
+            uri(Path::new("/tmp/-_.!~*'()@&=+$,:.png")),
+            "file:///tmp/-_.!~*'()@&=+$,:.png"
+        );
+    }
+
+    #[test]
+    fn names_are_md5_of_the_uri() {
+        // the example from the spec
+        assert_eq!(
+            source("file:///home/jens/photos/me.png").file_name(),
+            "c6ee772d9e49320e97ec29a7eb5b1697.png"
+        );
+    }
+
+    #[test]
+    fn outdated_thumbnails_are_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let thumbnail = dir.path().join("thumbnail.png");
+        let shown = source("file:///photo.png");
+
+        write(&thumbnail, &shown, "image/png", 1, 1, &[0; 4]).unwrap();
+        assert!(is_valid(&thumbnail, &shown));
+
+        let modified = Source {
+            mtime: shown.mtime + 1,
+            ..shown.clone()
+        };
+        assert!(!is_valid(&thumbnail, &modified));
+
+        let resized = Source {
+            size: shown.size + 1,
+            ..shown.clone()
+        };
+        assert!(!is_valid(&thumbnail, &resized));
+
+        assert!(!is_valid(&thumbnail, &source("file:///other.png")));
+        assert!(!is_valid(&dir.path().join("missing.png"), &shown));
+    }
+
+    #[test]
+    fn thumbnails_without_a_valid_mtime_are_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let thumbnail = dir.path().join("thumbnail.png");
+        let shown = source("file:///photo.png");
+
+        let file = File::create(&thumbnail).unwrap();
+        let mut encoder = png::Encoder::new(BufWriter::new(file), 1, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder
+            .add_text_chunk(KEY_URI.to_owned(), shown.uri.clone())
+            .unwrap();
+        encoder
+            .add_text_chunk(KEY_MTIME.to_owned(), "yesterday".to_owned())
+            .unwrap();
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&[0; 4])
+            .unwrap();
+
+        assert!(!is_valid(&thumbnail, &shown));
+    }
+
+    #[test]
+    fn existing_thumbnails_are_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let shown = source("file:///photo.png");
+
+        assert_eq!(find(dir.path(), &shown, Size::Normal), None);
+
+        let large = dir.path().join("large");
+        dirs::create_private_dir(&large).unwrap();
+        let thumbnail = large.join(shown.file_name());
+        write(&thumbnail, &shown, "image/png", 1, 1, &[0; 4]).unwrap();
+
+        // bigger thumbnails do just as well
+        assert_eq!(
+            find(dir.path(), &shown, Size::Normal),
+            Some(thumbnail.clone())
+        );
+        assert_eq!(find(dir.path(), &shown, Size::Large), Some(thumbnail));
+
+        let normal = dir.path().join("normal");
+        dirs::create_private_dir(&normal).unwrap();
+        write(
+            &normal.join(shown.file_name()),
+            &shown,
+            "image/png",
+            1,
+            1,
+            &[0; 4],
+        )
+        .unwrap();
+
+        assert_eq!(
+            find(dir.path(), &shown, Size::Normal),
+            Some(normal.join(shown.file_name()))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn thumbnails_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let thumbnail = dir.path().join("thumbnail.png");
+
+        write(
+            &thumbnail,
+            &source("file:///photo.png"),
+            "image/png",
+            1,
+            1,
+            &[0; 4],
+        )
+        .unwrap();
+
+        let mode = fs::metadata(&thumbnail).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
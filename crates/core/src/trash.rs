@@ -0,0 +1,656 @@
+//! Trash can following the [freedesktop trash spec].
+//!
+//! Items are trashed into the home trash when they live on the same filesystem, and into a
+//! `.Trash/$uid` or `.Trash-$uid` directory at the top of their own mount otherwise, so trashing
+//! is always a cheap rename.
+//!
+//! [freedesktop trash spec]: https://specifications.freedesktop.org/trash-spec/trashspec-latest.html
+
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, NaiveDateTime};
+use percent_encoding::{percent_decode, percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::dirs;
+#[cfg(unix)]
+use crate::mounts;
+use crate::ops::{copy::unique_name, Conflict};
+use crate::path::PathWrap;
+
+/// Format of the `DeletionDate` key.
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// The sticky bit of a unix file mode.
+#[cfg(unix)]
+const STICKY_BIT: u32 = 0o1000;
+
+/// Characters escaped in the `Path` key, which is a URL-escaped path.
+const PATH_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// A trash directory, containing `files` and `info` subdirectories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashDir {
+    root: PathBuf,
+    /// Top directory of the mount this trash belongs to. Not set for the home trash.
+    topdir: Option<PathBuf>,
+}
+
+impl TrashDir {
+    /// The user's home trash.
+    #[must_use]
+    pub fn home() -> Self {
+        Self {
+            root: dirs::BASE.data_dir().join("Trash"),
+            topdir: None,
+        }
+    }
+
+    /// The directory holding trashed items.
+    #[must_use]
+    #[inline]
+    pub fn files(&self) -> PathBuf {
+        self.root.join("files")
+    }
+
+    /// The directory holding `.trashinfo` files.
+    #[must_use]
+    #[inline]
+    pub fn info(&self) -> PathBuf {
+        self.root.join("info")
+    }
+
+    /// Create the trash directories if they don't exist yet.
+    pub fn create(&self) -> io::Result<()> {
+        for dir in [self.files(), self.info()] {
+            dirs::create_private_dir(&dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Check if the trash directories exist.
+    #[must_use]
+    pub fn exists(&self) -> bool {
+        self.files().is_dir() && self.info().is_dir()
+    }
+
+    /// Everything currently in this trash.
+    pub fn items(&self) -> io::Result<Vec<Trashed>> {
+        let mut items = vec![];
+
+        for entry in fs::read_dir(self.info())?.filter_map(Result::ok) {
+            let info = entry.path();
+
+            if info.extension() != Some(OsStr::new("trashinfo")) {
+                continue;
+            }
+
+            match self.parse_info(&info) {
+                Ok(item) => items.push(item),
+                Err(_) => continue,
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// The trash directory a path should be moved to, creating it if needed.
+    fn for_path(path: &Path) -> io::Result<Self> {
+        let home = Self::home();
+        home.create()?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let device = fs::symlink_metadata(path)?.dev();
+
+            if fs::metadata(&home.root)?.dev() != device {
+                let topdir = topdir(path, device)?;
+                return Self::at_topdir(&topdir, true).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "no usable trash directory on this filesystem",
+                    )
+                });
+            }
+        }
+
+        Ok(home)
+    }
+
+    /// The trash directory at the top of a mount.
+    ///
+    /// A shared `$topdir/.Trash` is only used if it's a real directory with the sticky bit set,
+    /// as required by the spec. Otherwise a per-user `$topdir/.Trash-$uid` is used.
+    #[cfg(unix)]
+    fn at_topdir(topdir: &Path, create: bool) -> Option<Self> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let uid = unsafe { libc::getuid() };
+
+        let shared = topdir.join(".Trash");
+        let candidates = match fs::symlink_metadata(&shared) {
+            Ok(m) if m.is_dir() && m.permissions().mode() & STICKY_BIT != 0 => {
+                vec![
+                    shared.join(uid.to_string()),
+                    topdir.join(format!(".Trash-{uid}")),
+                ]
+            }
+            _ => vec![topdir.join(format!(".Trash-{uid}"))],
+        };
+
+        candidates.into_iter().find_map(|root| {
+            let trash = Self {
+                root,
+                topdir: Some(topdir.to_path_buf()),
+            };
+
+            if trash.exists() || (create && trash.create().is_ok()) {
+                Some(trash)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Read a `.trashinfo` file belonging to this trash.
+    fn parse_info(&self, info: &Path) -> io::Result<Trashed> {
+        let contents = fs::read_to_string(info)?;
+
+        let mut original = None;
+        let mut deleted = None;
+        let mut in_section = false;
+
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_section = line == "[Trash Info]";
+                continue;
+            }
+
+            if !in_section {
+                continue;
+            }
+
+            if let Some(value) = line.strip_prefix("Path=") {
+                original = Some(decode_path(value));
+            } else if let Some(value) = line.strip_prefix("DeletionDate=") {
+                deleted = NaiveDateTime::parse_from_str(value, DATE_FORMAT).ok();
+            }
+        }
+
+        let original = original
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "trash info has no path"))?;
+
+        // Relative paths are relative to the top of the mount
+        let original = match &self.topdir {
+            Some(topdir) if original.is_relative() => topdir.join(original),
+            _ => original,
+        };
+
+        let name = info.file_stem().unwrap_or_default();
+
+        Ok(Trashed {
+            path: PathWrap::from_into_path(self.files().join(name)),
+            original: PathWrap::from_into_path(original),
+            deleted,
+            info: PathWrap::from_path(info),
+        })
+    }
+}
+
+/// An item in the trash.
+#[derive(Debug, Clone)]
+pub struct Trashed {
+    /// Where the item is stored inside the trash.
+    pub path: PathWrap,
+    /// Where the item was trashed from.
+    pub original: PathWrap,
+    /// When the item was trashed, in local time.
+    pub deleted: Option<NaiveDateTime>,
+    /// The `.trashinfo` file describing this item.
+    info: PathWrap,
+}
+
+impl Trashed {
+    /// Look up a trashed item from its path inside a trash `files` directory.
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let trash = containing_trash(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a trashed item"))?;
+
+        let mut name = path.file_name().unwrap_or_default().to_owned();
+        name.push(".trashinfo");
+
+        trash.parse_info(&trash.info().join(name))
+    }
+
+    /// The name the item had before it was trashed.
+    #[must_use]
+    #[inline]
+    pub fn name(&self) -> &OsStr {
+        self.original.file_name().unwrap_or_default()
+    }
+}
+
+/// The location of the home trash, which the UI treats as the "Trash" location.
+#[must_use]
+pub fn location() -> PathWrap {
+    PathWrap::from_into_path(TrashDir::home().files())
+}
+
+/// Move a path into the trash.
+pub fn trash(path: &Path) -> io::Result<Trashed> {
+    let original = std::path::absolute(path)?;
+    let trash = TrashDir::for_path(&original)?;
+
+    // Reserve a unique name by creating the info file first
+    let base = original
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+
+    let info_dir = trash.info();
+
+    let (name, info, mut file) = (1..)
+        .map(|n| {
+            let mut name = base.to_owned();
+            if n > 1 {
+                name.push(format!(".{n}"));
+            }
+            name
+        })
+        .find_map(|name| {
+            let mut info_name = name.clone();
+            info_name.push(".trashinfo");
+            let info = info_dir.join(info_name);
+
+            match OpenOptions::new().write(true).create_new(true).open(&info) {
+                Ok(file) => Some(Ok((name, info, file))),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+        .unwrap()?;
+
+    // Paths in mount trash directories are stored relative to the mount
+    let stored = match &trash.topdir {
+        Some(topdir) => original.strip_prefix(topdir).unwrap_or(&original),
+        None => &original,
+    };
+
+    let deleted = Local::now().naive_local();
+
+    let result = write!(
+        file,
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode_path(stored),
+        deleted.format(DATE_FORMAT)
+    )
+    .and_then(|_| fs::rename(&original, trash.files().join(&name)));
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&info);
+        return Err(e);
+    }
+
+    Ok(Trashed {
+        path: PathWrap::from_into_path(trash.files().join(name)),
+        original: PathWrap::from_into_path(original),
+        deleted: Some(deleted),
+        info: PathWrap::from_into_path(info),
+    })
+}
+
+/// Move a trashed item back to where it came from.
+///
+/// Returns where the item was restored to, or [`None`] if it was skipped because of a conflict.
+pub fn restore(path: &Path, conflict: Conflict) -> io::Result<Option<PathWrap>> {
+    let item = Trashed::from_path(path)?;
+    let mut target = item.original.to_path_buf();
+    // what's being overwritten, moved aside until the item is back in its place
+    let mut replaced = None;
+
+    if fs::symlink_metadata(&target).is_ok() {
+        match conflict {
+            Conflict::Rename => target = unique_name(&target, item.path.is_dir()),
+            Conflict::Skip => return Ok(None),
+            Conflict::Overwrite => {
+                let aside = aside_name(&target);
+                fs::rename(&target, &aside)?;
+                replaced = Some(aside);
+            }
+        }
+    }
+
+    let restored = target
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::rename(&item.path, &target));
+
+    if let Err(e) = restored {
+        if let Some(aside) = replaced {
+            let _ = fs::rename(aside, &target);
+        }
+
+        return Err(e);
+    }
+
+    if let Some(aside) = replaced {
+        let _ = crate::ops::copy::remove_all(&aside);
+    }
+
+    let _ = fs::remove_file(&item.info);
+
+    Ok(Some(PathWrap::from_into_path(target)))
+}
+
+/// A free, hidden name next to an item to move it aside to.
+fn aside_name(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let parent = path.parent().unwrap_or(Path::new(""));
+
+    (0..)
+        .map(|n| parent.join(format!(".{name}.doseer-restore-{n}")))
+        .find(|p| fs::symlink_metadata(p).is_err())
+        .unwrap()
+}
+
+/// Remove the trash information of an item that has been deleted from the trash.
+///
+/// Does nothing for paths that aren't in a trash.
+pub fn forget(path: &Path) -> io::Result<()> {
+    let Some(trash) = containing_trash(path) else {
+        return Ok(());
+    };
+
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".trashinfo");
+
+    match fs::remove_file(trash.info().join(name)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Check if a path is an item directly inside a trash.
+#[must_use]
+#[inline]
+pub fn is_trashed(path: &Path) -> bool {
+    containing_trash(path).is_some()
+}
+
+/// Every trash directory that currently exists.
+#[must_use]
+pub fn trash_dirs() -> Vec<TrashDir> {
+    let mut all = vec![];
+
+    let home = TrashDir::home();
+    if home.exists() {
+        all.push(home);
+    }
+
+    #[cfg(unix)]
+    for mount in mounts::list() {
+        if let Some(trash) = TrashDir::at_topdir(&mount.point, false) {
+            if !all.contains(&trash) {
+                all.push(trash);
+            }
+        }
+    }
+
+    all
+}
+
+/// Everything in every trash.
+#[must_use]
+pub fn list() -> Vec<Trashed> {
+    trash_dirs()
+        .iter()
+        .filter_map(|t| t.items().ok())
+        .flatten()
+        .collect()
+}
+
+/// The trash directory a path is stored in, if it's a trashed item.
+fn containing_trash(path: &Path) -> Option<TrashDir> {
+    let files = path.parent()?;
+
+    if files.file_name() != Some(OsStr::new("files")) {
+        return None;
+    }
+
+    let root = files.parent()?;
+    let home = TrashDir::home();
+
+    if root == home.root {
+        return Some(home);
+    }
+
+    // Either `$topdir/.Trash-$uid` or `$topdir/.Trash/$uid`
+    let root_name = root.file_name()?.to_string_lossy();
+
+    let topdir = if root_name.starts_with(".Trash-") {
+        root.parent()?
+    } else if root.parent()?.file_name() == Some(OsStr::new(".Trash")) {
+        root.parent()?.parent()?
+    } else {
+        return None;
+    };
+
+    Some(TrashDir {
+        root: root.to_path_buf(),
+        topdir: Some(topdir.to_path_buf()),
+    })
+}
+
+/// Find the top directory of the mount a path is on.
+#[cfg(unix)]
+fn topdir(path: &Path, device: u64) -> io::Result<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut top = path.parent().unwrap_or(path);
+
+    while let Some(parent) = top.parent() {
+        if fs::metadata(parent)?.dev() != device {
+            break;
+        }
+
+        top = parent;
+    }
+
+    Ok(top.to_path_buf())
+}
+
+/// URL-escape a path for a `.trashinfo` file.
+fn encode_path(path: &Path) -> String {
+    percent_encode(path.as_os_str().as_encoded_bytes(), PATH_ESCAPE).to_string()
+}
+
+/// Decode a URL-escaped path from a `.trashinfo` file.
+fn decode_path(value: &str) -> PathBuf {
+    let bytes = percent_decode(value.as_bytes()).collect::<Vec<_>>();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        PathBuf::from(OsString::from_vec(bytes))
+    }
+
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(OsString::from(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_escaped() {
+        let path = Path::new("/home/me/My Files/50%_off~v1.0-final.txt");
+        let encoded = encode_path(path);
+
+        assert_eq!(encoded, "/home/me/My%20Files/50%25_off~v1.0-final.txt");
+        assert_eq!(decode_path(&encoded), path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_that_arent_utf8_survive() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"/tmp/caf\xe9"));
+
+        assert_eq!(decode_path(&encode_path(path)), path);
+    }
+
+    #[test]
+    fn info_is_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash = TrashDir {
+            root: dir.path().to_path_buf(),
+            topdir: None,
+        };
+        trash.create().unwrap();
+
+        let info = trash.info().join("a b.txt.trashinfo");
+        fs::write(
+            &info,
+            "[Other]\nPath=/wrong\n[Trash Info]\nPath=/home/me/a%20b.txt\nDeletionDate=2024-01-02T03:04:05\n",
+        )
+        .unwrap();
+
+        let item = trash.parse_info(&info).unwrap();
+
+        assert_eq!(*item.path, trash.files().join("a b.txt"));
+        assert_eq!(*item.original, *Path::new("/home/me/a b.txt"));
+        assert_eq!(
+            item.deleted.unwrap().to_string(),
+            "2024-01-02 03:04:05".to_owned()
+        );
+    }
+
+    #[test]
+    fn relative_paths_are_below_the_mount() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash = TrashDir {
+            root: dir.path().join(".Trash-1000"),
+            topdir: Some(PathBuf::from("/media/usb")),
+        };
+        trash.create().unwrap();
+
+        let info = trash.info().join("photo.jpg.trashinfo");
+        fs::write(&info, "[Trash Info]\nPath=photos/photo.jpg\n").unwrap();
+
+        let item = trash.parse_info(&info).unwrap();
+
+        assert_eq!(*item.original, *Path::new("/media/usb/photos/photo.jpg"));
+        assert!(item.deleted.is_none());
+    }
+
+    /// Trash an item by hand in a trash at the top of a folder, returning where it went.
+    fn trash_at(topdir: &Path, original: &str, contents: &str) -> PathBuf {
+        let trash = TrashDir {
+            root: topdir.join(".Trash-1000"),
+            topdir: Some(topdir.to_path_buf()),
+        };
+        trash.create().unwrap();
+
+        let name = Path::new(original).file_name().unwrap();
+        let mut info = name.to_owned();
+        info.push(".trashinfo");
+
+        fs::write(
+            trash.info().join(info),
+            format!("[Trash Info]\nPath={original}\n"),
+        )
+        .unwrap();
+
+        let trashed = trash.files().join(name);
+        fs::write(&trashed, contents).unwrap();
+        trashed
+    }
+
+    /// Names of everything in a directory, sorted.
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn restoring_overwrites_what_is_there() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("notes.txt");
+        fs::write(&original, "newer").unwrap();
+
+        let trashed = trash_at(dir.path(), "notes.txt", "trashed");
+        let restored = restore(&trashed, Conflict::Overwrite).unwrap().unwrap();
+
+        assert_eq!(*restored, *original);
+        assert_eq!(fs::read_to_string(&original).unwrap(), "trashed");
+        assert_eq!(names(dir.path()), [".Trash-1000", "notes.txt"]);
+        assert!(names(&dir.path().join(".Trash-1000").join("info")).is_empty());
+    }
+
+    #[test]
+    fn failed_restores_keep_what_was_there() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("notes.txt");
+        fs::write(&original, "newer").unwrap();
+
+        // the item is gone, but its information is still there
+        let trashed = trash_at(dir.path(), "notes.txt", "trashed");
+        fs::remove_file(&trashed).unwrap();
+
+        assert!(restore(&trashed, Conflict::Overwrite).is_err());
+        assert_eq!(fs::read_to_string(&original).unwrap(), "newer");
+        assert_eq!(names(dir.path()), [".Trash-1000", "notes.txt"]);
+    }
+
+    #[test]
+    fn restoring_can_skip_or_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("notes.txt");
+        fs::write(&original, "newer").unwrap();
+
+        let trashed = trash_at(dir.path(), "notes.txt", "trashed");
+
+        assert_eq!(restore(&trashed, Conflict::Skip).unwrap(), None);
+        assert_eq!(fs::read_to_string(&original).unwrap(), "newer");
+
+        let restored = restore(&trashed, Conflict::Rename).unwrap().unwrap();
+        assert_ne!(*restored, *original);
+        assert_eq!(fs::read_to_string(&restored).unwrap(), "trashed");
+        assert_eq!(fs::read_to_string(&original).unwrap(), "newer");
+    }
+
+    #[test]
+    fn info_without_a_path_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash = TrashDir {
+            root: dir.path().to_path_buf(),
+            topdir: None,
+        };
+        trash.create().unwrap();
+
+        let info = trash.info().join("x.trashinfo");
+        fs::write(&info, "[Trash Info]\nDeletionDate=2024-01-02T03:04:05\n").unwrap();
+
+        assert_eq!(
+            trash.parse_info(&info).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Disk usage of whole directory trees.
+//!
+//! Unlike [folder sizes](crate::size), which only keep a total per folder, a scan keeps the size of
+//! every item in the tree, so the biggest items can be found at any depth.
+
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rayon::prelude::*;
+
+use crate::size::{self, Options};
+use crate::task::CancelToken;
+
+/// An item in a scanned tree.
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub path: PathBuf,
+    /// Size of the item, including everything inside it.
+    pub bytes: u64,
+    pub is_dir: bool,
+    /// Items inside a directory, biggest first.
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    /// Name of the item.
+    #[inline]
+    pub fn name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    /// Find an item anywhere in this tree.
+    #[must_use]
+    pub fn find(&self, path: &Path) -> Option<&Node> {
+        if self.path == path {
+            return Some(self);
+        }
+
+        let child = self.children.iter().find(|c| path.starts_with(&c.path))?;
+        child.find(path)
+    }
+
+    /// Every file in this tree.
+    pub fn files(&self) -> Box<dyn Iterator<Item = &Node> + '_> {
+        if self.is_dir {
+            Box::new(self.children.iter().flat_map(Node::files))
+        } else {
+            Box::new(std::iter::once(self))
+        }
+    }
+
+    /// The biggest files in this tree, biggest first.
+    #[must_use]
+    pub fn largest_files(&self, count: usize) -> Vec<&Node> {
+        let biggest =
+            |a: &&Node, b: &&Node| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path));
+        let mut files: Vec<_> = self.files().collect();
+
+        // trees can have millions of files, so avoid sorting all of them
+        if count < files.len() {
+            files.select_nth_unstable_by(count, biggest);
+            files.truncate(count);
+        }
+
+        files.sort_by(biggest);
+        files
+    }
+}
+
+/// Scan a whole directory tree, or [`None`] if cancelled or the directory can't be read.
+///
+/// Unreadable items are skipped, and files with several hard links are only counted once.
+pub fn scan(path: &Path, options: Options, cancel: &CancelToken) -> Option<Node> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+
+    let scanner = Scanner {
+        options,
+        cancel,
+        device: device(&metadata),
+        links: Mutex::new(HashSet::new()),
+    };
+
+    size::POOL.install(|| scanner.node(path.to_path_buf(), &metadata))
+}
+
+/// State shared while scanning a tree.
+struct Scanner<'a> {
+    options: Options,
+    cancel: &'a CancelToken,
+    /// Filesystem the scan started on.
+    device: Option<u64>,
+    /// Files with several hard links seen so far.
+    links: Mutex<HashSet<(u64, u64)>>,
+}
+
+impl Scanner<'_> {
+    fn node(&self, path: PathBuf, metadata: &Metadata) -> Option<Node> {
+        if self.cancel.is_cancelled() {
+            return None;
+        }
+
+        if !metadata.is_dir() {
+            let bytes = if self.is_duplicate(metadata) {
+                0
+            } else {
+                metadata.len()
+            };
+
+            return Some(Node {
+                path,
+                bytes,
+                is_dir: false,
+                children: vec![],
+            });
+        }
+
+        let outside = self.options.one_filesystem && device(metadata) != self.device;
+
+        let entries: Vec<_> = if outside {
+            vec![]
+        } else {
+            match fs::read_dir(&path) {
+                Ok(read) => read.filter_map(Result::ok).collect(),
+                Err(_) => vec![],
+            }
+        };
+
+        let mut children = entries
+            .into_par_iter()
+            .filter_map(|e| {
+                // symlinks are counted as themselves, never followed
+                let metadata = fs::symlink_metadata(e.path()).ok()?;
+                Some(self.node(e.path(), &metadata))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        children.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+
+        Some(Node {
+            path,
+            bytes: children.iter().map(|c| c.bytes).sum(),
+            is_dir: true,
+            children,
+        })
+    }
+
+    /// Check if another link to the same file was already counted.
+    #[cfg(unix)]
+    fn is_duplicate(&self, metadata: &Metadata) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        metadata.nlink() > 1
+            && !self
+                .links
+                .lock()
+                .unwrap()
+                .insert((metadata.dev(), metadata.ino()))
+    }
+
+    #[cfg(not(unix))]
+    fn is_duplicate(&self, _: &Metadata) -> bool {
+        false
+    }
+}
+
+/// Filesystem an item is on, where that can be told.
+#[cfg(unix)]
+fn device(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_: &Metadata) -> Option<u64> {
+    None
+}
@@ -0,0 +1,125 @@
+//! Items inside archives.
+//!
+//! Locations use the same paths as described in [`archive`](crate::archive). Archives are read
+//! only, and are indexed once until they change.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use super::{unsupported, Listing, Uri, Vfs, Watch};
+use crate::archive::{self, Archive};
+use crate::entry::{Entry, Kind};
+
+/// Scheme of locations inside archives.
+pub const SCHEME: &str = "archive";
+
+/// An indexed archive, along with its modification time when indexed.
+type Indexed = (Option<SystemTime>, Arc<Archive>);
+
+/// Backend for archives.
+#[derive(Debug, Default)]
+pub struct Archives {
+    /// Indexed archives by path.
+    cache: Mutex<HashMap<PathBuf, Indexed>>,
+}
+
+impl Archives {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The archive a location is in, indexing it if it changed, and the path within it.
+    fn open(&self, uri: &Uri) -> io::Result<(Arc<Archive>, PathBuf)> {
+        let (path, inner) = archive::split(uri.path())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not inside an archive"))?;
+
+        let modified = fs::metadata(&path)?.modified().ok();
+
+        if let Some((indexed, archive)) = self.cache.lock().unwrap().get(&path) {
+            if *indexed == modified {
+                return Ok((archive.clone(), inner));
+            }
+        }
+
+        // indexing can take a while, so don't hold up other archives meanwhile
+        let archive = Arc::new(Archive::open(&path)?);
+
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(path, (modified, archive.clone()));
+
+        Ok((archive, inner))
+    }
+}
+
+impl Vfs for Archives {
+    fn list(&self, uri: &Uri) -> io::Result<Listing> {
+        let (archive, inner) = self.open(uri)?;
+
+        if !archive.is_dir(&inner) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no such folder in the archive",
+            ));
+        }
+
+        Ok(Box::new(archive.entries(&inner).into_iter().map(Ok)))
+    }
+
+    fn stat(&self, uri: &Uri) -> io::Result<Entry> {
+        let (archive, inner) = self.open(uri)?;
+
+        // the archive itself is a folder here
+        if inner.as_os_str().is_empty() {
+            return Ok(Entry {
+                kind: Kind::Directory,
+                ..Entry::read(archive.path())?
+            });
+        }
+
+        archive
+            .get(&inner)
+            .map(|item| archive.entry(item))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not in the archive"))
+    }
+
+    fn open(&self, uri: &Uri) -> io::Result<Box<dyn Read + Send>> {
+        let (archive, inner) = self.open(uri)?;
+        Ok(Box::new(Cursor::new(archive.read(&inner)?)))
+    }
+
+    fn read(&self, uri: &Uri) -> io::Result<Vec<u8>> {
+        let (archive, inner) = self.open(uri)?;
+        archive.read(&inner)
+    }
+
+    fn write(&self, _: &Uri, _: &mut dyn Read) -> io::Result<u64> {
+        Err(unsupported("writing to archives"))
+    }
+
+    fn create_dir(&self, _: &Uri) -> io::Result<()> {
+        Err(unsupported("creating folders in archives"))
+    }
+
+    fn rename(&self, _: &Uri, _: &Uri) -> io::Result<()> {
+        Err(unsupported("renaming inside archives"))
+    }
+
+    fn delete(&self, _: &Uri) -> io::Result<()> {
+        Err(unsupported("deleting from archives"))
+    }
+
+    fn watch(&self, uri: &Uri) -> Watch {
+        // everything inside changes along with the archive
+        match archive::split(uri.path()) {
+            Some((path, _)) => Watch::Local(path),
+            None => Watch::None,
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! The local filesystem.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+
+use super::{Listing, Uri, Vfs, Watch};
+use crate::entry::Entry;
+use crate::ops::copy::remove_all;
+use crate::path::PathWrap;
+
+/// Scheme of local locations.
+pub const SCHEME: &str = "file";
+
+/// Backend for the local filesystem.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Local;
+
+impl Vfs for Local {
+    fn list(&self, uri: &Uri) -> io::Result<Listing> {
+        let entries = fs::read_dir(uri.path())?;
+
+        Ok(Box::new(entries.map(|entry| {
+            Entry::read_path(PathWrap::from_into_path(entry?.path()))
+        })))
+    }
+
+    #[inline]
+    fn stat(&self, uri: &Uri) -> io::Result<Entry> {
+        Entry::read_path(uri.path().clone())
+    }
+
+    fn open(&self, uri: &Uri) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(uri.path())?))
+    }
+
+    #[inline]
+    fn read(&self, uri: &Uri) -> io::Result<Vec<u8>> {
+        fs::read(uri.path())
+    }
+
+    fn write(&self, uri: &Uri, contents: &mut dyn Read) -> io::Result<u64> {
+        io::copy(contents, &mut File::create(uri.path())?)
+    }
+
+    #[inline]
+    fn create_dir(&self, uri: &Uri) -> io::Result<()> {
+        fs::create_dir(uri.path())
+    }
+
+    #[inline]
+    fn rename(&self, from: &Uri, to: &Uri) -> io::Result<()> {
+        fs::rename(from.path(), to.path())
+    }
+
+    #[inline]
+    fn delete(&self, uri: &Uri) -> io::Result<()> {
+        remove_all(uri.path())
+    }
+
+    #[inline]
+    fn watch(&self, uri: &Uri) -> Watch {
+        Watch::Local(uri.path().to_path_buf())
+    }
+}
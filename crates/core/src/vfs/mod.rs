@@ -0,0 +1,355 @@
+//! Virtual filesystems.
+//!
+//! Every location is a [`Uri`], and the scheme of the URI picks the [`Vfs`] backend that lists and
+//! reads it. Local paths use the `file` scheme, and paths inside archives the `archive` scheme, so
+//! both can be browsed by the same directory machinery. More backends can be added at runtime with
+//! [`register`].
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File, Permissions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use lazy_static::lazy_static;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::archive;
+use crate::dirs;
+use crate::entry::{Entry, Kind};
+use crate::path::PathWrap;
+
+pub mod archived;
+pub mod local;
+pub mod webdav;
+
+/// Characters escaped in the path of a URI.
+pub(crate) const PATH_ESCAPE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+lazy_static! {
+    /// Backends by the scheme they handle.
+    static ref BACKENDS: RwLock<HashMap<String, Arc<dyn Vfs>>> = {
+        let mut backends: HashMap<String, Arc<dyn Vfs>> = HashMap::new();
+        backends.insert(local::SCHEME.to_owned(), Arc::new(local::Local));
+        backends.insert(archived::SCHEME.to_owned(), Arc::new(archived::Archives::new()));
+
+        let webdav: Arc<dyn Vfs> = Arc::new(webdav::WebDav::new());
+        backends.insert(webdav::SCHEME.to_owned(), webdav.clone());
+        backends.insert(webdav::SECURE_SCHEME.to_owned(), webdav);
+        RwLock::new(backends)
+    };
+}
+
+/// Where an item is, on any backend.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Uri {
+    scheme: String,
+    /// Host, and maybe user and port, of remote locations. Empty for local ones.
+    authority: String,
+    /// Always absolute.
+    path: PathWrap,
+}
+
+impl Uri {
+    /// A location on some backend.
+    pub fn new<S, A, P>(scheme: S, authority: A, path: P) -> Self
+    where
+        S: Into<String>,
+        A: Into<String>,
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+
+        Self {
+            scheme: scheme.into(),
+            authority: authority.into(),
+            path: if path.is_absolute() {
+                PathWrap::from_path(path)
+            } else {
+                PathWrap::from_into_path(Path::new("/").join(path))
+            },
+        }
+    }
+
+    /// A path on the local filesystem.
+    #[inline]
+    pub fn local<P: AsRef<Path>>(path: P) -> Self {
+        Self::new(local::SCHEME, "", path)
+    }
+
+    /// Parse a URI such as `file:///home/me`.
+    ///
+    /// Anything without a scheme is taken as a local path, which may be inside an archive.
+    #[must_use]
+    pub fn parse(text: &str) -> Option<Self> {
+        let Some((scheme, rest)) = text.split_once("://") else {
+            return Some(locate(text));
+        };
+
+        let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+
+        if !valid {
+            return None;
+        }
+
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+
+        let path = percent_decode_str(path).decode_utf8().ok()?;
+
+        Some(Self::new(scheme.to_lowercase(), authority, path.as_ref()))
+    }
+
+    #[inline]
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    #[inline]
+    pub fn authority(&self) -> &str {
+        &self.authority
+    }
+
+    /// Path of the item on its backend.
+    #[inline]
+    pub fn path(&self) -> &PathWrap {
+        &self.path
+    }
+
+    /// Check if this is on the local filesystem, where [`Uri::path`] can be used directly.
+    #[must_use]
+    #[inline]
+    pub fn is_local(&self) -> bool {
+        self.scheme == local::SCHEME
+    }
+
+    /// The same location with another path.
+    #[must_use]
+    pub fn with_path<P: AsRef<Path>>(&self, path: P) -> Self {
+        Self::new(self.scheme.clone(), self.authority.clone(), path)
+    }
+
+    /// An item inside this location.
+    #[must_use]
+    #[inline]
+    pub fn join<P: AsRef<Path>>(&self, name: P) -> Self {
+        self.with_path(self.path.join(name))
+    }
+
+    /// The location containing this one, unless this is the root.
+    #[must_use]
+    pub fn parent(&self) -> Option<Self> {
+        self.path.parent().map(|p| self.with_path(p))
+    }
+}
+
+impl fmt::Display for Uri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.to_string_lossy();
+
+        write!(
+            f,
+            "{}://{}{}",
+            self.scheme,
+            self.authority,
+            utf8_percent_encode(&path, PATH_ESCAPE)
+        )
+    }
+}
+
+/// Local locations are written as plain paths, so they stay readable in configuration files.
+impl Serialize for Uri {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_local() {
+            self.path.serialize(serializer)
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Uri {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Self::parse(&text).ok_or_else(|| de::Error::custom(format!("invalid location `{text}`")))
+    }
+}
+
+/// Items being listed by a backend. Items that can't be read are skipped by most callers.
+pub type Listing = Box<dyn Iterator<Item = io::Result<Entry>> + Send>;
+
+/// How changes to a location can be noticed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Watch {
+    /// Watch a path on the local filesystem, which may be something other than the location
+    /// itself.
+    Local(PathBuf),
+    /// Changes can't be noticed.
+    None,
+}
+
+/// A filesystem backend.
+///
+/// Entries returned by a backend have the path part of their URI as their path. Backends that
+/// can't do something return an error of kind [`io::ErrorKind::Unsupported`].
+pub trait Vfs: fmt::Debug + Send + Sync {
+    /// List the items directly inside a directory, reading them as they're iterated.
+    fn list(&self, uri: &Uri) -> io::Result<Listing>;
+
+    /// Read a single item.
+    fn stat(&self, uri: &Uri) -> io::Result<Entry>;
+
+    /// Read the contents of a file as a stream.
+    fn open(&self, uri: &Uri) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Read the whole contents of a file.
+    fn read(&self, uri: &Uri) -> io::Result<Vec<u8>> {
+        let mut data = vec![];
+        self.open(uri)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Replace the contents of a file, creating it if needed. Returns the number of bytes written.
+    fn write(&self, uri: &Uri, contents: &mut dyn Read) -> io::Result<u64>;
+
+    /// Create an empty directory.
+    fn create_dir(&self, uri: &Uri) -> io::Result<()>;
+
+    /// Move an item to another path on the same backend.
+    fn rename(&self, from: &Uri, to: &Uri) -> io::Result<()>;
+
+    /// Permanently delete an item, with everything inside it.
+    fn delete(&self, uri: &Uri) -> io::Result<()>;
+
+    /// How changes to a location can be noticed.
+    fn watch(&self, uri: &Uri) -> Watch;
+}
+
+/// Error for backends that can't do something.
+pub(crate) fn unsupported(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{what} is not supported here"),
+    )
+}
+
+/// An entry for an item on a backend without permissions or owners.
+pub(crate) fn remote_entry<P: AsRef<Path>>(
+    path: P,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+) -> Entry {
+    let path = PathWrap::from_path(path);
+
+    Entry {
+        kind: if is_dir { Kind::Directory } else { Kind::File },
+        size,
+        modified,
+        created: None,
+        accessed: None,
+        permissions: permissions(is_dir),
+        owner: None,
+        hidden: path
+            .file_name()
+            .is_some_and(|n| n.as_encoded_bytes().starts_with(b".")),
+        symlink: None,
+        path,
+    }
+}
+
+/// Permissions that let the owner do anything that makes sense for an item.
+#[cfg(unix)]
+fn permissions(is_dir: bool) -> Permissions {
+    use std::os::unix::fs::PermissionsExt;
+
+    Permissions::from_mode(if is_dir { 0o755 } else { 0o644 })
+}
+
+/// Permissions that let the owner do anything that makes sense for an item.
+#[cfg(not(unix))]
+fn permissions(_: bool) -> Permissions {
+    // permissions can't be made up here, so borrow those of a folder that's always writable
+    let mut permissions = fs::metadata(std::env::temp_dir())
+        .map(|m| m.permissions())
+        .expect("temporary directory should exist");
+    permissions.set_readonly(false);
+    permissions
+}
+
+/// Add a backend for a scheme, replacing any backend it already had.
+pub fn register<S: Into<String>>(scheme: S, backend: Arc<dyn Vfs>) {
+    BACKENDS.write().unwrap().insert(scheme.into(), backend);
+}
+
+/// The backend for a location.
+pub fn backend(uri: &Uri) -> io::Result<Arc<dyn Vfs>> {
+    BACKENDS
+        .read()
+        .unwrap()
+        .get(uri.scheme())
+        .cloned()
+        .ok_or_else(|| unsupported(&format!("the {} scheme", uri.scheme())))
+}
+
+/// The location of a path, which may be inside an archive.
+#[must_use]
+pub fn locate<P: AsRef<Path>>(path: P) -> Uri {
+    let path = path.as_ref();
+
+    if archive::split(path).is_some() {
+        Uri::new(archived::SCHEME, "", path)
+    } else {
+        Uri::local(path)
+    }
+}
+
+/// A file on the local filesystem with the contents of an item, such as to open it with another
+/// program.
+///
+/// Local items are used as they are, while others are copied somewhere private first.
+pub fn local_copy(uri: &Uri) -> io::Result<PathBuf> {
+    if uri.is_local() {
+        return Ok(uri.path().to_path_buf());
+    }
+
+    // items with the same name in different places mustn't share a directory
+    let mut hasher = DefaultHasher::new();
+    uri.hash(&mut hasher);
+
+    let name = uri
+        .path()
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+
+    let dir = dirs::PROJECT
+        .cache_dir()
+        .join("remote")
+        .join(format!("{:016x}", hasher.finish()));
+    fs::create_dir_all(&dir)?;
+
+    let target = dir.join(name);
+    io::copy(&mut backend(uri)?.open(uri)?, &mut File::create(&target)?)?;
+
+    Ok(target)
+}
@@ -0,0 +1,726 @@
+//! Remote folders on WebDAV servers.
+//!
+//! Locations look like `dav://server/path`, or `davs://server/path` for servers only reachable over
+//! HTTPS. Logins are kept in their own file rather than the configuration, so the configuration
+//! can be shared without leaking passwords.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::DateTime;
+use percent_encoding::{percent_decode_str, utf8_percent_encode};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use ureq::{Agent, AgentBuilder, Request};
+
+use super::{remote_entry, Listing, Uri, Vfs, Watch, PATH_ESCAPE};
+use crate::dirs;
+use crate::entry::Entry;
+
+/// Scheme of locations on servers reached over plain HTTP.
+pub const SCHEME: &str = "dav";
+/// Scheme of locations on servers reached over HTTPS.
+pub const SECURE_SCHEME: &str = "davs";
+
+/// How long to wait for a server to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Properties asked for when listing.
+const PROPFIND: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+    <d:resourcetype/>
+    <d:getcontentlength/>
+    <d:getlastmodified/>
+  </d:prop>
+</d:propfind>"#;
+
+/// Username and password for a server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Login {
+    pub user: String,
+    pub password: String,
+}
+
+/// Where logins are saved, by server.
+fn logins_file() -> PathBuf {
+    dirs::PROJECT.data_local_dir().join("logins.toml")
+}
+
+/// Every saved login, by server.
+fn logins() -> HashMap<String, Login> {
+    fs::read_to_string(logins_file())
+        .ok()
+        .and_then(|s| toml::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// The saved login for a server, such as `example.com:8080`.
+#[must_use]
+pub fn login(server: &str) -> Option<Login> {
+    logins().remove(server)
+}
+
+/// Save the login for a server, replacing any login it had.
+///
+/// Only the current user can read the file logins are saved in.
+pub fn save_login(server: &str, login: Login) -> io::Result<()> {
+    let mut logins = logins();
+    logins.insert(server.to_owned(), login);
+
+    write_logins(&logins_file(), &logins)
+}
+
+/// Replace the logins saved in a file, which only the current user can read.
+fn write_logins(path: &Path, logins: &HashMap<String, Login>) -> io::Result<()> {
+    let contents = toml::to_string(logins).map_err(io::Error::other)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = File::options();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+
+    // the file may have been created with a looser mode by something else
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+
+    file.write_all(contents.as_bytes())
+}
+
+/// The server part of a location, without any user name.
+#[must_use]
+pub fn server(uri: &Uri) -> &str {
+    let authority = uri.authority();
+
+    match authority.rsplit_once('@') {
+        Some((_, server)) => server,
+        None => authority,
+    }
+}
+
+/// Backend for WebDAV servers.
+#[derive(Debug)]
+pub struct WebDav {
+    agent: Agent,
+}
+
+impl Default for WebDav {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebDav {
+    pub fn new() -> Self {
+        Self {
+            agent: AgentBuilder::new().timeout_connect(CONNECT_TIMEOUT).build(),
+        }
+    }
+
+    /// The HTTP address of a location.
+    fn url(uri: &Uri) -> String {
+        let protocol = match uri.scheme() {
+            SECURE_SCHEME => "https",
+            _ => "http",
+        };
+        let path = uri.path().to_string_lossy();
+
+        format!(
+            "{protocol}://{}{}",
+            server(uri),
+            utf8_percent_encode(&path, PATH_ESCAPE)
+        )
+    }
+
+    /// Start a request, logging in if there's a login for the server.
+    fn request(&self, method: &str, uri: &Uri) -> Request {
+        let request = self.agent.request(method, &Self::url(uri));
+
+        match login(server(uri)) {
+            Some(login) => {
+                let token = STANDARD.encode(format!("{}:{}", login.user, login.password));
+                request.set("Authorization", &format!("Basic {token}"))
+            }
+            None => request,
+        }
+    }
+
+    /// Read the properties of a location, along with everything directly inside it if `depth`
+    /// is 1.
+    fn propfind(&self, uri: &Uri, depth: u8) -> io::Result<Vec<Entry>> {
+        let response = self
+            .request("PROPFIND", uri)
+            .set("Depth", &depth.to_string())
+            .set("Content-Type", "application/xml; charset=utf-8")
+            .send_string(PROPFIND)
+            .map_err(error)?;
+
+        parse_multistatus(&response.into_string()?)
+    }
+}
+
+impl Vfs for WebDav {
+    fn list(&self, uri: &Uri) -> io::Result<Listing> {
+        let location = uri.path().clone();
+        let entries = self.propfind(uri, 1)?;
+
+        // the folder itself is listed along with its contents
+        Ok(Box::new(
+            entries
+                .into_iter()
+                .filter(move |e| e.path != location)
+                .map(Ok),
+        ))
+    }
+
+    fn stat(&self, uri: &Uri) -> io::Result<Entry> {
+        self.propfind(uri, 0)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not on the server"))
+    }
+
+    fn open(&self, uri: &Uri) -> io::Result<Box<dyn Read + Send>> {
+        let response = self.request("GET", uri).call().map_err(error)?;
+        Ok(Box::new(response.into_reader()))
+    }
+
+    fn write(&self, uri: &Uri, contents: &mut dyn Read) -> io::Result<u64> {
+        let mut counted = Counted {
+            inner: contents,
+            count: 0,
+        };
+
+        self.request("PUT", uri).send(&mut counted).map_err(error)?;
+
+        Ok(counted.count)
+    }
+
+    fn create_dir(&self, uri: &Uri) -> io::Result<()> {
+        self.request("MKCOL", uri).call().map_err(error)?;
+        Ok(())
+    }
+
+    fn rename(&self, from: &Uri, to: &Uri) -> io::Result<()> {
+        if server(from) != server(to) || from.scheme() != to.scheme() {
+            return Err(io::Error::new(
+                io::ErrorKind::CrossesDevices,
+                "can't move between servers",
+            ));
+        }
+
+        self.request("MOVE", from)
+            .set("Destination", &Self::url(to))
+            .set("Overwrite", "F")
+            .call()
+            .map_err(error)?;
+
+        Ok(())
+    }
+
+    fn delete(&self, uri: &Uri) -> io::Result<()> {
+        self.request("DELETE", uri).call().map_err(error)?;
+        Ok(())
+    }
+
+    #[inline]
+    fn watch(&self, _: &Uri) -> Watch {
+        Watch::None
+    }
+}
+
+/// A reader counting the bytes read through it.
+struct Counted<'a> {
+    inner: &'a mut dyn Read,
+    count: u64,
+}
+
+impl Read for Counted<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+/// Turn a failed request into the closest IO error.
+fn error(error: ureq::Error) -> io::Error {
+    match error {
+        ureq::Error::Status(code, response) => {
+            let kind = match code {
+                401 | 403 => io::ErrorKind::PermissionDenied,
+                404 | 410 => io::ErrorKind::NotFound,
+                405 | 501 => io::ErrorKind::Unsupported,
+                409 => io::ErrorKind::NotFound,
+                412 => io::ErrorKind::AlreadyExists,
+                507 => io::ErrorKind::StorageFull,
+                _ => io::ErrorKind::Other,
+            };
+
+            io::Error::new(kind, format!("{code} {}", response.status_text()))
+        }
+        ureq::Error::Transport(transport) => io::Error::other(transport.to_string()),
+    }
+}
+
+/// Properties of a single item, as they're being parsed.
+#[derive(Debug, Default)]
+struct Response {
+    href: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+/// Read the entries in a `207 Multi-Status` response.
+fn parse_multistatus(xml: &str) -> io::Result<Vec<Entry>> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut entries = vec![];
+    // names of the elements the reader is in, without namespaces
+    let mut stack: Vec<Vec<u8>> = vec![];
+    let mut response = Response::default();
+
+    loop {
+        match reader.read_event().map_err(io::Error::other)? {
+            Event::Start(e) => {
+                match e.local_name().as_ref() {
+                    b"response" => response = Response::default(),
+                    b"collection" => response.is_dir = true,
+                    _ => {}
+                }
+
+                stack.push(e.local_name().as_ref().to_vec());
+            }
+            Event::Empty(e) if e.local_name().as_ref() == b"collection" => {
+                response.is_dir = true;
+            }
+            Event::Text(text) => {
+                let text = text.unescape().map_err(io::Error::other)?;
+
+                match stack.last().map(Vec::as_slice) {
+                    Some(b"href") => response.href = text.into_owned(),
+                    Some(b"getcontentlength") => response.size = text.trim().parse().unwrap_or(0),
+                    Some(b"getlastmodified") => {
+                        response.modified = DateTime::parse_from_rfc2822(text.trim())
+                            .ok()
+                            .map(SystemTime::from);
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(e) => {
+                stack.pop();
+
+                if e.local_name().as_ref() == b"response" {
+                    let response = std::mem::take(&mut response);
+
+                    if let Some(path) = href_path(&response.href) {
+                        entries.push(remote_entry(
+                            path,
+                            response.is_dir,
+                            response.size,
+                            response.modified,
+                        ));
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(entries)
+}
+
+/// The path of an item from its `href`, which is either a path or a full address.
+fn href_path(href: &str) -> Option<PathBuf> {
+    let path = match href.split_once("://") {
+        Some((_, rest)) => &rest[rest.find('/')?..],
+        None => href,
+    };
+
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    let path = path.trim_end_matches('/');
+
+    Some(PathBuf::from(if path.is_empty() { "/" } else { path }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::io::{BufRead, BufReader};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use super::*;
+    use crate::entry::Kind;
+
+    #[test]
+    fn hrefs_are_paths() {
+        assert_eq!(
+            href_path("/docs/a%20b.txt"),
+            Some(PathBuf::from("/docs/a b.txt"))
+        );
+        assert_eq!(
+            href_path("https://example.com:8080/docs/"),
+            Some(PathBuf::from("/docs"))
+        );
+        assert_eq!(href_path("/"), Some(PathBuf::from("/")));
+        assert_eq!(href_path("http://example.com"), None);
+        assert_eq!(href_path("/caf%FF"), None);
+    }
+
+    #[test]
+    fn multistatus_is_parsed() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:multistatus xmlns:D="DAV:">
+  <D:response>
+    <D:href>http://example.com/docs/</D:href>
+    <D:propstat>
+      <D:prop>
+        <D:resourcetype><D:collection></D:collection></D:resourcetype>
+      </D:prop>
+      <D:status>HTTP/1.1 200 OK</D:status>
+    </D:propstat>
+  </D:response>
+  <D:response>
+    <D:href>/docs/a%20b.txt</D:href>
+    <D:propstat>
+      <D:prop>
+        <D:resourcetype/>
+        <D:getcontentlength> 1234 </D:getcontentlength>
+        <D:getlastmodified>Tue, 02 Jan 2024 03:04:05 GMT</D:getlastmodified>
+      </D:prop>
+      <D:status>HTTP/1.1 200 OK</D:status>
+    </D:propstat>
+  </D:response>
+  <D:response>
+    <D:href>/docs/sub/</D:href>
+    <D:propstat>
+      <D:prop><D:resourcetype><D:collection/></D:resourcetype></D:prop>
+    </D:propstat>
+  </D:response>
+</D:multistatus>"#;
+
+        let entries = parse_multistatus(xml).unwrap();
+
+        assert_eq!(entries.len(), 3);
+
+        assert_eq!(*entries[0].path, *Path::new("/docs"));
+        assert_eq!(entries[0].kind, Kind::Directory);
+
+        assert_eq!(*entries[1].path, *Path::new("/docs/a b.txt"));
+        assert_eq!(entries[1].kind, Kind::File);
+        assert_eq!(entries[1].size, 1234);
+        assert_eq!(
+            entries[1].modified,
+            Some(SystemTime::from(
+                DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z").unwrap()
+            ))
+        );
+
+        assert_eq!(*entries[2].path, *Path::new("/docs/sub"));
+        assert_eq!(entries[2].kind, Kind::Directory);
+        assert!(entries[2].modified.is_none());
+    }
+
+    #[test]
+    fn broken_multistatus_is_an_error() {
+        assert!(parse_multistatus("<D:multistatus><D:response></D:multistatus>").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn logins_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("doseer").join("logins.toml");
+        let logins = HashMap::from([(
+            "example.com:8080".to_owned(),
+            Login {
+                user: "me".to_owned(),
+                password: "secret".to_owned(),
+            },
+        )]);
+
+        write_logins(&path, &logins).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let saved: HashMap<String, Login> =
+            toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved, logins);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn loose_logins_are_made_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logins.toml");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_logins(&path, &HashMap::new()).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn files_round_trip() {
+        let dav = WebDav::new();
+        let root = Uri::new(SCHEME, serve(), "/");
+
+        let folder = root.join("docs");
+        dav.create_dir(&folder).unwrap();
+        assert!(dav.stat(&folder).unwrap().is_dir());
+
+        let file = folder.join("a b.txt");
+        assert_eq!(dav.write(&file, &mut &b"hello"[..]).unwrap(), 5);
+
+        let listed: Vec<Entry> = dav.list(&folder).unwrap().map(Result::unwrap).collect();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(*listed[0].path, *Path::new("/docs/a b.txt"));
+        assert_eq!(listed[0].kind, Kind::File);
+        assert_eq!(listed[0].size, 5);
+
+        assert_eq!(dav.read(&file).unwrap(), b"hello");
+
+        dav.delete(&file).unwrap();
+        assert_eq!(dav.stat(&file).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(dav.list(&folder).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn writing_outside_a_folder_fails() {
+        let dav = WebDav::new();
+        let file = Uri::new(SCHEME, serve(), "/missing/file.txt");
+
+        assert_eq!(
+            dav.write(&file, &mut &b"hello"[..]).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    /// Start a WebDAV server keeping files in memory, returning its address.
+    fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        thread::spawn(move || {
+            let mut server = Server {
+                files: HashMap::new(),
+                dirs: HashSet::from(["/".to_owned()]),
+            };
+
+            for stream in listener.incoming().flatten() {
+                server.answer(stream);
+            }
+        });
+
+        address
+    }
+
+    /// Just enough of a WebDAV server for the backend.
+    struct Server {
+        files: HashMap<String, Vec<u8>>,
+        dirs: HashSet<String>,
+    }
+
+    impl Server {
+        /// Answer a single request, closing the connection after.
+        fn answer(&mut self, stream: TcpStream) {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let mut parts = line.split_whitespace();
+            let method = parts.next().unwrap().to_owned();
+            let path = percent_decode_str(parts.next().unwrap())
+                .decode_utf8()
+                .unwrap()
+                .trim_end_matches('/')
+                .to_owned();
+            let path = if path.is_empty() {
+                "/".to_owned()
+            } else {
+                path
+            };
+
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+
+                match line.trim_end().split_once(':') {
+                    Some((name, value)) => {
+                        headers.insert(name.to_ascii_lowercase(), value.trim().to_owned());
+                    }
+                    None => break,
+                }
+            }
+
+            let body = read_body(&mut reader, &headers);
+            let (status, response) = self.handle(&method, &path, &headers, body);
+
+            let mut stream = stream;
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                response.len()
+            )
+            .unwrap();
+            stream.write_all(&response).unwrap();
+        }
+
+        fn handle(
+            &mut self,
+            method: &str,
+            path: &str,
+            headers: &HashMap<String, String>,
+            body: Vec<u8>,
+        ) -> (&'static str, Vec<u8>) {
+            let parent = parent_of(path);
+
+            match method {
+                "PROPFIND" => {
+                    let mut found = vec![];
+
+                    if let Some(data) = self.files.get(path) {
+                        found.push(response(path, false, data.len()));
+                    } else if self.dirs.contains(path) {
+                        found.push(response(path, true, 0));
+
+                        if headers.get("depth").map(String::as_str) == Some("1") {
+                            found.extend(
+                                self.files
+                                    .iter()
+                                    .filter(|(p, _)| parent_of(p) == path)
+                                    .map(|(p, data)| response(p, false, data.len())),
+                            );
+                            found.extend(
+                                self.dirs
+                                    .iter()
+                                    .filter(|p| *p != "/" && parent_of(p) == path)
+                                    .map(|p| response(p, true, 0)),
+                            );
+                        }
+                    } else {
+                        return ("404 Not Found", vec![]);
+                    }
+
+                    let xml = format!(
+                        r#"<?xml version="1.0" encoding="utf-8"?><d:multistatus xmlns:d="DAV:">{}</d:multistatus>"#,
+                        found.concat()
+                    );
+
+                    ("207 Multi-Status", xml.into_bytes())
+                }
+                "GET" => match self.files.get(path) {
+                    Some(data) => ("200 OK", data.clone()),
+                    None => ("404 Not Found", vec![]),
+                },
+                "PUT" if !self.dirs.contains(parent) => ("409 Conflict", vec![]),
+                "PUT" => {
+                    self.files.insert(path.to_owned(), body);
+                    ("201 Created", vec![])
+                }
+                "MKCOL" if self.dirs.contains(path) || self.files.contains_key(path) => {
+                    ("405 Method Not Allowed", vec![])
+                }
+                "MKCOL" if !self.dirs.contains(parent) => ("409 Conflict", vec![]),
+                "MKCOL" => {
+                    self.dirs.insert(path.to_owned());
+                    ("201 Created", vec![])
+                }
+                "DELETE" => {
+                    let inside = format!("{path}/");
+                    let before = self.files.len() + self.dirs.len();
+
+                    self.files
+                        .retain(|p, _| p != path && !p.starts_with(&inside));
+                    self.dirs.retain(|p| p != path && !p.starts_with(&inside));
+
+                    if self.files.len() + self.dirs.len() == before {
+                        ("404 Not Found", vec![])
+                    } else {
+                        ("204 No Content", vec![])
+                    }
+                }
+                _ => ("501 Not Implemented", vec![]),
+            }
+        }
+    }
+
+    /// The path of the folder an item is in.
+    fn parent_of(path: &str) -> &str {
+        match path.rsplit_once('/') {
+            Some(("", _)) | None => "/",
+            Some((parent, _)) => parent,
+        }
+    }
+
+    /// The properties of an item, as they're listed in a multistatus.
+    fn response(path: &str, is_dir: bool, size: usize) -> String {
+        let href = utf8_percent_encode(path, PATH_ESCAPE);
+        let kind = if is_dir { "<d:collection/>" } else { "" };
+
+        format!(
+            "<d:response><d:href>{href}</d:href><d:propstat><d:prop>\
+             <d:resourcetype>{kind}</d:resourcetype>\
+             <d:getcontentlength>{size}</d:getcontentlength>\
+             <d:getlastmodified>Tue, 02 Jan 2024 03:04:05 GMT</d:getlastmodified>\
+             </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+        )
+    }
+
+    /// Read the body of a request, sent either whole or in chunks.
+    fn read_body(reader: &mut impl BufRead, headers: &HashMap<String, String>) -> Vec<u8> {
+        let mut body = vec![];
+
+        if let Some(length) = headers.get("content-length") {
+            body.resize(length.parse().unwrap(), 0);
+            reader.read_exact(&mut body).unwrap();
+        } else if headers.get("transfer-encoding").map(String::as_str) == Some("chunked") {
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let size = usize::from_str_radix(line.trim(), 16).unwrap();
+
+                let mut chunk = vec![0; size + 2];
+                reader.read_exact(&mut chunk).unwrap();
+
+                if size == 0 {
+                    break;
+                }
+
+                body.extend_from_slice(&chunk[..size]);
+            }
+        }
+
+        body
+    }
+}
@@ -22,6 +22,7 @@ pub enum PanelPosition {
 }
 
 /// Internal state of a panelled widget.
+#[derive(Debug)]
 pub struct State {
     internal: pane_grid::State<ContentType>,
     panel_id: Pane,
@@ -54,6 +55,18 @@ impl State {
         }
     }
 
+    /// Initialize a new panel state, with the panel on a given side.
+    pub fn with_position(position: PanelPosition) -> Self {
+        let mut state = Self::new();
+
+        if position == PanelPosition::Right {
+            state.swap();
+        }
+
+        state.position = position;
+        state
+    }
+
     /// Swap the panel position.
     pub fn swap(&mut self) {
         self.internal.swap(&self.panel_id, &self.content_id)
@@ -70,6 +83,11 @@ impl State {
             },
         );
     }
+
+    /// Apply a resize event from the inner pane grid, whose ratio is that of the left pane.
+    pub fn apply(&mut self, event: pane_grid::ResizeEvent) {
+        self.internal.resize(&event.split, event.ratio);
+    }
 }
 
 // --- Unpanelled Wrapper ---
@@ -3,6 +3,7 @@
 use std::path::Path;
 use std::sync::Arc;
 
+use doseer_core::entry::Entry;
 use iced_core::{image, svg};
 
 mod platform;
@@ -35,4 +36,11 @@ impl Loader {
     pub fn load(&self, path: &Path) -> Option<Icon> {
         self.0.load(path)
     }
+
+    /// Try to load an appropriate icon for a directory entry, using its cached metadata.
+    #[inline]
+    #[must_use]
+    pub fn load_entry(&self, entry: &Entry) -> Option<Icon> {
+        self.0.load_entry(entry)
+    }
 }
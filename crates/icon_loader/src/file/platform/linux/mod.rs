@@ -8,8 +8,11 @@ use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::Receiver;
 use std::sync::{mpsc, Arc};
+use std::time::SystemTime;
 
 use doseer_core::dirs;
+use doseer_core::entry::Entry;
+use doseer_core::mime;
 use doseer_core::path::PathWrap;
 use iced_core::{image, svg};
 use ini::Ini;
@@ -35,6 +38,8 @@ macro_rules! pop_out {
 pub struct Loader {
     /// Icon cache for mimetype.
     mime_cache: Cache<String, Icon>,
+    /// Detected types of files, as of when they were last modified.
+    type_cache: Cache<(PathBuf, Option<SystemTime>), Arc<str>>,
     /// Icon cache for specific places such as home directory.
     place_cache: Cache<&'static str, Icon>,
     /// User themes in order of priority.
@@ -47,6 +52,7 @@ impl Loader {
         Self {
             themes: load_user_themes(),
             mime_cache: Cache::builder().max_capacity(100).build(),
+            type_cache: Cache::builder().max_capacity(4096).build(),
             place_cache: Cache::builder().max_capacity(36).build(),
         }
     }
@@ -55,21 +61,35 @@ impl Loader {
         if path.is_dir() {
             self.load_place(path)
         } else {
-            self.load_mime(path)
+            self.load_mime(&mime::detect(path))
         }
     }
 
-    fn load_mime(&self, path: &Path) -> Option<Icon> {
-        let mime = mime_guess::from_path(path).first_or_text_plain();
-        let essence = mime.essence_str().replace('/', "-");
-
-        if let Some(icon) = self.mime_cache.get(&essence) {
-            return Some(icon);
+    pub fn load_entry(&self, entry: &Entry) -> Option<Icon> {
+        if entry.is_dir() {
+            return self.load_place(&entry.path);
         }
 
-        if let Some(icon) = self._raw_load(|theme| theme.load_mime(&essence)) {
-            self.mime_cache.insert(essence, icon.clone());
-            return Some(icon);
+        // icons are loaded every time an item is drawn, so files are only read once
+        let key = (entry.path.to_path_buf(), entry.modified);
+        let mime = self
+            .type_cache
+            .get_with(key, || mime::DATABASE.detect(entry).into());
+
+        self.load_mime(&mime)
+    }
+
+    /// Load the most specific icon the themes have for a type.
+    fn load_mime(&self, mime: &str) -> Option<Icon> {
+        for essence in mime::DATABASE.icon_names(mime) {
+            if let Some(icon) = self.mime_cache.get(&essence) {
+                return Some(icon);
+            }
+
+            if let Some(icon) = self._raw_load(|theme| theme.load_mime(&essence)) {
+                self.mime_cache.insert(essence, icon.clone());
+                return Some(icon);
+            }
         }
 
         None
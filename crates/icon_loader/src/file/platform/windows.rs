@@ -15,6 +15,8 @@ use windows::Win32::UI::Shell::{
 };
 use windows::Win32::UI::WindowsAndMessaging::{GetIconInfo, HICON, ICONINFO};
 
+use doseer_core::entry::Entry;
+
 use crate::file::{Icon, ImageOrSvg};
 
 /// Icon loader implementation for windows shell.
@@ -46,6 +48,11 @@ impl Loader {
         None
     }
 
+    #[inline]
+    pub fn load_entry(&self, entry: &Entry) -> Option<Icon> {
+        self.load(&entry.path)
+    }
+
     fn load_winicon(&self, path: &Path) -> Option<Icon> {
         let path_c = U16CString::from_os_str(path).ok()?;
 
@@ -0,0 +1,90 @@
+//! Work asked for while drawing, done in the background.
+//!
+//! Drawing can't wait on the disk, so a [`Queue`] hands out what's ready and queues the rest.
+//! Its [`subscription`](Queue::subscription) does the queued work and reports every time a result
+//! is ready, so it gets drawn.
+
+use std::any::TypeId;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use iced::futures::{stream, SinkExt, StreamExt};
+use iced::{subscription, Subscription};
+use moka::sync::Cache;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Results of work done in the background, and the queue of work still to do.
+pub struct Queue<K, V> {
+    /// Nothing while a key is waiting to be done, and for keys without a result.
+    cache: Cache<K, Option<V>>,
+    queue: UnboundedSender<K>,
+    /// The other end of the queue, until the subscription takes it.
+    jobs: Mutex<Option<UnboundedReceiver<K>>>,
+}
+
+impl<K, V> Queue<K, V>
+where
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// An empty queue, remembering at most `capacity` results.
+    pub fn new(capacity: u64) -> Self {
+        let (queue, jobs) = mpsc::unbounded_channel();
+
+        Self {
+            cache: Cache::builder().max_capacity(capacity).build(),
+            queue,
+            jobs: Mutex::new(Some(jobs)),
+        }
+    }
+
+    /// The result for a key, if it's ready.
+    ///
+    /// The first time a key is asked for, it's queued to be done in the background.
+    pub fn get(&self, key: K) -> Option<V> {
+        if let Some(result) = self.cache.get(&key) {
+            return result;
+        }
+
+        self.cache.insert(key.clone(), None);
+        let _ = self.queue.send(key);
+
+        None
+    }
+
+    /// Do queued work, `workers` keys at once, reporting every time a result is ready.
+    ///
+    /// `id` tells the subscriptions of different queues apart.
+    pub fn subscription(
+        &'static self,
+        id: TypeId,
+        workers: usize,
+        work: fn(&K) -> Option<V>,
+    ) -> Subscription<()> {
+        subscription::channel(id, 16, move |mut output| async move {
+            let jobs = self.jobs.lock().unwrap().take();
+
+            if let Some(jobs) = jobs {
+                // the stream holds a future, so it has to be pinned to be polled
+                let jobs = Box::pin(stream::unfold(jobs, |mut jobs| async move {
+                    jobs.recv().await.map(|key| (key, jobs))
+                }));
+
+                let mut done = jobs
+                    .map(|key| tokio::task::spawn_blocking(move || (work(&key), key)))
+                    .buffer_unordered(workers);
+
+                while let Some(result) = done.next().await {
+                    let Ok((Some(value), key)) = result else {
+                        continue;
+                    };
+
+                    self.cache.insert(key, Some(value));
+                    let _ = output.send(()).await;
+                }
+            }
+
+            std::future::pending().await
+        })
+    }
+}
@@ -4,14 +4,20 @@ use std::fs::File;
 use std::io::{Read, Write};
 
 use doseer_core::dirs;
-use doseer_core::path::PathWrap;
+use doseer_core::vfs::Uri;
 use doseer_core::{resource, resource_make};
 
 /// Global application configuration.
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct Config {
-    /// Bookmark folder list.
-    pub bookmarks: Vec<PathWrap>,
+    /// Bookmark folder list, which may include folders on servers.
+    pub bookmarks: Vec<Uri>,
+    /// Globs for items that are never shown, such as `node_modules`.
+    #[serde(default)]
+    pub excludes: Vec<String>,
+    /// Leave out other filesystems, such as mount points, when measuring folders.
+    #[serde(default)]
+    pub one_filesystem: bool,
 }
 
 impl Config {
@@ -25,10 +31,14 @@ impl Config {
             dirs::USER.picture_dir(),
         ]
         .into_iter()
-        .filter_map(|p| p.map(PathWrap::from_path))
+        .filter_map(|p| p.map(Uri::local))
         .collect();
 
-        Self { bookmarks }
+        Self {
+            bookmarks,
+            excludes: vec![],
+            one_filesystem: false,
+        }
     }
 
     /// Try to load the configuration file.
@@ -1,17 +1,21 @@
 //! Main content area.
 
 use doseer_colorschemes::WithColorScheme;
-use doseer_core::path::PathWrap;
+use doseer_core::dirs::Change;
+use doseer_core::filter::Filter;
+use doseer_core::journal::Step;
+use doseer_core::size;
+use doseer_core::vfs::Uri;
 use doseer_iced_ext::widgets::only_one;
 use doseer_iced_ext::widgets::reorderable;
 
 use iced::widget::button::Appearance as ButtonAppearance;
 use iced::widget::{button, column, component, container, row, text};
-use iced::{alignment, Alignment, Command, Length};
+use iced::{alignment, Alignment, Command, Length, Subscription};
 use indexmap::IndexMap;
 
 use crate::gui::Element;
-use crate::tab::tab;
+use crate::tab::{tab, watcher};
 use crate::{tab, theme, Icon, Tab, Theme};
 
 /// Main content. Essentially just manages tabs.
@@ -21,14 +25,29 @@ pub struct Content {
     tabs: IndexMap<usize, tab::State>,
     /// Currently open tab.
     focused: usize,
+    /// Filter new tabs start with.
+    filter: Filter,
+    /// Folder sizes measured so far, shared by every tab.
+    sizes: size::Cache,
+    /// How folder sizes are measured.
+    size_options: size::Options,
 }
 
 impl Content {
-    pub fn new() -> Self {
+    pub fn new(filter: Filter, size_options: size::Options) -> Self {
         let mut tabs = IndexMap::new();
-        tabs.insert(0, tab::State::new().expect("failed to create tab"));
+        tabs.insert(
+            0,
+            tab::State::new(filter.clone()).expect("failed to create tab"),
+        );
 
-        Self { tabs, focused: 0 }
+        Self {
+            tabs,
+            focused: 0,
+            filter,
+            sizes: size::Cache::new(),
+            size_options,
+        }
     }
 
     /// Add a new tab to this pane.
@@ -40,7 +59,7 @@ impl Content {
     }
 
     /// Remove a tab from this pane.
-    pub fn remove_tab(&mut self, tab: usize) -> Option<Tab> {
+    pub fn remove_tab(&mut self, tab: usize) -> Option<Tab<'_>> {
         // Only remove tab if its not the only one remaining.
         if self.tabs.len() > 1 {
             self.tabs.remove(&tab)?;
@@ -69,6 +88,26 @@ impl Content {
         None
     }
 
+    /// Watch and read every location open in any tab.
+    pub fn subscription(&self) -> Subscription<Message> {
+        let watcher = watcher::subscription(self.tabs.values().flat_map(tab::State::watched))
+            .map(Message::Watcher);
+
+        let loads = self.tabs.values().map(|t| {
+            t.subscription(&self.sizes, self.size_options)
+                .map(Message::Loading)
+        });
+
+        Subscription::batch(std::iter::once(watcher).chain(loads))
+    }
+
+    /// Read every tab again where the watcher can't tell what changed.
+    pub fn refresh(&mut self) {
+        for tab in self.tabs.values_mut() {
+            tab.refresh();
+        }
+    }
+
     /// Get currently focused tab.
     #[inline]
     pub fn focused(&self) -> &tab::State {
@@ -85,18 +124,17 @@ pub enum Message {
     /// Remove the indicated tab.
     Remove(usize),
     /// Add a new tab, and maybe focus.
-    New(Option<PathWrap>, bool),
+    New(Option<Uri>, bool),
     /// Replace the focused tab with a new tab.
-    Replace(PathWrap),
+    Replace(Uri),
     /// Reorder two tab positions.
     Reorder(usize, usize),
-}
-
-impl Message {
-    /// A tab message for the currently focused tab.
-    pub fn tab(m: tab::Message) -> Self {
-        Self::Tab(m, None)
-    }
+    /// Message from the watcher of open locations.
+    Watcher(watcher::Message),
+    /// Progress of reading a location in the background.
+    Loading(tab::content::Loading),
+    /// Undo or redo the latest file operation. Handled by the application.
+    Journal(Step),
 }
 
 impl Content {
@@ -104,15 +142,29 @@ impl Content {
         let mut commands = vec![];
 
         match message {
+            Message::Tab(tab::Message::Grep(query), _) => {
+                let id = self.add_tab(tab::State::grep(query));
+                self.focus(id);
+            }
+            Message::Tab(tab::Message::Usage(path), _) => {
+                let id = self.add_tab(tab::State::usage(&path, self.filter.clone()));
+                self.focus(id);
+            }
+            Message::Tab(tab::Message::Duplicates(query), _) => {
+                let id = self.add_tab(tab::State::duplicates(query));
+                self.focus(id);
+            }
             Message::Tab(m, id) => {
-                if let tab::Message::Open(p) = m {
-                    let watcher = self
-                        .tabs
-                        .get_mut(&id.unwrap_or(self.focused))
-                        .unwrap()
-                        .open(&p)?;
-
-                    commands.push(watcher.map(move |m| Message::Tab(m, id)));
+                let id = id.unwrap_or(self.focused);
+                let tab = self.tabs.get_mut(&id).unwrap();
+
+                // what goes wrong in a tab is shown there, rather than taking everything down
+                match Self::update_tab(tab, m) {
+                    Ok(command) => commands.push(command.map(move |m| Message::Tab(m, Some(id)))),
+                    Err(e) => {
+                        tracing::warn!(location = %tab.uri(), "{:?}", e);
+                        tab.show_error(&e);
+                    }
                 }
             }
             Message::Focus(id) => {
@@ -123,8 +175,8 @@ impl Content {
             }
             Message::New(path, focus) => {
                 let tab = match path {
-                    Some(t) => tab::State::new_with(t)?,
-                    None => tab::State::new()?,
+                    Some(t) => tab::State::new_with(t, self.filter.clone())?,
+                    None => tab::State::new(self.filter.clone())?,
                 };
 
                 let id = self.add_tab(tab);
@@ -133,19 +185,69 @@ impl Content {
                     self.focus(id);
                 }
             }
-            Message::Replace(tab) => self.replace_focused(tab::State::new_with(tab)?),
+            Message::Replace(tab) => {
+                self.replace_focused(tab::State::new_with(tab, self.filter.clone())?)
+            }
             Message::Reorder(a, b) => {
                 self.tabs.swap_indices(a, b);
             }
+            Message::Watcher(watcher::Message::Changed(changes)) => {
+                for path in changes.iter().flat_map(Change::paths) {
+                    self.sizes.invalidate(path);
+                }
+
+                for tab in self.tabs.values_mut() {
+                    tab.apply(&changes);
+                }
+            }
+            Message::Watcher(watcher::Message::Failed(location, e)) => {
+                tracing::warn!(?location, "failed to watch: {}", e);
+            }
+            Message::Loading(tab::content::Loading::Sizes(_, measured)) => {
+                for m in measured {
+                    self.sizes.insert(m);
+                }
+            }
+            Message::Loading(mut loading) => {
+                for tab in self.tabs.values_mut() {
+                    match tab.receive(loading)? {
+                        Some(l) => loading = l,
+                        None => break,
+                    }
+                }
+            }
+            Message::Journal(_) => {}
         }
 
         Ok(Command::batch(commands))
     }
 
+    /// Do what's asked of a single tab.
+    fn update_tab(
+        tab: &mut tab::State,
+        message: tab::Message,
+    ) -> anyhow::Result<Command<tab::Message>> {
+        match message {
+            tab::Message::Open(p) => return Ok(tab.open(p, None)),
+            tab::Message::OpenWith(p, app) => return Ok(tab.open(p, Some(app))),
+            tab::Message::Opened(opened) => tab.opened(opened.map_err(anyhow::Error::msg)?)?,
+            tab::Message::Sort(sort) => tab.set_sort(sort),
+            tab::Message::Filter(filter) => tab.set_filter(filter),
+            tab::Message::TogglePreview => tab.toggle_preview(),
+            tab::Message::ResizePreview(event) => tab.resize_preview(event),
+            tab::Message::StopLoading => tab.stop_loading()?,
+            tab::Message::Search(text, mode) => tab.search(text, mode)?,
+            tab::Message::CloseSearch => tab.close_search()?,
+            _ => {}
+        }
+
+        Ok(Command::none())
+    }
+
     pub const TOP_BAR_HEIGHT: Length = Length::Fixed(38.0);
 
-    /// Tab switcher and controls.
-    fn top_panel(&self) -> Element<Message> {
+    /// Tab switcher and controls, with undo and redo enabled if there's anything to step through.
+    fn top_panel(&self, can: impl Fn(Step) -> bool) -> Element<'_, Message> {
         // re-orderable components (the tab list)
 
         let mut tab_list = reorderable::Row::new()
@@ -160,7 +262,7 @@ impl Content {
                     .svg()
                     .width(Length::Fixed(22.0))
                     .height(Length::Fixed(22.0)),
-                text(tab.location().display().to_string_lossy())
+                text(tab.title())
                     .size(18)
                     .font(theme::fonts::SofiaSans::Regular),
             )
@@ -236,18 +338,37 @@ impl Content {
             .on_press(Message::New(None, true)),
         );
 
+        // history controls, pushed to the far end
+        let history = |label, step| {
+            let button = button(text(label).size(16).font(theme::fonts::Roboto::Regular))
+                .padding([4, 8])
+                .style(TabButtonStyle::Default.into());
+
+            if can(step) {
+                button.on_press(Message::Journal(step))
+            } else {
+                button
+            }
+        };
+
+        elements = elements.push(
+            container(row!(history("Undo", Step::Undo), history("Redo", Step::Redo)).spacing(4))
+                .width(Length::Fill)
+                .align_x(alignment::Horizontal::Right),
+        );
+
         elements.into()
     }
 
-    pub fn view(&self) -> Element<Message> {
+    pub fn view(&self, can: impl Fn(Step) -> bool) -> Element<'_, Message> {
         // Tab switcher
-        let panel = self.top_panel();
+        let panel = self.top_panel(can);
 
         // Focused tab view
         let contents = only_one(
             self.tabs
                 .values()
-                .map(|t| component(tab(t)).map(move |m| Message::Tab(m, None))),
+                .map(|t| component(tab(t, &self.sizes)).map(move |m| Message::Tab(m, None))),
         )
         // We need to get the _index_ of the focused tab
         .focus(self.tabs.keys().position(|k| *k == self.focused).unwrap());
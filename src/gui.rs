@@ -1,14 +1,27 @@
 //! The GUI app.
 
+use std::any::TypeId;
+use std::time::Duration;
+
 use doseer_colorschemes::default::Wrap;
+use doseer_core::apps::Registry;
+use doseer_core::filter::Filter;
+use doseer_core::journal::Step;
+use doseer_core::mounts::{self, Monitor, Mount, Usage};
+use doseer_core::size;
+use doseer_core::vfs::webdav::{self, Login};
+use doseer_core::vfs::Uri;
 use doseer_iced_ext::components::panelled::{self, unpanelled};
 
-use iced::widget::component;
-use iced::{executor, Application, Command, Length};
+use iced::futures::SinkExt;
+use iced::widget::{column, component, container};
+use iced::{executor, keyboard, subscription, Application, Command, Length, Subscription};
 
 use crate::content::{self, Content};
+use crate::operations::{self, Operations};
+use crate::settings::{self, Settings};
 use crate::side_bar::side_bar;
-use crate::{config, item, tab, theme, Config, Theme};
+use crate::{config, tab, theme, thumbnails, Config, Theme};
 
 /// Shorthand for an iced element generic over some message.
 pub type Renderer = iced::Renderer<Wrap<Theme>>;
@@ -18,9 +31,21 @@ pub type Element<'a, T> = iced::Element<'a, T, Renderer>;
 pub enum Message {
     Content(content::Message),
     Config(config::Message),
+    Operation(operations::Message),
     ResizeMain(panelled::pane_grid::ResizeEvent),
     IcedEvent(iced::Event),
     FontLoad(Result<(), iced::font::Error>),
+    /// Mounted devices changed, or their free space was checked again.
+    Mounts(Vec<(Mount, Option<Usage>)>),
+    /// Open a folder on a server, saving its login and bookmarking it.
+    Connect(Uri, Option<Login>),
+    /// Show the settings page in place of the content.
+    OpenSettings,
+    Settings(settings::Message),
+    /// A thumbnail is ready to be drawn.
+    Thumbnail,
+    /// A preview of the selected item is ready to be drawn.
+    Preview,
 }
 
 /// The UI state.
@@ -29,8 +54,14 @@ pub struct Gui {
     split_state: panelled::State,
     /// Main content.
     content: Content,
+    /// Running file operations.
+    operations: Operations,
     /// Configuration.
     config: Config,
+    /// Mounted devices shown in the sidebar, with their free space.
+    mounts: Vec<(Mount, Option<Usage>)>,
+    /// The settings page, while it's open.
+    settings: Option<Settings>,
 }
 
 impl Application for Gui {
@@ -42,20 +73,28 @@ impl Application for Gui {
     fn new(config: Self::Flags) -> (Self, Command<Self::Message>) {
         let mut commands = vec![];
 
+        // items hidden from every tab
+        let filter = Filter::with_excludes(&config.excludes).unwrap_or_else(|e| {
+            tracing::warn!("invalid exclude pattern: {}", e);
+            Filter::default()
+        });
+
+        // reading every installed app takes a while, so it's done before anything needs them
+        std::thread::spawn(|| {
+            let _ = Registry::shared();
+        });
+
         // initialize the content area
-        let content = Content::new();
+        let content = Content::new(
+            filter,
+            size::Options {
+                one_filesystem: config.one_filesystem,
+            },
+        );
 
         let mut split_state = panelled::State::new();
         split_state.resize(0.2);
 
-        // tab update watcher
-        let tab_init_cmd = tab::watcher::command(content.focused().location());
-        commands.push(
-            tab_init_cmd
-                .map(content::Message::tab)
-                .map(Message::Content),
-        );
-
         // load all the fonts we need
         commands.push(theme::fonts::load_all().map(Message::FontLoad));
 
@@ -64,52 +103,196 @@ impl Application for Gui {
                 split_state,
                 config,
                 content,
+                operations: Operations::new(),
+                // filled in by the subscription
+                mounts: vec![],
+                settings: None,
             },
             Command::batch(commands),
         )
     }
 
     fn title(&self) -> String {
-        format!("{}", item::item_name(self.content.focused().location()))
+        self.content.focused().title().into_owned()
     }
 
-    fn subscription(&self) -> iced::Subscription<Self::Message> {
-        iced::subscription::events().map(Message::IcedEvent)
+    fn subscription(&self) -> Subscription<Self::Message> {
+        Subscription::batch([
+            iced::subscription::events().map(Message::IcedEvent),
+            self.content.subscription().map(Message::Content),
+            self.operations.subscription().map(Message::Operation),
+            watch_mounts(),
+            thumbnails::subscription().map(|()| Message::Thumbnail),
+            tab::preview::subscription().map(|()| Message::Preview),
+        ])
     }
 
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         let mut commands = vec![];
 
         match message {
+            Message::Content(content::Message::Tab(tab::Message::Operation(m), _)) => {
+                return self.update(Message::Operation(m));
+            }
+            Message::Content(content::Message::Journal(step)) => {
+                return self.update(Message::Operation(operations::Message::Step(step)));
+            }
             Message::Content(m) => {
-                let pane_cmd = self.content.update(m).unwrap();
-                commands.push(pane_cmd.map(Message::Content));
+                // going somewhere from the side bar leaves the settings
+                if matches!(m, content::Message::Replace(_)) {
+                    self.settings = None;
+                }
+
+                match self.content.update(m) {
+                    Ok(pane_cmd) => commands.push(pane_cmd.map(Message::Content)),
+                    Err(e) => tracing::warn!("{:?}", e),
+                }
             }
             Message::ResizeMain(m) => self.split_state.resize(m.ratio),
             Message::Config(m) => self.config.process_message(m),
+            Message::Mounts(mounts) => self.mounts = mounts,
+            Message::OpenSettings => self.settings = Some(Settings::new()),
+            // drawing again is all it takes
+            Message::Thumbnail | Message::Preview => {}
+            Message::Settings(m) => {
+                if let Some(settings) = &mut self.settings {
+                    if !settings.update(m) {
+                        self.settings = None;
+                    }
+                }
+            }
+            Message::Connect(location, login) => {
+                if let Some(login) = login {
+                    if let Err(e) = webdav::save_login(webdav::server(&location), login) {
+                        tracing::warn!("failed to save login: {}", e);
+                    }
+                }
+
+                if !self.config.bookmarks.contains(&location) {
+                    self.config.bookmarks.push(location.clone());
+
+                    if let Err(e) = self.config.flush() {
+                        tracing::warn!("failed to save config: {}", e);
+                    }
+                }
+
+                return self.update(Message::Content(content::Message::Replace(location)));
+            }
+            Message::Operation(m) => {
+                // a finished operation may have changed what's shown
+                let finished = self.operations.update(m).is_some();
+
+                if finished {
+                    self.content.refresh();
+                }
+            }
+            Message::IcedEvent(iced::Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                modifiers,
+            })) if modifiers.command() => {
+                let step = match key_code {
+                    keyboard::KeyCode::Z if modifiers.shift() => Step::Redo,
+                    keyboard::KeyCode::Z => Step::Undo,
+                    keyboard::KeyCode::Y => Step::Redo,
+                    _ => return Command::none(),
+                };
+
+                return self.update(Message::Operation(operations::Message::Step(step)));
+            }
             _ => {}
         }
 
         Command::batch(commands)
     }
 
-    fn view(&self) -> Element<Self::Message> {
-        unpanelled(|| self.content.view().map(Message::Content))
-            // add side panel
-            .panel(&self.split_state, |_| {
-                component(side_bar(&self.config, |path| {
-                    self.content.focused().location().as_ref() == path
-                }))
-            })
-            // configure inner pane_grid
-            .into_inner()
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .on_resize(16, Message::ResizeMain)
+    fn view(&self) -> Element<'_, Self::Message> {
+        unpanelled(|| {
+            let main = match &self.settings {
+                Some(settings) => settings.view().map(Message::Settings),
+                None => self
+                    .content
+                    .view(|step| self.operations.can(step))
+                    .map(Message::Content),
+            };
+
+            container(column!(
+                main,
+                self.operations.view().map(Message::Operation)
+            ))
+            .style(theme::container::Container::Strong)
             .into()
+        })
+        // add side panel
+        .panel(&self.split_state, |_| {
+            component(side_bar(&self.config, &self.mounts, |location| {
+                self.content.focused().uri() == location
+            }))
+        })
+        // configure inner pane_grid
+        .into_inner()
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .on_resize(16, Message::ResizeMain)
+        .into()
     }
 }
 
+/// Mounted devices with their free space.
+///
+/// Checking free space can block on unresponsive network mounts, so this is best kept off the UI
+/// thread.
+fn devices() -> Vec<(Mount, Option<Usage>)> {
+    mounts::devices()
+        .into_iter()
+        .map(|m| {
+            let usage = m.usage();
+            (m, usage)
+        })
+        .collect()
+}
+
+/// Keep the list of mounted devices up to date.
+fn watch_mounts() -> Subscription<Message> {
+    /// Free space is checked again this often, even if nothing is mounted.
+    const INTERVAL: Duration = Duration::from_secs(5);
+
+    struct Watcher;
+
+    subscription::channel(TypeId::of::<Watcher>(), 4, |mut output| async move {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        // waiting for changes blocks, so keep it off the async executor
+        tokio::task::spawn_blocking(move || {
+            let mut monitor = Monitor::new()
+                .map_err(|e| tracing::warn!("can't watch for mount changes: {}", e))
+                .ok();
+
+            // the list starts out empty, so it's sent right away rather than after a change
+            loop {
+                if sender.send(devices()).is_err() {
+                    break;
+                }
+
+                match &mut monitor {
+                    Some(m) => {
+                        if let Err(e) = m.wait(INTERVAL) {
+                            tracing::warn!("stopped watching for mount changes: {}", e);
+                            monitor = None;
+                        }
+                    }
+                    None => std::thread::sleep(INTERVAL),
+                }
+            }
+        });
+
+        while let Some(mounts) = receiver.recv().await {
+            let _ = output.send(Message::Mounts(mounts)).await;
+        }
+
+        std::future::pending().await
+    })
+}
+
 impl Drop for Gui {
     fn drop(&mut self) {
         match self.config.flush() {
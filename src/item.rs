@@ -1,10 +1,11 @@
 //! A single item (file/folder/whatever) in a directory.
 
 use std::borrow::Cow;
-use std::path::Path;
 
 use doseer_colorschemes::WithColorScheme;
+use doseer_core::entry::Entry;
 use doseer_core::path::PathWrap;
+use doseer_core::size;
 use doseer_icon_loader::file::{ImageOrSvg, Loader};
 
 use iced::widget::{button, column, container, image, svg, text};
@@ -12,7 +13,7 @@ use iced::{alignment, Background, Color, Length, Size};
 use lazy_static::lazy_static;
 
 use crate::gui::Element;
-use crate::{theme, Icon};
+use crate::{theme, thumbnails, Icon};
 
 lazy_static! {
     static ref ICONS: Loader = Loader::new();
@@ -21,7 +22,7 @@ lazy_static! {
 /// Dimensions of an item button.
 pub const DIMENSIONS: Size = Size {
     width: 128.0,
-    height: 140.0,
+    height: 156.0,
 };
 
 pub const ICON_DIMENSIONS: Size = Size {
@@ -33,12 +34,19 @@ pub const ICON_DIMENSIONS: Size = Size {
 pub enum Message {
     Select(PathWrap),
     Deselect,
-    // TODO: Delete, Properties, Copy, Paste, etc
+    // TODO: Properties, etc
 }
 
-/// Suitable display icon for some path.
-pub fn icon<'a, P: AsRef<Path>>(path: P) -> Element<'a, Message> {
-    let icon = ICONS.load(path.as_ref());
+/// Suitable display icon for some entry, or its thumbnail if it has one.
+pub fn icon<'a>(entry: &Entry) -> Element<'a, Message> {
+    if let Some(thumbnail) = thumbnails::get(entry) {
+        return image(thumbnail)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into();
+    }
+
+    let icon = ICONS.load_entry(entry);
 
     match icon {
         Some(i) => match i.as_ref().clone() {
@@ -61,13 +69,14 @@ fn trunc_path(name: Cow<str>) -> Cow<str> {
 }
 
 /// Appropriate item path to display in the button.
-pub fn item_name(path: &PathWrap) -> Cow<str> {
+pub fn item_name(path: &PathWrap) -> Cow<'_, str> {
     trunc_path(path.display().to_string_lossy())
 }
 
-pub fn view<'a>(path: PathWrap, theme: Style) -> Element<'a, Message> {
+/// An item button, with the item's size below its name if known.
+pub fn view<'a>(entry: &Entry, size: Option<u64>, theme: Style) -> Element<'a, Message> {
     let icon = container(
-        container(icon(&path))
+        container(icon(entry))
             .width(Length::Fixed(ICON_DIMENSIONS.width))
             .height(Length::Fixed(ICON_DIMENSIONS.height)),
     )
@@ -76,19 +85,24 @@ pub fn view<'a>(path: PathWrap, theme: Style) -> Element<'a, Message> {
     .align_x(alignment::Horizontal::Center)
     .align_y(alignment::Vertical::Center);
 
-    let text = text(item_name(&path))
+    let text = text(item_name(&entry.path))
         .size(16)
         .font(theme::fonts::Roboto::Regular)
         .horizontal_alignment(iced::alignment::Horizontal::Center);
 
+    let size = iced::widget::text(size.map(size::format).unwrap_or_default())
+        .size(12)
+        .font(theme::fonts::Roboto::Regular)
+        .horizontal_alignment(iced::alignment::Horizontal::Center);
+
     button(
-        column!(icon, text)
+        column!(icon, text, size)
             .align_items(iced::Alignment::Center)
             .width(iced::Length::Fill),
     )
     .width(iced::Length::Fill)
     .height(iced::Length::Fill)
-    .on_press(Message::Select(path))
+    .on_press(Message::Select(entry.path.clone()))
     .style(theme.into())
     .into()
 }
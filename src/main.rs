@@ -3,15 +3,19 @@
 use anyhow::Context;
 use iced::{Application, Settings};
 
+mod background;
 mod config;
 mod content;
 mod gui;
 mod icons;
 mod item;
 mod log;
+mod operations;
+mod settings;
 mod side_bar;
 mod tab;
 mod theme;
+mod thumbnails;
 
 use config::Config;
 use icons::Icon;
@@ -0,0 +1,417 @@
+//! Background file operations and the clipboard.
+
+use std::any::TypeId;
+use std::path::Path;
+
+use doseer_core::journal::{Journal, Step};
+use doseer_core::ops::{self, Conflict, Operation, Progress, Report, Totals};
+use doseer_core::path::PathWrap;
+use doseer_core::task::CancelToken;
+use doseer_core::trash;
+use doseer_core::vfs::{self, Uri};
+
+use iced::futures::SinkExt;
+use iced::widget::{button, container, row, text, Column, Space};
+use iced::{alignment, subscription, Alignment, Length, Subscription};
+use indexmap::IndexMap;
+
+use crate::content::TabButtonStyle;
+use crate::gui::Element;
+use crate::{theme, Icon};
+
+/// Most failures listed for a finished job, before the rest are only counted.
+const FAILURES_SHOWN: usize = 5;
+
+/// Items waiting to be pasted somewhere.
+#[derive(Debug, Clone)]
+struct Clipboard {
+    items: Vec<Uri>,
+    /// Whether the items should be moved instead of copied.
+    cut: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Put items on the clipboard to be copied.
+    Copy(Vec<Uri>),
+    /// Put items on the clipboard to be moved.
+    Cut(Vec<Uri>),
+    /// Paste the clipboard into a directory.
+    Paste(Uri),
+    /// Permanently delete items.
+    Delete(Vec<PathWrap>),
+    /// Permanently delete items that may be elsewhere, such as on a server.
+    Remove(Vec<Uri>),
+    /// Move items to the trash.
+    Trash(Vec<PathWrap>),
+    /// Move trashed items back to where they came from.
+    Restore(Vec<PathWrap>),
+    /// Permanently delete everything in the trash.
+    EmptyTrash,
+    /// Create a new folder inside a directory.
+    NewFolder(PathWrap),
+    /// Copy items out of archives into a directory.
+    Extract(Vec<PathWrap>, PathWrap),
+    /// Undo or redo the latest change.
+    Step(Step),
+    /// Start an operation.
+    Start(Operation),
+    /// Progress update from a running job.
+    Progress(usize, Progress),
+    /// Stop a running job.
+    Cancel(usize),
+    /// Hide a finished job.
+    Dismiss(usize),
+    /// Hide the current notice.
+    DismissNotice,
+}
+
+/// A job tracked by the UI.
+#[derive(Debug)]
+struct Job {
+    operation: Operation,
+    cancel: CancelToken,
+    totals: Totals,
+    /// Whether this job undoes or redoes an earlier change.
+    step: Option<Step>,
+    /// Set once the job is done.
+    report: Option<Report>,
+}
+
+/// All running and recently failed file operations.
+#[derive(Debug, Default)]
+pub struct Operations {
+    clipboard: Option<Clipboard>,
+    jobs: IndexMap<usize, Job>,
+    next_id: usize,
+    /// Undo/redo history of finished jobs.
+    journal: Journal,
+    /// A message for the user, such as why an undo failed.
+    notice: Option<String>,
+}
+
+impl Operations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking a new job. It starts running with the next subscription update.
+    fn start(&mut self, operation: Operation) {
+        self.start_step(operation, None);
+    }
+
+    /// Start tracking a job that may be part of undoing or redoing.
+    fn start_step(&mut self, operation: Operation, step: Option<Step>) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.jobs.insert(
+            id,
+            Job {
+                operation,
+                cancel: CancelToken::new(),
+                totals: Totals::default(),
+                step,
+                report: None,
+            },
+        );
+    }
+
+    /// Handle a message, returning the report of a job if it just finished.
+    pub fn update(&mut self, message: Message) -> Option<Report> {
+        match message {
+            Message::Copy(items) => self.clipboard = Some(Clipboard { items, cut: false }),
+            Message::Cut(items) => self.clipboard = Some(Clipboard { items, cut: true }),
+            Message::Paste(destination) => {
+                let clipboard = self.clipboard.as_ref()?;
+
+                let paths = || {
+                    clipboard
+                        .items
+                        .iter()
+                        .map(|i| i.path().clone())
+                        .collect::<Vec<_>>()
+                };
+
+                let is_local = destination.is_local() && clipboard.items.iter().all(Uri::is_local);
+                // items in archives can only be copied out of them
+                let from_archive = destination.is_local()
+                    && clipboard
+                        .items
+                        .iter()
+                        .all(|i| i.scheme() == vfs::archived::SCHEME);
+
+                let operation = if from_archive {
+                    Operation::Extract {
+                        sources: paths(),
+                        destination: destination.path().clone(),
+                        conflict: Conflict::default(),
+                    }
+                } else if !is_local {
+                    // anything else can only be copied between backends
+                    Operation::Transfer {
+                        sources: clipboard.items.clone(),
+                        destination,
+                        conflict: Conflict::default(),
+                    }
+                } else if clipboard.cut {
+                    Operation::Move {
+                        sources: paths(),
+                        destination: destination.path().clone(),
+                        conflict: Conflict::default(),
+                    }
+                } else {
+                    Operation::Copy {
+                        sources: paths(),
+                        destination: destination.path().clone(),
+                        conflict: Conflict::default(),
+                    }
+                };
+
+                // Cut items can only be pasted once
+                if clipboard.cut && is_local {
+                    self.clipboard = None;
+                }
+
+                self.start(operation);
+            }
+            Message::Delete(targets) => self.start(Operation::Delete { targets }),
+            Message::Remove(targets) => self.start(Operation::Remove { targets }),
+            Message::Trash(targets) => self.start(Operation::Trash { targets }),
+            Message::Restore(items) => self.start(Operation::Restore {
+                items,
+                conflict: Conflict::default(),
+            }),
+            Message::Extract(sources, destination) => self.start(Operation::Extract {
+                sources,
+                destination,
+                conflict: Conflict::default(),
+            }),
+            Message::EmptyTrash => {
+                let targets = trash::list().into_iter().map(|t| t.path).collect();
+                self.start(Operation::Delete { targets });
+            }
+            Message::NewFolder(directory) => self.start(Operation::CreateDir {
+                path: ops::unused_path(&directory, "New Folder"),
+            }),
+            Message::Step(step) => match self.journal.step(step) {
+                Ok(Some(operation)) => self.start_step(operation, Some(step)),
+                Ok(None) => {}
+                Err(e) => {
+                    let verb = match step {
+                        Step::Undo => "undo",
+                        Step::Redo => "redo",
+                    };
+
+                    self.notice = Some(format!("Can't {verb}: {e}"));
+                }
+            },
+            Message::Start(operation) => self.start(operation),
+            Message::Progress(id, progress) => {
+                let job = self.jobs.get_mut(&id)?;
+
+                match progress {
+                    Progress::Scanned(totals) | Progress::Total(totals) => job.totals = totals,
+                    Progress::File { .. } => {}
+                    Progress::Finished(report) => {
+                        self.journal.record(&report, job.step);
+
+                        for failure in &report.failed {
+                            tracing::warn!(
+                                path = ?failure.path,
+                                "{} failed: {}",
+                                report.operation.verb(),
+                                failure.error
+                            );
+                        }
+
+                        // Keep failed jobs around so the user can see what went wrong
+                        if report.is_success() || report.cancelled {
+                            self.jobs.shift_remove(&id);
+                        } else {
+                            job.report = Some(report.clone());
+                        }
+
+                        return Some(report);
+                    }
+                }
+            }
+            Message::Cancel(id) => {
+                if let Some(job) = self.jobs.get(&id) {
+                    job.cancel.cancel();
+                }
+            }
+            Message::Dismiss(id) => {
+                self.jobs.shift_remove(&id);
+            }
+            Message::DismissNotice => self.notice = None,
+        }
+
+        None
+    }
+
+    /// Check if there's anything to undo or redo.
+    #[inline]
+    pub fn can(&self, step: Step) -> bool {
+        self.journal.can(step)
+    }
+
+    /// Drive every running job.
+    pub fn subscription(&self) -> Subscription<Message> {
+        Subscription::batch(
+            self.jobs
+                .iter()
+                .filter(|(_, job)| job.report.is_none())
+                .map(|(id, job)| run(*id, job.operation.clone(), job.cancel.clone())),
+        )
+    }
+
+    /// Progress of all tracked jobs.
+    pub fn view(&self) -> Element<'_, Message> {
+        let mut col = Column::new().spacing(4);
+
+        if let Some(notice) = &self.notice {
+            col = col.push(
+                row!(
+                    text(notice)
+                        .size(16)
+                        .font(theme::fonts::Roboto::Regular)
+                        .width(Length::Fill),
+                    close_button(theme::svg::Neutral::Bright0, Message::DismissNotice)
+                )
+                .spacing(8)
+                .align_items(Alignment::Center),
+            );
+        }
+
+        for (id, job) in &self.jobs {
+            let count = job.operation.count();
+
+            let description = match &job.report {
+                Some(report) => format!(
+                    "{} {} item(s): {} failed",
+                    job.operation.verb(),
+                    count,
+                    report.failed.len()
+                ),
+                None => format!("{} {} item(s)", job.operation.verb(), count),
+            };
+
+            let (action, icon_style) = match job.report {
+                Some(_) => (Message::Dismiss(*id), theme::svg::Neutral::Bright0),
+                None => (Message::Cancel(*id), theme::svg::Neutral::Bright1),
+            };
+
+            col = col.push(
+                row!(
+                    text(description)
+                        .size(16)
+                        .font(theme::fonts::Roboto::Regular)
+                        .width(Length::FillPortion(2)),
+                    container(progress_bar(job.totals.fraction())).width(Length::FillPortion(3)),
+                    close_button(icon_style, action)
+                )
+                .spacing(8)
+                .align_items(Alignment::Center),
+            );
+
+            // what failed and why, so it can be fixed without digging through logs
+            if let Some(report) = &job.report {
+                for failure in report.failed.iter().take(FAILURES_SHOWN) {
+                    col = col.push(failure_line(format!(
+                        "{}: {}",
+                        Path::display(&failure.path),
+                        failure.error
+                    )));
+                }
+
+                if report.failed.len() > FAILURES_SHOWN {
+                    col = col.push(failure_line(format!(
+                        "and {} more",
+                        report.failed.len() - FAILURES_SHOWN
+                    )));
+                }
+            }
+        }
+
+        container(col).padding([0, 8]).into()
+    }
+}
+
+/// A line about a failure in a finished job.
+fn failure_line<'a>(line: String) -> Element<'a, Message> {
+    container(text(line).size(14).font(theme::fonts::Roboto::Regular))
+        .padding([0, 0, 0, 16])
+        .into()
+}
+
+/// A small cross button.
+fn close_button<'a>(style: theme::svg::Neutral, on_press: Message) -> Element<'a, Message> {
+    button(
+        container(
+            Icon::Cross
+                .svg()
+                .height(Length::Fixed(18.0))
+                .width(Length::Fixed(18.0))
+                .style(style.into()),
+        )
+        .align_x(alignment::Horizontal::Center)
+        .align_y(alignment::Vertical::Center)
+        .height(Length::Fixed(24.0))
+        .width(Length::Fixed(24.0)),
+    )
+    .style(TabButtonStyle::Default.into())
+    .on_press(on_press)
+    .into()
+}
+
+/// A thin bar filled up to some fraction.
+pub fn progress_bar<'a, T: 'a>(fraction: f32) -> Element<'a, T> {
+    const RESOLUTION: f32 = 1000.0;
+
+    // Portions of zero would make the containers shrink instead of fill
+    let done = (fraction.clamp(0.0, 1.0) * RESOLUTION) as u16;
+    let left = RESOLUTION as u16 - done;
+
+    row!(
+        container(Space::new(Length::Fill, Length::Fill))
+            .width(Length::FillPortion(done.max(1)))
+            .height(Length::Fill)
+            .style(theme::container::Container::Accent),
+        container(Space::new(Length::Fill, Length::Fill))
+            .width(Length::FillPortion(left.max(1)))
+            .height(Length::Fill)
+            .style(theme::container::Container::Weak),
+    )
+    .height(Length::Fixed(6.0))
+    .into()
+}
+
+/// Run a job in the background, forwarding its progress.
+fn run(id: usize, operation: Operation, cancel: CancelToken) -> Subscription<Message> {
+    struct Runner;
+
+    subscription::channel(
+        (TypeId::of::<Runner>(), id),
+        64,
+        move |mut output| async move {
+            let job = ops::spawn_with(operation, cancel);
+            let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+            // The job blocks while waiting for progress, so keep it off the async executor
+            tokio::task::spawn_blocking(move || {
+                for progress in job {
+                    if sender.send(progress).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            while let Some(progress) = receiver.recv().await {
+                let _ = output.send(Message::Progress(id, progress)).await;
+            }
+
+            std::future::pending().await
+        },
+    )
+}
@@ -0,0 +1,134 @@
+//! Settings page, for preferences shared with the rest of the desktop.
+
+use doseer_core::apps::{self, Registry};
+
+use iced::widget::scrollable::Properties;
+use iced::widget::{button, column, container, row, scrollable, text, Column};
+use iced::{alignment, Alignment, Length};
+
+use crate::content::{self, TabButtonStyle};
+use crate::gui::Element;
+use crate::theme;
+
+/// Settings page events.
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Stop picking a default app for a MIME type.
+    Reset(String),
+    Close,
+}
+
+/// The settings page.
+#[derive(Debug, Default)]
+pub struct Settings {
+    /// Every MIME type with a default app, and the name of the app.
+    defaults: Vec<(String, String)>,
+    /// Why the last change failed, if it did.
+    error: Option<String>,
+}
+
+impl Settings {
+    /// Read the settings as they are now.
+    pub fn new() -> Self {
+        let mut settings = Self::default();
+        settings.reload();
+        settings
+    }
+
+    /// Read the default apps again.
+    fn reload(&mut self) {
+        self.defaults = Registry::shared()
+            .defaults()
+            .into_iter()
+            .map(|(mime, app)| (mime.to_owned(), app.name.clone()))
+            .collect();
+    }
+
+    /// Apply a message, returning whether the page is still open.
+    pub fn update(&mut self, message: Message) -> bool {
+        match message {
+            Message::Reset(mime) => {
+                self.error = apps::unset_default(&mime)
+                    .err()
+                    .map(|e| format!("Couldn't reset {mime}: {e}"));
+
+                self.reload();
+                true
+            }
+            Message::Close => false,
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let title = row!(
+            text("Settings")
+                .font(theme::fonts::SofiaSans::Black)
+                .size(28)
+                .width(Length::Fill),
+            button(text("Close").size(16).font(theme::fonts::Roboto::Regular))
+                .padding([4, 8])
+                .style(TabButtonStyle::Default.into())
+                .on_press(Message::Close),
+        )
+        .align_items(Alignment::Center);
+
+        let title = container(title)
+            .height(content::Content::TOP_BAR_HEIGHT)
+            .align_y(alignment::Vertical::Center);
+
+        let mut defaults = Column::new().spacing(4);
+
+        if self.defaults.is_empty() {
+            defaults = defaults.push(
+                text("No default applications are set. Pick one with Open With… to set it.")
+                    .size(16)
+                    .font(theme::fonts::Roboto::Regular),
+            );
+        }
+
+        for (mime, app) in &self.defaults {
+            defaults = defaults.push(
+                row!(
+                    text(mime)
+                        .size(14)
+                        .font(theme::fonts::RobotoMono::Regular)
+                        .width(Length::FillPortion(1)),
+                    text(app)
+                        .size(16)
+                        .font(theme::fonts::Roboto::Regular)
+                        .width(Length::FillPortion(1)),
+                    button(text("Reset").size(16).font(theme::fonts::Roboto::Regular))
+                        .padding([4, 8])
+                        .style(TabButtonStyle::Default.into())
+                        .on_press(Message::Reset(mime.clone())),
+                )
+                .spacing(8)
+                .align_items(Alignment::Center),
+            );
+        }
+
+        let mut page = column!(
+            title,
+            text("Default Applications")
+                .size(18)
+                .font(theme::fonts::SofiaSans::Black),
+        )
+        .spacing(8);
+
+        if let Some(error) = &self.error {
+            page = page.push(text(error).size(16).font(theme::fonts::Roboto::Regular));
+        }
+
+        page.push(
+            scrollable(container(defaults).padding([0.0, 13.0, 0.0, 0.0]))
+                .direction(scrollable::Direction::Vertical(
+                    Properties::new().width(5.6).scroller_width(5.0),
+                ))
+                .height(Length::Fill),
+        )
+        .padding([0, 16])
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
+    }
+}
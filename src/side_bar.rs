@@ -1,16 +1,17 @@
 //! Side bar widget.
 
-use std::path::Path;
-
 use doseer_colorschemes::WithColorScheme;
-use doseer_core::path::PathWrap;
+use doseer_core::mounts::{Mount, Usage};
+use doseer_core::vfs::webdav::{self, Login};
+use doseer_core::vfs::Uri;
+use doseer_core::{size, trash};
 use doseer_iced_ext::widgets::reorderable;
 
-use iced::widget::{button, column, container, row, text, Component};
+use iced::widget::{button, column, container, row, text, text_input, Component, TextInput};
 use iced::{alignment, Alignment, Length, Padding};
 
 use crate::gui::{self, Element};
-use crate::{config, content, theme, Config, Icon};
+use crate::{config, content, operations, theme, Config, Icon};
 
 /// Sidebar events.
 #[derive(Debug, Clone)]
@@ -19,54 +20,135 @@ pub enum Message {
     Content(content::Message),
     /// Config modification request.
     Config(config::Message),
+    /// Address typed into the server form.
+    Address(String),
+    /// User name typed into the server form.
+    User(String),
+    /// Password typed into the server form.
+    Password(String),
+    /// Connect to the server in the form.
+    Connect,
+    /// Open the settings page.
+    Settings,
+}
+
+/// The form for connecting to a server.
+#[derive(Debug, Default)]
+pub struct Connect {
+    address: String,
+    user: String,
+    password: String,
+    /// Why the address can't be connected to, if it can't.
+    error: Option<String>,
+}
+
+impl Connect {
+    /// The location and login to connect with.
+    fn submit(&mut self) -> Result<(Uri, Option<Login>), String> {
+        let address = self.address.trim();
+
+        // servers are reached over HTTPS unless asked otherwise
+        let address = if address.contains("://") {
+            address.to_owned()
+        } else {
+            format!("{}://{address}", webdav::SECURE_SCHEME)
+        };
+
+        let location = Uri::parse(&address)
+            .filter(|u| [webdav::SCHEME, webdav::SECURE_SCHEME].contains(&u.scheme()))
+            .filter(|u| !webdav::server(u).is_empty())
+            .ok_or_else(|| "Not a WebDAV address".to_owned())?;
+
+        let login = (!self.user.is_empty()).then(|| Login {
+            user: self.user.clone(),
+            password: std::mem::take(&mut self.password),
+        });
+
+        Ok((location, login))
+    }
 }
 
 /// Creates the sidebar component.
 #[inline]
-pub const fn side_bar<IsOpen>(config: &Config, is_open: IsOpen) -> SideBar<IsOpen>
+pub const fn side_bar<'a, IsOpen>(
+    config: &'a Config,
+    mounts: &'a [(Mount, Option<Usage>)],
+    is_open: IsOpen,
+) -> SideBar<'a, IsOpen>
 where
-    IsOpen: Fn(&Path) -> bool,
+    IsOpen: Fn(&Uri) -> bool,
 {
-    SideBar::new(config, is_open)
+    SideBar::new(config, mounts, is_open)
 }
 
 /// The file picker side bar.
 #[derive(Debug)]
 pub struct SideBar<'conf, IsOpen>
 where
-    IsOpen: Fn(&Path) -> bool,
+    IsOpen: Fn(&Uri) -> bool,
 {
     /// User configuration.
     config: &'conf Config,
+    /// Mounted devices, with their free space.
+    mounts: &'conf [(Mount, Option<Usage>)],
     /// Location open check.
     is_open: IsOpen,
 }
 
 impl<'conf, IsOpen> SideBar<'conf, IsOpen>
 where
-    IsOpen: Fn(&Path) -> bool,
+    IsOpen: Fn(&Uri) -> bool,
 {
     #[inline]
-    pub const fn new(config: &'conf Config, is_open: IsOpen) -> Self {
-        Self { config, is_open }
+    pub const fn new(
+        config: &'conf Config,
+        mounts: &'conf [(Mount, Option<Usage>)],
+        is_open: IsOpen,
+    ) -> Self {
+        Self {
+            config,
+            mounts,
+            is_open,
+        }
     }
 }
 
 impl<'conf, IsOpen> Component<gui::Message, gui::Renderer> for SideBar<'conf, IsOpen>
 where
-    IsOpen: Fn(&Path) -> bool,
+    IsOpen: Fn(&Uri) -> bool,
 {
-    type State = ();
+    type State = Connect;
     type Event = Message;
 
-    fn update(&mut self, _: &mut Self::State, event: Self::Event) -> Option<gui::Message> {
+    fn update(&mut self, connect: &mut Self::State, event: Self::Event) -> Option<gui::Message> {
         match event {
             Message::Content(p) => Some(gui::Message::Content(p)),
             Message::Config(c) => Some(gui::Message::Config(c)),
+            Message::Address(address) => {
+                connect.address = address;
+                connect.error = None;
+                None
+            }
+            Message::User(user) => {
+                connect.user = user;
+                None
+            }
+            Message::Password(password) => {
+                connect.password = password;
+                None
+            }
+            Message::Settings => Some(gui::Message::OpenSettings),
+            Message::Connect => match connect.submit() {
+                Ok((location, login)) => Some(gui::Message::Connect(location, login)),
+                Err(e) => {
+                    connect.error = Some(e);
+                    None
+                }
+            },
         }
     }
 
-    fn view(&self, _: &Self::State) -> Element<'_, Self::Event> {
+    fn view(&self, connect: &Self::State) -> Element<'_, Self::Event> {
         let title = container(text("Files").font(theme::fonts::SofiaSans::Black).size(28))
             .height(content::Content::TOP_BAR_HEIGHT)
             .align_y(alignment::Vertical::Center)
@@ -79,13 +161,67 @@ where
             .spacing(4)
             .on_reorder(|a, b| Message::Config(config::Message::ReorderBookmarks(a, b)));
 
-        for path in &self.config.bookmarks {
-            col = col.push(item_button(path, &self.is_open));
+        for location in &self.config.bookmarks {
+            col = col.push(item_button(
+                location,
+                bookmark_label(location),
+                &self.is_open,
+            ));
+        }
+
+        // Other locations
+        let places = column!(item_button(
+            &Uri::local(trash::location()),
+            "Trash".to_owned(),
+            &self.is_open
+        ))
+        .align_items(Alignment::Center)
+        .padding(8)
+        .spacing(4);
+
+        // Mounted devices
+        let mut devices = column!(section_title("Devices")).padding(8).spacing(4);
+
+        for (mount, usage) in self.mounts {
+            devices = devices.push(device_button(mount, *usage, &self.is_open));
         }
 
-        // TODO: Network column, Other locations
+        // Remote servers
+        let mut servers = column!(
+            section_title("Servers"),
+            server_field("dav://server/folder", &connect.address, Message::Address),
+            server_field("User", &connect.user, Message::User),
+            server_field("Password", &connect.password, Message::Password).password(),
+            button(
+                text("Connect")
+                    .size(16)
+                    .font(theme::fonts::SofiaSans::Regular)
+            )
+            .padding([4, 8])
+            .style(ButtonStyle::Default.into())
+            .on_press(Message::Connect),
+        )
+        .padding(8)
+        .spacing(4);
+
+        if let Some(error) = &connect.error {
+            servers = servers.push(text(error).size(13).font(theme::fonts::SofiaSans::Regular));
+        }
 
-        container(column!(title, col))
+        let settings = container(
+            button(
+                text("Settings")
+                    .size(18)
+                    .font(theme::fonts::SofiaSans::Regular),
+            )
+            .on_press(Message::Settings)
+            .width(Length::Fill)
+            .padding(Padding::from([4, 8]))
+            .style(ButtonStyle::Default.into()),
+        )
+        .padding(8);
+
+        container(column!(title, col, places, devices, servers, settings))
             .style(theme::container::Container::Weak)
             .width(Length::Fill)
             .height(Length::Fill)
@@ -93,16 +229,28 @@ where
     }
 }
 
-fn item_button(path: &PathWrap, is_open: impl Fn(&Path) -> bool) -> Element<Message> {
+/// Name shown for a bookmarked location.
+fn bookmark_label(location: &Uri) -> String {
+    match location.path().file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        // the root of a server is best known by the server's name
+        None if !location.is_local() => location.authority().to_owned(),
+        None => location.path().display().to_string_lossy().into_owned(),
+    }
+}
+
+fn item_button<'a>(
+    location: &Uri,
+    label: String,
+    is_open: impl Fn(&Uri) -> bool,
+) -> Element<'a, Message> {
     button(
         row!(
             Icon::Directory
                 .svg()
                 .width(Length::Fixed(22.0))
                 .height(Length::Fixed(22.0)),
-            text(path.display().to_string_lossy())
-                .size(18)
-                .font(theme::fonts::SofiaSans::Regular),
+            text(label).size(18).font(theme::fonts::SofiaSans::Regular),
         )
         .spacing(6)
         .align_items(Alignment::Center)
@@ -110,12 +258,87 @@ fn item_button(path: &PathWrap, is_open: impl Fn(&Path) -> bool) -> Element<Mess
         .height(Length::Fill),
     )
     // focus tab when the button is pressed
-    .on_press(Message::Content(content::Message::Replace(path.clone())))
+    .on_press(Message::Content(content::Message::Replace(
+        location.clone(),
+    )))
     .width(Length::Fill)
     .height(Length::Fixed(38.0))
     .padding(Padding::from([4, 8]))
     .style(
-        if (is_open)(path) {
+        if (is_open)(location) {
+            ButtonStyle::Focused
+        } else {
+            ButtonStyle::Default
+        }
+        .into(),
+    )
+    .into()
+}
+
+fn server_field<'a>(
+    placeholder: &str,
+    value: &str,
+    on_input: fn(String) -> Message,
+) -> TextInput<'a, Message, gui::Renderer> {
+    text_input(placeholder, value)
+        .on_input(on_input)
+        .on_submit(Message::Connect)
+        .size(16)
+        .padding([4, 8])
+}
+
+fn section_title<'a>(label: &str) -> Element<'a, Message> {
+    container(text(label).size(14).font(theme::fonts::SofiaSans::Black))
+        .padding([4, 8])
+        .into()
+}
+
+fn device_button<'a>(
+    mount: &Mount,
+    usage: Option<Usage>,
+    is_open: impl Fn(&Uri) -> bool,
+) -> Element<'a, Message> {
+    let location = Uri::local(&mount.point);
+
+    let mut details = column!(text(mount.name())
+        .size(18)
+        .font(theme::fonts::SofiaSans::Regular))
+    .spacing(4)
+    .width(Length::Fill);
+
+    if let Some(usage) = usage {
+        details = details
+            .push(operations::progress_bar(usage.fraction()))
+            .push(
+                text(format!(
+                    "{} free of {}",
+                    size::format(usage.available),
+                    size::format(usage.total)
+                ))
+                .size(13)
+                .font(theme::fonts::SofiaSans::Regular),
+            );
+    }
+
+    button(
+        row!(
+            Icon::Directory
+                .svg()
+                .width(Length::Fixed(22.0))
+                .height(Length::Fixed(22.0)),
+            details,
+        )
+        .spacing(6)
+        .align_items(Alignment::Center)
+        .width(Length::Fill),
+    )
+    .on_press(Message::Content(content::Message::Replace(
+        location.clone(),
+    )))
+    .width(Length::Fill)
+    .padding(Padding::from([6, 8]))
+    .style(
+        if (is_open)(&location) {
             ButtonStyle::Focused
         } else {
             ButtonStyle::Default
@@ -0,0 +1,99 @@
+//! Sheet asking before items are deleted for good.
+
+use iced::widget::scrollable::Properties;
+use iced::widget::{button, column, container, row, scrollable, text, Column};
+use iced::{Alignment, Length};
+
+use super::content::Action;
+use crate::content::TabButtonStyle;
+use crate::gui::Element;
+use crate::theme;
+
+/// Most names listed, before the rest are only counted.
+const NAMES_SHOWN: usize = 50;
+
+/// Answers to the sheet.
+#[derive(Debug, Clone)]
+pub enum Message {
+    Confirm,
+    Cancel,
+}
+
+/// An action waiting to be confirmed, and the names of the items it deletes.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    action: Action,
+    names: Vec<String>,
+}
+
+impl Prompt {
+    #[inline]
+    pub fn new(action: Action, names: Vec<String>) -> Self {
+        Self { action, names }
+    }
+
+    /// The action to take once it's confirmed.
+    #[inline]
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    pub fn view<'a>(&self) -> Element<'a, Message> {
+        let (title, warning) = match (self.action, &self.names[..]) {
+            (Action::EmptyTrash, names) => (
+                format!("Empty the trash of {} items?", names.len()),
+                "They'll be deleted permanently, so this can't be undone.",
+            ),
+            (_, [name]) => (
+                format!("Permanently delete {name}?"),
+                "It won't be moved to the trash, so this can't be undone.",
+            ),
+            (_, names) => (
+                format!("Permanently delete {} items?", names.len()),
+                "They won't be moved to the trash, so this can't be undone.",
+            ),
+        };
+
+        let mut list = Column::new().spacing(2);
+
+        for name in self.names.iter().take(NAMES_SHOWN) {
+            list = list.push(text(name).size(14).font(theme::fonts::Roboto::Regular));
+        }
+
+        if self.names.len() > NAMES_SHOWN {
+            list = list.push(
+                text(format!("and {} more", self.names.len() - NAMES_SHOWN))
+                    .size(14)
+                    .font(theme::fonts::Roboto::Regular),
+            );
+        }
+
+        let names = scrollable(container(list).padding([0.0, 13.0, 0.0, 0.0]))
+            .direction(scrollable::Direction::Vertical(
+                Properties::new().width(5.6).scroller_width(5.0),
+            ))
+            .height(Length::Fill);
+
+        let answer = |label, message| {
+            button(text(label).size(16).font(theme::fonts::Roboto::Regular))
+                .padding([4, 8])
+                .style(TabButtonStyle::Default.into())
+                .on_press(message)
+        };
+
+        column!(
+            text(title).size(20).font(theme::fonts::SofiaSans::Black),
+            text(warning).size(14).font(theme::fonts::Roboto::Regular),
+            names,
+            row!(
+                column!().width(Length::Fill),
+                answer("Cancel", Message::Cancel),
+                answer("Delete", Message::Confirm),
+            )
+            .spacing(4)
+            .align_items(Alignment::Center),
+        )
+        .spacing(8)
+        .into()
+    }
+}
@@ -1,60 +1,945 @@
 //! The actual *content* to be displayed by a tab - directory contents, etc.
 
-use std::path::Path;
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
-use doseer_core::dirs;
+use doseer_core::apps;
+use doseer_core::archive;
+use doseer_core::dirs::Change;
+use doseer_core::duplicates::{self, Found};
+use doseer_core::entry::Entry;
+use doseer_core::filter::Filter;
+use doseer_core::grep::{self, Case};
 use doseer_core::path::PathWrap;
+use doseer_core::search::{self, Mode, Query};
+use doseer_core::size;
+use doseer_core::sort::{Key, Order, Sort};
+use doseer_core::task::CancelToken;
+use doseer_core::usage;
+use doseer_core::vfs::{self, Uri};
+use doseer_core::{dirs, trash};
+use doseer_iced_ext::components::panelled::{self, pane_grid, unpanelled, PanelPosition};
 use doseer_iced_ext::widgets::grid::uniform;
 
+use iced::futures::SinkExt;
 use iced::widget::scrollable::Properties;
-use iced::widget::{container, scrollable, Component};
-use iced::Length;
+use iced::widget::{
+    button, canvas, column, container, row, scrollable, text, text_input, Column, Component, Row,
+};
+use iced::{subscription, Alignment, Length, Subscription};
 
+use super::usage::Treemap;
+use super::{confirm, open_with, preview, properties, rename};
+use crate::content::TabButtonStyle;
 use crate::gui::Element;
-use crate::{gui, item};
+use crate::{gui, item, operations, theme};
 
 /// Create location content state from predefined state.
 #[inline]
-pub const fn content(state: &State) -> Content {
-    Content::new(state)
+pub const fn content<'app>(state: &'app State, sizes: &'app size::Cache) -> Content<'app> {
+    Content::new(state, sizes)
+}
+
+/// What a pane is showing.
+#[derive(Debug)]
+pub enum View {
+    /// The contents of a directory.
+    Directory,
+    /// Everything in the trash.
+    Trash(Vec<Entry>),
+    /// Items found by searching below the location.
+    Search(Results),
+    /// Lines found by searching the contents of files below the location.
+    Grep(Matches),
+    /// What is taking up space below the location.
+    Usage(Usage),
+    /// Files below the location with the same contents.
+    Duplicates(Duplicates),
+}
+
+impl View {
+    /// The appropriate view for a location.
+    fn at(location: &Uri, sort: Sort) -> anyhow::Result<Self> {
+        if location.is_local() && location.path() == &trash::location() {
+            // Make sure there's something to open
+            trash::TrashDir::home().create()?;
+            return Ok(Self::Trash(Self::trashed(sort)));
+        }
+
+        Ok(Self::Directory)
+    }
+
+    /// Entries for every trashed item, across all trash directories.
+    fn trashed(sort: Sort) -> Vec<Entry> {
+        let mut items: Vec<_> = trash::list()
+            .into_iter()
+            .filter_map(|t| Entry::read_path(t.path).ok())
+            .collect();
+
+        sort.apply(&mut items);
+        items
+    }
+}
+
+/// Items found by a search, in display order.
+#[derive(Debug)]
+pub struct Results {
+    pub query: Query,
+    pub items: Vec<Entry>,
+    /// Match scores, parallel to `items`. Only kept for fuzzy searches, which are ordered by score.
+    scores: Vec<i64>,
+    /// Why the search failed, if it did.
+    pub error: Option<String>,
+}
+
+impl Results {
+    fn new(query: Query) -> Self {
+        Self {
+            query,
+            items: vec![],
+            scores: vec![],
+            error: None,
+        }
+    }
+
+    /// Add newly found items in order.
+    fn insert(&mut self, mut hits: Vec<search::Hit>, sort: Sort) {
+        if self.query.mode == Mode::Fuzzy {
+            hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+
+            let found = std::mem::take(&mut self.items)
+                .into_iter()
+                .zip(std::mem::take(&mut self.scores))
+                .collect();
+            let hits = hits.into_iter().map(|h| (h.entry, h.score)).collect();
+
+            (self.items, self.scores) = merge(found, hits, |a, b| a.1 >= b.1).into_iter().unzip();
+        } else {
+            hits.sort_by(|a, b| sort.compare(&a.entry, &b.entry));
+
+            let hits = hits.into_iter().map(|h| h.entry).collect();
+            self.items = merge(std::mem::take(&mut self.items), hits, |a, b| {
+                sort.compare(a, b).is_le()
+            });
+        }
+    }
+}
+
+/// Merge a sorted batch into sorted items, in one pass rather than one search per new item.
+///
+/// `before` tells if an item already there goes before a new one.
+fn merge<T>(items: Vec<T>, batch: Vec<T>, before: impl Fn(&T, &T) -> bool) -> Vec<T> {
+    let mut merged = Vec::with_capacity(items.len() + batch.len());
+    let mut batch = batch.into_iter().peekable();
+
+    for item in items {
+        while let Some(new) = batch.next_if(|new| !before(&item, new)) {
+            merged.push(new);
+        }
+
+        merged.push(item);
+    }
+
+    merged.extend(batch);
+    merged
+}
+
+/// Files with lines found by a content search, in path order.
+#[derive(Debug)]
+pub struct Matches {
+    pub query: grep::Query,
+    pub hits: Vec<grep::Hit>,
+    /// Why the search failed, if it did.
+    pub error: Option<String>,
+}
+
+impl Matches {
+    fn new(query: grep::Query) -> Self {
+        Self {
+            query,
+            hits: vec![],
+            error: None,
+        }
+    }
+
+    /// Add newly searched files in order.
+    fn insert(&mut self, mut hits: Vec<grep::Hit>) {
+        hits.sort_by(|a, b| a.path.as_ref().cmp(b.path.as_ref()));
+
+        self.hits = merge(std::mem::take(&mut self.hits), hits, |a, b| {
+            a.path.as_ref() < b.path.as_ref()
+        });
+    }
+
+    /// Total number of matching lines.
+    pub fn count(&self) -> usize {
+        self.hits.iter().map(|h| h.matches.len()).sum()
+    }
+}
+
+/// A scanned directory tree.
+#[derive(Debug, Default)]
+pub struct Usage {
+    /// The whole tree, once scanned.
+    pub root: Option<Arc<usage::Node>>,
+    /// Why the scan failed, if it did.
+    pub error: Option<String>,
+}
+
+/// Files found to have copies, most wasted space first.
+#[derive(Debug)]
+pub struct Duplicates {
+    pub query: duplicates::Query,
+    pub groups: Vec<duplicates::Group>,
+    /// How far the search has got.
+    pub progress: duplicates::Progress,
+    /// Why the search failed, if it did.
+    pub error: Option<String>,
+}
+
+impl Duplicates {
+    fn new(query: duplicates::Query) -> Self {
+        Self {
+            query,
+            groups: vec![],
+            progress: duplicates::Progress::default(),
+            error: None,
+        }
+    }
+
+    /// Add newly found groups and progress.
+    fn insert(&mut self, found: Vec<Found>) {
+        for found in found {
+            match found {
+                Found::Progress(progress) => self.progress = progress,
+                Found::Group(group) => {
+                    let index = self
+                        .groups
+                        .partition_point(|g| g.wasted() >= group.wasted());
+                    self.groups.insert(index, group);
+                }
+            }
+        }
+    }
+
+    /// Forget copies that are gone, such as after being trashed.
+    fn prune(&mut self) {
+        duplicates::prune(&mut self.groups);
+    }
+
+    /// Total space that would be freed by keeping one copy of everything.
+    pub fn wasted(&self) -> u64 {
+        self.groups.iter().map(duplicates::Group::wasted).sum()
+    }
+}
+
+/// Source of ids for background reads.
+static NEXT_LOAD: AtomicUsize = AtomicUsize::new(0);
+
+/// A background read or search of the current location. Dropping it cancels it.
+#[derive(Debug)]
+struct Load {
+    id: usize,
+    cancel: CancelToken,
+}
+
+impl Load {
+    fn new() -> Self {
+        Self {
+            id: NEXT_LOAD.fetch_add(1, Ordering::Relaxed),
+            cancel: CancelToken::new(),
+        }
+    }
+}
+
+impl Drop for Load {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+/// Progress of a background read.
+#[derive(Debug, Clone)]
+pub enum Loading {
+    /// More entries were read.
+    Batch(usize, Vec<Entry>),
+    /// More items were found by a search.
+    Hits(usize, Vec<search::Hit>),
+    /// More files were found by a content search.
+    Matches(usize, Vec<grep::Hit>),
+    /// More folders were measured.
+    Sizes(usize, Vec<size::Measured>),
+    /// A whole tree was scanned.
+    Usage(usize, Arc<usage::Node>),
+    /// More duplicates were found, or the search got further.
+    Duplicates(usize, Vec<Found>),
+    /// The read is done, with an error if it failed.
+    Finished(usize, Option<String>),
+}
+
+impl Loading {
+    /// Id of the read this belongs to.
+    #[inline]
+    pub fn id(&self) -> usize {
+        match self {
+            Self::Batch(id, _)
+            | Self::Hits(id, _)
+            | Self::Matches(id, _)
+            | Self::Sizes(id, _)
+            | Self::Usage(id, _)
+            | Self::Duplicates(id, _)
+            | Self::Finished(id, _) => *id,
+        }
+    }
 }
 
 /// Externally managed content state.
 #[derive(Debug)]
 pub struct State {
-    /// Directory stack.
-    pub stack: Vec<PathWrap>,
     /// Contents of the current location.
     pub contents: dirs::Contents,
+    /// How the location is displayed.
+    pub view: View,
+    /// Background read of the contents or search, if still running.
+    load: Option<Load>,
+    /// Background measurement of folder sizes, once the items are known.
+    measure: Option<Load>,
+    /// Why the location couldn't be read, if it couldn't.
+    error: Option<String>,
+    /// Split between the items and the preview panel, while it's shown.
+    preview: Option<panelled::State>,
 }
 
 impl State {
     /// Default tab content.
     #[inline]
-    pub fn new() -> anyhow::Result<Self> {
-        Self::new_with(dirs::BASE.home_dir())
+    pub fn new(filter: Filter) -> anyhow::Result<Self> {
+        Self::new_with(Uri::local(dirs::BASE.home_dir()), filter)
     }
 
     /// Tab content with a specified location.
     #[inline]
-    pub fn new_with<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+    pub fn new_with(location: Uri, filter: Filter) -> anyhow::Result<Self> {
         Ok(Self {
-            contents: dirs::Contents::new(path)?,
-            stack: vec![],
+            view: View::at(&location, Sort::default())?,
+            contents: dirs::Contents::empty(location, Sort::default(), filter),
+            load: Some(Load::new()),
+            measure: None,
+            error: None,
+            preview: None,
         })
     }
 
-    /// Get the location this content points to.
+    /// Tab content showing the results of a content search.
+    pub fn grep(query: grep::Query) -> Self {
+        Self {
+            contents: dirs::Contents::empty(
+                Uri::local(&query.root),
+                Sort::default(),
+                query.filter.clone(),
+            ),
+            view: View::Grep(Matches::new(query)),
+            load: Some(Load::new()),
+            measure: None,
+            error: None,
+            preview: None,
+        }
+    }
+
+    /// Tab content showing the disk usage of a location.
+    pub fn usage<P: AsRef<Path>>(path: P, filter: Filter) -> Self {
+        Self {
+            contents: dirs::Contents::empty(Uri::local(path), Sort::default(), filter),
+            view: View::Usage(Usage::default()),
+            load: Some(Load::new()),
+            measure: None,
+            error: None,
+            preview: None,
+        }
+    }
+
+    /// Tab content showing duplicate files below a location.
+    pub fn duplicates(query: duplicates::Query) -> Self {
+        Self {
+            contents: dirs::Contents::empty(
+                Uri::local(&query.root),
+                Sort::default(),
+                query.filter.clone(),
+            ),
+            view: View::Duplicates(Duplicates::new(query)),
+            load: Some(Load::new()),
+            measure: None,
+            error: None,
+            preview: None,
+        }
+    }
+
+    /// Get the path this content points to, on whichever backend it's on.
     #[inline]
     pub fn location(&self) -> &PathWrap {
         self.contents.location()
     }
 
+    /// Get the location this content points to.
+    #[inline]
+    pub fn uri(&self) -> &Uri {
+        self.contents.uri()
+    }
+
+    /// Display name of the location.
+    pub fn title(&self) -> Cow<'_, str> {
+        match &self.view {
+            View::Directory if self.uri().scheme() == vfs::archived::SCHEME => {
+                // the archive's name along with the folder inside it
+                let location = self.location();
+                let parent =
+                    archive::split(location).and_then(|(a, _)| a.parent().map(Path::to_path_buf));
+
+                match parent {
+                    Some(parent) => location
+                        .strip_prefix(parent)
+                        .unwrap_or(location)
+                        .to_string_lossy(),
+                    None => location.display().to_string_lossy(),
+                }
+            }
+            View::Directory if !self.uri().is_local() => self.uri().to_string().into(),
+            View::Directory => self.location().display().to_string_lossy(),
+            View::Trash(_) => "Trash".into(),
+            View::Search(results) => format!("Search: {}", results.query.text).into(),
+            View::Grep(matches) => format!("In Files: {}", matches.query.text).into(),
+            View::Usage(_) => {
+                format!("Usage: {}", self.location().display().to_string_lossy()).into()
+            }
+            View::Duplicates(_) => format!(
+                "Duplicates: {}",
+                self.location().display().to_string_lossy()
+            )
+            .into(),
+        }
+    }
+
+    /// What needs watching to notice changes to the location, if anything can be.
+    ///
+    /// Items inside an archive only change along with the archive, for example.
+    pub fn watched(&self) -> Option<PathWrap> {
+        let backend = vfs::backend(self.uri()).ok()?;
+
+        match backend.watch(self.uri()) {
+            vfs::Watch::Local(path) => Some(PathWrap::from_into_path(path)),
+            vfs::Watch::None => None,
+        }
+    }
+
+    /// Items to display.
+    pub fn items(&self) -> &[Entry] {
+        match &self.view {
+            View::Directory => self.contents.contents(),
+            View::Trash(items) => items,
+            View::Search(results) => &results.items,
+            // matches are lines rather than items, and the others have their own views
+            View::Grep(_) | View::Usage(_) | View::Duplicates(_) => &[],
+        }
+    }
+
+    /// Check if a background read or search is running.
+    #[inline]
+    pub fn is_loading(&self) -> bool {
+        self.load.is_some()
+    }
+
     /// Change this content to point to a new location.
-    pub fn update_location<P: AsRef<Path>>(&mut self, new: P) -> anyhow::Result<()> {
-        self.contents = dirs::Contents::new(new)?;
+    pub fn update_location(&mut self, new: Uri) -> anyhow::Result<()> {
+        let sort = self.contents.sort();
+        let filter = self.contents.filter().clone();
+
+        self.view = View::at(&new, sort)?;
+        self.contents = dirs::Contents::empty(new, sort, filter);
+        self.load = Some(Load::new());
+        self.measure = None;
+        self.error = None;
         Ok(())
     }
+
+    /// Read the location again after it may have changed.
+    ///
+    /// Local folders are kept up to date by the watcher, so only other locations are read again,
+    /// in the background like when they're opened. Searches and scans are left to finish, and so
+    /// is a read that's still running.
+    pub fn refresh(&mut self) {
+        if self.load.is_some() {
+            return;
+        }
+
+        if let View::Directory = self.view {
+            if !self.uri().is_local() {
+                let (sort, filter) = (self.contents.sort(), self.contents.filter().clone());
+
+                self.contents = dirs::Contents::empty(self.uri().clone(), sort, filter);
+                self.load = Some(Load::new());
+                self.measure = None;
+                self.error = None;
+            }
+
+            return;
+        }
+
+        match &mut self.view {
+            View::Trash(items) => {
+                *items = View::trashed(self.contents.sort());
+                self.measure_sizes();
+            }
+            View::Duplicates(duplicates) => duplicates.prune(),
+            _ => {}
+        }
+    }
+
+    /// Apply changes reported by the watcher.
+    pub fn apply(&mut self, changes: &[Change]) -> anyhow::Result<()> {
+        // trashed items span several directories, so just list them again
+        if let View::Trash(_) = self.view {
+            if changes.iter().any(|c| c.is_inside(self.location())) {
+                self.refresh();
+            }
+
+            return Ok(());
+        }
+
+        // changes are to local paths, so other locations are read again whenever what they
+        // watch changes
+        if !self.uri().is_local() {
+            let watched = self.watched();
+
+            if changes
+                .iter()
+                .flat_map(Change::paths)
+                .any(|p| Some(p) == watched.as_deref())
+            {
+                self.refresh();
+            }
+
+            return Ok(());
+        }
+
+        // search results are a snapshot, but keep the listing underneath up to date
+        let mut changed = false;
+
+        for change in changes {
+            changed |= self.contents.apply(change)?;
+        }
+
+        if changed && self.load.is_none() {
+            self.measure_sizes();
+        }
+
+        Ok(())
+    }
+
+    /// Show the preview panel, or hide it.
+    pub fn toggle_preview(&mut self) {
+        self.preview = match self.preview {
+            Some(_) => None,
+            None => {
+                let mut split = panelled::State::with_position(PanelPosition::Right);
+                split.resize(0.3);
+                Some(split)
+            }
+        };
+    }
+
+    /// Move the split between the items and the preview panel.
+    pub fn resize_preview(&mut self, event: pane_grid::ResizeEvent) {
+        if let Some(split) = &mut self.preview {
+            split.apply(event);
+        }
+    }
+
+    /// Change how items are sorted.
+    pub fn set_sort(&mut self, sort: Sort) {
+        match &mut self.view {
+            View::Trash(items) => sort.apply(items),
+            View::Search(results) if results.query.mode != Mode::Fuzzy => {
+                sort.apply(&mut results.items)
+            }
+            _ => {}
+        }
+
+        self.contents.set_sort(sort);
+    }
+
+    /// Change which items are shown, reading the location again.
+    pub fn set_filter(&mut self, filter: Filter) {
+        let location = self.uri().clone();
+
+        self.view = match std::mem::replace(&mut self.view, View::Directory) {
+            // search again with the new filter
+            View::Search(results) => View::Search(Results::new(Query {
+                filter: filter.clone(),
+                ..results.query
+            })),
+            View::Grep(matches) => View::Grep(Matches::new(grep::Query {
+                filter: filter.clone(),
+                ..matches.query
+            })),
+            View::Usage(_) => View::Usage(Usage::default()),
+            View::Duplicates(duplicates) => View::Duplicates(Duplicates::new(duplicates::Query {
+                filter: filter.clone(),
+                ..duplicates.query
+            })),
+            view => view,
+        };
+        self.contents = dirs::Contents::empty(location, self.contents.sort(), filter);
+        self.load = Some(Load::new());
+        self.measure = None;
+    }
+
+    /// Search below the location, replacing the displayed items with what is found.
+    pub fn search(&mut self, text: String, mode: Mode) -> anyhow::Result<()> {
+        // whatever was read so far stays around for when the search is closed
+        self.stop_loading()?;
+
+        self.view = View::Search(Results::new(Query {
+            root: self.location().clone(),
+            text,
+            mode,
+            filter: self.contents.filter().clone(),
+        }));
+        self.load = Some(Load::new());
+        self.measure = None;
+        Ok(())
+    }
+
+    /// Stop showing search results, disk usage or duplicates, going back to the location.
+    pub fn close_search(&mut self) -> anyhow::Result<()> {
+        if let View::Search(_) | View::Grep(_) | View::Usage(_) | View::Duplicates(_) = self.view {
+            self.update_location(self.uri().clone())?;
+        }
+
+        Ok(())
+    }
+
+    /// Check if background work with some id belongs to this content.
+    #[inline]
+    pub fn is_waiting_for(&self, id: usize) -> bool {
+        [&self.load, &self.measure]
+            .into_iter()
+            .flatten()
+            .any(|l| l.id == id)
+    }
+
+    /// Check if folder sizes are being measured.
+    #[inline]
+    pub fn is_measuring(&self) -> bool {
+        self.measure.is_some()
+    }
+
+    /// Measure the size of every folder shown that isn't known yet.
+    fn measure_sizes(&mut self) {
+        self.measure = match self.view {
+            // folders elsewhere already know their size, or can't be measured cheaply
+            View::Directory if !self.uri().is_local() => None,
+            View::Grep(_) | View::Usage(_) | View::Duplicates(_) => None,
+            _ => Some(Load::new()),
+        };
+    }
+
+    /// Handle progress of the background read.
+    pub fn receive(&mut self, loading: Loading) -> anyhow::Result<()> {
+        if self.measure.as_ref().map(|m| m.id) == Some(loading.id()) {
+            // sizes themselves are kept by the caller
+            if let Loading::Finished(..) = loading {
+                self.measure = None;
+            }
+
+            return Ok(());
+        }
+
+        if self.load.as_ref().map(|l| l.id) != Some(loading.id()) {
+            return Ok(());
+        }
+
+        match loading {
+            Loading::Batch(_, batch) => self.contents.extend(batch),
+            Loading::Hits(_, hits) => {
+                let sort = self.contents.sort();
+
+                if let View::Search(results) = &mut self.view {
+                    results.insert(hits, sort);
+                }
+            }
+            Loading::Matches(_, hits) => {
+                if let View::Grep(matches) = &mut self.view {
+                    matches.insert(hits);
+                }
+            }
+            Loading::Sizes(..) => {}
+            Loading::Usage(_, root) => {
+                if let View::Usage(usage) = &mut self.view {
+                    usage.root = Some(root);
+                }
+            }
+            Loading::Duplicates(_, found) => {
+                if let View::Duplicates(duplicates) = &mut self.view {
+                    duplicates.insert(found);
+                }
+            }
+            Loading::Finished(_, error) => {
+                if let Some(e) = error {
+                    match &mut self.view {
+                        View::Search(results) => results.error = Some(e),
+                        View::Grep(matches) => matches.error = Some(e),
+                        View::Usage(usage) => usage.error = Some(e),
+                        View::Duplicates(duplicates) => duplicates.error = Some(e),
+                        _ => {
+                            tracing::warn!(location = %self.uri(), "failed to read: {}", e);
+                            self.error = Some(e);
+                        }
+                    }
+                }
+
+                self.stop_loading()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Show why something asked of this content couldn't be done, until the location is read
+    /// again.
+    #[inline]
+    pub fn show_error(&mut self, error: &anyhow::Error) {
+        self.error = Some(format!("{error:#}"));
+    }
+
+    /// Stop the background read, keeping whatever was read so far.
+    pub fn stop_loading(&mut self) -> anyhow::Result<()> {
+        self.load = None;
+        self.contents.finish()?;
+        self.measure_sizes();
+        Ok(())
+    }
+
+    /// Read, search and measure the location in the background while needed.
+    ///
+    /// Only folders without a size in the cache are measured.
+    pub fn subscription(
+        &self,
+        sizes: &size::Cache,
+        options: size::Options,
+    ) -> Subscription<Loading> {
+        let measure = match &self.measure {
+            Some(measure) => {
+                let (dirs, cancel) = (sizes.missing(self.items()), measure.cancel.clone());
+                find(
+                    measure.id,
+                    move || Ok::<_, String>(size::spawn(dirs, options, cancel)),
+                    Loading::Sizes,
+                )
+            }
+            None => Subscription::none(),
+        };
+
+        let load = match (&self.load, &self.view) {
+            (Some(load), View::Search(results)) => {
+                let (query, cancel) = (results.query.clone(), load.cancel.clone());
+                find(load.id, move || search::spawn(query, cancel), Loading::Hits)
+            }
+            (Some(load), View::Grep(matches)) => {
+                let (query, cancel) = (matches.query.clone(), load.cancel.clone());
+                find(
+                    load.id,
+                    move || grep::spawn(query, cancel),
+                    Loading::Matches,
+                )
+            }
+            (Some(load), View::Duplicates(duplicates)) => {
+                let (query, cancel) = (duplicates.query.clone(), load.cancel.clone());
+                find(
+                    load.id,
+                    move || Ok::<_, String>(duplicates::spawn(query, cancel)),
+                    Loading::Duplicates,
+                )
+            }
+            (Some(load), View::Usage(_)) => scan(
+                load.id,
+                self.location().clone(),
+                options,
+                load.cancel.clone(),
+            ),
+            (Some(load), _) => read(
+                load.id,
+                self.uri().clone(),
+                self.contents.filter().clone(),
+                load.cancel.clone(),
+            ),
+            (None, _) => Subscription::none(),
+        };
+
+        Subscription::batch([load, measure])
+    }
+}
+
+/// Stream the contents of a location in batches.
+fn read(id: usize, location: Uri, filter: Filter, cancel: CancelToken) -> Subscription<Loading> {
+    struct Reader;
+
+    subscription::channel(
+        (TypeId::of::<Reader>(), id),
+        16,
+        move |mut output| async move {
+            let reader = dirs::Reader::new(location, filter, cancel);
+            let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+            // The reader blocks while waiting for batches, so keep it off the async executor
+            tokio::task::spawn_blocking(move || {
+                for batch in reader {
+                    if sender.send(batch).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let mut error = None;
+
+            while let Some(batch) = receiver.recv().await {
+                match batch {
+                    Ok(batch) => {
+                        let _ = output.send(Loading::Batch(id, batch)).await;
+                    }
+                    Err(e) => error = Some(e.to_string()),
+                }
+            }
+
+            let _ = output.send(Loading::Finished(id, error)).await;
+
+            std::future::pending().await
+        },
+    )
+}
+
+/// Scan a whole tree for its disk usage.
+fn scan(
+    id: usize,
+    location: PathWrap,
+    options: size::Options,
+    cancel: CancelToken,
+) -> Subscription<Loading> {
+    struct Scanner;
+
+    subscription::channel(
+        (TypeId::of::<Scanner>(), id),
+        16,
+        move |mut output| async move {
+            let scanned =
+                tokio::task::spawn_blocking(move || usage::scan(&location, options, &cancel))
+                    .await
+                    .ok()
+                    .flatten();
+
+            let finished = match scanned {
+                Some(root) => {
+                    let _ = output.send(Loading::Usage(id, Arc::new(root))).await;
+                    Loading::Finished(id, None)
+                }
+                None => Loading::Finished(id, Some("failed to scan".to_owned())),
+            };
+
+            let _ = output.send(finished).await;
+
+            std::future::pending().await
+        },
+    )
+}
+
+/// Background work whose results arrive in batches.
+trait Batches: Send + 'static {
+    type Item: Send + 'static;
+
+    /// Block until more results are ready, or [`None`] once done.
+    fn next_batch(&mut self) -> Option<Vec<Self::Item>>;
+
+    /// Stop working as soon as possible.
+    fn cancel(&self);
+}
+
+impl<T: Send + 'static> Batches for search::Search<T> {
+    type Item = T;
+
+    #[inline]
+    fn next_batch(&mut self) -> Option<Vec<T>> {
+        search::Search::next_batch(self)
+    }
+
+    #[inline]
+    fn cancel(&self) {
+        search::Search::cancel(self);
+    }
+}
+
+impl Batches for size::Measurement {
+    type Item = size::Measured;
+
+    #[inline]
+    fn next_batch(&mut self) -> Option<Vec<size::Measured>> {
+        size::Measurement::next_batch(self)
+    }
+
+    #[inline]
+    fn cancel(&self) {
+        size::Measurement::cancel(self);
+    }
+}
+
+/// Stream what a search finds as it is found.
+fn find<B, E, F>(
+    id: usize,
+    spawn: F,
+    batch: fn(usize, Vec<B::Item>) -> Loading,
+) -> Subscription<Loading>
+where
+    B: Batches,
+    E: ToString,
+    F: FnOnce() -> Result<B, E> + Send + 'static,
+{
+    struct Finder;
+
+    subscription::channel(
+        (TypeId::of::<Finder>(), id),
+        16,
+        move |mut output| async move {
+            // the error is made a string first, since it's kept across the await
+            let mut search = match spawn().map_err(|e| e.to_string()) {
+                Ok(s) => s,
+                Err(e) => {
+                    let _ = output.send(Loading::Finished(id, Some(e))).await;
+                    return std::future::pending().await;
+                }
+            };
+
+            let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+            tokio::task::spawn_blocking(move || {
+                while let Some(hits) = search.next_batch() {
+                    if sender.send(hits).is_err() {
+                        search.cancel();
+                        break;
+                    }
+                }
+            });
+
+            while let Some(hits) = receiver.recv().await {
+                let _ = output.send(batch(id, hits)).await;
+            }
+
+            let _ = output.send(Loading::Finished(id, None)).await;
+
+            std::future::pending().await
+        },
+    )
 }
 
 /// Internally managed content state.
@@ -62,6 +947,30 @@ impl State {
 pub struct InternalState {
     /// The currently selected item.
     selected: Option<PathWrap>,
+    /// Text typed into the search box.
+    query: String,
+    /// How the search box matches names.
+    mode: Mode,
+    /// Search the contents of files rather than names.
+    in_files: bool,
+    /// Search file contents with a regular expression.
+    regex: bool,
+    /// Whether letter case matters when searching file contents.
+    case: Case,
+    /// Folder shown in the disk usage view, if not the location itself.
+    drill: Option<PathBuf>,
+    /// Copies picked to be trashed in the duplicates view.
+    picked: HashSet<PathWrap>,
+    /// Items being renamed together, while the rename sheet is open.
+    rename: Option<rename::Form>,
+    /// Permissions and owner of an item, while the properties panel is open.
+    properties: Option<properties::Form>,
+    /// Apps the selection can be opened with, while the chooser is open.
+    open_with: Option<open_with::Chooser>,
+    /// Preview of the selection, read when it was selected.
+    preview: Option<preview::Preview>,
+    /// An action that can't be undone, while it's waiting to be confirmed.
+    confirm: Option<confirm::Prompt>,
 }
 
 impl InternalState {
@@ -77,22 +986,176 @@ impl InternalState {
     }
 }
 
+/// Actions available from the toolbar.
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    Copy,
+    Cut,
+    Paste,
+    NewFolder,
+    Trash,
+    Delete,
+    Restore,
+    EmptyTrash,
+    /// Copy items out of the archive, next to it.
+    Extract,
+    /// Trash the copies picked in the duplicates view.
+    TrashPicked,
+}
+
 /// Internal messages.
 #[derive(Debug, Clone)]
 pub enum Event {
     Item(item::Message),
+    Action(Action),
+    Sort(Sort),
+    Filter(Filter),
+    StopLoading,
+    Query(String),
+    Mode,
+    InFiles,
+    Regex,
+    Case,
+    Search,
+    CloseSearch,
+    /// Open an item directly.
+    Open(PathWrap),
+    /// Show the disk usage of the location.
+    Usage,
+    /// Show the disk usage of a folder below the location.
+    Drill(PathBuf),
+    /// Show the disk usage of the folder containing the one shown.
+    DrillUp,
+    /// Look for duplicate files below the location.
+    Duplicates,
+    /// Pick a copy to be trashed, or unpick it.
+    Pick(PathWrap),
+    /// Pick every copy but the first in each group.
+    PickExtra,
+    /// Rename the selection, or everything shown if nothing is selected, all at once.
+    BulkRename,
+    Rename(rename::Message),
+    /// Show the permissions and owner of the selection.
+    Properties,
+    Property(properties::Message),
+    /// Pick the app to open the selection with.
+    OpenWith,
+    Choose(open_with::Message),
+    /// Show the preview panel, or hide it.
+    TogglePreview,
+    ResizePreview(pane_grid::ResizeEvent),
+    /// Go ahead with an action that can't be undone, or don't.
+    Confirm(confirm::Message),
 }
 
 /// Content component.
 pub struct Content<'app> {
     state: &'app State,
+    /// Known folder sizes.
+    sizes: &'app size::Cache,
 }
 
 impl<'app> Content<'app> {
     /// Create location content state from predefined state.
     #[inline]
-    pub const fn new(state: &'app State) -> Self {
-        Self { state }
+    pub const fn new(state: &'app State, sizes: &'app size::Cache) -> Self {
+        Self { state, sizes }
+    }
+
+    /// Turn an action from the toolbar into the operation it stands for.
+    fn act(&self, internal_state: &mut InternalState, action: Action) -> Option<super::Event> {
+        let selected: Vec<_> = internal_state.selected.iter().cloned().collect();
+        let uris = || {
+            selected
+                .iter()
+                .map(|s| self.state.uri().with_path(s))
+                .collect()
+        };
+
+        let message = match action {
+            Action::Copy => operations::Message::Copy(uris()),
+            Action::Cut => operations::Message::Cut(uris()),
+            Action::Paste => operations::Message::Paste(self.state.uri().clone()),
+            Action::NewFolder => operations::Message::NewFolder(self.state.location().clone()),
+            Action::Trash => {
+                internal_state.selected = None;
+                operations::Message::Trash(selected)
+            }
+            Action::Delete if !self.state.uri().is_local() => {
+                let targets = uris();
+                internal_state.selected = None;
+                operations::Message::Remove(targets)
+            }
+            Action::Delete => {
+                internal_state.selected = None;
+                operations::Message::Delete(selected)
+            }
+            Action::Restore => {
+                internal_state.selected = None;
+                operations::Message::Restore(selected)
+            }
+            Action::EmptyTrash => operations::Message::EmptyTrash,
+            Action::TrashPicked => {
+                let picked = internal_state.picked.drain().collect();
+                operations::Message::Trash(picked)
+            }
+            Action::Extract => {
+                let (archive, _) = archive::split(self.state.location())?;
+
+                // everything shown is extracted when nothing is selected
+                let sources = if selected.is_empty() {
+                    self.state.items().iter().map(|e| e.path.clone()).collect()
+                } else {
+                    selected
+                };
+
+                let destination = archive.parent().unwrap_or(&archive);
+                operations::Message::Extract(sources, PathWrap::from_path(destination))
+            }
+        };
+
+        Some(super::Event::Operation(message))
+    }
+
+    /// Every item, in a grid filling the space it's given.
+    fn items(&self, internal_state: InternalState) -> Element<'_, Event> {
+        let responsive_area = uniform::responsive(move |_| {
+            let grid = uniform(
+                self.state.items().iter().map(|entry| {
+                    container(
+                        item::view(
+                            entry,
+                            item_size(self.state, entry, self.sizes),
+                            if internal_state.is_selected(entry) {
+                                item::Style::Selected
+                            } else {
+                                item::Style::Default
+                            },
+                        )
+                        .map(Event::Item),
+                    )
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .into()
+                }),
+                item::DIMENSIONS,
+            )
+            .spacing_x(12)
+            .spacing_y(12)
+            .allow_more_spacing(true)
+            .on_empty_click(Event::Item(item::Message::Deselect));
+
+            scrollable(container(grid).padding([0.0, 13.0, 0.0, 0.0]))
+                .direction(scrollable::Direction::Vertical(
+                    Properties::new().width(5.6).scroller_width(5.0),
+                ))
+                .into()
+        });
+
+        container(responsive_area)
+            .padding(8)
+            .height(Length::Fill)
+            .into()
     }
 }
 
@@ -109,54 +1172,914 @@ impl<'app> Component<super::Event, gui::Renderer> for Content<'app> {
             Event::Item(i) => match i {
                 item::Message::Select(s) => {
                     if internal_state.is_selected(&s) {
-                        return Some(super::Event::Open(s));
+                        return Some(super::Event::Open(self.state.uri().with_path(s)));
                     } else {
+                        internal_state.preview = self
+                            .state
+                            .items()
+                            .iter()
+                            .find(|e| e.path == s)
+                            .map(|e| preview::Preview::new(e, self.state.uri().is_local()));
                         internal_state.selected = Some(s);
                     }
                 }
-                item::Message::Deselect => internal_state.selected = None,
+                item::Message::Deselect => {
+                    internal_state.selected = None;
+                    internal_state.preview = None;
+                }
             },
+            // nothing is deleted for good without asking first
+            Event::Action(action @ (Action::Delete | Action::EmptyTrash)) => {
+                let names: Vec<String> = match action {
+                    Action::EmptyTrash => self
+                        .state
+                        .items()
+                        .iter()
+                        .map(|e| e.name().to_string_lossy().into_owned())
+                        .collect(),
+                    _ => internal_state
+                        .selected
+                        .iter()
+                        .map(|s| {
+                            s.file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .into_owned()
+                        })
+                        .collect(),
+                };
+
+                if !names.is_empty() {
+                    internal_state.confirm = Some(confirm::Prompt::new(action, names));
+                }
+            }
+            Event::Action(action) => return self.act(internal_state, action),
+            Event::Confirm(confirm::Message::Cancel) => internal_state.confirm = None,
+            Event::Confirm(confirm::Message::Confirm) => {
+                let action = internal_state.confirm.take()?.action();
+                return self.act(internal_state, action);
+            }
+            Event::Sort(sort) => return Some(super::Event::Sort(sort)),
+            Event::Filter(filter) => return Some(super::Event::Filter(filter)),
+            Event::StopLoading => return Some(super::Event::StopLoading),
+            Event::Query(query) => internal_state.query = query,
+            Event::Mode => internal_state.mode = internal_state.mode.next(),
+            Event::InFiles => internal_state.in_files = !internal_state.in_files,
+            Event::Regex => internal_state.regex = !internal_state.regex,
+            Event::Case => internal_state.case = internal_state.case.next(),
+            Event::Search if internal_state.in_files => {
+                if !internal_state.query.is_empty() {
+                    let query = grep::Query {
+                        regex: internal_state.regex,
+                        case: internal_state.case,
+                        ..grep::Query::new(
+                            self.state.location().clone(),
+                            internal_state.query.clone(),
+                            self.state.contents.filter().clone(),
+                        )
+                    };
+
+                    return Some(super::Event::Grep(query));
+                }
+            }
+            Event::Search => {
+                if !internal_state.query.is_empty() {
+                    internal_state.selected = None;
+
+                    return Some(super::Event::Search(
+                        internal_state.query.clone(),
+                        internal_state.mode,
+                    ));
+                }
+            }
+            Event::CloseSearch => {
+                internal_state.selected = None;
+                return Some(super::Event::CloseSearch);
+            }
+            Event::Open(path) => return Some(super::Event::Open(self.state.uri().with_path(path))),
+            Event::Usage => return Some(super::Event::Usage(self.state.location().clone())),
+            Event::Drill(path) => internal_state.drill = Some(path),
+            Event::DrillUp => {
+                internal_state.drill = internal_state
+                    .drill
+                    .take()
+                    .and_then(|d| d.parent().map(Path::to_path_buf))
+                    .filter(|d| d.as_path() != self.state.location().as_ref());
+            }
+            Event::Duplicates => {
+                return Some(super::Event::Duplicates(duplicates::Query {
+                    root: self.state.location().clone(),
+                    filter: self.state.contents.filter().clone(),
+                }));
+            }
+            Event::Pick(path) => {
+                if !internal_state.picked.remove(&path) {
+                    internal_state.picked.insert(path);
+                }
+            }
+            Event::PickExtra => {
+                if let View::Duplicates(duplicates) = &self.state.view {
+                    internal_state.picked = duplicates
+                        .groups
+                        .iter()
+                        .flat_map(|g| g.paths.iter().skip(1).cloned())
+                        .collect();
+                }
+            }
+            Event::BulkRename => {
+                let sources = match &internal_state.selected {
+                    Some(selected) => vec![selected.clone()],
+                    None => self.state.items().iter().map(|e| e.path.clone()).collect(),
+                };
+
+                internal_state.rename = Some(rename::Form::new(sources));
+            }
+            Event::Rename(rename::Message::Cancel) => internal_state.rename = None,
+            Event::Rename(rename::Message::Apply) => {
+                let operation = internal_state.rename.take()?.operation()?;
+                internal_state.selected = None;
+
+                return Some(super::Event::Operation(operations::Message::Start(
+                    operation,
+                )));
+            }
+            Event::Rename(message) => {
+                if let Some(form) = &mut internal_state.rename {
+                    form.update(message);
+                }
+            }
+            Event::Properties => {
+                let selected = internal_state.selected.as_ref()?;
+                let entry = self.state.items().iter().find(|e| e.path == *selected)?;
+
+                internal_state.properties = Some(properties::Form::new(entry));
+            }
+            Event::Property(properties::Message::Cancel) => internal_state.properties = None,
+            Event::Property(properties::Message::Apply) => {
+                let operation = internal_state.properties.take()?.operation().ok()??;
+
+                return Some(super::Event::Operation(operations::Message::Start(
+                    operation,
+                )));
+            }
+            Event::Property(message) => {
+                if let Some(form) = &mut internal_state.properties {
+                    form.update(message);
+                }
+            }
+            Event::OpenWith => {
+                let selected = internal_state.selected.as_ref()?;
+                let location = self.state.uri().with_path(selected);
+
+                internal_state.open_with = Some(open_with::Chooser::new(location));
+            }
+            Event::Choose(open_with::Message::Cancel) => internal_state.open_with = None,
+            Event::Choose(open_with::Message::Pick(id)) => {
+                let chooser = internal_state.open_with.take()?;
+                let app = chooser.app(&id)?.clone();
+
+                if chooser.remember() {
+                    if let Err(e) = apps::set_default(chooser.mime(), &app.id) {
+                        tracing::warn!(mime = chooser.mime(), "failed to set default app: {}", e);
+                    }
+                }
+
+                return Some(super::Event::OpenWith(
+                    chooser.location().clone(),
+                    Box::new(app),
+                ));
+            }
+            Event::Choose(message) => {
+                if let Some(chooser) = &mut internal_state.open_with {
+                    chooser.update(message);
+                }
+            }
+            Event::TogglePreview => return Some(super::Event::TogglePreview),
+            Event::ResizePreview(event) => return Some(super::Event::ResizePreview(event)),
         }
 
         None
     }
 
     fn view(&self, internal_state: &Self::State) -> Element<'_, Self::Event> {
+        let bar = toolbar(self.state, internal_state);
+        let status = status_bar(self.state, self.sizes, internal_state);
+
+        if let Some(prompt) = &internal_state.confirm {
+            return column!(
+                bar,
+                container(prompt.view().map(Event::Confirm))
+                    .padding(8)
+                    .height(Length::Fill)
+            )
+            .into();
+        }
+
+        if let Some(chooser) = &internal_state.open_with {
+            return column!(
+                bar,
+                container(chooser.view().map(Event::Choose))
+                    .padding(8)
+                    .height(Length::Fill)
+            )
+            .into();
+        }
+
+        if let Some(form) = &internal_state.rename {
+            return column!(
+                bar,
+                container(form.view().map(Event::Rename))
+                    .padding(8)
+                    .height(Length::Fill)
+            )
+            .into();
+        }
+
+        match &self.state.view {
+            View::Grep(matches) => {
+                return column!(bar, container(matches_view(matches)).padding(8)).into();
+            }
+            View::Usage(usage) => {
+                return column!(bar, container(usage_view(usage, internal_state)).padding(8))
+                    .into();
+            }
+            View::Duplicates(duplicates) => {
+                return column!(
+                    bar,
+                    container(duplicates_view(duplicates, internal_state)).padding(8)
+                )
+                .into();
+            }
+            _ => {}
+        }
+
+        let panel = internal_state.properties.as_ref().map(|form| {
+            container(form.view().map(Event::Property))
+                .padding(8)
+                .width(320)
+                .height(Length::Fill)
+        });
+
+        // the selection may have been dropped since it was previewed
+        let preview = internal_state
+            .preview
+            .clone()
+            .filter(|p| internal_state.is_selected(&p.entry().path));
+
         // weird lifetime shenanigans without ownership
         let internal_state = internal_state.clone();
+        let items = move || self.items(internal_state.clone());
 
-        let responsive_area = uniform::responsive(move |_| {
-            let grid = uniform(
-                self.state.contents.contents().iter().map(|path| {
-                    container(
-                        item::view(
-                            path.clone(),
-                            if internal_state.is_selected(path) {
-                                item::Style::Selected
-                            } else {
-                                item::Style::Default
-                            },
-                        )
-                        .map(Event::Item),
+        let mut area = match &self.state.preview {
+            Some(split) => Row::new().push(
+                unpanelled(items)
+                    .panel(split, move |_| preview_panel(preview.as_ref()))
+                    .into_inner()
+                    .on_resize(8, Event::ResizePreview),
+            ),
+            None => Row::new().push(items()),
+        };
+
+        if let Some(panel) = panel {
+            area = area.push(panel);
+        }
+
+        column!(bar, area.height(Length::Fill), status).into()
+    }
+}
+
+/// Preview of the selection, or a hint to select something.
+fn preview_panel<'a>(preview: Option<&preview::Preview>) -> Element<'a, Event> {
+    let content = match preview {
+        Some(preview) => preview.view(),
+        None => text("Select an item to preview it")
+            .size(16)
+            .font(theme::fonts::Roboto::Regular)
+            .into(),
+    };
+
+    container(content)
+        .padding(8)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
+}
+
+/// Size of an item, if known.
+fn item_size(state: &State, entry: &Entry, sizes: &size::Cache) -> Option<u64> {
+    // folders elsewhere already know their size
+    if entry.is_dir() && state.uri().is_local() {
+        // symlinks to folders aren't measured
+        sizes.get(entry).map(|s| s.bytes)
+    } else {
+        Some(entry.size)
+    }
+}
+
+/// Item count and sizes of the location and selection.
+fn status_bar<'a>(
+    state: &State,
+    sizes: &size::Cache,
+    internal_state: &InternalState,
+) -> Element<'a, Event> {
+    let items = state.items();
+
+    let total = if state.uri().is_local() {
+        sizes.total(items)
+    } else {
+        Some(items.iter().map(|e| e.size).sum())
+    };
+
+    let summary = match total {
+        Some(total) => format!("{} items, {}", items.len(), size::format(total)),
+        None if state.is_measuring() => format!("{} items, measuring folders…", items.len()),
+        None => format!("{} items", items.len()),
+    };
+
+    let selection = internal_state
+        .selected
+        .as_ref()
+        .and_then(|s| items.iter().find(|e| e.path == *s))
+        .map(|entry| {
+            let name = entry.name().to_string_lossy().into_owned();
+
+            match item_size(state, entry, sizes) {
+                Some(bytes) => format!("{name}: {}", size::format(bytes)),
+                None => name,
+            }
+        })
+        .unwrap_or_default();
+
+    row!(
+        text(summary)
+            .size(14)
+            .font(theme::fonts::Roboto::Regular)
+            .width(Length::Fill),
+        text(selection).size(14).font(theme::fonts::Roboto::Regular),
+    )
+    .padding([4, 8])
+    .into()
+}
+
+/// How many of the largest files are listed next to the treemap.
+const LARGEST_FILES: usize = 50;
+
+/// Treemap of the folder being looked at, with the largest files inside it.
+fn usage_view<'a>(usage: &'a Usage, internal_state: &InternalState) -> Element<'a, Event> {
+    let Some(root) = &usage.root else {
+        return text(if usage.error.is_some() {
+            "Nothing to show"
+        } else {
+            "Scanning…"
+        })
+        .size(16)
+        .font(theme::fonts::Roboto::Regular)
+        .into();
+    };
+
+    let node = internal_state
+        .drill
+        .as_deref()
+        .and_then(|d| root.find(d))
+        .unwrap_or(root);
+
+    let mut largest = Column::new().spacing(2);
+
+    for file in node.largest_files(LARGEST_FILES) {
+        let name = file
+            .path
+            .strip_prefix(&node.path)
+            .unwrap_or(&file.path)
+            .to_string_lossy()
+            .into_owned();
+
+        largest = largest.push(
+            button(
+                row!(
+                    text(name)
+                        .size(14)
+                        .font(theme::fonts::Roboto::Regular)
+                        .width(Length::Fill),
+                    text(size::format(file.bytes))
+                        .size(14)
+                        .font(theme::fonts::Roboto::Regular),
+                )
+                .spacing(8),
+            )
+            .width(Length::Fill)
+            .padding([2, 6])
+            .style(TabButtonStyle::Default.into())
+            .on_press(Event::Open(PathWrap::from_path(&file.path))),
+        );
+    }
+
+    row!(
+        canvas(Treemap::new(node, Event::Drill))
+            .width(Length::FillPortion(3))
+            .height(Length::Fill),
+        column!(
+            text("Largest Files")
+                .size(16)
+                .font(theme::fonts::Roboto::Regular),
+            scrollable(container(largest).padding([0.0, 13.0, 0.0, 0.0])).direction(
+                scrollable::Direction::Vertical(Properties::new().width(5.6).scroller_width(5.0),)
+            ),
+        )
+        .spacing(8)
+        .width(Length::FillPortion(1)),
+    )
+    .spacing(8)
+    .into()
+}
+
+/// Every group of copies, with the copies picked to be trashed highlighted.
+fn duplicates_view<'a>(
+    duplicates: &'a Duplicates,
+    internal_state: &InternalState,
+) -> Element<'a, Event> {
+    let mut list = Column::new().spacing(4);
+
+    for group in &duplicates.groups {
+        list = list.push(
+            text(format!(
+                "{} copies of {}, {} wasted",
+                group.paths.len(),
+                size::format(group.size),
+                size::format(group.wasted())
+            ))
+            .size(16)
+            .font(theme::fonts::SofiaSans::Black),
+        );
+
+        for path in &group.paths {
+            let name = path
+                .strip_prefix(&duplicates.query.root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned();
+
+            let picked = internal_state.picked.contains(path);
+
+            list = list.push(
+                row!(
+                    button(
+                        text(if picked { format!("✕  {name}") } else { name })
+                            .size(14)
+                            .font(theme::fonts::Roboto::Regular)
                     )
                     .width(Length::Fill)
-                    .height(Length::Fill)
-                    .into()
-                }),
-                item::DIMENSIONS,
+                    .padding([2, 6])
+                    .style(
+                        if picked {
+                            TabButtonStyle::Focused
+                        } else {
+                            TabButtonStyle::Default
+                        }
+                        .into()
+                    )
+                    .on_press(Event::Pick(path.clone())),
+                    button(text("Open").size(14).font(theme::fonts::Roboto::Regular))
+                        .padding([2, 6])
+                        .style(TabButtonStyle::Default.into())
+                        .on_press(Event::Open(path.clone())),
+                )
+                .spacing(4),
+            );
+        }
+    }
+
+    scrollable(container(list).padding([0.0, 13.0, 0.0, 0.0]))
+        .direction(scrollable::Direction::Vertical(
+            Properties::new().width(5.6).scroller_width(5.0),
+        ))
+        .into()
+}
+
+/// Most matching lines drawn, since every one is a widget of its own.
+const MATCHES_SHOWN: usize = 500;
+
+/// Matching lines of a content search, grouped by file, up to [`MATCHES_SHOWN`].
+fn matches_view<'a>(matches: &Matches) -> Element<'a, Event> {
+    let line = |number: usize, content: &str| {
+        text(format!("{number:>6}  {content}"))
+            .size(14)
+            .font(theme::fonts::RobotoMono::Regular)
+    };
+
+    let mut list = Column::new().spacing(4);
+    let mut shown = 0;
+
+    for hit in &matches.hits {
+        if shown >= MATCHES_SHOWN {
+            break;
+        }
+
+        let name = hit
+            .path
+            .strip_prefix(&matches.query.root)
+            .unwrap_or(&hit.path)
+            .to_string_lossy()
+            .into_owned();
+
+        list = list.push(text(name).size(16).font(theme::fonts::RobotoMono::Bold));
+
+        for m in hit.matches.iter().take(MATCHES_SHOWN - shown) {
+            shown += 1;
+            let first = m.line - m.before.len();
+
+            let lines = m
+                .before
+                .iter()
+                .chain(std::iter::once(&m.text))
+                .chain(&m.after)
+                .enumerate()
+                .map(|(i, content)| line(first + i, content).into());
+
+            list = list.push(
+                button(Column::with_children(lines.collect()))
+                    .width(Length::Fill)
+                    .padding([4, 8])
+                    .style(TabButtonStyle::Default.into())
+                    .on_press(Event::Open(hit.path.clone())),
+            );
+        }
+    }
+
+    let count = matches.count();
+
+    if count > shown {
+        list = list.push(
+            text(format!(
+                "and {} more matches, narrow the search to see them",
+                count - shown
+            ))
+            .size(16)
+            .font(theme::fonts::Roboto::Regular),
+        );
+    }
+
+    scrollable(container(list).padding([0.0, 13.0, 0.0, 0.0]))
+        .direction(scrollable::Direction::Vertical(
+            Properties::new().width(5.6).scroller_width(5.0),
+        ))
+        .into()
+}
+
+/// Actions that can be performed on the current location and selection.
+fn toolbar<'a>(state: &State, internal_state: &InternalState) -> Element<'a, Event> {
+    let has_selection = internal_state.selected.is_some();
+    let sort = state.contents.sort();
+    let filter = state.contents.filter();
+
+    // folders are never opened with other apps
+    let has_file = internal_state
+        .selected
+        .as_ref()
+        .and_then(|s| state.items().iter().find(|e| e.path == *s))
+        .is_some_and(|e| !e.is_dir());
+
+    let open_with = || {
+        let b = button(
+            text("Open With…")
+                .size(16)
+                .font(theme::fonts::Roboto::Regular),
+        )
+        .padding([4, 8])
+        .style(TabButtonStyle::Default.into());
+
+        if has_file {
+            b.on_press(Event::OpenWith)
+        } else {
+            b
+        }
+    };
+
+    let tool = |label: &'a str, action: Action, enabled: bool| {
+        let b = button(text(label).size(16).font(theme::fonts::Roboto::Regular))
+            .padding([4, 8])
+            .style(TabButtonStyle::Default.into());
+
+        if enabled {
+            b.on_press(Event::Action(action))
+        } else {
+            b
+        }
+    };
+
+    let tools = match &state.view {
+        // archives can only be copied out of
+        View::Directory if state.uri().scheme() == vfs::archived::SCHEME => row!(
+            tool("Copy", Action::Copy, has_selection),
+            tool("Extract", Action::Extract, !state.items().is_empty()),
+        ),
+        // items elsewhere can't be trashed or measured
+        View::Directory if !state.uri().is_local() => row!(
+            open_with(),
+            tool("Copy", Action::Copy, has_selection),
+            tool("Paste", Action::Paste, true),
+            tool("Delete", Action::Delete, has_selection),
+        ),
+        View::Directory => row!(
+            open_with(),
+            tool("Copy", Action::Copy, has_selection),
+            tool("Cut", Action::Cut, has_selection),
+            tool("Paste", Action::Paste, true),
+            tool("New Folder", Action::NewFolder, true),
+            tool("Move to Trash", Action::Trash, has_selection),
+            tool("Delete", Action::Delete, has_selection),
+            {
+                let properties = button(
+                    text("Properties")
+                        .size(16)
+                        .font(theme::fonts::Roboto::Regular),
+                )
+                .padding([4, 8])
+                .style(TabButtonStyle::Default.into());
+
+                if has_selection {
+                    properties.on_press(Event::Properties)
+                } else {
+                    properties
+                }
+            },
+            {
+                let rename = button(text("Rename…").size(16).font(theme::fonts::Roboto::Regular))
+                    .padding([4, 8])
+                    .style(TabButtonStyle::Default.into());
+
+                if state.items().is_empty() || internal_state.rename.is_some() {
+                    rename
+                } else {
+                    rename.on_press(Event::BulkRename)
+                }
+            },
+            button(
+                text("Disk Usage")
+                    .size(16)
+                    .font(theme::fonts::Roboto::Regular)
             )
-            .spacing_x(12)
-            .spacing_y(12)
-            .allow_more_spacing(true)
-            .on_empty_click(Event::Item(item::Message::Deselect));
+            .padding([4, 8])
+            .style(TabButtonStyle::Default.into())
+            .on_press(Event::Usage),
+            button(
+                text("Duplicates")
+                    .size(16)
+                    .font(theme::fonts::Roboto::Regular)
+            )
+            .padding([4, 8])
+            .style(TabButtonStyle::Default.into())
+            .on_press(Event::Duplicates),
+        ),
+        View::Trash(items) => row!(
+            tool("Restore", Action::Restore, has_selection),
+            tool("Delete", Action::Delete, has_selection),
+            tool("Empty Trash", Action::EmptyTrash, !items.is_empty()),
+        ),
+        View::Search(_) => row!(
+            button(text("Back").size(16).font(theme::fonts::Roboto::Regular))
+                .padding([4, 8])
+                .style(TabButtonStyle::Default.into())
+                .on_press(Event::CloseSearch),
+            tool("Copy", Action::Copy, has_selection),
+            tool("Cut", Action::Cut, has_selection),
+            tool("Move to Trash", Action::Trash, has_selection),
+            tool("Delete", Action::Delete, has_selection),
+        ),
+        View::Grep(_) => row!(
+            button(text("Back").size(16).font(theme::fonts::Roboto::Regular))
+                .padding([4, 8])
+                .style(TabButtonStyle::Default.into())
+                .on_press(Event::CloseSearch)
+        ),
+        View::Usage(_) => {
+            let up = button(text("Up").size(16).font(theme::fonts::Roboto::Regular))
+                .padding([4, 8])
+                .style(TabButtonStyle::Default.into());
 
-            scrollable(container(grid).padding([0.0, 13.0, 0.0, 0.0]))
-                .direction(scrollable::Direction::Vertical(
-                    Properties::new().width(5.6).scroller_width(5.0),
+            row!(
+                button(text("Back").size(16).font(theme::fonts::Roboto::Regular))
+                    .padding([4, 8])
+                    .style(TabButtonStyle::Default.into())
+                    .on_press(Event::CloseSearch),
+                if internal_state.drill.is_some() {
+                    up.on_press(Event::DrillUp)
+                } else {
+                    up
+                },
+            )
+        }
+        View::Duplicates(duplicates) => row!(
+            button(text("Back").size(16).font(theme::fonts::Roboto::Regular))
+                .padding([4, 8])
+                .style(TabButtonStyle::Default.into())
+                .on_press(Event::CloseSearch),
+            button(
+                text("Pick Extra Copies")
+                    .size(16)
+                    .font(theme::fonts::Roboto::Regular)
+            )
+            .padding([4, 8])
+            .style(TabButtonStyle::Default.into())
+            .on_press(Event::PickExtra),
+            tool(
+                "Move Picked to Trash",
+                Action::TrashPicked,
+                !internal_state.picked.is_empty() && !duplicates.groups.is_empty()
+            ),
+        ),
+    };
+
+    let mut bar = Row::new()
+        .push(tools.spacing(4).width(Length::Fill))
+        .spacing(8)
+        .align_items(Alignment::Center);
+
+    let status = match &state.view {
+        _ if state.error.is_some() => state.error.clone(),
+        View::Search(results) => match &results.error {
+            Some(e) => Some(e.clone()),
+            None if state.is_loading() => Some(format!("Searching… {} found", results.items.len())),
+            None => Some(format!("{} found", results.items.len())),
+        },
+        View::Grep(matches) => match &matches.error {
+            Some(e) => Some(e.clone()),
+            None => Some(format!(
+                "{}{} matches in {} files",
+                if state.is_loading() {
+                    "Searching… "
+                } else {
+                    ""
+                },
+                matches.count(),
+                matches.hits.len()
+            )),
+        },
+        View::Usage(usage) => match (&usage.error, &usage.root) {
+            (Some(e), _) => Some(e.clone()),
+            (None, Some(root)) => {
+                let node = internal_state
+                    .drill
+                    .as_deref()
+                    .and_then(|d| root.find(d))
+                    .unwrap_or(root);
+
+                Some(format!(
+                    "{} in {}",
+                    size::format(node.bytes),
+                    node.path.display()
                 ))
-                .into()
-        });
+            }
+            (None, None) => None,
+        },
+        View::Duplicates(duplicates) => match &duplicates.error {
+            Some(e) => Some(e.clone()),
+            None => {
+                let progress = duplicates.progress;
+                let found = format!(
+                    "{} groups, {} wasted",
+                    duplicates.groups.len(),
+                    size::format(duplicates.wasted())
+                );
 
-        container(responsive_area).padding(8).into()
+                Some(if !state.is_loading() {
+                    found
+                } else if progress.candidates == 0 {
+                    format!("Listing… {} files", progress.listed)
+                } else {
+                    format!(
+                        "Comparing… {} of {} files, {found}",
+                        progress.compared, progress.candidates
+                    )
+                })
+            }
+        },
+        _ if state.is_loading() => Some(format!("Loading… {} items", state.items().len())),
+        _ => None,
+    };
+
+    if let Some(status) = status {
+        bar = bar.push(text(status).size(16).font(theme::fonts::Roboto::Regular));
+    }
+
+    if state.is_loading() {
+        bar = bar.push(
+            button(text("Stop").size(16).font(theme::fonts::Roboto::Regular))
+                .padding([4, 8])
+                .style(TabButtonStyle::Default.into())
+                .on_press(Event::StopLoading),
+        );
     }
+
+    // filter and sort controls, pushed to the far end
+    let mut options = Row::new().spacing(4).align_items(Alignment::Center);
+
+    // searching reads everything below the location, so only local ones can be searched
+    if let (View::Directory, true) = (&state.view, state.uri().is_local()) {
+        options = options.push(
+            text_input("Search…", &internal_state.query)
+                .on_input(Event::Query)
+                .on_submit(Event::Search)
+                .size(16)
+                .padding([4, 8])
+                .width(200),
+        );
+        options = options.push(toggle_button(
+            "In Files",
+            internal_state.in_files,
+            Event::InFiles,
+        ));
+
+        if internal_state.in_files {
+            options = options.push(toggle_button("Regex", internal_state.regex, Event::Regex));
+            options = options.push(toggle_button(
+                internal_state.case.label(),
+                internal_state.case != Case::Smart,
+                Event::Case,
+            ));
+        } else {
+            options = options.push(toggle_button(
+                internal_state.mode.label(),
+                false,
+                Event::Mode,
+            ));
+        }
+    }
+
+    // only views showing items in a grid have room for a preview
+    if matches!(
+        state.view,
+        View::Directory | View::Trash(_) | View::Search(_)
+    ) {
+        options = options.push(toggle_button(
+            "Preview",
+            state.preview.is_some(),
+            Event::TogglePreview,
+        ));
+    }
+
+    let mut hidden = filter.clone();
+    hidden.show_hidden = !filter.show_hidden;
+    options = options.push(toggle_button(
+        "Hidden",
+        filter.show_hidden,
+        Event::Filter(hidden),
+    ));
+
+    let mut ignored = filter.clone();
+    ignored.respect_ignore = !filter.respect_ignore;
+    options = options.push(toggle_button(
+        "Ignored",
+        !filter.respect_ignore,
+        Event::Filter(ignored),
+    ));
+
+    for key in Key::ALL {
+        let label = if key == sort.key {
+            let arrow = match sort.order {
+                Order::Ascending => "↑",
+                Order::Descending => "↓",
+            };
+
+            format!("{} {arrow}", key.label())
+        } else {
+            key.label().to_owned()
+        };
+
+        options = options.push(toggle_button(
+            label,
+            sort.key == key,
+            Event::Sort(sort.toggle(key)),
+        ));
+    }
+
+    options = options.push(toggle_button(
+        "Folders First",
+        sort.folders_first,
+        Event::Sort(Sort {
+            folders_first: !sort.folders_first,
+            ..sort
+        }),
+    ));
+
+    bar.push(options).padding([8, 8, 0, 8]).into()
+}
+
+/// A toolbar button for a view option, highlighted while active.
+pub(super) fn toggle_button<'a, M: Clone + 'a>(
+    label: impl ToString,
+    active: bool,
+    on_press: M,
+) -> Element<'a, M> {
+    button(text(label).size(16).font(theme::fonts::Roboto::Regular))
+        .padding([4, 8])
+        .style(
+            if active {
+                TabButtonStyle::Focused
+            } else {
+                TabButtonStyle::Default
+            }
+            .into(),
+        )
+        .on_press(on_press)
+        .into()
 }
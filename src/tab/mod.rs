@@ -1,35 +1,112 @@
 //! Tab widget.
 
-use std::path::Path;
+use std::borrow::Cow;
+use std::path::PathBuf;
 
+use doseer_core::apps::{App, Registry};
+use doseer_core::archive;
+use doseer_core::dirs::Change;
+use doseer_core::duplicates;
+use doseer_core::filter::Filter;
+use doseer_core::grep;
+use doseer_core::mime;
 use doseer_core::path::PathWrap;
+use doseer_core::search::Mode;
+use doseer_core::size;
+use doseer_core::sort::Sort;
+use doseer_core::vfs::{self, Uri};
 
 use iced::widget::{component, pane_grid, Component};
-use iced::Command;
+use iced::{Command, Subscription};
 
 use crate::gui::{self, Element};
-use crate::theme;
+use crate::{operations, theme};
 
-use self::content::content;
+use self::content::{content, Loading};
 
+pub mod confirm;
 pub mod content;
+pub mod open_with;
+pub mod preview;
+pub mod properties;
+pub mod rename;
+pub mod usage;
 pub mod watcher;
 
+/// What opening a location takes, found out in the background.
+#[derive(Debug, Clone)]
+pub enum Opened {
+    /// Show a folder, or the inside of an archive, in the current pane.
+    Folder(Uri),
+    /// Open a local copy of a file with an app, or whatever the system picks without one.
+    File(PathBuf, Option<Box<App>>),
+}
+
+/// Find out what opening a location takes, copying files from elsewhere to open them locally.
+fn resolve(location: Uri, app: Option<Box<App>>) -> anyhow::Result<Opened> {
+    if app.is_none() {
+        if vfs::backend(&location)?.stat(&location)?.is_dir() {
+            return Ok(Opened::Folder(location));
+        }
+
+        // archives are browsed like folders
+        let path = location.path();
+        if location.is_local() && archive::Format::detect(path).is_some() {
+            return Ok(Opened::Folder(vfs::locate(path)));
+        }
+    }
+
+    // other programs need a real file to open
+    let local = vfs::local_copy(&location)?;
+
+    // the app Open With would suggest first
+    let app = app.or_else(|| {
+        Registry::shared()
+            .default_for(&mime::detect(&local))
+            .cloned()
+            .map(Box::new)
+    });
+
+    Ok(Opened::File(local, app))
+}
+
 /// Create tab widget from given state.
 #[inline]
-pub const fn tab(state: &State) -> Tab {
-    Tab::new(state)
+pub const fn tab<'app>(state: &'app State, sizes: &'app size::Cache) -> Tab<'app> {
+    Tab::new(state, sizes)
 }
 
 /// External messages.
 #[derive(Debug, Clone)]
 pub enum Message {
     /// Open this location in the current pane.
-    Open(PathWrap),
-    /// Update the contents of the current pane.
-    Update,
-    /// Failed to watch location.
-    WatchFail,
+    Open(Uri),
+    /// Open a file with a specific app.
+    OpenWith(Uri, Box<App>),
+    /// What opening a location takes, or why it can't be opened.
+    Opened(Result<Opened, String>),
+    /// Run a file operation.
+    Operation(operations::Message),
+    /// Change how the current pane is sorted.
+    Sort(Sort),
+    /// Change which items the current pane shows.
+    Filter(Filter),
+    /// Stop reading the current pane's location.
+    StopLoading,
+    /// Search below the current pane's location.
+    Search(String, Mode),
+    /// Go back from search results to the current pane's location.
+    CloseSearch,
+    /// Search the contents of files, showing the results in a new tab.
+    Grep(grep::Query),
+    /// Show the disk usage of a location in a new tab.
+    Usage(PathWrap),
+    /// Look for duplicate files, showing them in a new tab.
+    Duplicates(duplicates::Query),
+    /// Show the current pane's preview panel, or hide it.
+    TogglePreview,
+    /// Move the split between the current pane's items and preview panel.
+    ResizePreview(pane_grid::ResizeEvent),
 }
 
 /// Externally managed state.
@@ -44,38 +121,207 @@ pub struct State {
 impl State {
     /// Default tab state.
     #[inline]
-    pub fn new() -> anyhow::Result<Self> {
-        let (pane_grid, focused) = pane_grid::State::new(content::State::new()?);
+    pub fn new(filter: Filter) -> anyhow::Result<Self> {
+        let (pane_grid, focused) = pane_grid::State::new(content::State::new(filter)?);
 
         Ok(Self { pane_grid, focused })
     }
 
     /// New tab state with specified location for the first pane.
     #[inline]
-    pub fn new_with<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
-        let (pane_grid, focused) = pane_grid::State::new(content::State::new_with(path)?);
+    pub fn new_with(location: Uri, filter: Filter) -> anyhow::Result<Self> {
+        let (pane_grid, focused) =
+            pane_grid::State::new(content::State::new_with(location, filter)?);
 
         Ok(Self { pane_grid, focused })
     }
 
-    /// Open a location in the current pane.
-    pub fn open(&mut self, path: &PathWrap) -> anyhow::Result<Command<Message>> {
-        if path.is_dir() {
-            self.pane_grid
+    /// New tab showing the results of a content search.
+    #[inline]
+    pub fn grep(query: grep::Query) -> Self {
+        let (pane_grid, focused) = pane_grid::State::new(content::State::grep(query));
+
+        Self { pane_grid, focused }
+    }
+
+    /// New tab showing the disk usage of a location.
+    #[inline]
+    pub fn usage(path: &PathWrap, filter: Filter) -> Self {
+        let (pane_grid, focused) = pane_grid::State::new(content::State::usage(path, filter));
+
+        Self { pane_grid, focused }
+    }
+
+    /// New tab showing duplicate files below a location.
+    #[inline]
+    pub fn duplicates(query: duplicates::Query) -> Self {
+        let (pane_grid, focused) = pane_grid::State::new(content::State::duplicates(query));
+
+        Self { pane_grid, focused }
+    }
+
+    /// Open a location in the current pane, or with another program if it's a file, or with a
+    /// specific app.
+    ///
+    /// Finding out which it is can take a network round trip, and files elsewhere have to be
+    /// downloaded first, so that's done in the background.
+    pub fn open(&self, location: Uri, app: Option<Box<App>>) -> Command<Message> {
+        Command::perform(
+            async move {
+                match tokio::task::spawn_blocking(move || resolve(location, app)).await {
+                    Ok(result) => result.map_err(|e| format!("{e:#}")),
+                    Err(e) => Err(e.to_string()),
+                }
+            },
+            Message::Opened,
+        )
+    }
+
+    /// Finish opening a location, once it's known what it takes.
+    pub fn opened(&mut self, opened: Opened) -> anyhow::Result<()> {
+        match opened {
+            Opened::Folder(location) => self
+                .pane_grid
                 .get_mut(&self.focused)
                 .unwrap()
-                .update_location(path)?;
-            return Ok(watcher::command(path));
+                .update_location(location)?,
+            Opened::File(path, Some(app)) => app.launch(&[path])?,
+            Opened::File(path, None) => open::that(path)?,
         }
 
-        open::that(path.as_ref())?;
-        Ok(Command::none())
+        Ok(())
+    }
+
+    /// Change how the current pane is sorted.
+    #[inline]
+    pub fn set_sort(&mut self, sort: Sort) {
+        self.pane_grid
+            .get_mut(&self.focused)
+            .unwrap()
+            .set_sort(sort);
+    }
+
+    /// Show the current pane's preview panel, or hide it.
+    #[inline]
+    pub fn toggle_preview(&mut self) {
+        self.pane_grid
+            .get_mut(&self.focused)
+            .unwrap()
+            .toggle_preview();
+    }
+
+    /// Move the split between the current pane's items and preview panel.
+    #[inline]
+    pub fn resize_preview(&mut self, event: pane_grid::ResizeEvent) {
+        self.pane_grid
+            .get_mut(&self.focused)
+            .unwrap()
+            .resize_preview(event);
+    }
+
+    /// Change which items the current pane shows.
+    #[inline]
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.pane_grid
+            .get_mut(&self.focused)
+            .unwrap()
+            .set_filter(filter);
+    }
+
+    /// Stop reading the current pane's location, keeping what was read so far.
+    #[inline]
+    pub fn stop_loading(&mut self) -> anyhow::Result<()> {
+        self.pane_grid
+            .get_mut(&self.focused)
+            .unwrap()
+            .stop_loading()
+    }
+
+    /// Search below the current pane's location.
+    #[inline]
+    pub fn search(&mut self, text: String, mode: Mode) -> anyhow::Result<()> {
+        self.pane_grid
+            .get_mut(&self.focused)
+            .unwrap()
+            .search(text, mode)
+    }
+
+    /// Go back from search results to the current pane's location.
+    #[inline]
+    pub fn close_search(&mut self) -> anyhow::Result<()> {
+        self.pane_grid
+            .get_mut(&self.focused)
+            .unwrap()
+            .close_search()
+    }
+
+    /// Show why something asked of the current pane couldn't be done.
+    #[inline]
+    pub fn show_error(&mut self, error: &anyhow::Error) {
+        self.pane_grid
+            .get_mut(&self.focused)
+            .unwrap()
+            .show_error(error);
+    }
+
+    /// Pass progress of a background read to the pane it belongs to.
+    ///
+    /// Gives the progress back if no pane in this tab is waiting for it.
+    pub fn receive(&mut self, loading: Loading) -> anyhow::Result<Option<Loading>> {
+        for (_, pane) in self.pane_grid.iter_mut() {
+            if pane.is_waiting_for(loading.id()) {
+                pane.receive(loading)?;
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(loading))
+    }
+
+    /// Background reads and measurements of every pane.
+    pub fn subscription(
+        &self,
+        sizes: &size::Cache,
+        options: size::Options,
+    ) -> Subscription<Loading> {
+        Subscription::batch(
+            self.pane_grid
+                .iter()
+                .map(|(_, pane)| pane.subscription(sizes, options)),
+        )
+    }
+
+    /// Apply changes to every pane showing the affected location.
+    pub fn apply(&mut self, changes: &[Change]) {
+        for (_, pane) in self.pane_grid.iter_mut() {
+            if let Err(e) = pane.apply(changes) {
+                tracing::warn!(location = %pane.uri(), "failed to update: {:?}", e);
+            }
+        }
+    }
+
+    /// Read every pane again where the watcher can't tell what changed.
+    pub fn refresh(&mut self) {
+        for (_, pane) in self.pane_grid.iter_mut() {
+            pane.refresh();
+        }
+    }
+
+    /// Display name of the current pane's location.
+    #[inline]
+    pub fn title(&self) -> Cow<'_, str> {
+        self.pane_grid.get(&self.focused).unwrap().title()
+    }
+
+    /// What needs watching to notice changes in every pane.
+    pub fn watched(&self) -> impl Iterator<Item = PathWrap> + '_ {
+        self.pane_grid.iter().filter_map(|(_, pane)| pane.watched())
     }
 
     /// Get the location of the current pane.
     #[inline]
-    pub fn location(&self) -> &PathWrap {
-        self.pane_grid.get(&self.focused).unwrap().location()
+    pub fn uri(&self) -> &Uri {
+        self.pane_grid.get(&self.focused).unwrap().uri()
     }
 }
 
@@ -83,19 +329,45 @@ impl State {
 #[derive(Debug)]
 pub enum Event {
     /// Open this location in the current pane.
-    Open(PathWrap),
+    Open(Uri),
+    /// Open a file with a specific app.
+    OpenWith(Uri, Box<App>),
+    /// Run a file operation.
+    Operation(operations::Message),
+    /// Change how the current pane is sorted.
+    Sort(Sort),
+    /// Change which items the current pane shows.
+    Filter(Filter),
+    /// Stop reading the current pane's location.
+    StopLoading,
+    /// Search below the current pane's location.
+    Search(String, Mode),
+    /// Go back from search results to the current pane's location.
+    CloseSearch,
+    /// Search the contents of files, showing the results in a new tab.
+    Grep(grep::Query),
+    /// Show the disk usage of a location in a new tab.
+    Usage(PathWrap),
+    /// Look for duplicate files, showing them in a new tab.
+    Duplicates(duplicates::Query),
+    /// Show the current pane's preview panel, or hide it.
+    TogglePreview,
+    /// Move the split between the current pane's items and preview panel.
+    ResizePreview(pane_grid::ResizeEvent),
 }
 
 /// Tab component.
 pub struct Tab<'app> {
     state: &'app State,
+    /// Known folder sizes.
+    sizes: &'app size::Cache,
 }
 
 impl<'app> Tab<'app> {
     /// Create tab widget with given state.
     #[inline]
-    pub const fn new(state: &'app State) -> Self {
-        Self { state }
+    pub const fn new(state: &'app State, sizes: &'app size::Cache) -> Self {
+        Self { state, sizes }
     }
 }
 
@@ -107,13 +379,25 @@ impl<'app> Component<Message, gui::Renderer> for Tab<'app> {
         // TODO: handle pane grid events
         match event {
             Event::Open(o) => Some(Message::Open(o)),
+            Event::OpenWith(o, app) => Some(Message::OpenWith(o, app)),
+            Event::Operation(m) => Some(Message::Operation(m)),
+            Event::Sort(s) => Some(Message::Sort(s)),
+            Event::Filter(f) => Some(Message::Filter(f)),
+            Event::StopLoading => Some(Message::StopLoading),
+            Event::Search(t, m) => Some(Message::Search(t, m)),
+            Event::CloseSearch => Some(Message::CloseSearch),
+            Event::Grep(q) => Some(Message::Grep(q)),
+            Event::Usage(p) => Some(Message::Usage(p)),
+            Event::Duplicates(q) => Some(Message::Duplicates(q)),
+            Event::TogglePreview => Some(Message::TogglePreview),
+            Event::ResizePreview(r) => Some(Message::ResizePreview(r)),
         }
     }
 
     fn view(&self, _: &Self::State) -> Element<'_, Self::Event> {
         // TODO: top toolkit
         pane_grid::PaneGrid::new(&self.state.pane_grid, |_, content_state, _| {
-            pane_grid::Content::new(component(content(content_state)))
+            pane_grid::Content::new(component(content(content_state, self.sizes)))
                 .style(theme::container::Container::BaseSoftBorder)
         })
         .into()
@@ -0,0 +1,201 @@
+//! Sheet for picking the app a file is opened with.
+
+use doseer_core::apps::{App, Registry};
+use doseer_core::mime;
+use doseer_core::vfs::Uri;
+
+use iced::widget::scrollable::Properties;
+use iced::widget::{button, column, container, row, scrollable, text, Column};
+use iced::{Alignment, Length};
+
+use super::content::toggle_button;
+use crate::content::TabButtonStyle;
+use crate::gui::Element;
+use crate::theme;
+
+/// Changes to the chooser.
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Open the file with the app with this id.
+    Pick(String),
+    /// Show every app, not only those for the type of the file.
+    All,
+    /// Make the picked app the default for the type of the file.
+    Remember,
+    Cancel,
+}
+
+/// Apps a file can be opened with.
+#[derive(Debug, Clone)]
+pub struct Chooser {
+    location: Uri,
+    /// MIME type of the file.
+    mime: String,
+    /// Apps that open the type, with the default first.
+    suggested: Vec<App>,
+    /// Every other app.
+    others: Vec<App>,
+    all: bool,
+    /// Make the picked app the default.
+    remember: bool,
+}
+
+impl Chooser {
+    /// A chooser for a file, reading the installed apps.
+    pub fn new(location: Uri) -> Self {
+        let registry = Registry::shared();
+
+        let mime = if location.is_local() {
+            mime::detect(location.path())
+        } else {
+            // remote files would have to be downloaded to look inside them
+            let name = location.path().file_name().unwrap_or_default();
+
+            mime::DATABASE
+                .for_name(&name.to_string_lossy())
+                .first()
+                .map_or(mime::OCTET_STREAM, |m| m)
+                .to_owned()
+        };
+
+        let suggested: Vec<App> = registry.apps_for(&mime).into_iter().cloned().collect();
+
+        let others = registry
+            .all()
+            .into_iter()
+            .filter(|a| !a.no_display && !suggested.contains(a))
+            .cloned()
+            .collect();
+
+        Self {
+            location,
+            mime,
+            // with nothing to suggest, everything is shown right away
+            all: suggested.is_empty(),
+            suggested,
+            others,
+            remember: false,
+        }
+    }
+
+    /// The file being opened.
+    #[inline]
+    pub fn location(&self) -> &Uri {
+        &self.location
+    }
+
+    /// MIME type of the file.
+    #[inline]
+    pub fn mime(&self) -> &str {
+        &self.mime
+    }
+
+    /// Check if the picked app should become the default for the type.
+    #[inline]
+    pub fn remember(&self) -> bool {
+        self.remember
+    }
+
+    /// An app shown by the chooser.
+    pub fn app(&self, id: &str) -> Option<&App> {
+        self.suggested
+            .iter()
+            .chain(&self.others)
+            .find(|a| a.id == id)
+    }
+
+    pub fn update(&mut self, message: Message) {
+        match message {
+            Message::All => self.all = !self.all,
+            Message::Remember => self.remember = !self.remember,
+            // handled by the owner of the chooser
+            Message::Pick(_) | Message::Cancel => {}
+        }
+    }
+
+    pub fn view<'a>(&self) -> Element<'a, Message> {
+        let name = self
+            .location
+            .path()
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        let mut list = Column::new().spacing(2);
+
+        for (index, app) in self.suggested.iter().enumerate() {
+            list = list.push(app_button(app, index == 0));
+        }
+
+        if self.all {
+            if !self.suggested.is_empty() {
+                list = list.push(
+                    container(
+                        text("Other Applications")
+                            .size(14)
+                            .font(theme::fonts::Roboto::Regular),
+                    )
+                    .padding([8, 0, 4, 0]),
+                );
+            }
+
+            for app in &self.others {
+                list = list.push(app_button(app, false));
+            }
+        }
+
+        let apps = scrollable(container(list).padding([0.0, 13.0, 0.0, 0.0]))
+            .direction(scrollable::Direction::Vertical(
+                Properties::new().width(5.6).scroller_width(5.0),
+            ))
+            .height(Length::Fill);
+
+        column!(
+            text(format!("Open {name} with"))
+                .size(20)
+                .font(theme::fonts::SofiaSans::Black),
+            text(&self.mime)
+                .size(14)
+                .font(theme::fonts::RobotoMono::Regular),
+            apps,
+            row!(
+                toggle_button("Show All Applications", self.all, Message::All),
+                toggle_button("Set as Default", self.remember, Message::Remember),
+                column!().width(Length::Fill),
+                button(text("Cancel").size(16).font(theme::fonts::Roboto::Regular))
+                    .padding([4, 8])
+                    .style(TabButtonStyle::Default.into())
+                    .on_press(Message::Cancel),
+            )
+            .spacing(4)
+            .align_items(Alignment::Center),
+        )
+        .spacing(8)
+        .into()
+    }
+}
+
+/// A button opening the file with an app, marked if it's the default.
+fn app_button<'a>(app: &App, is_default: bool) -> Element<'a, Message> {
+    let mut label = Column::new().push(
+        text(if is_default {
+            format!("{} (default)", app.name)
+        } else {
+            app.name.clone()
+        })
+        .size(16)
+        .font(theme::fonts::Roboto::Regular),
+    );
+
+    if let Some(generic) = &app.generic_name {
+        label = label.push(text(generic).size(13).font(theme::fonts::Roboto::Regular));
+    }
+
+    button(label)
+        .padding([4, 8])
+        .width(Length::Fill)
+        .style(TabButtonStyle::Default.into())
+        .on_press(Message::Pick(app.id.clone()))
+        .into()
+}
@@ -0,0 +1,256 @@
+//! Panel previewing the selected item.
+//!
+//! Telling the type of a file and reading its start can block on slow disks, so that's done in
+//! the background, the same way thumbnails are. Only the details are shown until it's ready, and
+//! [`subscription`] reports when it is, so it gets drawn.
+
+use std::any::TypeId;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+use doseer_core::entry::{Entry, Kind};
+use doseer_core::{mime, permissions, size, thumbnail};
+
+use iced::widget::scrollable::Properties;
+use iced::widget::{column, container, image, row, scrollable, svg, text, Column, Text};
+use iced::{ContentFit, Length, Subscription};
+use lazy_static::lazy_static;
+
+use crate::background::Queue;
+use crate::gui::{self, Element};
+use crate::theme;
+
+/// Most of a text file that's shown.
+const TEXT_LIMIT: u64 = 16 * 1024;
+
+/// Number of previews read at once.
+const WORKERS: usize = 2;
+
+/// A file, as it was when its preview was asked for.
+type Key = (PathBuf, Option<SystemTime>);
+
+lazy_static! {
+    /// Previews read so far, and the queue of those still to read.
+    static ref PREVIEWS: Queue<Key, Inside> = Queue::new(64);
+}
+
+/// What was found by looking inside an item.
+#[derive(Debug, Clone)]
+struct Inside {
+    mime: String,
+    content: Content,
+}
+
+/// Read previews in the background, reporting every time one is ready.
+pub fn subscription() -> Subscription<()> {
+    struct Worker;
+
+    PREVIEWS.subscription(TypeId::of::<Worker>(), WORKERS, |key| {
+        Entry::read(&key.0).ok().map(|entry| inspect(&entry))
+    })
+}
+
+/// Look inside an item.
+fn inspect(entry: &Entry) -> Inside {
+    let mime = mime::DATABASE.detect(entry);
+
+    let content = if entry.kind == Kind::File {
+        content(entry, &mime)
+    } else {
+        Content::None
+    };
+
+    Inside { mime, content }
+}
+
+/// What's shown of an item besides its details.
+#[derive(Debug, Clone)]
+enum Content {
+    Image(image::Handle),
+    Svg(svg::Handle),
+    /// The start of a text file.
+    Text(String),
+    /// Only the details.
+    None,
+}
+
+/// Preview of an item, read when it's selected.
+#[derive(Debug, Clone)]
+pub struct Preview {
+    entry: Entry,
+    is_local: bool,
+}
+
+impl Preview {
+    /// Preview an item, reading what's inside it in the background.
+    ///
+    /// Items elsewhere would have to be downloaded to look inside them, so only their details
+    /// are shown.
+    pub fn new(entry: &Entry, is_local: bool) -> Self {
+        Self {
+            entry: entry.clone(),
+            is_local,
+        }
+    }
+
+    /// The item previewed.
+    #[inline]
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+
+    /// What was found inside the item, once it's been read.
+    fn inside(&self) -> Option<Inside> {
+        if !self.is_local {
+            return None;
+        }
+
+        PREVIEWS.get((self.entry.path.to_path_buf(), self.entry.modified))
+    }
+
+    pub fn view<'a, Message: 'a>(&self) -> Element<'a, Message> {
+        let name = self.entry.name().to_string_lossy().into_owned();
+        let inside = self.inside();
+
+        let shown: Element<'a, Message> = match inside.as_ref().map(|i| &i.content) {
+            Some(Content::Image(handle)) => image(handle.clone())
+                .width(Length::Fill)
+                .content_fit(ContentFit::ScaleDown)
+                .into(),
+            Some(Content::Svg(handle)) => svg(handle.clone())
+                .width(Length::Fill)
+                .content_fit(ContentFit::ScaleDown)
+                .into(),
+            Some(Content::Text(contents)) => container(
+                text(contents)
+                    .size(13)
+                    .font(theme::fonts::RobotoMono::Regular),
+            )
+            .padding(4)
+            .width(Length::Fill)
+            .into(),
+            Some(Content::None) | None => column!().into(),
+        };
+
+        // the name is all there is to go by until the item's been looked into
+        let mime = match inside {
+            Some(inside) => inside.mime,
+            None => mime::DATABASE.guess(&self.entry).to_owned(),
+        };
+        let details = self.details(mime);
+
+        scrollable(
+            column!(
+                text(name)
+                    .size(20)
+                    .font(theme::fonts::SofiaSans::Black)
+                    .width(Length::Fill),
+                shown,
+                details,
+            )
+            .spacing(8)
+            .padding([0.0, 13.0, 0.0, 0.0]),
+        )
+        .direction(scrollable::Direction::Vertical(
+            Properties::new().width(5.6).scroller_width(5.0),
+        ))
+        .height(Length::Fill)
+        .into()
+    }
+
+    /// A label and value for every detail known about the item.
+    fn details<'a, Message: 'a>(&self, mime: String) -> Column<'a, Message, gui::Renderer> {
+        let entry = &self.entry;
+        let mut details = vec![("Type", mime)];
+
+        if !entry.is_dir() {
+            details.push(("Size", size::format(entry.size)));
+        }
+
+        details.extend(entry.modified.map(|t| ("Modified", date(t))));
+        details.extend(entry.created.map(|t| ("Created", date(t))));
+
+        #[cfg(unix)]
+        {
+            let mode = entry.mode();
+            details.push((
+                "Permissions",
+                format!("{} {mode:04o}", permissions::symbolic(mode)),
+            ));
+        }
+
+        if let Some(owner) = entry.owner {
+            let user = permissions::user_name(owner.uid).unwrap_or_else(|| owner.uid.to_string());
+            let group = permissions::group_name(owner.gid).unwrap_or_else(|| owner.gid.to_string());
+
+            details.push(("Owner", format!("{user}:{group}")));
+        }
+
+        if let Some(target) = &entry.symlink {
+            details.push(("Links to", target.display().to_string()));
+        }
+
+        let mut column = Column::new().spacing(4);
+
+        for (name, value) in details {
+            column = column
+                .push(row!(label(name).width(96), label(value).width(Length::Fill)).spacing(8));
+        }
+
+        column
+    }
+}
+
+/// What can be shown of a file of some type.
+fn content(entry: &Entry, mime: &str) -> Content {
+    if thumbnail::supports(mime) {
+        return Content::Image(image::Handle::from_path(entry.path.to_path_buf()));
+    }
+
+    if mime::DATABASE.is_a(mime, "image/svg+xml") {
+        return Content::Svg(svg::Handle::from_path(entry.path.to_path_buf()));
+    }
+
+    if mime::DATABASE.is_a(mime, mime::TEXT_PLAIN) {
+        match read_text(entry) {
+            Ok(text) => return Content::Text(text),
+            Err(e) => tracing::warn!("failed to preview {}: {}", Path::display(&entry.path), e),
+        }
+    }
+
+    Content::None
+}
+
+/// The start of a text file, ending with a whole line if it's cut short.
+fn read_text(entry: &Entry) -> std::io::Result<String> {
+    let mut data = vec![];
+    File::open(&entry.path)?
+        .take(TEXT_LIMIT)
+        .read_to_end(&mut data)?;
+
+    let mut text = String::from_utf8_lossy(&data).into_owned();
+
+    if entry.size > TEXT_LIMIT {
+        if let Some(end) = text.rfind('\n') {
+            text.truncate(end);
+        }
+
+        text.push_str("\n…");
+    }
+
+    Ok(text)
+}
+
+/// A time in the local time zone.
+fn date(time: SystemTime) -> String {
+    DateTime::<Local>::from(time)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+fn label<'a>(content: impl ToString) -> Text<'a, gui::Renderer> {
+    text(content).size(14).font(theme::fonts::Roboto::Regular)
+}
@@ -0,0 +1,374 @@
+//! Panel showing the permissions and owner of an item, and changing them.
+
+use doseer_core::entry::Entry;
+use doseer_core::ops::Operation;
+use doseer_core::path::PathWrap;
+use doseer_core::permissions::{self, Change};
+#[cfg(unix)]
+use doseer_core::permissions::{Access, Class, SETGID, SETUID, STICKY};
+
+use iced::widget::{
+    button, checkbox, column, row, text, text_input, Checkbox, Column, Row, Text, TextInput,
+};
+use iced::{Alignment, Length};
+
+use crate::content::TabButtonStyle;
+use crate::gui::{self, Element};
+use crate::theme;
+
+/// Which mode is being edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// The mode of the item itself, and of directories inside it.
+    Item,
+    /// The mode of files inside a directory.
+    Files,
+}
+
+/// Changes to the properties form.
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Set or clear a mode bit.
+    Bit(Target, u32, bool),
+    Owner(String),
+    Group(String),
+    /// Change everything inside a directory too.
+    Recursive(bool),
+    Apply,
+    Cancel,
+}
+
+/// Permissions and owner of an item, as they are and as they'll be changed to.
+#[derive(Debug, Clone)]
+pub struct Form {
+    path: PathWrap,
+    is_dir: bool,
+    mode: u32,
+    /// Mode given to files inside a directory, when changing recursively.
+    file_mode: u32,
+    recursive: bool,
+    owner: String,
+    group: String,
+    original: Original,
+    /// The operation making the changes, worked out again whenever the form changes.
+    ///
+    /// Looking up users and groups reads the account databases, too slow to do every frame.
+    operation: Result<Option<Operation>, String>,
+}
+
+/// What the item had when the form was opened.
+#[derive(Debug, Clone)]
+struct Original {
+    mode: u32,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    owner: String,
+    group: String,
+}
+
+impl Form {
+    /// A form for an item, starting out with what it has now.
+    pub fn new(entry: &Entry) -> Self {
+        #[cfg(unix)]
+        let mode = entry.mode();
+        // other systems have no mode bits to show
+        #[cfg(not(unix))]
+        let mode = 0;
+        let uid = entry.owner.map(|o| o.uid);
+        let gid = entry.owner.map(|o| o.gid);
+
+        let owner = uid.map_or_else(String::new, |uid| {
+            permissions::user_name(uid).unwrap_or_else(|| uid.to_string())
+        });
+        let group = gid.map_or_else(String::new, |gid| {
+            permissions::group_name(gid).unwrap_or_else(|| gid.to_string())
+        });
+
+        let mut form = Self {
+            path: entry.path.clone(),
+            is_dir: entry.is_dir() && !entry.is_symlink(),
+            mode,
+            // files rarely need to be run just because they're in a folder that can be entered
+            file_mode: mode & !0o111,
+            recursive: false,
+            owner: owner.clone(),
+            group: group.clone(),
+            original: Original {
+                mode,
+                uid,
+                gid,
+                owner,
+                group,
+            },
+            operation: Ok(None),
+        };
+
+        form.operation = form.build();
+        form
+    }
+
+    /// Apply a change to the form.
+    pub fn update(&mut self, message: Message) {
+        match message {
+            Message::Bit(target, bit, set) => {
+                let mode = match target {
+                    Target::Item => &mut self.mode,
+                    Target::Files => &mut self.file_mode,
+                };
+
+                if set {
+                    *mode |= bit;
+                } else {
+                    *mode &= !bit;
+                }
+            }
+            Message::Owner(owner) => self.owner = owner,
+            Message::Group(group) => self.group = group,
+            Message::Recursive(recursive) => self.recursive = recursive,
+            // handled by the owner of the form
+            Message::Apply | Message::Cancel => return,
+        }
+
+        self.operation = self.build();
+    }
+
+    /// The operation making the changes, or why they can't be made.
+    ///
+    /// Nothing is returned if nothing was changed.
+    pub fn operation(self) -> Result<Option<Operation>, String> {
+        self.operation
+    }
+
+    /// Work out the operation from what's in the form.
+    fn build(&self) -> Result<Option<Operation>, String> {
+        let uid = if self.owner.trim() == self.original.owner {
+            None
+        } else {
+            Some(
+                permissions::user_id(&self.owner)
+                    .ok_or_else(|| format!("there's no user called {}", self.owner.trim()))?,
+            )
+        };
+
+        let gid = if self.group.trim() == self.original.group {
+            None
+        } else {
+            Some(
+                permissions::group_id(&self.group)
+                    .ok_or_else(|| format!("there's no group called {}", self.group.trim()))?,
+            )
+        };
+
+        let mode = (self.recursive || self.mode != self.original.mode).then_some(self.mode);
+
+        let change = Change {
+            // without recursing, the item itself is the only thing with a mode
+            file_mode: if self.recursive {
+                Some(self.file_mode)
+            } else if self.is_dir {
+                None
+            } else {
+                mode
+            },
+            dir_mode: if self.is_dir { mode } else { None },
+            uid: uid.filter(|uid| Some(*uid) != self.original.uid),
+            gid: gid.filter(|gid| Some(*gid) != self.original.gid),
+            recursive: self.recursive,
+        };
+
+        if change.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Operation::SetPermissions {
+            targets: vec![self.path.clone()],
+            change,
+        }))
+    }
+
+    pub fn view<'a>(&self) -> Element<'a, Message> {
+        let name = self
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        let mut panel = Column::new().spacing(8).push(
+            text(name)
+                .size(20)
+                .font(theme::fonts::SofiaSans::Black)
+                .width(Length::Fill),
+        );
+
+        #[cfg(unix)]
+        {
+            panel = panel.push(mode_grid(
+                if self.is_dir { "Folder" } else { "File" },
+                Target::Item,
+                self.mode,
+                self.is_dir,
+            ));
+        }
+
+        if self.is_dir {
+            panel = panel.push(
+                checkbox(
+                    "Apply to everything inside",
+                    self.recursive,
+                    Message::Recursive,
+                )
+                .size(16)
+                .text_size(16)
+                .font(theme::fonts::Roboto::Regular),
+            );
+
+            #[cfg(unix)]
+            if self.recursive {
+                panel = panel.push(mode_grid(
+                    "Files inside",
+                    Target::Files,
+                    self.file_mode,
+                    false,
+                ));
+            }
+        }
+
+        panel = panel
+            .push(
+                row!(
+                    label("Owner").width(60),
+                    input("User", &self.owner, Message::Owner).width(Length::Fill),
+                )
+                .spacing(8)
+                .align_items(Alignment::Center),
+            )
+            .push(
+                row!(
+                    label("Group").width(60),
+                    input("Group", &self.group, Message::Group).width(Length::Fill),
+                )
+                .spacing(8)
+                .align_items(Alignment::Center),
+            );
+
+        // warn before trying, rather than only after being refused
+        let problem = match (&self.operation, self.original.uid) {
+            (Err(e), _) => Some(e.clone()),
+            #[cfg(unix)]
+            (_, Some(uid)) if !permissions::can_change(uid) => Some(format!(
+                "Only {} or root can change the permissions of this item",
+                self.original.owner
+            )),
+            _ => None,
+        };
+
+        if let Some(problem) = problem {
+            panel = panel.push(label(problem).width(Length::Fill));
+        }
+
+        let apply = button(text("Apply").size(16).font(theme::fonts::Roboto::Regular))
+            .padding([4, 8])
+            .style(TabButtonStyle::Default.into());
+
+        panel
+            .push(
+                row!(
+                    column!().width(Length::Fill),
+                    button(text("Close").size(16).font(theme::fonts::Roboto::Regular))
+                        .padding([4, 8])
+                        .style(TabButtonStyle::Default.into())
+                        .on_press(Message::Cancel),
+                    if matches!(self.operation, Ok(Some(_))) {
+                        apply.on_press(Message::Apply)
+                    } else {
+                        apply
+                    },
+                )
+                .spacing(4)
+                .align_items(Alignment::Center),
+            )
+            .into()
+    }
+}
+
+/// A checkbox for every bit of a mode, with the mode written out above them.
+#[cfg(unix)]
+fn mode_grid<'a>(title: &str, target: Target, mode: u32, is_dir: bool) -> Element<'a, Message> {
+    let mut grid = Column::new().spacing(4).push(
+        row!(
+            label(title.to_owned()).width(Length::Fill),
+            text(format!("{} {mode:04o}", permissions::symbolic(mode)))
+                .size(14)
+                .font(theme::fonts::RobotoMono::Regular),
+        )
+        .align_items(Alignment::Center),
+    );
+
+    for class in Class::ALL {
+        let mut bits = Row::new()
+            .spacing(4)
+            .align_items(Alignment::Center)
+            .push(label(class.label()).width(60));
+
+        for access in Access::ALL {
+            let name = match access {
+                // the execute bit of a directory lets it be entered
+                Access::Execute if is_dir => "Enter",
+                access => access.label(),
+            };
+
+            bits = bits.push(mode_bit(
+                target,
+                mode,
+                permissions::bit(class, access),
+                name,
+            ));
+        }
+
+        grid = grid.push(bits);
+    }
+
+    grid.push(
+        row!(
+            column!().width(60),
+            mode_bit(target, mode, SETUID, "Set user"),
+            mode_bit(target, mode, SETGID, "Set group"),
+            mode_bit(target, mode, STICKY, "Sticky"),
+        )
+        .spacing(4)
+        .align_items(Alignment::Center),
+    )
+    .into()
+}
+
+#[cfg(unix)]
+fn mode_bit<'a>(
+    target: Target,
+    mode: u32,
+    bit: u32,
+    label: &str,
+) -> Checkbox<'a, Message, gui::Renderer> {
+    checkbox(label, mode & bit != 0, move |set| {
+        Message::Bit(target, bit, set)
+    })
+    .size(16)
+    .text_size(14)
+    .font(theme::fonts::Roboto::Regular)
+    .width(Length::FillPortion(1))
+}
+
+fn label<'a>(content: impl ToString) -> Text<'a, gui::Renderer> {
+    text(content).size(16).font(theme::fonts::Roboto::Regular)
+}
+
+fn input<'a>(
+    placeholder: &str,
+    value: &str,
+    on_input: fn(String) -> Message,
+) -> TextInput<'a, Message, gui::Renderer> {
+    text_input(placeholder, value)
+        .on_input(on_input)
+        .size(16)
+        .padding([4, 8])
+}
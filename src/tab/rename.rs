@@ -0,0 +1,321 @@
+//! Sheet for renaming many items at once, previewing every new name before anything is renamed.
+
+use doseer_core::ops::Operation;
+use doseer_core::path::PathWrap;
+use doseer_core::rename::{Case, Pattern, Plan, Position, Rule};
+
+use iced::widget::scrollable::Properties;
+use iced::widget::{
+    button, column, container, row, scrollable, text, text_input, Column, Row, TextInput,
+};
+use iced::{Alignment, Length};
+
+use super::content::toggle_button;
+use crate::content::TabButtonStyle;
+use crate::gui::{self, Element};
+use crate::theme;
+
+/// Date format used until another is typed.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Changes to the rename form.
+#[derive(Debug, Clone)]
+pub enum Message {
+    Find(String),
+    With(String),
+    /// Treat the text to find as a regular expression.
+    Regex,
+    /// Change letter case, cycling through the options.
+    Case,
+    /// Add a counter to every name.
+    Counter,
+    /// Number the counter starts at.
+    Start(String),
+    /// Add the modification date to every name.
+    Date,
+    Format(String),
+    /// Add counters and dates at the other end of names.
+    Position,
+    /// Apply the changes to extensions too.
+    Extension,
+    Apply,
+    Cancel,
+}
+
+/// What to rename, and how.
+#[derive(Debug, Clone)]
+pub struct Form {
+    sources: Vec<PathWrap>,
+    find: String,
+    with: String,
+    regex: bool,
+    case: Option<Case>,
+    counter: bool,
+    start: String,
+    date: bool,
+    format: String,
+    position: Position,
+    extension: bool,
+    /// New names for the current form, or why they can't be worked out.
+    plan: Result<Plan, String>,
+}
+
+impl Form {
+    /// A form renaming some items, which starts out changing nothing.
+    pub fn new(sources: Vec<PathWrap>) -> Self {
+        let mut form = Self {
+            sources,
+            find: String::new(),
+            with: String::new(),
+            regex: false,
+            case: None,
+            counter: false,
+            start: "1".to_owned(),
+            date: false,
+            format: DATE_FORMAT.to_owned(),
+            position: Position::End,
+            extension: false,
+            plan: Ok(Plan::default()),
+        };
+
+        form.replan();
+        form
+    }
+
+    /// Apply a change to the form, working out the new names again.
+    pub fn update(&mut self, message: Message) {
+        match message {
+            Message::Find(find) => self.find = find,
+            Message::With(with) => self.with = with,
+            Message::Regex => self.regex = !self.regex,
+            Message::Case => {
+                self.case = match self.case {
+                    None => Some(Case::ALL[0]),
+                    Some(case) if case == Case::ALL[Case::ALL.len() - 1] => None,
+                    Some(case) => Some(case.next()),
+                }
+            }
+            Message::Counter => self.counter = !self.counter,
+            Message::Start(start) => self.start = start,
+            Message::Date => self.date = !self.date,
+            Message::Format(format) => self.format = format,
+            Message::Position => {
+                self.position = match self.position {
+                    Position::Start => Position::End,
+                    Position::End => Position::Start,
+                }
+            }
+            Message::Extension => self.extension = !self.extension,
+            // handled by the owner of the form
+            Message::Apply | Message::Cancel => return,
+        }
+
+        self.replan();
+    }
+
+    /// The operation renaming everything, if the new names are all fine.
+    pub fn operation(&self) -> Option<Operation> {
+        self.plan.as_ref().ok()?.operation()
+    }
+
+    /// Work out the new names for the form as it is.
+    fn replan(&mut self) {
+        self.plan = self
+            .pattern()
+            .and_then(|p| p.plan(&self.sources).map_err(|e| e.to_string()));
+    }
+
+    /// The rules described by the form.
+    fn pattern(&self) -> Result<Pattern, String> {
+        let mut rules = vec![];
+
+        if !self.find.is_empty() {
+            rules.push(if self.regex {
+                Rule::Regex {
+                    pattern: self.find.clone(),
+                    with: self.with.clone(),
+                }
+            } else {
+                Rule::Replace {
+                    find: self.find.clone(),
+                    with: self.with.clone(),
+                }
+            });
+        }
+
+        if let Some(case) = self.case {
+            rules.push(Rule::Case(case));
+        }
+
+        if self.counter {
+            let start: u64 = self
+                .start
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid counter start: {}", self.start))?;
+
+            // every number is as wide as the last one
+            let last = start + self.sources.len().saturating_sub(1) as u64;
+
+            rules.push(Rule::Counter {
+                start,
+                step: 1,
+                width: last.to_string().len(),
+                position: self.position,
+                separator: " ".to_owned(),
+            });
+        }
+
+        if self.date {
+            rules.push(Rule::Date {
+                format: self.format.clone(),
+                position: self.position,
+                separator: " ".to_owned(),
+            });
+        }
+
+        Ok(Pattern {
+            rules,
+            extension: self.extension,
+        })
+    }
+
+    pub fn view<'a>(&self) -> Element<'a, Message> {
+        let replace = row!(
+            input("Find", &self.find, Message::Find).width(Length::Fill),
+            input("Replace with", &self.with, Message::With).width(Length::Fill),
+            toggle_button("Regex", self.regex, Message::Regex),
+        )
+        .spacing(4)
+        .align_items(Alignment::Center);
+
+        let mut additions = Row::new()
+            .spacing(4)
+            .align_items(Alignment::Center)
+            .push(toggle_button(
+                self.case.map_or("Keep Case", Case::label),
+                self.case.is_some(),
+                Message::Case,
+            ))
+            .push(toggle_button("Counter", self.counter, Message::Counter));
+
+        if self.counter {
+            additions = additions.push(input("Start", &self.start, Message::Start).width(80));
+        }
+
+        additions = additions.push(toggle_button("Date", self.date, Message::Date));
+
+        if self.date {
+            additions = additions.push(input("Format", &self.format, Message::Format).width(140));
+        }
+
+        additions = additions
+            .push(toggle_button(
+                match self.position {
+                    Position::Start => "Add at Start",
+                    Position::End => "Add at End",
+                },
+                false,
+                Message::Position,
+            ))
+            .push(toggle_button(
+                "Include Extension",
+                self.extension,
+                Message::Extension,
+            ));
+
+        let (preview, summary) = match &self.plan {
+            Ok(plan) => (
+                preview(plan),
+                match plan.problems() {
+                    0 => format!("{} of {} items renamed", plan.changes(), plan.renames.len()),
+                    n => format!("{n} items can't be renamed"),
+                },
+            ),
+            Err(e) => (column!().into(), e.clone()),
+        };
+
+        let apply = button(text("Rename").size(16).font(theme::fonts::Roboto::Regular))
+            .padding([4, 8])
+            .style(TabButtonStyle::Default.into());
+
+        let actions = row!(
+            text(summary)
+                .size(16)
+                .font(theme::fonts::Roboto::Regular)
+                .width(Length::Fill),
+            button(text("Cancel").size(16).font(theme::fonts::Roboto::Regular))
+                .padding([4, 8])
+                .style(TabButtonStyle::Default.into())
+                .on_press(Message::Cancel),
+            if self.operation().is_some() {
+                apply.on_press(Message::Apply)
+            } else {
+                apply
+            },
+        )
+        .spacing(4)
+        .align_items(Alignment::Center);
+
+        column!(
+            replace,
+            additions,
+            container(preview).height(Length::Fill),
+            actions
+        )
+        .spacing(8)
+        .into()
+    }
+}
+
+fn input<'a>(
+    placeholder: &str,
+    value: &str,
+    on_input: fn(String) -> Message,
+) -> TextInput<'a, Message, gui::Renderer> {
+    text_input(placeholder, value)
+        .on_input(on_input)
+        .size(16)
+        .padding([4, 8])
+}
+
+/// Every old name next to its new one, with the problem in place of the new name if there is one.
+fn preview<'a>(plan: &Plan) -> Element<'a, Message> {
+    let name = |path: &PathWrap| {
+        path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    let mut list = Column::new().spacing(2);
+
+    for rename in &plan.renames {
+        let new = match &rename.problem {
+            Some(problem) => format!("{} ({problem})", name(&rename.target)),
+            None if rename.is_change() => name(&rename.target),
+            None => "unchanged".to_owned(),
+        };
+
+        list = list.push(
+            row!(
+                text(name(&rename.source))
+                    .size(14)
+                    .font(theme::fonts::Roboto::Regular)
+                    .width(Length::FillPortion(1)),
+                text("→").size(14).font(theme::fonts::Roboto::Regular),
+                text(new)
+                    .size(14)
+                    .font(theme::fonts::Roboto::Regular)
+                    .width(Length::FillPortion(1)),
+            )
+            .spacing(8),
+        );
+    }
+
+    scrollable(container(list).padding([0.0, 13.0, 0.0, 0.0]))
+        .direction(scrollable::Direction::Vertical(
+            Properties::new().width(5.6).scroller_width(5.0),
+        ))
+        .into()
+}
@@ -0,0 +1,392 @@
+//! Disk usage treemap.
+//!
+//! Every item is drawn as a rectangle with an area proportional to its size, with the contents of
+//! folders nested inside them. Clicking a top level folder drills down into it.
+
+use std::path::PathBuf;
+
+use doseer_colorschemes::default::Wrap;
+use doseer_colorschemes::WithColorScheme;
+use doseer_core::size;
+use doseer_core::usage::Node;
+
+use iced::mouse;
+use iced::widget::canvas::{self, event, Frame, Geometry, Path, Program, Stroke, Text};
+use iced::{Color, Point, Rectangle, Size};
+
+use crate::{gui, theme, Theme};
+
+/// How many levels of folders are drawn at once.
+const MAX_DEPTH: usize = 2;
+
+/// Space at the top of a folder for its name.
+const HEADER: f32 = 18.0;
+
+/// Space around the contents of a folder.
+const PADDING: f32 = 2.0;
+
+/// Items smaller than this, in either direction, aren't drawn.
+const MIN_SIDE: f32 = 2.0;
+
+/// An item laid out on the map.
+struct Tile<'a> {
+    node: &'a Node,
+    bounds: Rectangle,
+    /// How deep below the shown folder this is, starting from 1.
+    depth: usize,
+    /// Position of the top level item this is in, used for coloring.
+    group: usize,
+}
+
+/// Treemap of a folder.
+pub struct Treemap<'a, Message> {
+    node: &'a Node,
+    on_drill: fn(PathBuf) -> Message,
+}
+
+impl<'a, Message> Treemap<'a, Message> {
+    /// Map of a folder, sending a message when one of its folders is clicked.
+    #[inline]
+    pub const fn new(node: &'a Node, on_drill: fn(PathBuf) -> Message) -> Self {
+        Self { node, on_drill }
+    }
+
+    /// Every item drawn within some bounds, parents before their children.
+    fn tiles(&self, bounds: Size) -> Vec<Tile<'a>> {
+        let mut tiles = vec![];
+        layout(
+            self.node,
+            Rectangle::new(Point::ORIGIN, bounds),
+            1,
+            None,
+            &mut tiles,
+        );
+        tiles
+    }
+}
+
+impl<'a, Message> Program<Message, gui::Renderer> for Treemap<'a, Message> {
+    type State = ();
+
+    fn update(
+        &self,
+        _: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (event::Status, Option<Message>) {
+        let canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event else {
+            return (event::Status::Ignored, None);
+        };
+
+        let Some(position) = cursor.position_in(bounds) else {
+            return (event::Status::Ignored, None);
+        };
+
+        let clicked = self
+            .tiles(bounds.size())
+            .into_iter()
+            .find(|t| t.depth == 1 && t.bounds.contains(position));
+
+        match clicked {
+            Some(tile) if tile.node.is_dir => (
+                event::Status::Captured,
+                Some((self.on_drill)(tile.node.path.clone())),
+            ),
+            _ => (event::Status::Ignored, None),
+        }
+    }
+
+    fn draw(
+        &self,
+        _: &Self::State,
+        renderer: &gui::Renderer,
+        app_theme: &Wrap<Theme>,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let palette = app_theme.palette();
+        let mut frame = Frame::new(renderer, bounds.size());
+
+        let tiles = self.tiles(bounds.size());
+
+        for tile in &tiles {
+            let color = tile_color(tile.group, tile.depth);
+
+            frame.fill_rectangle(tile.bounds.position(), tile.bounds.size(), color);
+            frame.stroke(
+                &Path::rectangle(tile.bounds.position(), tile.bounds.size()),
+                Stroke::default()
+                    .with_color(palette.surface.base.base)
+                    .with_width(1.0),
+            );
+
+            if tile.bounds.width > 48.0 && tile.bounds.height > HEADER {
+                frame.fill_text(Text {
+                    content: fit(&label(tile.node), tile.bounds.width - 8.0),
+                    position: Point::new(tile.bounds.x + 4.0, tile.bounds.y + 2.0),
+                    color: Color::BLACK,
+                    size: 13.0,
+                    font: theme::fonts::Roboto::Regular.into(),
+                    ..Text::default()
+                });
+            }
+        }
+
+        // details of whatever is under the cursor, drawn over everything else
+        let hovered = cursor.position_in(bounds).and_then(|p| {
+            tiles
+                .iter()
+                .rev()
+                .find(|t| t.bounds.contains(p))
+                .map(|t| (p, t))
+        });
+
+        if let Some((position, tile)) = hovered {
+            frame.stroke(
+                &Path::rectangle(tile.bounds.position(), tile.bounds.size()),
+                Stroke::default()
+                    .with_color(palette.primary.base.accent)
+                    .with_width(2.0),
+            );
+
+            let details = [
+                tile.node.path.display().to_string(),
+                size::format(tile.node.bytes),
+            ];
+            let width = details.iter().map(String::len).max().unwrap_or(0) as f32 * 7.0 + 16.0;
+            let size = Size::new(width, 44.0);
+
+            // keep the box inside the map
+            let position = Point::new(
+                (position.x + 12.0).min(bounds.width - size.width).max(0.0),
+                (position.y + 12.0)
+                    .min(bounds.height - size.height)
+                    .max(0.0),
+            );
+
+            frame.fill_rectangle(position, size, palette.surface.strong.base);
+            for (line, content) in details.into_iter().enumerate() {
+                frame.fill_text(Text {
+                    content,
+                    position: Point::new(position.x + 8.0, position.y + 5.0 + line as f32 * 18.0),
+                    color: palette.surface.strong.on_base,
+                    size: 14.0,
+                    font: theme::fonts::Roboto::Regular.into(),
+                    ..Text::default()
+                });
+            }
+        }
+
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        _: &Self::State,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        let Some(position) = cursor.position_in(bounds) else {
+            return mouse::Interaction::default();
+        };
+
+        let over_folder = self
+            .tiles(bounds.size())
+            .iter()
+            .any(|t| t.depth == 1 && t.node.is_dir && t.bounds.contains(position));
+
+        if over_folder {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
+
+/// Lay out the contents of a folder, and the contents of the folders in it up to [`MAX_DEPTH`].
+fn layout<'a>(
+    node: &'a Node,
+    bounds: Rectangle,
+    depth: usize,
+    group: Option<usize>,
+    tiles: &mut Vec<Tile<'a>>,
+) {
+    let children: Vec<_> = node.children.iter().filter(|c| c.bytes > 0).collect();
+    let weights: Vec<_> = children.iter().map(|c| c.bytes as f32).collect();
+
+    for (index, (child, bounds)) in children
+        .into_iter()
+        .zip(squarify(&weights, bounds))
+        .enumerate()
+    {
+        if bounds.width < MIN_SIDE || bounds.height < MIN_SIDE {
+            continue;
+        }
+
+        let group = group.unwrap_or(index);
+
+        tiles.push(Tile {
+            node: child,
+            bounds,
+            depth,
+            group,
+        });
+
+        let inner = Rectangle {
+            x: bounds.x + PADDING,
+            y: bounds.y + HEADER,
+            width: bounds.width - PADDING * 2.0,
+            height: bounds.height - HEADER - PADDING,
+        };
+
+        if child.is_dir && depth < MAX_DEPTH && inner.width > 0.0 && inner.height > 0.0 {
+            layout(child, inner, depth + 1, Some(group), tiles);
+        }
+    }
+}
+
+/// Split an area into rectangles with areas proportional to some weights, keeping them as close
+/// to squares as possible.
+///
+/// Weights must be sorted from biggest to smallest.
+fn squarify(weights: &[f32], bounds: Rectangle) -> Vec<Rectangle> {
+    let total: f32 = weights.iter().sum();
+
+    if total <= 0.0 {
+        return vec![];
+    }
+
+    let scale = bounds.width * bounds.height / total;
+    let areas: Vec<_> = weights.iter().map(|w| w * scale).collect();
+
+    let mut rects = Vec::with_capacity(areas.len());
+    let mut rest = bounds;
+    let mut start = 0;
+
+    while start < areas.len() {
+        let side = rest.width.min(rest.height);
+        let mut end = start + 1;
+
+        // grow the row for as long as that makes its worst rectangle more square
+        while end < areas.len()
+            && worst(&areas[start..=end], side) <= worst(&areas[start..end], side)
+        {
+            end += 1;
+        }
+
+        rest = place_row(&areas[start..end], rest, &mut rects);
+        start = end;
+    }
+
+    rects
+}
+
+/// Aspect ratio of the least square rectangle in a row along a side.
+fn worst(row: &[f32], side: f32) -> f32 {
+    let sum: f32 = row.iter().sum();
+    let (min, max) = row.iter().fold((f32::MAX, 0.0f32), |(min, max), a| {
+        (min.min(*a), max.max(*a))
+    });
+
+    let side = side * side;
+    let sum = sum * sum;
+
+    (side * max / sum).max(sum / (side * min))
+}
+
+/// Place a row of rectangles along the shorter side of an area, returning what's left of it.
+fn place_row(row: &[f32], rest: Rectangle, rects: &mut Vec<Rectangle>) -> Rectangle {
+    let sum: f32 = row.iter().sum();
+
+    if rest.width >= rest.height {
+        // a column on the left
+        let width = sum / rest.height;
+        let mut y = rest.y;
+
+        for area in row {
+            let height = area / width;
+            rects.push(Rectangle::new(
+                Point::new(rest.x, y),
+                Size::new(width, height),
+            ));
+            y += height;
+        }
+
+        Rectangle {
+            x: rest.x + width,
+            width: rest.width - width,
+            ..rest
+        }
+    } else {
+        // a row along the top
+        let height = sum / rest.width;
+        let mut x = rest.x;
+
+        for area in row {
+            let width = area / height;
+            rects.push(Rectangle::new(
+                Point::new(x, rest.y),
+                Size::new(width, height),
+            ));
+            x += width;
+        }
+
+        Rectangle {
+            y: rest.y + height,
+            height: rest.height - height,
+            ..rest
+        }
+    }
+}
+
+/// Name and size of an item.
+fn label(node: &Node) -> String {
+    format!(
+        "{} ({})",
+        node.name().to_string_lossy(),
+        size::format(node.bytes)
+    )
+}
+
+/// Cut off text to roughly fit within a width.
+fn fit(text: &str, width: f32) -> String {
+    // a rough average for the label font size
+    let max = (width / 7.0) as usize;
+
+    if text.chars().count() > max {
+        let cut: String = text.chars().take(max.saturating_sub(1)).collect();
+        format!("{cut}…")
+    } else {
+        text.to_owned()
+    }
+}
+
+/// Color of an item, by its top level group and depth.
+fn tile_color(group: usize, depth: usize) -> Color {
+    // spread hues around the wheel so neighbours stand apart
+    let hue = (group as f32 * 137.5) % 360.0;
+    let lightness = 0.62 + 0.1 * (depth - 1) as f32;
+
+    hsl(hue, 0.55, lightness.min(0.9))
+}
+
+/// Convert a color from HSL, with hue in degrees.
+fn hsl(hue: f32, saturation: f32, lightness: f32) -> Color {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let m = lightness - chroma / 2.0;
+
+    Color::from_rgb(r + m, g + m, b + m)
+}
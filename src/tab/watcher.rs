@@ -1,62 +1,123 @@
 //! File watcher.
+//!
+//! Every location open in any tab or pane gets exactly one watcher, which lives as long as the
+//! location stays open. Bursts of events are coalesced before being reported.
 
-use doseer_core::path::PathWrap;
-
-use iced::Command;
-use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use tokio::runtime::Handle;
-use tokio::sync::mpsc::{self, Receiver};
-
-use super::Message;
-
-type EventReceiver = Receiver<notify::Result<Event>>;
+use std::any::TypeId;
+use std::time::Duration;
 
-/// Create the watcher.
-fn create_watcher() -> notify::Result<(RecommendedWatcher, EventReceiver)> {
-    let (sender, receiver) = mpsc::channel(16);
+use doseer_core::dirs::Change;
+use doseer_core::path::PathWrap;
 
-    let handle = Handle::current();
+use iced::futures::SinkExt;
+use iced::{subscription, Subscription};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::time::{self, Instant};
 
-    let handler = move |res| {
-        handle.block_on(async {
-            let _ = sender.send(res).await;
-        });
-    };
+/// How long to keep collecting events after the first one of a burst.
+const DEBOUNCE: Duration = Duration::from_millis(150);
 
-    notify::recommended_watcher(handler).map(|w| (w, receiver))
+/// Watcher messages.
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Items in a watched location changed.
+    Changed(Vec<Change>),
+    /// Failed to watch a location.
+    Failed(PathWrap, String),
 }
 
-/// Handle or ignore an fs event.
-fn handle_notif(result: notify::Result<Event>) -> Option<Message> {
-    match result {
-        Ok(event) => match event.kind {
-            EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(_) => {
-                Some(Message::Update)
-            }
-            _ => None,
-        },
-        _ => Some(Message::WatchFail),
-    }
-}
-
-/// Watch the current location, producing a [`Message`] when an event occurs.
-pub async fn watch(path: PathWrap) -> Message {
-    if let Ok((mut watcher, mut receiver)) = create_watcher() {
-        if watcher.watch(&path, RecursiveMode::NonRecursive).is_err() {
-            return Message::WatchFail;
-        }
+/// Describe an fs event as changes to individual items.
+fn changes(event: Event) -> Vec<Change> {
+    let mut paths = event.paths.into_iter();
 
-        loop {
-            if let Some(message) = receiver.recv().await.and_then(handle_notif) {
-                return message;
+    match event.kind {
+        EventKind::Create(_) => paths.map(Change::Created).collect(),
+        EventKind::Remove(_) => paths.map(Change::Removed).collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            match (paths.next(), paths.next()) {
+                (Some(from), Some(to)) => vec![Change::Renamed(from, to)],
+                _ => vec![],
             }
         }
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            paths.map(Change::Removed).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => paths.map(Change::Created).collect(),
+        // reloading an item also notices if it was renamed away
+        EventKind::Modify(_) => paths.map(Change::Modified).collect(),
+        _ => vec![],
     }
+}
 
-    Message::WatchFail
+/// Watch every given location.
+///
+/// Locations that stop being passed in are unwatched on the next subscription update.
+pub fn subscription<I>(locations: I) -> Subscription<Message>
+where
+    I: IntoIterator<Item = PathWrap>,
+{
+    Subscription::batch(locations.into_iter().map(watch))
 }
 
-pub fn command(path: &PathWrap) -> Command<Message> {
-    let path = path.clone();
-    Command::perform(watch(path), |x| x)
+/// Watch a single location until the subscription is dropped.
+fn watch(location: PathWrap) -> Subscription<Message> {
+    struct Watch;
+
+    subscription::channel(
+        (TypeId::of::<Watch>(), location.clone()),
+        16,
+        move |mut output| async move {
+            let (sender, mut receiver) = mpsc::unbounded_channel();
+
+            // notify calls this from its own thread, so it must not block
+            let watcher = notify::recommended_watcher(move |res| {
+                let _ = sender.send(res);
+            })
+            .and_then(|mut w| {
+                w.watch(&location, RecursiveMode::NonRecursive)?;
+                Ok(w)
+            });
+
+            // dropping the watcher unwatches the location, so keep it around
+            let _watcher = match watcher {
+                Ok(w) => w,
+                Err(e) => {
+                    let _ = output.send(Message::Failed(location, e.to_string())).await;
+                    return std::future::pending().await;
+                }
+            };
+
+            while let Some(first) = receiver.recv().await {
+                let mut results = vec![first];
+                let deadline = Instant::now() + DEBOUNCE;
+
+                while let Ok(Some(res)) = time::timeout_at(deadline, receiver.recv()).await {
+                    results.push(res);
+                }
+
+                let mut batch = vec![];
+
+                for res in results {
+                    match res {
+                        Ok(event) => batch.extend(changes(event)),
+                        Err(e) => {
+                            let _ = output
+                                .send(Message::Failed(location.clone(), e.to_string()))
+                                .await;
+                        }
+                    }
+                }
+
+                batch.dedup();
+
+                if !batch.is_empty() {
+                    let _ = output.send(Message::Changed(batch)).await;
+                }
+            }
+
+            std::future::pending().await
+        },
+    )
 }
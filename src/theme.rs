@@ -25,9 +25,11 @@ impl Default for Theme {
 }
 
 impl default::application::DevAuto for Theme {}
+impl default::checkbox::DevAuto for Theme {}
 impl default::pane_grid::DevAuto for Theme {}
 impl default::rule::DevAuto for Theme {}
 impl default::scrollable::DevAuto for Theme {}
+impl default::text_input::DevAuto for Theme {}
 
 pub mod button {
     use super::*;
@@ -100,6 +102,8 @@ pub mod container {
         Strong,
         /// Regular box with application background.
         BaseSoftBorder,
+        /// A box filled with the accent color.
+        Accent,
     }
 
     impl default::container::StyleSheet for Theme {
@@ -123,6 +127,10 @@ pub mod container {
                     border_radius: BASE_BORDER_RADIUS(),
                     ..Default::default()
                 },
+                Container::Accent => Appearance {
+                    background: Some(palette.primary.base.accent.into()),
+                    ..Default::default()
+                },
             }
         }
     }
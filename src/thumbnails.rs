@@ -0,0 +1,65 @@
+//! Thumbnails shown in place of icons.
+//!
+//! Thumbnails are looked up and made in the background, since reading and scaling images takes
+//! far too long to do while drawing. Items are drawn with their icon until their thumbnail is
+//! ready, and [`subscription`] reports when one is, so it gets drawn.
+
+use std::any::TypeId;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use doseer_core::entry::{Entry, Kind};
+use doseer_core::mime;
+use doseer_core::thumbnail::{self, Size};
+
+use iced::widget::image;
+use iced::Subscription;
+use lazy_static::lazy_static;
+
+use crate::background::Queue;
+
+/// Number of thumbnails made at once.
+const WORKERS: usize = 4;
+
+/// A file, as it was when its thumbnail was asked for.
+type Key = (PathBuf, Option<SystemTime>);
+
+lazy_static! {
+    /// Thumbnails asked for so far, and the queue of those still to look for.
+    static ref THUMBNAILS: Queue<Key, image::Handle> = Queue::new(2048);
+}
+
+/// The thumbnail of an entry, if it's ready.
+///
+/// The first time an entry is asked for, its thumbnail is looked for in the background.
+pub fn get(entry: &Entry) -> Option<image::Handle> {
+    if entry.kind != Kind::File {
+        return None;
+    }
+
+    THUMBNAILS.get((entry.path.to_path_buf(), entry.modified))
+}
+
+/// Look for thumbnails in the background, reporting every time one is ready.
+pub fn subscription() -> Subscription<()> {
+    struct Worker;
+
+    THUMBNAILS.subscription(TypeId::of::<Worker>(), WORKERS, |key| load(&key.0))
+}
+
+/// Find or make the thumbnail of a file.
+fn load(path: &Path) -> Option<image::Handle> {
+    let mime = mime::detect(path);
+
+    let thumbnail = thumbnail::load(path, &mime, Size::Normal)
+        .map_err(|e| tracing::debug!("no thumbnail for {}: {}", path.display(), e))
+        .ok()?;
+
+    // the same file is made again when the original changes, so it's read rather than referred to
+    let data = fs::read(&thumbnail)
+        .map_err(|e| tracing::warn!("failed to read thumbnail {}: {}", thumbnail.display(), e))
+        .ok()?;
+
+    Some(image::Handle::from_memory(data))
+}